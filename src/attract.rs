@@ -0,0 +1,245 @@
+//! Looks picked at random: by `surprise` once at launch, and by the
+//! `attract` mode every `attract_minutes`, the old look fading into the new.
+
+use crate::config::Config;
+use crate::offscreen::Offscreen;
+use crate::palette::Palette;
+use crate::pattern::Pattern;
+use crate::screensaver::clock_seed;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, Serialize};
+
+/// What the screensaver shows over time.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// The configured look all along.
+    #[default]
+    Normal,
+    /// A new shape, palette and pattern every `attract_minutes`.
+    Attract,
+}
+
+/// Shapes the attract mode picks from.
+const SHAPES: [&str; 8] = ["hexagon", "heart", "star", "triangle", "circle", "snowflake", "polygon:5", "star:7/2"];
+/// Patterns it picks from, all but the system load readout and scripts.
+const PATTERNS: [Pattern; 8] = [
+    Pattern::Random,
+    Pattern::Wave,
+    Pattern::Ripple,
+    Pattern::Noise,
+    Pattern::Life,
+    Pattern::Ant,
+    Pattern::Reaction,
+    Pattern::Rain,
+];
+/// Seconds the old look takes to fade into the new one.
+const CROSS_FADE_SECONDS: f32 = 3.0;
+
+/// Lowest and highest density `surprise` picks.
+const SURPRISE_DENSITY: (f32, f32) = (0.2, 1.0);
+
+/// A shape, palette and pattern picked together.
+#[derive(Clone, Debug, PartialEq)]
+struct Look {
+    shape: String,
+    palette: String,
+    pattern: Pattern,
+}
+
+/// A random number generator seeded with `seed`, or from the clock when 0.
+fn seeded(seed: u64) -> RandGenerator {
+    let rng = RandGenerator::new();
+    rng.srand(if seed == 0 { clock_seed() } else { seed });
+    rng
+}
+
+/// The look `surprise` picked at launch.
+pub(crate) struct Surprise {
+    look: Look,
+    density: f32,
+}
+
+impl Surprise {
+    /// A look picked from `config`'s allow-lists, or from every choice for
+    /// the lists left empty.
+    pub(crate) fn pick(config: &Config) -> Self {
+	let rng = seeded(config.seed);
+	let choose = |allowed: &[String], all: &[&str]| match allowed {
+	    [] => all[rng.gen_range(0, all.len())].to_string(),
+	    allowed => allowed[rng.gen_range(0, allowed.len())].clone(),
+	};
+	let palettes: Vec<&str> = Palette::names().collect();
+	let patterns = if config.surprise_patterns.is_empty() { &PATTERNS[..] } else { &config.surprise_patterns };
+	let look = Look {
+	    shape: choose(&config.surprise_shapes, &SHAPES),
+	    palette: choose(&config.surprise_palettes, &palettes),
+	    pattern: patterns[rng.gen_range(0, patterns.len())],
+	};
+	// Rounded to read well in the log
+	let density = (rng.gen_range(SURPRISE_DENSITY.0, SURPRISE_DENSITY.1) * 100.0).round() / 100.0;
+	Self { look, density }
+    }
+
+    /// `config` showing the picked look.
+    pub(crate) fn apply(&self, mut config: Config) -> Config {
+	config.shape = self.look.shape.clone();
+	config.palette = self.look.palette.clone();
+	config.palette_image.clear();
+	config.pattern = self.look.pattern;
+	config.density = self.density;
+	config
+    }
+
+    /// The picked settings in the config's TOML, to copy into it.
+    pub(crate) fn settings(&self) -> String {
+	let mut table = toml::Table::new();
+	table.insert("shape".to_string(), self.look.shape.clone().into());
+	table.insert("palette".to_string(), self.look.palette.clone().into());
+	table.insert("density".to_string(), ((f64::from(self.density) * 100.0).round() / 100.0).into());
+	table.insert("pattern".to_string(), toml::Value::try_from(self.look.pattern).expect("patterns serialize"));
+	toml::to_string(&table).expect("settings serialize")
+    }
+}
+
+/// The look shown and the time left until the next.
+pub(crate) struct Attract {
+    rng: RandGenerator,
+    look: Option<Look>,
+    until_next: f32,
+}
+
+impl Attract {
+    /// Start on the configured look, picking the next ones from `seed`,
+    /// or from the clock when 0.
+    pub(crate) fn new(config: &Config) -> Self {
+	Self { rng: seeded(config.seed), look: None, until_next: config.attract_minutes * 60.0 }
+    }
+
+    /// Count down `dt` seconds, moving on to a new look once
+    /// `config.attract_minutes` have passed; true when it did.
+    pub(crate) fn step(&mut self, dt: f32, config: &Config) -> bool {
+	self.until_next -= dt;
+	if self.until_next > 0.0 {
+	    return false;
+	}
+	self.until_next = config.attract_minutes * 60.0;
+	let palettes: Vec<&str> = Palette::names().collect();
+	let current = self.apply(config.clone());
+	self.look = Some(Look {
+	    shape: pick_other(&self.rng, &SHAPES, |shape| shape.eq_ignore_ascii_case(&current.shape)).to_string(),
+	    palette: pick_other(&self.rng, &palettes, |palette| palette.eq_ignore_ascii_case(&current.palette)).to_string(),
+	    pattern: pick_other(&self.rng, &PATTERNS, |pattern| *pattern == current.pattern),
+	});
+	true
+    }
+
+    /// `config` showing the current look.
+    pub(crate) fn apply(&self, mut config: Config) -> Config {
+	if let Some(look) = &self.look {
+	    config.shape = look.shape.clone();
+	    config.palette = look.palette.clone();
+	    config.palette_image.clear();
+	    config.pattern = look.pattern;
+	}
+	config
+    }
+}
+
+/// One of `choices` that is not `current`, when there is one.
+fn pick_other<T: Copy>(rng: &RandGenerator, choices: &[T], current: impl Fn(&T) -> bool) -> T {
+    let others: Vec<T> = choices.iter().copied().filter(|choice| !current(choice)).collect();
+    let choices = if others.is_empty() { choices } else { &others };
+    choices[rng.gen_range(0, choices.len())]
+}
+
+/// The last frame of the old look, drawn fading out over the new one.
+pub(crate) struct CrossFade {
+    offscreen: Offscreen,
+    remaining: f32,
+}
+
+impl CrossFade {
+    pub(crate) fn new(bounds: Rect) -> Self {
+	Self { offscreen: Offscreen::new(bounds, 1.0, FilterMode::Linear), remaining: 0.0 }
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.offscreen.fits(bounds)
+    }
+
+    /// Capture what is drawn next over `bounds` as the old frame, starting
+    /// from `background`.
+    pub(crate) fn capture(&mut self, bounds: Rect, background: Color) {
+	self.offscreen.begin(bounds);
+	clear_background(background);
+    }
+
+    /// Stop capturing and start fading the captured frame out.
+    pub(crate) fn finish(&mut self) {
+	self.offscreen.end();
+	self.remaining = CROSS_FADE_SECONDS;
+    }
+
+    pub(crate) fn step(&mut self, dt: f32) {
+	self.remaining = (self.remaining - dt).max(0.0);
+    }
+
+    /// Whether the old frame still shows.
+    pub(crate) fn fading(&self) -> bool {
+	self.remaining > 0.0
+    }
+
+    /// Draw the old frame over `bounds` as far as it has faded.
+    pub(crate) fn draw(&self, bounds: Rect) {
+	if !self.fading() {
+	    return;
+	}
+	// Eased at both ends, without a jump as the fade starts or ends
+	let t = self.remaining / CROSS_FADE_SECONDS;
+	let opacity = t * t * (3.0 - 2.0 * t);
+	let params = DrawTextureParams { dest_size: Some(bounds.size()), flip_y: true, ..Default::default() };
+	draw_texture_ex(&self.offscreen.target.texture, bounds.x, bounds.y, Color::new(1.0, 1.0, 1.0, opacity), params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_look_differs_from_the_last() {
+	let config = Config { seed: 7, attract_minutes: 1.0, ..Default::default() };
+	let mut attract = Attract::new(&config);
+	assert!(!attract.step(59.0, &config));
+	assert_eq!(attract.apply(config.clone()).shape, config.shape);
+	let mut last = attract.apply(config.clone());
+	for _ in 0..20 {
+	    assert!(attract.step(60.0, &config));
+	    let next = attract.apply(config.clone());
+	    assert!(next.shape != last.shape && next.palette != last.palette && next.pattern != last.pattern);
+	    last = next;
+	}
+    }
+
+    #[test]
+    fn surprises_keep_to_their_allow_lists() {
+	let config = Config {
+	    seed: 3,
+	    surprise_shapes: vec!["heart".to_string()],
+	    surprise_patterns: vec![Pattern::Wave, Pattern::Rain],
+	    ..Default::default()
+	};
+	let surprise = Surprise::pick(&config);
+	let picked = surprise.apply(config.clone());
+	assert_eq!(picked.shape, "heart");
+	assert!(Palette::names().any(|name| name == picked.palette));
+	assert!(config.surprise_patterns.contains(&picked.pattern));
+	assert!((SURPRISE_DENSITY.0..=SURPRISE_DENSITY.1).contains(&picked.density));
+	// What it logs reads back as the same settings
+	let logged: Config = toml::from_str(&surprise.settings()).unwrap();
+	assert_eq!((logged.shape, logged.palette, logged.pattern, logged.density), (picked.shape, picked.palette, picked.pattern, picked.density));
+    }
+}
@@ -0,0 +1,207 @@
+use log::warn;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Samples analysed per frame; a power of two for the FFT.
+const FFT_SIZE: usize = 1024;
+/// Frequency bands reported by `AudioMeter::bands`, spaced logarithmically.
+const BANDS: usize = 8;
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 16_000.0;
+
+/// Smoothed spectrum of the system audio, used to drive `audio_reactive`.
+///
+/// The capture callback keeps the latest `FFT_SIZE` mono samples; `bands`
+/// runs an FFT over them once per frame and smooths each band with an
+/// attack/release envelope.
+pub(crate) struct AudioMeter {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    sample_rate: f32,
+    /// Slowly decaying loudest level seen per band, for automatic gain.
+    peaks: [f32; BANDS],
+    envelopes: [f32; BANDS],
+    #[cfg(feature = "audio")]
+    _stream: cpal::Stream,
+}
+
+impl AudioMeter {
+    #[cfg(feature = "audio")]
+    pub(crate) fn start() -> Option<Self> {
+	use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+	let host = cpal::default_host();
+	// WASAPI captures loopback when an input stream is opened on an output
+	// device; elsewhere fall back to the default input (e.g. a monitor source).
+	let candidates = [
+	    host.default_output_device().and_then(|device| Some((device.default_output_config().ok()?, device))),
+	    host.default_input_device().and_then(|device| Some((device.default_input_config().ok()?, device))),
+	];
+
+	let samples = Arc::new(Mutex::new(VecDeque::with_capacity(FFT_SIZE)));
+	for (supported, device) in candidates.into_iter().flatten() {
+	    let config = supported.config();
+	    let stream = match supported.sample_format() {
+		cpal::SampleFormat::F32 => Self::build_stream::<f32>(&device, &config, samples.clone()),
+		cpal::SampleFormat::I16 => Self::build_stream::<i16>(&device, &config, samples.clone()),
+		cpal::SampleFormat::U16 => Self::build_stream::<u16>(&device, &config, samples.clone()),
+		format => {
+		    warn!("unsupported audio sample format {:?}", format);
+		    None
+		}
+	    };
+	    if let Some(stream) = stream
+		&& stream.play().is_ok()
+	    {
+		return Some(Self {
+		    samples,
+		    sample_rate: config.sample_rate.0 as f32,
+		    peaks: [0.0; BANDS],
+		    envelopes: [0.0; BANDS],
+		    _stream: stream,
+		});
+	    }
+	}
+	warn!("no audio capture device available, audio_reactive disabled");
+	None
+    }
+
+    #[cfg(not(feature = "audio"))]
+    pub(crate) fn start() -> Option<Self> {
+	warn!("built without the `audio` feature, audio_reactive disabled");
+	None
+    }
+
+    #[cfg(feature = "audio")]
+    fn build_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, samples: Arc<Mutex<VecDeque<f32>>>) -> Option<cpal::Stream>
+    where
+	T: cpal::SizedSample,
+	f32: cpal::FromSample<T>,
+    {
+	use cpal::Sample;
+	use cpal::traits::DeviceTrait;
+
+	let channels = config.channels.max(1) as usize;
+	device
+	    .build_input_stream(
+		config,
+		move |data: &[T], _: &cpal::InputCallbackInfo| {
+		    let Ok(mut samples) = samples.lock() else {
+			return;
+		    };
+		    for frame in data.chunks(channels) {
+			let sum: f32 = frame.iter().map(|sample| f32::from_sample(*sample)).sum();
+			samples.push_back(sum / frame.len() as f32);
+		    }
+		    let excess = samples.len().saturating_sub(FFT_SIZE);
+		    samples.drain(..excess);
+		},
+		|err| warn!("audio capture error: {}", err),
+		None,
+	    )
+	    .ok()
+    }
+
+    /// Advance the band envelopes by `dt` and return each band's level,
+    /// 0..1 from the lowest frequencies to the highest.
+    pub(crate) fn bands(&mut self, dt: f32) -> &[f32] {
+	let samples: Vec<f32> = match self.samples.lock() {
+	    Ok(samples) => samples.iter().copied().collect(),
+	    Err(_) => Vec::new(),
+	};
+	let magnitudes = spectrum_bands(&samples, self.sample_rate);
+	for ((magnitude, peak), envelope) in magnitudes.iter().zip(&mut self.peaks).zip(&mut self.envelopes) {
+	    *peak = (*peak * (1.0 - 0.2 * dt).max(0.0)).max(*magnitude).max(1e-4);
+	    let target = magnitude / *peak;
+	    let rate = if target > *envelope { 20.0 } else { 3.0 };
+	    *envelope += (target - *envelope) * (rate * dt).min(1.0);
+	}
+	&self.envelopes
+    }
+}
+
+/// Mean FFT magnitude of `samples` in each of `BANDS` log-spaced bands.
+fn spectrum_bands(samples: &[f32], sample_rate: f32) -> [f32; BANDS] {
+    let mut bands = [0.0; BANDS];
+    if samples.len() < FFT_SIZE {
+	return bands;
+    }
+
+    // Hann window to keep leakage between bands down
+    let samples = &samples[samples.len() - FFT_SIZE..];
+    let mut re: Vec<f32> = samples
+	.iter()
+	.enumerate()
+	.map(|(i, sample)| sample * (0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()))
+	.collect();
+    let mut im = vec![0.0; FFT_SIZE];
+    fft(&mut re, &mut im);
+
+    let bin_width = sample_rate / FFT_SIZE as f32;
+    let ratio = (MAX_FREQUENCY / MIN_FREQUENCY).powf(1.0 / BANDS as f32);
+    for (band, level) in bands.iter_mut().enumerate() {
+	let low = MIN_FREQUENCY * ratio.powi(band as i32);
+	let start = ((low / bin_width) as usize).max(1);
+	let end = (((low * ratio) / bin_width) as usize).clamp(start + 1, FFT_SIZE / 2);
+	if start >= end {
+	    continue;
+	}
+	let sum: f32 = (start..end).map(|bin| re[bin].hypot(im[bin])).sum();
+	*level = sum / (end - start) as f32;
+    }
+    bands
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+	let mut bit = n >> 1;
+	while j & bit != 0 {
+	    j ^= bit;
+	    bit >>= 1;
+	}
+	j |= bit;
+	if i < j {
+	    re.swap(i, j);
+	    im.swap(i, j);
+	}
+    }
+
+    let mut len = 2;
+    while len <= n {
+	let angle = -2.0 * PI / len as f32;
+	for start in (0..n).step_by(len) {
+	    for k in 0..len / 2 {
+		let (sin, cos) = (angle * k as f32).sin_cos();
+		let (a, b) = (start + k, start + k + len / 2);
+		let t_re = re[b] * cos - im[b] * sin;
+		let t_im = re[b] * sin + im[b] * cos;
+		re[b] = re[a] - t_re;
+		im[b] = im[a] - t_im;
+		re[a] += t_re;
+		im[a] += t_im;
+	    }
+	}
+	len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tones_land_in_their_band() {
+	let sample_rate = 48_000.0;
+	let tone = |frequency: f32| -> Vec<f32> {
+	    (0..FFT_SIZE).map(|i| (2.0 * PI * frequency * i as f32 / sample_rate).sin()).collect()
+	};
+	let loudest = |bands: [f32; BANDS]| (0..BANDS).max_by(|&a, &b| bands[a].total_cmp(&bands[b])).unwrap();
+
+	assert_eq!(loudest(spectrum_bands(&tone(100.0), sample_rate)), 1);
+	assert_eq!(loudest(spectrum_bands(&tone(8_000.0), sample_rate)), BANDS - 1);
+	assert_eq!(spectrum_bands(&[0.5; 10], sample_rate), [0.0; BANDS]);
+    }
+}
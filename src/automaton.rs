@@ -0,0 +1,190 @@
+//! Cellular automata run on the shapes' neighbor graph by the `life`
+//! pattern.
+
+use crate::config::Config;
+use crate::pattern::Pattern;
+use crate::shapes::Shape;
+use macroquad::rand::RandGenerator;
+
+/// How cells change state each generation, given their neighbors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Rule {
+    /// Life-like birth and survival counts, as bit sets of live neighbor
+    /// counts, written `B2/S34`.
+    Life { birth: u16, survive: u16 },
+    /// Cells of `states` states advance to the next one when a neighbor is
+    /// already there, written `cyclic` or `cyclic:N`.
+    Cyclic { states: u8 },
+    /// Live cells die through a dying state; dead ones with two live
+    /// neighbors are born. Written `brain`.
+    Brain,
+}
+
+impl Rule {
+    /// The rule named by `text`, or none when it is not one.
+    pub(crate) fn parse(text: &str) -> Option<Rule> {
+	let text = text.trim().to_ascii_lowercase();
+	if text == "brain" || text == "brians-brain" {
+	    return Some(Rule::Brain);
+	}
+	if let Some(states) = text.strip_prefix("cyclic") {
+	    let states = match states.strip_prefix(':') {
+		Some(count) => count.parse().ok().filter(|count| (2..=32).contains(count))?,
+		None if states.is_empty() => 8,
+		None => return None,
+	    };
+	    return Some(Rule::Cyclic { states });
+	}
+	let (birth, survive) = text.split_once('/')?;
+	let counts = |digits: &str| {
+	    digits.chars().try_fold(0u16, |set, digit| digit.to_digit(10).filter(|count| *count <= 12).map(|count| set | 1 << count))
+	};
+	Some(Rule::Life { birth: counts(birth.strip_prefix('b')?)?, survive: counts(survive.strip_prefix('s')?)? })
+    }
+
+    /// The state after this generation of a cell in `state` with
+    /// `neighbors` in theirs.
+    fn step(self, state: u8, neighbors: impl Iterator<Item = u8>) -> u8 {
+	match self {
+	    Rule::Life { birth, survive } => {
+		let live = neighbors.filter(|&neighbor| neighbor == 1).count();
+		let counts = if state == 1 { survive } else { birth };
+		u8::from(live < 16 && counts & 1 << live != 0)
+	    }
+	    Rule::Cyclic { states } => {
+		let next = (state + 1) % states;
+		if neighbors.into_iter().any(|neighbor| neighbor == next) { next } else { state }
+	    }
+	    Rule::Brain => match state {
+		0 if neighbors.filter(|&neighbor| neighbor == 1).count() == 2 => 1,
+		0 => 0,
+		1 => 2,
+		_ => 0,
+	    },
+	}
+    }
+
+    /// How lit a cell in `state` is, 0..1.
+    fn level(self, state: u8) -> f32 {
+	match self {
+	    Rule::Life { .. } => state as f32,
+	    Rule::Cyclic { states } => state as f32 / (states - 1) as f32,
+	    Rule::Brain => [0.0, 1.0, 0.4][state as usize],
+	}
+    }
+
+    /// A random starting state.
+    fn seed(self, rng: &RandGenerator) -> u8 {
+	match self {
+	    Rule::Cyclic { states } => rng.gen_range(0, states),
+	    // A third of the cells alive
+	    _ => u8::from(rng.gen_range(0, 3) == 0),
+	}
+    }
+}
+
+/// The cells of the `life` pattern, one per shape.
+#[derive(Default)]
+pub(crate) struct Automaton {
+    states: Vec<u8>,
+    /// Opacity of each cell, fading toward its state's level.
+    levels: Vec<f32>,
+    since_tick: f32,
+    rule: Option<Rule>,
+}
+
+impl Automaton {
+    /// Advance the cells by `dt` seconds, stepping a generation every
+    /// `config.life_tick`.
+    pub(crate) fn update(&mut self, shapes: &[Shape], dt: f32, config: &Config, rng: &RandGenerator) {
+	if config.pattern != Pattern::Life {
+	    self.states.clear();
+	    self.levels.clear();
+	    return;
+	}
+	// The config is sanitized, so the rule parses
+	let rule = Rule::parse(&config.automaton).unwrap_or(Rule::Life { birth: 1 << 2, survive: 1 << 3 | 1 << 4 });
+	// A new layout or rule starts a new run
+	if self.states.len() != shapes.len() || self.rule != Some(rule) {
+	    self.rule = Some(rule);
+	    self.seed(shapes.len(), rule, rng);
+	    self.levels = self.states.iter().map(|&state| rule.level(state)).collect();
+	}
+
+	// Generations advance on their own clock, however fast frames come
+	self.since_tick += dt;
+	while self.since_tick >= config.life_tick {
+	    self.since_tick -= config.life_tick;
+	    let next: Vec<u8> = shapes
+		.iter()
+		.zip(&self.states)
+		.map(|(shape, &state)| rule.step(state, shape.neighbors.iter().map(|&neighbor| self.states[neighbor])))
+		.collect();
+	    // Dead or frozen boards start over
+	    if next == self.states {
+		self.seed(shapes.len(), rule, rng);
+	    } else {
+		self.states = next;
+	    }
+	}
+
+	let fade = (dt / config.life_tick).min(1.0);
+	for (level, &state) in self.levels.iter_mut().zip(&self.states) {
+	    *level += (rule.level(state) - *level) * fade;
+	}
+    }
+
+    /// How lit the `index`th cell is, 0..1.
+    pub(crate) fn level(&self, index: usize) -> f32 {
+	self.levels.get(index).copied().unwrap_or(0.0)
+    }
+
+    fn seed(&mut self, count: usize, rule: Rule, rng: &RandGenerator) {
+	self.states = (0..count).map(|_| rule.seed(rng)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+
+    #[test]
+    fn rules_parse_from_their_names() {
+	assert_eq!(Rule::parse("B2/S34"), Some(Rule::Life { birth: 0b100, survive: 0b11000 }));
+	assert_eq!(Rule::parse("cyclic"), Some(Rule::Cyclic { states: 8 }));
+	assert_eq!(Rule::parse("Cyclic:5"), Some(Rule::Cyclic { states: 5 }));
+	assert_eq!(Rule::parse("brain"), Some(Rule::Brain));
+	for invalid in ["", "B2", "S3/B2", "cyclic:1", "cyclicx", "B2x/S3"] {
+	    assert_eq!(Rule::parse(invalid), None, "{invalid}");
+	}
+    }
+
+    #[test]
+    fn life_follows_the_hex_rule() {
+	let rng = RandGenerator::new();
+	let config = Config { pattern: Pattern::Life, life_tick: 1.0, ..Default::default() };
+	// A center cell and its six neighbors, which border only it
+	let mut shapes: Vec<Shape> = (0..7).map(|_| Shape::new(0.0, 0.0, 10.0, false, &Palette::random(), &rng)).collect();
+	shapes[0].neighbors = (1..7).collect();
+	for shape in &mut shapes[1..] {
+	    shape.neighbors = vec![0];
+	}
+	let mut automaton = Automaton::default();
+	automaton.update(&shapes, 0.0, &config, &rng);
+	// Two live neighbors bring the dead center to life, and ring cells
+	// with a single neighbor die
+	automaton.states = vec![0, 1, 1, 0, 0, 0, 0];
+	automaton.update(&shapes, 1.0, &config, &rng);
+	assert_eq!(automaton.states, vec![1, 0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn brain_cells_die_through_the_dying_state() {
+	let neighbors = |live: usize| std::iter::repeat_n(1, live);
+	assert_eq!(Rule::Brain.step(0, neighbors(2)), 1);
+	assert_eq!(Rule::Brain.step(0, neighbors(3)), 0);
+	assert_eq!(Rule::Brain.step(1, neighbors(2)), 2);
+	assert_eq!(Rule::Brain.step(2, neighbors(2)), 0);
+    }
+}
@@ -0,0 +1,195 @@
+//! Layers drawn behind the shapes.
+
+use crate::config::{Config, ImageFit};
+use log::warn;
+use macroquad::models::{Mesh, Vertex, draw_mesh};
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use std::cell::OnceCell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Something animated behind the grid, drawn before the shapes.
+pub(crate) trait BackgroundLayer {
+    /// Advance by `dt` seconds.
+    fn step(&mut self, dt: f32);
+    /// Draw with every color scaled by `brightness`.
+    fn draw(&self, brightness: f32);
+    /// Whether the layer moves, so frames must keep coming.
+    fn animated(&self) -> bool {
+	true
+    }
+}
+
+/// The layers `config` enables, covering `displays`.
+pub(crate) fn layers(config: &Config, displays: &[Rect], rng: &RandGenerator) -> Vec<Box<dyn BackgroundLayer>> {
+    let mut layers: Vec<Box<dyn BackgroundLayer>> = Vec::new();
+    if let Some((top, bottom)) = config.gradient_colors() {
+	for display in displays {
+	    layers.push(Box::new(Gradient { bounds: *display, top, bottom }));
+	}
+    }
+    if !config.background_image.is_empty() {
+	match load_image(Path::new(&config.background_image)) {
+	    Ok(image) => {
+		let image = Rc::new(image);
+		for display in displays {
+		    let (source, dest) = fit_image(Vec2::new(image.width as f32, image.height as f32), *display, config.background_fit);
+		    layers.push(Box::new(Picture { image: image.clone(), texture: OnceCell::new(), source, dest }));
+		}
+	    }
+	    Err(err) => warn!("cannot read background image {}: {err}", config.background_image),
+	}
+    }
+    if config.stars {
+	for display in displays {
+	    layers.push(Box::new(Starfield::new(*display, config.star_density, config.star_speed, rng)));
+	}
+    }
+    layers
+}
+
+/// A still vertical gradient over a display.
+struct Gradient {
+    bounds: Rect,
+    top: Color,
+    bottom: Color,
+}
+
+impl BackgroundLayer for Gradient {
+    fn step(&mut self, _dt: f32) {}
+
+    fn draw(&self, brightness: f32) {
+	let scaled = |color: Color| Color::new(color.r * brightness, color.g * brightness, color.b * brightness, color.a);
+	let Rect { x, y, w, h } = self.bounds;
+	let (top, bottom) = (scaled(self.top), scaled(self.bottom));
+	let vertices = vec![
+	    Vertex::new(x, y, 0.0, 0.0, 0.0, top),
+	    Vertex::new(x + w, y, 0.0, 0.0, 0.0, top),
+	    Vertex::new(x + w, y + h, 0.0, 0.0, 0.0, bottom),
+	    Vertex::new(x, y + h, 0.0, 0.0, 0.0, bottom),
+	];
+	draw_mesh(&Mesh { vertices, indices: vec![0, 1, 2, 0, 2, 3], texture: None });
+    }
+
+    fn animated(&self) -> bool {
+	false
+    }
+}
+
+/// The PNG or TGA at `path`, found like the config's other files.
+pub(crate) fn load_image(path: &Path) -> io::Result<Image> {
+    let bytes = fs::read(Config::find_file(path))?;
+    Image::from_file_with_format(&bytes, None).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+}
+
+/// The part of an `image_size` image to show, and where on `display` to
+/// show it, for `fit`.
+fn fit_image(image_size: Vec2, display: Rect, fit: ImageFit) -> (Rect, Rect) {
+    let scales = display.size() / image_size.max(Vec2::ONE);
+    match fit {
+	ImageFit::Cover => {
+	    let size = display.size() / scales.max_element();
+	    let source = Rect::new((image_size.x - size.x) / 2.0, (image_size.y - size.y) / 2.0, size.x, size.y);
+	    (source, display)
+	}
+	ImageFit::Contain => {
+	    let size = image_size * scales.min_element();
+	    let offset = (display.size() - size) / 2.0;
+	    (Rect::new(0.0, 0.0, image_size.x, image_size.y), Rect::new(display.x + offset.x, display.y + offset.y, size.x, size.y))
+	}
+    }
+}
+
+/// A still image over a display, uploaded on the first frame drawn.
+struct Picture {
+    image: Rc<Image>,
+    texture: OnceCell<Texture2D>,
+    source: Rect,
+    dest: Rect,
+}
+
+impl BackgroundLayer for Picture {
+    fn step(&mut self, _dt: f32) {}
+
+    fn draw(&self, brightness: f32) {
+	let texture = self.texture.get_or_init(|| Texture2D::from_image(&self.image));
+	let params = DrawTextureParams { dest_size: Some(self.dest.size()), source: Some(self.source), ..Default::default() };
+	draw_texture_ex(texture, self.dest.x, self.dest.y, Color::new(brightness, brightness, brightness, 1.0), params);
+    }
+
+    fn animated(&self) -> bool {
+	false
+    }
+}
+
+/// Relative speed, and so size and brightness, of each depth of stars.
+const DEPTHS: [f32; 3] = [1.0, 0.5, 0.25];
+
+/// Stars drifting to the left across a display, nearer ones faster.
+pub(crate) struct Starfield {
+    bounds: Rect,
+    /// Position and depth index of each star.
+    stars: Vec<(Vec2, usize)>,
+    speed: f32,
+}
+
+impl Starfield {
+    /// `density` stars per 100x100 pixels of `bounds`, the nearest moving
+    /// `speed` pixels per second.
+    pub(crate) fn new(bounds: Rect, density: f32, speed: f32, rng: &RandGenerator) -> Self {
+	let count = (bounds.w * bounds.h / 10_000.0 * density) as usize;
+	let stars = (0..count)
+	    .map(|_| {
+		let pos = Vec2::new(rng.gen_range(bounds.left(), bounds.right()), rng.gen_range(bounds.top(), bounds.bottom()));
+		(pos, rng.gen_range(0, DEPTHS.len()))
+	    })
+	    .collect();
+	Self { bounds, stars, speed }
+    }
+}
+
+impl BackgroundLayer for Starfield {
+    fn step(&mut self, dt: f32) {
+	for (pos, depth) in &mut self.stars {
+	    pos.x -= self.speed * DEPTHS[*depth] * dt;
+	    if pos.x < self.bounds.left() {
+		pos.x += self.bounds.w;
+	    }
+	}
+    }
+
+    fn draw(&self, brightness: f32) {
+	for &(pos, depth) in &self.stars {
+	    let depth = DEPTHS[depth];
+	    draw_circle(pos.x, pos.y, 0.5 + depth * 1.5, Color::new(brightness, brightness, brightness, 0.3 + depth * 0.7));
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_cover_or_fit_inside_their_display() {
+	let display = Rect::new(100.0, 0.0, 200.0, 100.0);
+	let (source, dest) = fit_image(Vec2::new(400.0, 400.0), display, ImageFit::Cover);
+	assert_eq!((source, dest), (Rect::new(0.0, 100.0, 400.0, 200.0), display));
+	let (source, dest) = fit_image(Vec2::new(400.0, 400.0), display, ImageFit::Contain);
+	assert_eq!((source, dest), (Rect::new(0.0, 0.0, 400.0, 400.0), Rect::new(150.0, 0.0, 100.0, 100.0)));
+    }
+
+    #[test]
+    fn stars_wrap_around_their_display() {
+	let bounds = Rect::new(100.0, 0.0, 200.0, 100.0);
+	let mut stars = Starfield::new(bounds, 5.0, 50.0, &RandGenerator::new());
+	assert_eq!(stars.stars.len(), 10);
+	for _ in 0..100 {
+	    stars.step(0.1);
+	}
+	assert!(stars.stars.iter().all(|(pos, _)| bounds.contains(*pos)));
+    }
+}
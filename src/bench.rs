@@ -0,0 +1,90 @@
+//! The `bench` subcommand: the grid stepped and rendered in software at
+//! several shape sizes and densities, with the time each frame took, so
+//! slowdowns in tessellation and rendering show up as numbers.
+
+use crate::config::Config;
+use crate::render::rasterize;
+use crate::screensaver::Screensaver;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Shape radii and densities measured, every pairing of them.
+const RADII: [f32; 3] = [20.0, 40.0, 80.0];
+const DENSITIES: [f32; 3] = [0.25, 0.5, 1.0];
+/// Size of the frames rendered, in pixels.
+const BENCH_SIZE: (usize, usize) = (640, 360);
+/// Animation time each case renders, after as long again to warm up.
+const BENCH_SECONDS: f32 = 2.0;
+const BENCH_FPS: f32 = 60.0;
+/// Seed of a config without one, so runs compare.
+const BENCH_SEED: u64 = 1;
+
+/// Average times of one case's frames.
+struct Measurement {
+    shapes: usize,
+    drawn: f32,
+    step: Duration,
+    render: Duration,
+}
+
+/// Step and render `frames` frames of `config` after as many to warm up.
+fn measure(config: Config, frames: u32) -> Measurement {
+    let display = Rect::new(0.0, 0.0, BENCH_SIZE.0 as f32, BENCH_SIZE.1 as f32);
+    let radius = config.radius;
+    let mut screensaver = Screensaver::new(config, &[display], radius);
+    let dt = 1.0 / BENCH_FPS;
+    for _ in 0..frames {
+	screensaver.step(dt, &[]);
+    }
+    let (mut step, mut render, mut drawn) = (Duration::ZERO, Duration::ZERO, 0);
+    for _ in 0..frames {
+	let start = Instant::now();
+	let commands = screensaver.step(dt, &[]);
+	let stepped = Instant::now();
+	std::hint::black_box(rasterize(screensaver.registry(), &commands, BENCH_SIZE.0, BENCH_SIZE.1));
+	step += stepped - start;
+	render += stepped.elapsed();
+	drawn += commands.len();
+    }
+    let frames = frames.max(1);
+    Measurement { shapes: screensaver.shape_count(), drawn: drawn as f32 / frames as f32, step: step / frames, render: render / frames }
+}
+
+fn milliseconds(time: Duration) -> f64 {
+    time.as_secs_f64() * 1000.0
+}
+
+/// Measure every radius and density on top of `config`, printing a row
+/// of the table as each finishes.
+pub fn run(config: &Config) {
+    let seed = if config.seed == 0 { BENCH_SEED } else { config.seed };
+    let frames = (BENCH_SECONDS * BENCH_FPS) as u32;
+    println!("{}x{} software frames, {frames} per case", BENCH_SIZE.0, BENCH_SIZE.1);
+    println!("{:>6} {:>7} {:>7} {:>7} {:>9} {:>9} {:>9}", "radius", "density", "shapes", "drawn", "step ms", "render ms", "frame ms");
+    for radius in RADII {
+	for density in DENSITIES {
+	    let case = Config { radius, density, seed, gpu_animation: false, ..config.clone() };
+	    let Measurement { shapes, drawn, step, render } = measure(case, frames);
+	    println!(
+		"{radius:>6} {density:>7} {shapes:>7} {drawn:>7.0} {:>9.3} {:>9.3} {:>9.3}",
+		milliseconds(step),
+		milliseconds(render),
+		milliseconds(step + render)
+	    );
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denser_grids_draw_more() {
+	let config = |radius, density| Config { radius, density, seed: BENCH_SEED, ..Default::default() };
+	let small = measure(config(20.0, 1.0), 2);
+	let large = measure(config(80.0, 1.0), 2);
+	assert!(small.shapes > large.shapes && large.shapes > 0);
+	assert!(measure(config(20.0, 0.25), 2).drawn <= small.drawn);
+    }
+}
@@ -0,0 +1,19 @@
+//! The browser entry point: the screensaver in the page's canvas with the
+//! settings from its URL. See `hexagon_screensaver::web`.
+
+#[cfg(target_arch = "wasm32")]
+fn window_conf() -> macroquad::window::Conf {
+    macroquad::window::Conf { window_title: "Heart Screensaver".to_owned(), high_dpi: true, ..Default::default() }
+}
+
+#[cfg(target_arch = "wasm32")]
+#[macroquad::main(window_conf)]
+async fn main() {
+    hexagon_screensaver::web::run().await;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("error: the web build runs in a browser; build it for wasm32-unknown-unknown");
+    std::process::exit(2);
+}
@@ -0,0 +1,112 @@
+//! Glow around lit shapes: the shapes are drawn again into a half sized
+//! texture, blurred across and then down, and added onto the screen.
+
+use crate::offscreen::Offscreen;
+use macroquad::miniquad::{BlendFactor, BlendState, Equation, PipelineParams, UniformType};
+use macroquad::prelude::*;
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying lowp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}
+"#;
+
+// A nine tap Gaussian along `direction`, the texture offset between taps
+const FRAGMENT_SHADER: &str = r#"#version 100
+precision mediump float;
+
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 direction;
+uniform float intensity;
+
+void main() {
+    vec3 sum = texture2D(Texture, uv).rgb * 0.227027;
+    sum += (texture2D(Texture, uv + direction).rgb + texture2D(Texture, uv - direction).rgb) * 0.1945946;
+    sum += (texture2D(Texture, uv + direction * 2.0).rgb + texture2D(Texture, uv - direction * 2.0).rgb) * 0.1216216;
+    sum += (texture2D(Texture, uv + direction * 3.0).rgb + texture2D(Texture, uv - direction * 3.0).rgb) * 0.054054;
+    sum += (texture2D(Texture, uv + direction * 4.0).rgb + texture2D(Texture, uv - direction * 4.0).rgb) * 0.016216;
+    gl_FragColor = vec4(sum * intensity, 1.0);
+}
+"#;
+
+/// The textures and shaders of the glow, sized for one set of bounds.
+pub(crate) struct Bloom {
+    scene: Offscreen,
+    across: RenderTarget,
+    /// Blurs into a texture, replacing what was there.
+    blur: Material,
+    /// Blurs onto the screen, adding to what was there.
+    glow: Material,
+}
+
+impl Bloom {
+    /// Textures for glowing over `bounds`, or the reason the shaders could
+    /// not be built.
+    pub(crate) fn new(bounds: Rect) -> Result<Self, String> {
+	let material = |color_blend| {
+	    load_material(
+		ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+		MaterialParams {
+		    uniforms: vec![UniformDesc::new("direction", UniformType::Float2), UniformDesc::new("intensity", UniformType::Float1)],
+		    pipeline_params: PipelineParams { color_blend, ..Default::default() },
+		    ..Default::default()
+		},
+	    )
+	    .map_err(|err| err.to_string())
+	};
+	let blur = material(None)?;
+	let glow = material(Some(BlendState::new(Equation::Add, BlendFactor::One, BlendFactor::One)))?;
+	let scene = Offscreen::new(bounds, 0.5, FilterMode::Linear);
+	let across = render_target(scene.size.0, scene.size.1);
+	across.texture.set_filter(FilterMode::Linear);
+	Ok(Self { scene, across, blur, glow })
+    }
+
+    /// Whether these textures fit `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.scene.fits(bounds)
+    }
+
+    /// Send what is drawn next, in the coordinates of `bounds`, into the
+    /// texture that glows; `compose` switches back.
+    pub(crate) fn capture(&mut self, bounds: Rect) {
+	self.scene.begin(bounds);
+	clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+    }
+
+    /// Blur what was captured by `radius` pixels and add it, scaled by
+    /// `intensity`, over `bounds` on the screen.
+    pub(crate) fn compose(&self, bounds: Rect, radius: f32, intensity: f32) {
+	let (width, height) = (self.scene.size.0 as f32, self.scene.size.1 as f32);
+	// Four taps to each side of the center, in half sized texels
+	let spacing = radius / 2.0 / 4.0;
+	let params = |size| DrawTextureParams { dest_size: Some(size), flip_y: true, ..Default::default() };
+
+	set_camera(&Camera2D {
+	    render_target: Some(self.across.clone()),
+	    ..Camera2D::from_display_rect(Rect::new(0.0, 0.0, width, height))
+	});
+	gl_use_material(&self.blur);
+	self.blur.set_uniform("direction", [spacing / width, 0.0]);
+	self.blur.set_uniform("intensity", 1.0f32);
+	draw_texture_ex(&self.scene.target.texture, 0.0, 0.0, WHITE, params(Vec2::new(width, height)));
+
+	self.scene.end();
+	gl_use_material(&self.glow);
+	self.glow.set_uniform("direction", [0.0, spacing / height]);
+	self.glow.set_uniform("intensity", intensity);
+	draw_texture_ex(&self.across.texture, bounds.x, bounds.y, WHITE, params(bounds.size()));
+	gl_use_default_material();
+    }
+}
@@ -0,0 +1,46 @@
+//! Saving frames as PNG files.
+
+use crate::clock::local_now;
+use macroquad::prelude::*;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Reverse the row order of an image; GL reads pixels back bottom row first.
+pub(crate) fn flip_rows(pixels: &[u8], row_bytes: usize) -> Vec<u8> {
+    pixels.chunks_exact(row_bytes).rev().flatten().copied().collect()
+}
+
+/// Write top-down RGBA pixels to `path` as a PNG.
+pub(crate) fn save_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgba).map_err(io::Error::other)
+}
+
+/// Save what has been drawn so far this frame as a timestamped PNG in the
+/// pictures directory, or the current one if there is none. A page has no
+/// files to save to.
+pub(crate) fn save_screenshot() -> io::Result<PathBuf> {
+    if cfg!(target_arch = "wasm32") {
+	return Err(io::Error::new(io::ErrorKind::Unsupported, "screenshots are not supported in the browser"));
+    }
+    let image = get_screen_data();
+    let name = local_now().format("hexagon-screensaver-%Y%m%d-%H%M%S.png").to_string();
+    let path = dirs::picture_dir().unwrap_or_default().join(name);
+    let rgba = flip_rows(&image.bytes, image.width as usize * 4);
+    save_png(&path, image.width as u32, image.height as u32, &rgba)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flip_rows_reverses_row_order() {
+	assert_eq!(flip_rows(&[1, 2, 3, 4, 5, 6], 2), vec![5, 6, 3, 4, 1, 2]);
+    }
+}
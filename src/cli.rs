@@ -0,0 +1,351 @@
+//! Command line parsing for the screensaver flags Windows passes and the
+//! long options used when running it by hand.
+
+use crate::config::{CommandLine, Config};
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: hexagon_screensaver [MODE] [OPTIONS]
+       hexagon_screensaver install [--system] | uninstall
+       hexagon_screensaver config path | export FILE | import FILE
+       hexagon_screensaver bench [OPTIONS]
+
+Modes:
+  /s                     Run the screensaver full screen
+  /c[:HWND]              Show the settings dialog
+  /p HWND, /p:HWND       Draw the preview inside the given window
+  -root                  Draw in the window xscreensaver provides (X11)
+  -window-id ID          Draw inside the given X window, decimal or 0x hex
+  (none)                 Run in a window
+  install                Copy to a .scr and select it as the screensaver
+  uninstall              Remove the installed copies and deselect them
+  config path            Print where the config is kept
+  config export FILE     Save a copy of the config to FILE
+  config import FILE     Check FILE and make it the config
+  bench                  Time software frames at several radii and densities
+
+Options:
+  --system               Install for every user, from an elevated prompt
+  --windowed             Run in a window even with /s
+  --portable             Keep the config in config.toml beside the executable
+  --kiosk                Ignore input except the kiosk_exit keys, for signage
+  --widget               Run on the desktop under other windows, over widget_region
+  --wallpaper            Run behind the desktop icons as a live wallpaper (Windows)
+  --debug-overlay        Show the frame rate and draw counts; F3 in a window
+  --share-texture        Publish frames through Spout or Syphon
+  --profile NAME         Use the [profiles.NAME] settings from the config
+  --record SECONDS       Record the animation to --out (default screensaver.gif)
+  --out FILE             Output of --record; .gif or anything ffmpeg can write
+  --launch               Run full screen after idle_minutes of idle time
+  --tray                 The same from an icon in the tray (Windows)
+  --dump-frames COUNT    Save COUNT frames as PNG files in the current directory
+  --print-config         Print the effective config and exit
+  --timer DURATIONS      Count down in lit shapes, e.g. 25m or 25m,5m to repeat
+  --KEY VALUE            Override any config key for this run, e.g. --shape heart
+  -v, --verbose          Log debug messages too; HEXAGON_LOG sets levels by module
+  -h, --help             Show this help
+  -V, --version          Show the version
+";
+
+/// What the program was asked to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Mode {
+    Windowed,
+    Fullscreen,
+    Configure { owner: Option<isize> },
+    Preview { parent: isize },
+    /// Drawing in an X window for xscreensaver: the given one, or with
+    /// `-root` the one it names in the environment or the virtual root.
+    XScreenSaver { window: Option<u64> },
+    Record { seconds: f32, out: PathBuf },
+    DumpFrames { count: u32 },
+    /// Wait for idle time and run the screensaver full screen each time,
+    /// from a tray icon when `tray`.
+    Launch { tray: bool },
+    /// Copy into place and select as the Windows screensaver, for every
+    /// user when `system`.
+    Install { system: bool },
+    Uninstall,
+    Config(ConfigCommand),
+    /// Desktop decoration: a borderless window under the others, passing
+    /// clicks through and letting the desktop show where it is black.
+    Widget,
+    /// Live wallpaper: drawn behind the desktop icons at `wallpaper_fps`.
+    Wallpaper,
+    /// Time the grid stepped and rendered without a window, with the
+    /// config and overrides as the base of each case.
+    Bench,
+    PrintConfig,
+    Help,
+    Version,
+}
+
+/// What `config` does with the saved settings.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigCommand {
+    Path,
+    Export(PathBuf),
+    Import(PathBuf),
+}
+
+/// Parsed command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cli {
+    pub mode: Mode,
+    /// Stay windowed even in screensaver mode.
+    pub windowed: bool,
+    /// Store the config beside the executable.
+    pub portable: bool,
+    /// Log debug messages too.
+    pub verbose: bool,
+    /// `--profile` selection replacing the saved one.
+    pub profile: Option<String>,
+    /// `--KEY VALUE` config overrides, in order.
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Cli {
+    /// Parse the arguments after the program name.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+	let mut mode = None;
+	// Help, version and config printing work alongside any mode flag
+	let mut info = None;
+	let mut windowed = false;
+	let mut portable = false;
+	let mut verbose = false;
+	let mut system = false;
+	let mut profile = None;
+	let mut record = None;
+	let mut out = None;
+	let mut overrides = Vec::new();
+
+	let mut args = args.iter().peekable();
+	while let Some(arg) = args.next() {
+	    let mut value = |name: &str| args.next().cloned().ok_or_else(|| format!("{name} expects a value"));
+	    let new_mode = match arg.as_str() {
+		"-h" | "--help" | "-V" | "--version" | "--print-config" => {
+		    info = info.or(Some(match arg.as_str() {
+			"-h" | "--help" => Mode::Help,
+			"-V" | "--version" => Mode::Version,
+			_ => Mode::PrintConfig,
+		    }));
+		    None
+		}
+		"--windowed" => {
+		    windowed = true;
+		    None
+		}
+		"--portable" => {
+		    portable = true;
+		    None
+		}
+		"install" => Some(Mode::Install { system: false }),
+		"uninstall" => Some(Mode::Uninstall),
+		"bench" => Some(Mode::Bench),
+		"config" => {
+		    let command = value(arg)?;
+		    Some(Mode::Config(match command.as_str() {
+			"path" => ConfigCommand::Path,
+			"export" => ConfigCommand::Export(PathBuf::from(value("config export")?)),
+			"import" => ConfigCommand::Import(PathBuf::from(value("config import")?)),
+			_ => return Err(format!("unknown config command `{command}`, expected path, export or import")),
+		    }))
+		}
+		"--system" => {
+		    system = true;
+		    None
+		}
+		"-v" | "--verbose" => {
+		    verbose = true;
+		    None
+		}
+		"--kiosk" | "--debug-overlay" | "--share-texture" => {
+		    overrides.push((arg[2..].replace('-', "_"), "true".to_string()));
+		    None
+		}
+		"--record" => {
+		    let seconds = value(arg)?;
+		    record = Some(seconds.parse::<f32>().map_err(|_| format!("--record expects seconds, got `{seconds}`"))?);
+		    None
+		}
+		"--profile" => {
+		    profile = Some(value(arg)?);
+		    None
+		}
+		"--out" => {
+		    out = Some(PathBuf::from(value(arg)?));
+		    None
+		}
+		"-root" => Some(Mode::XScreenSaver { window: None }),
+		// xscreensaver's way of asking for a window of our own
+		"-window" => Some(Mode::Windowed),
+		"-window-id" => {
+		    let id = value(arg)?;
+		    let window = parse_window_id(&id).ok_or_else(|| format!("-window-id expects a window id, got `{id}`"))?;
+		    Some(Mode::XScreenSaver { window: Some(window) })
+		}
+		"--launch" => Some(Mode::Launch { tray: false }),
+		"--widget" => Some(Mode::Widget),
+		"--wallpaper" => Some(Mode::Wallpaper),
+		"--tray" => Some(Mode::Launch { tray: true }),
+		"--dump-frames" => {
+		    let count = value(arg)?;
+		    let count = count.parse().map_err(|_| format!("--dump-frames expects a frame count, got `{count}`"))?;
+		    Some(Mode::DumpFrames { count })
+		}
+		long if long.starts_with("--") => {
+		    let key = long[2..].replace('-', "_");
+		    if !Config::is_key(&key) {
+			return Err(format!("unknown option `{long}`"));
+		    }
+		    let raw = value(arg)?;
+		    // Catch unknown keys and bad values before any window opens
+		    Config::default().with_override(&key, &raw)?;
+		    overrides.push((key, raw));
+		    None
+		}
+		flag => {
+		    let next_hwnd = args.peek().and_then(|next| next.trim().parse::<isize>().ok());
+		    let parsed = parse_mode_flag(flag, next_hwnd).ok_or_else(|| format!("unknown argument `{flag}`"))?;
+		    if parsed.1 {
+			args.next();
+		    }
+		    Some(parsed.0)
+		}
+	    };
+	    if let Some(new_mode) = new_mode {
+		if mode.is_some() {
+		    return Err(format!("`{arg}` conflicts with an earlier mode"));
+		}
+		mode = Some(new_mode);
+	    }
+	}
+
+	if system {
+	    match &mut mode {
+		Some(Mode::Install { system }) => *system = true,
+		_ => return Err("--system requires install".to_string()),
+	    }
+	}
+	if let Some(info) = info {
+	    return Ok(Self { mode: info, windowed, portable, verbose, profile, overrides });
+	}
+	let mode = match (record, mode) {
+	    (Some(seconds), None) => Mode::Record { seconds, out: out.unwrap_or_else(|| PathBuf::from("screensaver.gif")) },
+	    (Some(_), Some(_)) => return Err("--record cannot be combined with another mode".to_string()),
+	    (None, _) if out.is_some() => return Err("--out requires --record".to_string()),
+	    (None, mode) => mode.unwrap_or(Mode::Windowed),
+	};
+	Ok(Self { mode, windowed, portable, verbose, profile, overrides })
+    }
+
+    /// The saved config with the selected profile and the command line
+    /// overrides applied.
+    pub fn config(&self) -> Config {
+	self.command_line().apply(Config::load())
+    }
+
+    /// The `--profile` and `--KEY VALUE` arguments, for reloads to apply
+    /// again.
+    pub fn command_line(&self) -> CommandLine {
+	CommandLine { profile: self.profile.clone(), overrides: self.overrides.clone() }
+    }
+}
+
+/// An X window id, in decimal or as `0x` hex the way xscreensaver passes them.
+pub(crate) fn parse_window_id(id: &str) -> Option<u64> {
+    let id = id.trim();
+    match id.strip_prefix("0x").or_else(|| id.strip_prefix("0X")) {
+	Some(hex) => u64::from_str_radix(hex, 16).ok(),
+	None => id.parse().ok(),
+    }
+}
+
+/// Parse a `/s`, `/c[:hwnd]` or `/p[:hwnd]` style flag, also accepted with a
+/// dash and in any case. `next_hwnd` is the following argument if it is a
+/// number; the flag consumes it when the HWND is not attached with a colon,
+/// which is reported by the returned bool.
+fn parse_mode_flag(flag: &str, next_hwnd: Option<isize>) -> Option<(Mode, bool)> {
+    let lower = flag.to_ascii_lowercase();
+    let rest = lower.strip_prefix(['/', '-'])?;
+    let mut chars = rest.chars();
+    let letter = chars.next()?;
+    let attached = match chars.as_str() {
+	"" => None,
+	hwnd => Some(hwnd.strip_prefix(':')?.trim().parse::<isize>().ok()?),
+    };
+    let (hwnd, consumed) = match attached {
+	Some(hwnd) => (Some(hwnd), false),
+	None => (next_hwnd, next_hwnd.is_some()),
+    };
+    match letter {
+	's' if attached.is_none() => Some((Mode::Fullscreen, false)),
+	'c' => Some((Mode::Configure { owner: hwnd }, consumed)),
+	'p' => Some((Mode::Preview { parent: hwnd? }, consumed)),
+	_ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(list: &[&str]) -> Result<Cli, String> {
+	Cli::parse(&list.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn mode_flags_accept_both_hwnd_forms() {
+	assert_eq!(parse(&["/p", "1234"]).unwrap().mode, Mode::Preview { parent: 1234 });
+	assert_eq!(parse(&["/P:5678"]).unwrap().mode, Mode::Preview { parent: 5678 });
+	assert_eq!(parse(&["/c:42"]).unwrap().mode, Mode::Configure { owner: Some(42) });
+	assert_eq!(parse(&["-c"]).unwrap().mode, Mode::Configure { owner: None });
+	assert_eq!(parse(&["/S"]).unwrap().mode, Mode::Fullscreen);
+	assert_eq!(parse(&[]).unwrap().mode, Mode::Windowed);
+	assert_eq!(parse(&["/s", "--print-config"]).unwrap().mode, Mode::PrintConfig);
+	assert!(parse(&["/p"]).is_err());
+	assert!(parse(&["/x"]).is_err());
+    }
+
+    #[test]
+    fn xscreensaver_flags_pick_the_window() {
+	assert_eq!(parse(&["-root"]).unwrap().mode, Mode::XScreenSaver { window: None });
+	assert_eq!(parse(&["-window-id", "0x2a00007"]).unwrap().mode, Mode::XScreenSaver { window: Some(0x2a00007) });
+	assert_eq!(parse(&["-window-id", "1234"]).unwrap().mode, Mode::XScreenSaver { window: Some(1234) });
+	assert_eq!(parse(&["-window"]).unwrap().mode, Mode::Windowed);
+	assert!(parse(&["-window-id", "root"]).is_err());
+	assert!(parse(&["-root", "/s"]).is_err());
+	assert!(parse(&["-root", "-v"]).unwrap().verbose);
+	assert_eq!(parse(&["--launch", "--shape", "heart"]).unwrap().mode, Mode::Launch { tray: false });
+	assert_eq!(parse(&["--tray"]).unwrap().mode, Mode::Launch { tray: true });
+	assert_eq!(parse(&["--widget", "--shape", "heart"]).unwrap().mode, Mode::Widget);
+	assert_eq!(parse(&["--wallpaper"]).unwrap().mode, Mode::Wallpaper);
+	assert_eq!(parse(&["install", "--system"]).unwrap().mode, Mode::Install { system: true });
+	assert_eq!(parse(&["uninstall"]).unwrap().mode, Mode::Uninstall);
+	assert_eq!(parse(&["bench", "--shape", "heart"]).unwrap().mode, Mode::Bench);
+	assert_eq!(parse(&["config", "export", "backup.toml"]).unwrap().mode, Mode::Config(ConfigCommand::Export(PathBuf::from("backup.toml"))));
+	assert_eq!(parse(&["config", "path"]).unwrap().mode, Mode::Config(ConfigCommand::Path));
+	assert!(parse(&["config", "import"]).is_err());
+	assert!(parse(&["config", "delete"]).is_err());
+	assert!(parse(&["/s", "--system"]).is_err());
+	assert!(parse(&["--launch", "/s"]).is_err());
+    }
+
+    #[test]
+    fn long_flags_override_config_keys() {
+	let cli = parse(&["/s", "--shape", "heart", "--threshold", "0.95", "--windowed"]).unwrap();
+	assert_eq!(cli.mode, Mode::Fullscreen);
+	assert!(cli.windowed);
+	assert_eq!(cli.overrides, vec![("shape".to_string(), "heart".to_string()), ("threshold".to_string(), "0.95".to_string())]);
+	assert!(parse(&["--threshold", "high"]).is_err());
+	assert!(parse(&["--no-such-key", "1"]).is_err());
+	assert!(parse(&["--out", "a.gif"]).is_err());
+	assert_eq!(parse(&["/s", "--kiosk"]).unwrap().overrides, vec![("kiosk".to_string(), "true".to_string())]);
+	assert_eq!(parse(&["--debug-overlay"]).unwrap().overrides, vec![("debug_overlay".to_string(), "true".to_string())]);
+	assert_eq!(parse(&["--share-texture"]).unwrap().overrides, vec![("share_texture".to_string(), "true".to_string())]);
+	assert_eq!(
+	    parse(&["--record", "2", "--out", "a.mp4"]).unwrap().mode,
+	    Mode::Record { seconds: 2.0, out: PathBuf::from("a.mp4") }
+	);
+    }
+}
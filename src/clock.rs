@@ -0,0 +1,65 @@
+//! The current time spelled out in lit shapes, laid over each display.
+
+use crate::font::{GLYPH_HEIGHT, light_pixels, rasterize_text};
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+
+/// Share of a display's width and height the text may take.
+const TEXT_SHARE: Vec2 = Vec2::new(0.6, 0.4);
+
+/// The local date and time; a page asks the browser.
+pub(crate) fn local_now() -> chrono::NaiveDateTime {
+    #[cfg(target_arch = "wasm32")]
+    {
+	let seconds = miniquad::date::now() as i64 + i64::from(crate::web::utc_offset());
+	chrono::DateTime::from_timestamp(seconds, 0).unwrap_or_default().naive_utc()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    chrono::Local::now().naive_local()
+}
+
+/// The local time as the clock shows it, `HH:MM`.
+pub(crate) fn current_text() -> String {
+    local_now().format("%H:%M").to_string()
+}
+
+/// Mark the `shapes` under `text`, centered on each of `displays` as large
+/// as fits, as lit and every other shape as not. Font pixels are at least
+/// `cell_radius * 2` wide so each one covers a shape.
+pub(crate) fn light_text(shapes: &mut [Shape], displays: &[Rect], text: &str, cell_radius: f32) {
+    let pixels = rasterize_text(text);
+    let size = Vec2::new(pixels[0].len() as f32, GLYPH_HEIGHT as f32);
+    for shape in shapes.iter_mut() {
+	shape.lit = false;
+    }
+    if size.x == 0.0 {
+	return;
+    }
+    for display in displays {
+	let pixel = (display.size() * TEXT_SHARE / size).min_element().max(cell_radius * 2.0);
+	light_pixels(shapes, *display, &pixels, display.center() - size * pixel / 2.0, pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+    use macroquad::rand::RandGenerator;
+
+    #[test]
+    fn shapes_under_the_text_are_lit() {
+	let (palette, rng) = (Palette::random(), RandGenerator::new());
+	let display = Rect::new(0.0, 0.0, 170.0, 50.0);
+	// Shapes 10 pixels apart, the smallest font pixel at that radius
+	let mut shapes: Vec<Shape> = (0..17)
+	    .flat_map(|x| (0..5).map(move |y| (x, y)))
+	    .map(|(x, y)| Shape::new(x as f32 * 10.0 + 5.0, y as f32 * 10.0 + 5.0, 4.0, false, &palette, &rng))
+	    .collect();
+	light_text(&mut shapes, &[display], "8", 5.0);
+	let lit: Vec<(f32, f32)> = shapes.iter().filter(|shape| shape.lit).map(|shape| (shape.x, shape.y)).collect();
+	// All of the 8 but its two holes, in the middle three columns
+	assert_eq!(lit.len(), 13);
+	assert!(lit.iter().all(|(x, _)| (75.0..=95.0).contains(x)));
+    }
+}
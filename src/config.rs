@@ -0,0 +1,1826 @@
+use crate::attract::Mode;
+use crate::automaton::Rule;
+use crate::easing::Easing;
+use crate::flow::Motion;
+use crate::input::{key_name, parse_key_combo, parse_key_names};
+use crate::kaleidoscope::{MAX_WEDGES, valid_wedges};
+use crate::layout::{Layout, Orientation};
+use crate::live::DEFAULT_MAP;
+use crate::palette::{ColorMode, Harmony, parse_gradient, parse_hex_color};
+use crate::pattern::{Pattern, TWINKLE_SPEED};
+use crate::post::PostEffect;
+use crate::prisms::RenderMode;
+use crate::qr;
+use crate::reaction::PRESETS;
+use crate::sync::{DEFAULT_GROUP, parse_group};
+use crate::timer::parse_periods;
+use crate::turmite::parse_rule;
+use chrono::NaiveTime;
+use log::{info, warn};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::SystemTime;
+
+/// Set by `--portable`; a `portable.txt` beside the executable also counts.
+static PORTABLE: AtomicBool = AtomicBool::new(false);
+
+/// What the command line changed, applied again whenever the file reloads.
+static COMMAND_LINE: Mutex<CommandLine> = Mutex::new(CommandLine { profile: None, overrides: Vec::new() });
+
+/// Version of the layout `serialize` writes. Configs from before there was
+/// a version count as 1.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// The upgrades from each version to the next, starting from 1.
+const MIGRATIONS: [fn(&mut toml::Table); CONFIG_VERSION as usize - 1] = [density_from_threshold];
+
+/// User settings, persisted as TOML in the per-user config directory.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The `CONFIG_VERSION` the settings were written for, so older ones can
+    /// be upgraded.
+    pub version: u32,
+    /// Name of a shape in the `ShapeRegistry`, or `mix:heart,star=2,...`
+    /// to give each cell one of several, as likely as their weights.
+    pub shape: String,
+    /// A built-in palette name, `random`, or hex colors such as
+    /// `"#ff0066,#00ffcc,#ffffff"`.
+    pub palette: String,
+    /// PNG or TGA to take the palette from instead; empty to use `palette`.
+    pub palette_image: String,
+    /// Lowest and highest saturation and value of `random` colors, each
+    /// 0..1: `[0.2, 0.4]` and `[0.9, 1.0]` for pastels, `[0.8, 1.0]` and
+    /// `[0.8, 1.0]` for vivid colors, any saturation and `[0.2, 0.5]` for
+    /// dark ones.
+    #[serde(with = "vec2_array")]
+    pub random_saturation: Vec2,
+    #[serde(with = "vec2_array")]
+    pub random_value: Vec2,
+    /// `random` colors per shape, a `gradient` or `radial` color field,
+    /// `bands` of the palette's colors top to bottom like a flag, one hue
+    /// slowly shared by all in `synced_cycle`, colors in a `harmony`,
+    /// `image` to paint a mosaic of `color_image`, or `webcam` for a live
+    /// mirror. `image:<path>` sets both.
+    pub color_mode: ColorMode,
+    /// Seconds the `synced_cycle` color mode takes around the color wheel.
+    pub color_cycle_period: f32,
+    /// The rule of the `harmony` color mode, and the minutes it keeps each
+    /// base hue.
+    pub harmony: Harmony,
+    pub harmony_minutes: f32,
+    /// PNG or TGA the `image` color mode paints.
+    pub color_image: String,
+    /// Camera the `webcam` color mode films, as ffmpeg names it; empty for
+    /// the default one.
+    pub webcam_device: String,
+    /// Degrees every color is turned around the color wheel.
+    pub hue_shift: f32,
+    /// How many shapes show, 0..1, the inverse of the `threshold()` their
+    /// wave has to rise above: at 1 each is lit about half the time, at 0
+    /// never. Older configs stored the threshold.
+    pub density: f32,
+    /// Share of each twinkle spent at full brightness, 0..0.95, the rest
+    /// fading in and out.
+    pub twinkle_duty: f32,
+    /// How far apart in time shapes twinkle, 0..1: at 0 the `random`
+    /// pattern lights them all together and the `wave` becomes one pulse.
+    pub twinkle_stagger: f32,
+    /// Shapes blinking on their own at `random`, sweeping in a `wave`, lit
+    /// by rings spreading out in a `ripple`, in drifting `noise` clouds, as
+    /// the cells of a `life`-like automaton, in the trails of an `ant`, by a
+    /// `reaction`-diffusion simulation, as falling `rain`, by the load of
+    /// each CPU core and memory in `monitor`, or as a `script` has it.
+    pub pattern: Pattern,
+    /// Shape radii per second the rings of the `ripple` pattern spread.
+    pub ripple_speed: f32,
+    /// How fast ripples fade; each second scales them by e^-decay.
+    pub ripple_decay: f32,
+    /// Size of the regions of the `noise` pattern, in shape radii.
+    pub noise_scale: f32,
+    /// How fast the `noise` pattern's regions change.
+    pub noise_speed: f32,
+    /// Seconds per generation of the `life` pattern.
+    pub life_tick: f32,
+    /// Rule of the `life` pattern's cellular automaton: birth and survival
+    /// neighbor counts such as `B2/S34`, `cyclic` or `cyclic:N` for N
+    /// states, or `brain` for Brian's Brain.
+    pub automaton: String,
+    /// How many ants walk the cells in the `ant` pattern.
+    pub ants: u32,
+    /// Turns of the `ant` pattern's ants on cells in each state: `N` goes
+    /// on, `U` turns around, and `L` or `R` turn by the following count of
+    /// neighbors, 1 when left out. Langton's ant is `RL`.
+    pub ant_rule: String,
+    /// Preset of the `reaction` pattern: `coral`, `mitosis`, `maze`,
+    /// `waves`, or `custom` to use `feed` and `kill`.
+    pub reaction: String,
+    /// Rates the `reaction` pattern's first chemical is fed at and its
+    /// second one removed at.
+    pub feed: f32,
+    pub kill: f32,
+    /// `#rrggbb` color of the `rain` pattern's drops.
+    pub rain_color: String,
+    /// File of the `script` pattern, looked up in the config directory.
+    pub script: String,
+    pub static_color: bool,
+    #[serde(with = "key_set")]
+    pub ignore_keys: HashSet<KeyCode>,
+    /// Seconds after starting during which input doesn't dismiss the
+    /// screensaver.
+    pub exit_grace: f32,
+    /// Pixels the mouse has to move to dismiss the screensaver.
+    pub exit_distance: f32,
+    /// Which other input dismisses the screensaver.
+    pub exit_on: ExitTriggers,
+    /// Lock the session once dismissed, as Windows' "On resume, display
+    /// logon screen" does.
+    pub lock_on_exit: bool,
+    /// Hide the mouse cursor while the screensaver runs.
+    pub hide_cursor: bool,
+    /// Light up the shapes within `spotlight_radius` pixels of the mouse,
+    /// the more the closer, when run in a window. Moving the mouse no
+    /// longer closes it then.
+    pub spotlight: bool,
+    pub spotlight_radius: f32,
+    /// Spread a ring from each click when run in a window, lighting the
+    /// shapes it passes over and turning their hues. Clicks no longer
+    /// close it then.
+    pub click_rings: bool,
+    /// Keep running through any input but `kiosk_exit`, for signage.
+    pub kiosk: bool,
+    /// The key combination leaving kiosk mode, such as `ctrl+shift+q`.
+    pub kiosk_exit: String,
+    /// When set, input opens a prompt over the animation and only typing
+    /// this passphrase quits. It is kept in plain text, so protect the
+    /// config file.
+    pub exit_passphrase: String,
+    /// Leave the kiosk window out of Alt-Tab and the taskbar (Windows).
+    pub kiosk_hide: bool,
+    /// Part of the desktop `--widget` covers across every monitor, as
+    /// fractions of it: `[x, y, width, height]`.
+    pub widget_region: [f32; 4],
+    pub focus: bool,
+    #[serde(with = "vec2_array")]
+    pub focus_pos: Vec2,
+    /// Keep the shapes spelling out the local time, `HH:MM`, lit in the
+    /// middle of each display.
+    pub clock: bool,
+    /// Scroll this text across each display in lit shapes, such as
+    /// `BACK IN 5 MINUTES`; empty for none. Takes the place of the clock.
+    pub message: String,
+    /// Font pixels a second the message scrolls by.
+    pub message_speed: f32,
+    /// Show the weekday and date, faintly, in `date_corner` of each display.
+    pub date_overlay: bool,
+    /// The corner of the date and weather readouts.
+    pub date_corner: Corner,
+    /// Where to fetch the weather from every half hour to theme the
+    /// animation by, answering like Open-Meteo's
+    /// `current=temperature_2m,weather_code`; empty for none.
+    pub weather_url: String,
+    /// Show the temperature in `date_corner` while `weather_url` is set.
+    pub weather_overlay: bool,
+    /// Show the track the system's media player is playing in
+    /// `date_corner` (Linux and Windows).
+    pub media_overlay: bool,
+    /// Take the palette from the playing track's album art, when the
+    /// player keeps it in a PNG file (Linux).
+    pub media_tint: bool,
+    /// RSS or Atom feed URLs and text files whose headlines, or lines,
+    /// scroll along the bottom of each display; empty for no ticker.
+    pub ticker: Vec<String>,
+    /// Display heights a second the ticker scrolls by, so it reads the same
+    /// on any screen.
+    pub ticker_speed: f32,
+    /// Count down these durations in turn as a bar of lit shapes, such as
+    /// `25m` or `25m, 5m` for Pomodoro work and breaks; empty for none.
+    /// Takes the place of the message and clock.
+    pub timer: String,
+    /// `#rrggbb` the screen pulses with as each period runs out.
+    pub timer_color: String,
+    /// Text shown as a QR code in the middle of each display, such as a URL
+    /// or `WIFI:S:name;T:WPA;P:password;;` for guests; empty for none.
+    /// Takes the place of the timer, message and clock.
+    pub qr: String,
+    pub audio_reactive: bool,
+    /// Rotation of the whole grid around the screen center, in degrees.
+    pub grid_rotation: f32,
+    /// Degrees per second each shape spins around its center.
+    pub rotation_speed: f32,
+    /// Spin a random half of the shapes the other way.
+    pub random_spin: bool,
+    /// How much larger shapes grow as they fade in, 0.1 being 10%.
+    pub breathing: f32,
+    /// Curve of the shapes' fades in and out: `linear`, `quad`, `cubic`,
+    /// `sine`, `elastic` or `bounce`.
+    pub fade_easing: Easing,
+    /// Curve of the transitions between colors, from the same choices.
+    pub color_easing: Easing,
+    /// Seconds each transition to the next color takes.
+    pub color_transition_seconds: f32,
+    /// Seconds a shape stays on a color before fading to the next; 0 to
+    /// keep fading.
+    pub color_hold_seconds: f32,
+    /// Seconds the `morph` shape takes to turn into a heart and back.
+    pub morph_period: f32,
+    /// `hex`, `square`, `triangle`, `voronoi` or `penrose` cells to place the
+    /// shapes in.
+    pub layout: Layout,
+    /// `grid` to keep the shapes in their cells, `flow` for them to wander
+    /// along a slowly changing field at up to `flow_speed` pixels a
+    /// second, its eddies `flow_scale` shape radii across, or `fall` for
+    /// lit ones to drop out of their cells now and then.
+    pub motion: Motion,
+    pub flow_speed: f32,
+    pub flow_scale: f32,
+    /// `flat` or `pointy` topped hexagons, and the grid to match.
+    pub orientation: Orientation,
+    /// How round the corners of hexagons, tiles and `polygon:` and `star:`
+    /// shapes are, as a share of their radius; 0 for sharp corners.
+    pub corner_radius: f32,
+    /// How finely hearts are outlined for the size they are drawn at, 1
+    /// keeping their edges within about a quarter pixel of the curve.
+    pub shape_quality: f32,
+    /// Multiplier on how fast the animation runs.
+    pub speed: f32,
+    /// Keep shapes from flashing more than three times a second, however
+    /// fast the animation, for photosensitive viewers. Turn off only on
+    /// purpose.
+    pub flash_limit: bool,
+    /// Slow everything right down and stop spinning and pulsing, for
+    /// viewers sensitive to motion. Also on while the system asks for
+    /// reduced motion.
+    pub reduced_motion: bool,
+    /// Frames per second to draw at most, to save power; 0 for no limit.
+    pub max_fps: f32,
+    /// Frame rate cap as a `--wallpaper`, on top of `max_fps`.
+    pub wallpaper_fps: f32,
+    /// Wait for the display's refresh between frames. Takes effect on the
+    /// next start.
+    pub vsync: bool,
+    /// Samples per pixel smoothing the edges of everything drawn, 1, 2, 4,
+    /// 8 or 16; 1 for none. Takes effect on the next start, and not inside
+    /// the post effects, trails or kaleidoscope, which draw into textures.
+    pub samples: u32,
+    /// Smooth edges in a shader instead, for graphics that offer no
+    /// multisampling.
+    pub smooth_edges: bool,
+    /// Show the frame rate, a graph of frame times and how many shapes and
+    /// meshes are drawn; F3 toggles it in a window.
+    pub debug_overlay: bool,
+    /// Publish every frame as a shared texture named "Hexagon Screensaver",
+    /// through Spout on Windows or Syphon on macOS, for OBS and VJ software
+    /// to take as a source. Needs a build with the `share_texture` feature.
+    pub share_texture: bool,
+    /// Port of the HTTP control API on localhost, such as 8686, for
+    /// changing how it looks and setting off effects from scripts; 0 for
+    /// none.
+    pub control_port: u16,
+    /// UDP port to take OSC messages on, such as 9000 for a VJ controller
+    /// app; 0 for none.
+    pub osc_port: u16,
+    /// Take OSC from other machines on the network too, such as a phone
+    /// running the controller, rather than only from this one.
+    pub osc_network: bool,
+    /// MIDI input to take controllers from, by a part of its name, or
+    /// `first`; empty for none.
+    pub midi_device: String,
+    /// Seconds live input takes to glide most of the way to a new value;
+    /// 0 to jump.
+    pub live_smoothing: f32,
+    /// What OSC addresses and MIDI `cc:N` and `note:N` numbers control:
+    /// `density`, `speed` or `hue_shift`, with the range their 0..1 values
+    /// span such as `speed:0.5..4`, or a `ring`, `pause`, `resume` or
+    /// `screenshot` on a nonzero value. A ring starts at the point two
+    /// values give.
+    pub live_map: BTreeMap<String, String>,
+    /// Keep several machines in step, such as for a wall of monitors: the
+    /// `master` sends its animation time, seed and palette to `sync_group`
+    /// and each `follower` locks to it; `off` for none.
+    pub sync: SyncRole,
+    /// The multicast address and port the instances in step share.
+    pub sync_group: String,
+    /// Animate the grid in a shader so the CPU only advances the clock, for
+    /// very large grids. Only the `random` pattern with random colors and
+    /// no spinning, morphing, focus or audio runs there; each shape then
+    /// fades back and forth between two colors.
+    pub gpu_animation: bool,
+    /// How to save power while running on battery.
+    pub battery: BatterySaver,
+    /// Shape radius in pixels; previews scale it down to match their size.
+    pub radius: f32,
+    /// Pixels between the edges of neighboring shapes, at `radius` scale.
+    pub gap: f32,
+    /// How far each shape's radius strays at random from `radius`, up and
+    /// down, as a share of it: 0.3 for 70% to 130%. The grid keeps its
+    /// spacing.
+    pub radius_jitter: f32,
+    /// Pixels, at `radius` scale, to slowly drift the whole grid by against
+    /// burn-in on OLED and plasma screens; 0 keeps it still.
+    pub pixel_shift: f32,
+    /// Slowly zoom the view between 90% and 110% and turn it a few degrees
+    /// either way over minutes, so it never sits quite still. Off with
+    /// `reduced_motion`.
+    pub camera_drift: bool,
+    /// `#rrggbb` color behind the shapes.
+    pub background: String,
+    /// `#top,#bottom` colors of a vertical gradient behind the shapes,
+    /// drawn over `background`; empty for none.
+    pub background_gradient: String,
+    /// PNG or TGA shown behind the shapes, over the background colors;
+    /// empty for none.
+    pub background_image: String,
+    /// How `background_image` fits each display.
+    pub background_fit: ImageFit,
+    /// Folder of PNG or TGA photos the lit shapes show instead of their
+    /// colors, revealing a photo as they fade in; empty for colors.
+    pub photo_folder: String,
+    /// Minutes each photo shows before the next.
+    pub photo_minutes: f32,
+    /// Let lit shapes glow onto their surroundings.
+    pub bloom: bool,
+    /// Brightness of the glow, 1 adding about as much light again.
+    pub bloom_intensity: f32,
+    /// Pixels, at `radius` scale, the glow spreads.
+    pub bloom_radius: f32,
+    /// Leave trails behind the shapes, fading by this much each frame,
+    /// 0..1; 0 for none.
+    pub trails: f32,
+    /// A look given to the whole frame: `crt` for an old tube screen,
+    /// `gameboy`, `c64` or `ega` for dithered pixels in their colors, or
+    /// `file:NAME.glsl` for a fragment shader in the config directory.
+    pub post_effect: PostEffect,
+    /// `2d`; `isometric` to tilt the grid back into a surface each shape
+    /// rises from as it shows; or `3d` to stand each shape up as a prism as
+    /// tall as it is visible, lit from above and seen by a slowly circling
+    /// camera. The glow and post effects do not draw in `3d`.
+    pub render_mode: RenderMode,
+    /// Fold each display into this many mirrored wedges around its center,
+    /// an even number up to 16, or 0 not to. Not drawn in `3d`.
+    pub kaleidoscope: u32,
+    /// Draw drifting stars behind the shapes.
+    pub stars: bool,
+    /// Stars per 100x100 pixels.
+    pub star_density: f32,
+    /// Pixels per second the nearest stars drift; farther ones are slower.
+    pub star_speed: f32,
+    /// Minutes of idle time before `--launch` or `--tray` start the
+    /// screensaver.
+    pub idle_minutes: f32,
+    /// Seconds to hold a black screen before the animation starts.
+    pub start_delay: f32,
+    /// Reveal the shapes at launch along a spiral out from each display's
+    /// center, instead of all at once.
+    pub spiral_start: bool,
+    /// Seconds to fade in from the desktop at launch, from black where the
+    /// window cannot show through.
+    pub fade_in: f32,
+    /// Seconds to fade out for once dismissed, before quitting.
+    pub fade_out: f32,
+    /// What to fade out to: `black`, or `desktop`, which is black where the
+    /// window cannot show through.
+    pub fade_to: Fade,
+    /// Seed for every random choice, making runs reproducible; 0 picks a
+    /// new seed each run.
+    pub seed: u64,
+    /// Warm and dim colors at night, following the hours and color
+    /// temperatures below.
+    pub temperature_shift: bool,
+    /// Color temperatures in Kelvin; 6500 leaves colors unchanged.
+    pub day_temperature: f32,
+    pub night_temperature: f32,
+    /// Local hours, 0..24, that the night settings start and end.
+    pub sunset_hour: f32,
+    pub sunrise_hour: f32,
+    /// Brightness multiplier at night.
+    pub night_brightness: f32,
+    /// Minutes of running after which everything starts dimming; 0 never
+    /// dims.
+    pub dim_after_minutes: f32,
+    /// Brightness multiplier dimmed down to.
+    pub dim_to: f32,
+    /// Brightness multiplier over everything, such as for a dimmer
+    /// `schedule` entry at night.
+    pub dim: f32,
+    /// `attract` for a new shape, palette and pattern every
+    /// `attract_minutes`, fading from one look into the next; `normal` to
+    /// keep the ones set.
+    pub mode: Mode,
+    pub attract_minutes: f32,
+    /// Pick the shape, palette, density and pattern at random on each
+    /// launch, from the lists below or from every choice where empty.
+    pub surprise: bool,
+    pub surprise_shapes: Vec<String>,
+    pub surprise_palettes: Vec<String>,
+    pub surprise_patterns: Vec<Pattern>,
+    /// Switch to the season's shape and palette at launch: snowflakes in
+    /// December, hearts in mid-February, pumpkins in late October.
+    pub auto_theme: bool,
+    /// Language of the settings dialog, such as `de` or `ja`; empty to
+    /// follow the system.
+    pub language: String,
+    /// Where the settings are kept: the `file`, or with `registry` the
+    /// values under `HKCU\Software\HexagonScreensaver`, which group policy
+    /// can deploy. Either one asking for the registry selects it.
+    pub config_backend: ConfigBackend,
+    /// Name of the `[profiles.<name>]` table applied on top of the settings
+    /// above; empty for none.
+    pub profile: String,
+    /// Named sets of settings overriding the ones above, selected with
+    /// `profile` or `--profile`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, toml::Table>,
+    /// Settings for times of day, as `[schedule.<name>]` tables of a
+    /// `from` time such as `"22:00"`, an optional `to`, and the settings
+    /// overriding the ones above. Each lasts until its `to` or the next
+    /// one's `from`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub schedule: BTreeMap<String, toml::Table>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+	Self {
+	    version: CONFIG_VERSION,
+	    shape: "hexagon".to_string(),
+	    palette: "random".to_string(),
+	    palette_image: String::new(),
+	    random_saturation: Vec2::new(0.0, 1.0),
+	    random_value: Vec2::new(0.0, 1.0),
+	    color_mode: ColorMode::default(),
+	    color_cycle_period: 120.0,
+	    harmony: Harmony::default(),
+	    harmony_minutes: 3.0,
+	    color_image: String::new(),
+	    webcam_device: String::new(),
+	    hue_shift: 0.0,
+	    density: 1.0,
+	    twinkle_duty: 0.0,
+	    twinkle_stagger: 1.0,
+	    pattern: Pattern::default(),
+	    ripple_speed: 8.0,
+	    ripple_decay: 0.5,
+	    noise_scale: 10.0,
+	    noise_speed: 0.2,
+	    life_tick: 0.5,
+	    automaton: "B2/S34".to_string(),
+	    ants: 3,
+	    ant_rule: "L2NNL1L2L1".to_string(),
+	    reaction: "coral".to_string(),
+	    feed: 0.0545,
+	    kill: 0.062,
+	    rain_color: "#00ff41".to_string(),
+	    script: "pattern.script".to_string(),
+	    static_color: false,
+	    ignore_keys: HashSet::new(),
+	    exit_grace: 1.0,
+	    exit_distance: 10.0,
+	    exit_on: ExitTriggers::default(),
+	    lock_on_exit: false,
+	    hide_cursor: true,
+	    spotlight: false,
+	    spotlight_radius: 150.0,
+	    click_rings: false,
+	    kiosk: false,
+	    kiosk_exit: "ctrl+shift+q".to_string(),
+	    exit_passphrase: String::new(),
+	    kiosk_hide: false,
+	    widget_region: [0.0, 0.0, 1.0, 1.0],
+	    focus: false,
+	    focus_pos: Vec2::new(0.5, 0.5),
+	    clock: false,
+	    message: String::new(),
+	    message_speed: 8.0,
+	    date_overlay: false,
+	    date_corner: Corner::BottomRight,
+	    weather_url: String::new(),
+	    weather_overlay: true,
+	    media_overlay: false,
+	    media_tint: false,
+	    ticker: Vec::new(),
+	    ticker_speed: 0.1,
+	    timer: String::new(),
+	    timer_color: "#ffb060".to_string(),
+	    qr: String::new(),
+	    audio_reactive: false,
+	    grid_rotation: 0.0,
+	    rotation_speed: 0.0,
+	    random_spin: false,
+	    breathing: 0.0,
+	    fade_easing: Easing::Quad,
+	    color_easing: Easing::Linear,
+	    // The fixed pace transitions had before they could be set
+	    color_transition_seconds: 1.0 / 0.3,
+	    color_hold_seconds: 0.0,
+	    morph_period: 6.0,
+	    layout: Layout::default(),
+	    motion: Motion::default(),
+	    flow_speed: 20.0,
+	    flow_scale: 15.0,
+	    orientation: Orientation::default(),
+	    corner_radius: 0.0,
+	    shape_quality: 1.0,
+	    speed: 1.0,
+	    flash_limit: true,
+	    reduced_motion: false,
+	    max_fps: 0.0,
+	    wallpaper_fps: 15.0,
+	    vsync: true,
+	    samples: 4,
+	    smooth_edges: false,
+	    debug_overlay: false,
+	    share_texture: false,
+	    control_port: 0,
+	    osc_port: 0,
+	    osc_network: false,
+	    midi_device: String::new(),
+	    live_smoothing: 0.25,
+	    live_map: DEFAULT_MAP.iter().map(|(key, target)| (key.to_string(), target.to_string())).collect(),
+	    sync: SyncRole::Off,
+	    sync_group: DEFAULT_GROUP.to_string(),
+	    gpu_animation: false,
+	    battery: BatterySaver::default(),
+	    radius: 40.0,
+	    gap: 0.0,
+	    radius_jitter: 0.0,
+	    pixel_shift: 0.0,
+	    camera_drift: false,
+	    background: "#000000".to_string(),
+	    background_gradient: String::new(),
+	    background_image: String::new(),
+	    background_fit: ImageFit::Cover,
+	    photo_folder: String::new(),
+	    photo_minutes: 5.0,
+	    bloom: false,
+	    bloom_intensity: 0.8,
+	    bloom_radius: 12.0,
+	    trails: 0.0,
+	    post_effect: PostEffect::None,
+	    render_mode: RenderMode::Flat,
+	    kaleidoscope: 0,
+	    stars: false,
+	    star_density: 1.0,
+	    star_speed: 30.0,
+	    idle_minutes: 5.0,
+	    start_delay: 0.0,
+	    spiral_start: false,
+	    fade_in: 1.5,
+	    fade_out: 1.0,
+	    fade_to: Fade::Black,
+	    seed: 0,
+	    temperature_shift: false,
+	    day_temperature: 6500.0,
+	    night_temperature: 3400.0,
+	    sunset_hour: 20.0,
+	    sunrise_hour: 7.0,
+	    night_brightness: 0.7,
+	    dim_after_minutes: 0.0,
+	    dim_to: 0.2,
+	    dim: 1.0,
+	    mode: Mode::Normal,
+	    attract_minutes: 5.0,
+	    surprise: false,
+	    surprise_shapes: Vec::new(),
+	    surprise_palettes: Vec::new(),
+	    surprise_patterns: Vec::new(),
+	    auto_theme: false,
+	    language: String::new(),
+	    config_backend: ConfigBackend::File,
+	    profile: String::new(),
+	    profiles: BTreeMap::new(),
+	    schedule: BTreeMap::new(),
+	}
+    }
+}
+
+/// The `[exit_on]` section: kinds of input that dismiss the screensaver.
+/// Escape always does unless it is in `ignore_keys`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExitTriggers {
+    /// Any key not in `ignore_keys`.
+    pub keys: bool,
+    /// Any mouse button.
+    pub buttons: bool,
+    /// The scroll wheel.
+    pub scroll: bool,
+    /// Moving the mouse by `exit_distance`.
+    pub mouse_move: bool,
+    /// Touching the screen.
+    pub touch: bool,
+    /// A game controller button or stick.
+    pub gamepad: bool,
+}
+
+impl Default for ExitTriggers {
+    fn default() -> Self {
+	Self { keys: true, buttons: true, scroll: true, mouse_move: true, touch: true, gamepad: true }
+    }
+}
+
+/// How a background image fits a display.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFit {
+    /// Fill the display, cropping the image to its shape.
+    #[default]
+    Cover,
+    /// Show the whole image, leaving bars of background at two sides.
+    Contain,
+}
+
+/// A corner of a display.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// What the screen fades out to.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Fade {
+    #[default]
+    Black,
+    Desktop,
+}
+
+/// What part an instance plays in keeping several in step.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncRole {
+    #[default]
+    Off,
+    Master,
+    Follower,
+}
+
+/// Where the config is kept.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigBackend {
+    #[default]
+    File,
+    /// Values under `REGISTRY_KEY`, on Windows.
+    Registry,
+}
+
+/// What `Config::redacted` shows in place of the exit passphrase.
+pub(crate) const HIDDEN: &str = "(hidden)";
+
+/// The key under the current user holding the config with the registry
+/// backend.
+const REGISTRY_KEY: &str = "Software\\HexagonScreensaver";
+
+/// The `[battery]` section: what changes while running on battery.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatterySaver {
+    /// Whether to save power at all.
+    pub enabled: bool,
+    /// Show a black screen instead of the animation.
+    pub blank: bool,
+    /// Frame rate cap, on top of `max_fps`.
+    pub max_fps: f32,
+    /// Factor on `density`, so fewer shapes show.
+    pub density: f32,
+    /// Factor on the shape colors.
+    pub brightness: f32,
+}
+
+impl Default for BatterySaver {
+    fn default() -> Self {
+	Self { enabled: true, blank: false, max_fps: 30.0, density: 0.5, brightness: 0.6 }
+    }
+}
+
+impl Config {
+    /// Read the config from the registry when the file or the registry
+    /// itself selects it, otherwise from the file.
+    pub fn load() -> Self {
+	Self::load_reporting().0
+    }
+
+    /// Load the config as `load` does, returning the problems found in it
+    /// too. They are also printed and kept in `config.log` beside it, which
+    /// goes once they are fixed.
+    pub fn load_reporting() -> (Self, Vec<String>) {
+	let (mut config, mut problems) = Self::load_file();
+	if let Some(content) = read_registry() {
+	    let (stored, stored_problems) = Self::parse_reporting(&content);
+	    if stored.config_backend == ConfigBackend::Registry || config.config_backend == ConfigBackend::Registry {
+		info!("config: loaded the registry");
+		config = Config { config_backend: ConfigBackend::Registry, ..stored };
+		problems = stored_problems.into_iter().map(|problem| format!("registry: {problem}")).collect();
+	    }
+	}
+	for problem in &problems {
+	    warn!("{problem}");
+	}
+	let logs = Self::paths_with_extension("log");
+	if problems.is_empty() {
+	    for log in logs.iter().filter(|log| log.is_file()) {
+		let _ = fs::remove_file(log);
+	    }
+	} else {
+	    write_first_writable(&logs, &format!("{}\n", problems.join("\n")));
+	}
+	(config, problems)
+    }
+
+    /// Read the first config file found, copying one found in a fallback
+    /// location into the per-user directory so later saves land there.
+    fn load_file() -> (Self, Vec<String>) {
+	let paths = Self::config_paths();
+	let mut problems = Vec::new();
+	if let Some((mut content, config_path)) = read_first(&paths, &mut problems) {
+	    info!("config: loaded {}", config_path.display());
+	    if let Some((version, upgraded)) = upgraded(&content) {
+		// The old file is kept in case the upgrade loses something
+		let backup = config_path.with_extension(format!("toml.v{version}.bak"));
+		match fs::write(&backup, &content).and_then(|()| fs::write(&config_path, &upgraded)) {
+		    Ok(()) => info!("config: upgraded from version {version}, the old config kept as {}", backup.display()),
+		    Err(err) => problems.push(format!("cannot upgrade {}: {err}", config_path.display())),
+		}
+		content = upgraded;
+	    }
+	    if config_path != paths[0]
+		&& let Some(migrated) = write_first_writable(&paths[..1], &content)
+	    {
+		info!("config: migrated {} to {}", config_path.display(), migrated.display());
+	    }
+	    let (config, found) = Self::parse_reporting(&content);
+	    problems.extend(found.into_iter().map(|problem| format!("{}: {problem}", config_path.display())));
+	    return (config, problems);
+	}
+	if let Some((content, config_path)) = read_first(&Self::legacy_config_paths(), &mut problems) {
+	    info!("config: migrating legacy {}", config_path.display());
+	    let config = Self::parse_legacy(&content);
+	    config.save();
+	    return (config, problems);
+	}
+	(Config::default(), problems)
+    }
+
+    /// Parse a TOML config. Unknown keys and values of the wrong type are
+    /// reported and skipped; a malformed document is reported and replaced
+    /// by the defaults.
+    #[cfg(test)]
+    fn parse(content: &str) -> Self {
+	let (config, problems) = Self::parse_reporting(content);
+	for problem in problems {
+	    warn!("{problem}");
+	}
+	config
+    }
+
+    /// Parse a TOML config as `parse` does, returning what it would warn
+    /// about alongside.
+    fn parse_reporting(content: &str) -> (Self, Vec<String>) {
+	let (config, problems, _) = Self::parse_checked(content);
+	(config, problems)
+    }
+
+    /// Like `parse_reporting`, also telling whether a setting was lost: the
+    /// document was malformed or a value was of the wrong type.
+    fn parse_checked(content: &str) -> (Self, Vec<String>, bool) {
+	let mut table: toml::Table = match content.parse() {
+	    Ok(table) => table,
+	    Err(err) => return (Config::default(), vec![format!("invalid config, using defaults: {err}")], true),
+	};
+	let (version, _) = upgrade(&mut table);
+	each_table(&mut table, migrate);
+
+	// The tables are left out of the defaults while empty
+	let known = toml::Table::try_from(Config::default()).unwrap_or_default();
+	let mut problems: Vec<String> = table
+	    .keys()
+	    .filter(|key| !known.contains_key(*key) && !matches!(key.as_str(), "profiles" | "schedule"))
+	    .map(|key| format!("ignoring unknown config key '{key}'"))
+	    .collect();
+	if version > CONFIG_VERSION {
+	    problems.push(format!("written for config version {version}, newer than this build's {CONFIG_VERSION}"));
+	}
+
+	// Each key is tried alone, so one bad value only loses itself
+	let lost = toml::Value::Table(table.clone()).try_into::<Config>().is_err();
+	if lost {
+	    table.retain(|key, value| {
+		let alone = toml::Table::from_iter([(key.to_string(), value.clone())]);
+		match toml::Value::Table(alone).try_into::<Config>() {
+		    Ok(_) => true,
+		    Err(err) => {
+			problems.push(format!("{key} = {value} is not valid, using the default: {}", err.message()));
+			false
+		    }
+		}
+	    });
+	}
+	let config: Config = match toml::Value::Table(table).try_into() {
+	    Ok(config) => config,
+	    Err(err) => {
+		problems.push(format!("invalid config, using defaults: {err}"));
+		return (Config::default(), problems, true);
+	    }
+	};
+	let sanitized = config.clone().sanitized();
+	problems.extend(changed_keys(&config, &sanitized).into_iter().map(|(key, raw, used)| format!("{key} = {raw} is out of range or invalid, using {used}")));
+	(sanitized, problems, lost)
+    }
+
+    /// Copy the stored config this one was loaded from aside, before a
+    /// save replaces one that loading had problems with.
+    pub fn back_up(&self) {
+	let content = match self.config_backend {
+	    ConfigBackend::Registry => read_registry(),
+	    ConfigBackend::File => fs::read_to_string(Self::file_path()).ok(),
+	};
+	let Some(content) = content else {
+	    return;
+	};
+	match write_first_writable(&Self::paths_with_extension("toml.bak"), &content) {
+	    Some(backup) => info!("config: kept the old config as {}", backup.display()),
+	    None => warn!("could not back up the config"),
+	}
+    }
+
+    /// Store the config in `path`, checked first: anything `load` would
+    /// warn about is an error here.
+    pub fn import(path: &Path) -> Result<(), String> {
+	let content = fs::read_to_string(path).map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+	let (config, problems) = Self::parse_reporting(&content);
+	if !problems.is_empty() {
+	    return Err(format!("{} was not imported:\n  {}", path.display(), problems.join("\n  ")));
+	}
+	config.try_save()
+    }
+
+    /// Write the saved config to `path`.
+    pub fn export(path: &Path) -> Result<(), String> {
+	fs::write(path, Self::load().serialize()).map_err(|err| format!("cannot write {}: {err}", path.display()))
+    }
+
+    /// Where the config is read from: the registry key, the file found, or
+    /// the one `save` would create.
+    pub fn location() -> String {
+	if Self::load().config_backend == ConfigBackend::Registry {
+	    return format!("HKEY_CURRENT_USER\\{REGISTRY_KEY}");
+	}
+	Self::file_path().display().to_string()
+    }
+
+    /// The config file found, or the one `save` would create.
+    fn file_path() -> PathBuf {
+	let paths = Config::config_paths();
+	paths.iter().find(|path| path.is_file()).or(paths.first()).cloned().unwrap_or_default()
+    }
+
+    /// The settings with the selected profile applied. The profile list and
+    /// selection are kept so the result can be edited and stored back with
+    /// `with_profile_edits`.
+    pub fn effective(&self) -> Config {
+	if self.profile.is_empty() {
+	    return self.clone();
+	}
+	let Some(overrides) = self.profiles.get(&self.profile) else {
+	    warn!("unknown profile `{}`, using the base settings", self.profile);
+	    return self.clone();
+	};
+	self.with_overrides(overrides, &format!("profile `{}`", self.profile))
+    }
+
+    /// The `schedule` entry in force at `now`: the last to start, unless
+    /// its `to` time has passed.
+    pub fn schedule_entry(&self, now: NaiveTime) -> Option<&str> {
+	let mut entries: Vec<(NaiveTime, Option<NaiveTime>, &str)> = self
+	    .schedule
+	    .iter()
+	    .filter_map(|(name, entry)| Some((schedule_time(entry.get("from")?)?, entry.get("to").and_then(schedule_time), name.as_str())))
+	    .collect();
+	entries.sort();
+	// Before the day's first start, the last one runs on from yesterday
+	let (from, to, name) = entries.iter().rev().find(|(from, ..)| *from <= now).or(entries.last())?;
+	match to {
+	    Some(to) if *from <= *to && !(*from..*to).contains(&now) => None,
+	    Some(to) if *from > *to && (*to..*from).contains(&now) => None,
+	    _ => Some(name),
+	}
+    }
+
+    /// The settings with the `schedule` entry `name` applied on top.
+    pub fn scheduled(&self, name: &str) -> Config {
+	match self.schedule.get(name) {
+	    Some(overrides) => self.with_overrides(overrides, &format!("schedule entry `{name}`")),
+	    None => self.clone(),
+	}
+    }
+
+    /// The settings with `overrides` from `source` applied on top, keeping
+    /// the profiles and schedule.
+    fn with_overrides(&self, overrides: &toml::Table, source: &str) -> Config {
+	let mut table = toml::Table::try_from(self.clone()).expect("config always serializes");
+	for (key, value) in overrides.iter().filter(|(key, _)| !matches!(key.as_str(), "profile" | "profiles" | "schedule" | "from" | "to")) {
+	    table.insert(key.clone(), value.clone());
+	}
+	match toml::Value::Table(table).try_into::<Config>() {
+	    Ok(config) => config.sanitized(),
+	    Err(err) => {
+		warn!("invalid {source}, using the base settings: {}", err.message());
+		self.clone()
+	    }
+	}
+    }
+
+    /// Store `edited`, an `effective` config, back into the base settings
+    /// or, with a profile selected, into that profile as the settings that
+    /// differ from the base.
+    pub fn with_profile_edits(&self, edited: &Config) -> Config {
+	if self.profile.is_empty() {
+	    return Config { profile: String::new(), profiles: self.profiles.clone(), ..edited.clone() };
+	}
+	let base = toml::Table::try_from(self.clone()).expect("config always serializes");
+	let edited = toml::Table::try_from(edited.clone()).expect("config always serializes");
+	let changed = edited
+	    .into_iter()
+	    .filter(|(key, value)| !matches!(key.as_str(), "profile" | "profiles" | "schedule") && base.get(key) != Some(value))
+	    .collect();
+	let mut config = self.clone();
+	config.profiles.insert(self.profile.clone(), changed);
+	config
+    }
+
+    /// Whether `key` names a config setting, including the `threshold`
+    /// that `density` replaced.
+    pub fn is_key(key: &str) -> bool {
+	key == "threshold" || toml::Table::try_from(Config::default()).is_ok_and(|table| table.contains_key(key))
+    }
+
+    /// Set `key` from its command line form: any TOML value, or a bare
+    /// string such as `heart`.
+    pub fn with_override(self, key: &str, value: &str) -> Result<Self, String> {
+	if key == "threshold" {
+	    let threshold: f32 = value.trim().parse().map_err(|_| format!("invalid value `{value}` for --threshold: expected a number"))?;
+	    let speed = self.speed * speed_for_threshold(threshold);
+	    return self.with_override("density", &(1.0 - threshold).to_string())?.with_override("speed", &speed.to_string());
+	}
+	let mut table = toml::Table::try_from(self).map_err(|err| err.to_string())?;
+	if !table.contains_key(key) {
+	    return Err(format!("unknown config key `{key}`"));
+	}
+	let parsed = toml::from_str::<toml::Table>(&format!("value = {value}"))
+	    .ok()
+	    .and_then(|mut wrapper| wrapper.remove("value"))
+	    .unwrap_or_else(|| toml::Value::String(value.to_string()));
+	table.insert(key.to_string(), parsed);
+	migrate(&mut table);
+	toml::Value::Table(table)
+	    .try_into::<Config>()
+	    .map(Config::sanitized)
+	    .map_err(|err| format!("invalid value `{value}` for --{}: {}", key.replace('_', "-"), err.message()))
+    }
+
+    /// Read the `key=value` file written before the switch to TOML. Values
+    /// that do not parse keep their defaults, as they did then.
+    fn parse_legacy(content: &str) -> Self {
+	let mut config = Config::default();
+	for (key, value) in content.lines().filter_map(|line| line.split_once('=')) {
+	    let value = value.trim();
+	    match key.trim() {
+		"shape" => config.shape = value.to_string(),
+		"threshold" => {
+		    if let Ok(threshold) = value.parse::<f32>() {
+			config.density = 1.0 - threshold;
+			config.speed = speed_for_threshold(threshold);
+		    }
+		}
+		"static_color" => config.static_color = value.parse().unwrap_or(config.static_color),
+		"ignore_keys" => config.ignore_keys = parse_key_names(&value.split(',').filter(|name| !name.is_empty()).collect::<Vec<_>>()),
+		"focus" => config.focus = value.parse().unwrap_or(config.focus),
+		"focus_pos" => {
+		    if let Some((x, y)) = value.split_once(',')
+			&& let (Ok(x), Ok(y)) = (x.trim().parse(), y.trim().parse())
+		    {
+			config.focus_pos = Vec2::new(x, y);
+		    }
+		}
+		"audio_reactive" => config.audio_reactive = value.parse().unwrap_or(config.audio_reactive),
+		"grid_rotation" => config.grid_rotation = value.parse().unwrap_or(config.grid_rotation),
+		"start_delay" => config.start_delay = value.parse().unwrap_or(config.start_delay),
+		key => warn!("ignoring unknown legacy config key '{key}'"),
+	    }
+	}
+	config.sanitized()
+    }
+
+    /// How fast the animation runs: `speed`, slowed down with
+    /// `reduced_motion` so color fades take that much longer too.
+    pub fn animation_speed(&self) -> f32 {
+	if self.reduced_motion { self.speed * 0.25 } else { self.speed }
+    }
+
+    /// The settings to run with: cut down by `battery` while `on_battery`.
+    pub fn for_power(&self, on_battery: bool) -> Config {
+	if !on_battery || !self.battery.enabled {
+	    return self.clone();
+	}
+	let max_fps = if self.max_fps > 0.0 { self.max_fps.min(self.battery.max_fps) } else { self.battery.max_fps };
+	Config { max_fps, density: self.density * self.battery.density, ..self.clone() }
+    }
+
+    /// The wave level above which shapes show.
+    pub fn threshold(&self) -> f32 {
+	1.0 - self.density
+    }
+
+    /// Clamp values into their supported ranges.
+    fn sanitized(mut self) -> Self {
+	self.hue_shift = self.hue_shift.rem_euclid(360.0);
+	self.density = self.density.clamp(0.0, 1.0);
+	self.twinkle_duty = self.twinkle_duty.clamp(0.0, 0.95);
+	self.twinkle_stagger = self.twinkle_stagger.clamp(0.0, 1.0);
+	self.color_cycle_period = self.color_cycle_period.clamp(5.0, 3600.0);
+	self.color_transition_seconds = self.color_transition_seconds.clamp(0.1, 600.0);
+	self.color_hold_seconds = self.color_hold_seconds.clamp(0.0, 3600.0);
+	self.harmony_minutes = self.harmony_minutes.clamp(0.5, 1440.0);
+	// Either way round, as the lowest and highest
+	let unit_range = |range: Vec2| {
+	    let range = range.clamp(Vec2::ZERO, Vec2::ONE);
+	    Vec2::new(range.min_element(), range.max_element())
+	};
+	self.random_saturation = unit_range(self.random_saturation);
+	self.random_value = unit_range(self.random_value);
+	self.focus_pos = self.focus_pos.clamp(Vec2::ZERO, Vec2::ONE);
+	let [x, y, width, height] = self.widget_region.map(|value| value.clamp(0.0, 1.0));
+	let (width, height) = (width.max(0.05), height.max(0.05));
+	self.widget_region = [x.min(1.0 - width), y.min(1.0 - height), width, height];
+	self.grid_rotation = self.grid_rotation.rem_euclid(360.0);
+	self.rotation_speed = self.rotation_speed.clamp(-720.0, 720.0);
+	self.breathing = self.breathing.clamp(0.0, 1.0);
+	self.corner_radius = self.corner_radius.clamp(0.0, 1.0);
+	self.shape_quality = self.shape_quality.clamp(0.25, 4.0);
+	self.morph_period = self.morph_period.clamp(0.5, 600.0);
+	self.speed = self.speed.clamp(0.0, 10.0);
+	self.max_fps = self.max_fps.clamp(0.0, 1000.0);
+	self.wallpaper_fps = self.wallpaper_fps.clamp(1.0, 1000.0);
+	self.live_smoothing = self.live_smoothing.clamp(0.0, 10.0);
+	self.battery.max_fps = self.battery.max_fps.clamp(1.0, 1000.0);
+	self.battery.density = self.battery.density.clamp(0.0, 1.0);
+	self.battery.brightness = self.battery.brightness.clamp(0.0, 1.0);
+	self.radius = self.radius.clamp(3.0, 500.0);
+	self.gap = self.gap.clamp(0.0, 200.0);
+	self.radius_jitter = self.radius_jitter.clamp(0.0, 0.9);
+	self.pixel_shift = self.pixel_shift.clamp(0.0, 50.0);
+	self.ripple_speed = self.ripple_speed.clamp(0.1, 100.0);
+	self.ripple_decay = self.ripple_decay.clamp(0.0, 10.0);
+	self.noise_scale = self.noise_scale.clamp(1.0, 200.0);
+	self.flow_speed = self.flow_speed.clamp(0.0, 1000.0);
+	self.flow_scale = self.flow_scale.clamp(1.0, 200.0);
+	self.noise_speed = self.noise_speed.clamp(0.0, 10.0);
+	self.life_tick = self.life_tick.clamp(0.02, 10.0);
+	self.message_speed = self.message_speed.clamp(0.1, 100.0);
+	self.ticker_speed = self.ticker_speed.clamp(0.01, 2.0);
+	if !valid_wedges(self.kaleidoscope) {
+	    warn!("a kaleidoscope needs an even number of wedges up to {MAX_WEDGES}, not {}; using none", self.kaleidoscope);
+	    self.kaleidoscope = 0;
+	}
+	if Rule::parse(&self.automaton).is_none() {
+	    warn!("invalid automaton rule '{}', using {}", self.automaton, Config::default().automaton);
+	    self.automaton = Config::default().automaton;
+	}
+	self.ants = self.ants.clamp(1, 100);
+	if parse_rule(&self.ant_rule).is_none() {
+	    warn!("invalid ant rule '{}', using {}", self.ant_rule, Config::default().ant_rule);
+	    self.ant_rule = Config::default().ant_rule;
+	}
+	if !self.reaction.eq_ignore_ascii_case("custom") && !PRESETS.iter().any(|(name, ..)| name.eq_ignore_ascii_case(&self.reaction)) {
+	    warn!("unknown reaction preset '{}', using custom rates", self.reaction);
+	    self.reaction = "custom".to_string();
+	}
+	self.feed = self.feed.clamp(0.0, 0.1);
+	self.kill = self.kill.clamp(0.0, 0.1);
+	if parse_key_combo(&self.kiosk_exit).is_none() {
+	    warn!("invalid kiosk exit '{}', using {}", self.kiosk_exit, Config::default().kiosk_exit);
+	    self.kiosk_exit = Config::default().kiosk_exit;
+	}
+	if parse_group(&self.sync_group).is_none() {
+	    warn!("invalid sync group '{}', expected a multicast address and port; using {DEFAULT_GROUP}", self.sync_group);
+	    self.sync_group = DEFAULT_GROUP.to_string();
+	}
+	if parse_hex_color(&self.rain_color).is_none() {
+	    warn!("invalid rain color '{}', using {}", self.rain_color, Config::default().rain_color);
+	    self.rain_color = Config::default().rain_color;
+	}
+	if parse_hex_color(&self.background).is_none() {
+	    warn!("invalid background color '{}', using black", self.background);
+	    self.background = Config::default().background;
+	}
+	if !self.timer.is_empty() && parse_periods(&self.timer).is_none() {
+	    warn!("invalid timer '{}', using none", self.timer);
+	    self.timer = String::new();
+	}
+	if parse_hex_color(&self.timer_color).is_none() {
+	    warn!("invalid timer color '{}', using {}", self.timer_color, Config::default().timer_color);
+	    self.timer_color = Config::default().timer_color;
+	}
+	if !self.qr.is_empty() && qr::encode(&self.qr).is_none() {
+	    warn!("QR code text '{}' is too long, using none", self.qr);
+	    self.qr = String::new();
+	}
+	if !self.background_gradient.is_empty() && self.gradient_colors().is_none() {
+	    warn!("invalid background gradient '{}', using none", self.background_gradient);
+	    self.background_gradient = String::new();
+	}
+	self.photo_minutes = self.photo_minutes.clamp(0.1, 1440.0);
+	self.bloom_intensity = self.bloom_intensity.clamp(0.0, 4.0);
+	self.bloom_radius = self.bloom_radius.clamp(1.0, 100.0);
+	self.trails = self.trails.clamp(0.0, 1.0);
+	self.samples = self.samples.clamp(1, 16).next_power_of_two();
+	self.star_density = self.star_density.clamp(0.0, 20.0);
+	self.star_speed = self.star_speed.clamp(0.0, 1000.0);
+	self.idle_minutes = self.idle_minutes.clamp(0.5, 1440.0);
+	self.start_delay = self.start_delay.max(0.0);
+	self.fade_in = self.fade_in.clamp(0.0, 10.0);
+	self.fade_out = self.fade_out.clamp(0.0, 10.0);
+	self.exit_grace = self.exit_grace.clamp(0.0, 60.0);
+	self.exit_distance = self.exit_distance.max(0.0);
+	self.spotlight_radius = self.spotlight_radius.clamp(10.0, 2000.0);
+	self.day_temperature = self.day_temperature.clamp(1000.0, 40000.0);
+	self.night_temperature = self.night_temperature.clamp(1000.0, 40000.0);
+	self.sunset_hour = self.sunset_hour.rem_euclid(24.0);
+	self.sunrise_hour = self.sunrise_hour.rem_euclid(24.0);
+	self.night_brightness = self.night_brightness.clamp(0.0, 1.0);
+	self.dim_after_minutes = self.dim_after_minutes.max(0.0);
+	self.dim_to = self.dim_to.clamp(0.0, 1.0);
+	self.dim = self.dim.clamp(0.0, 1.0);
+	self.schedule.retain(|name, entry| {
+	    let from = entry.get("from").and_then(schedule_time);
+	    if from.is_none() {
+		warn!("schedule entry `{name}` needs a `from` time such as \"22:00\", ignoring it");
+	    }
+	    if entry.get("to").is_some_and(|to| schedule_time(to).is_none()) {
+		warn!("invalid `to` time in schedule entry `{name}`, running it until the next entry");
+		entry.remove("to");
+	    }
+	    from.is_some()
+	});
+	self.attract_minutes = self.attract_minutes.clamp(0.5, 1440.0);
+	self
+    }
+
+    pub fn background_color(&self) -> Color {
+	parse_hex_color(&self.background).unwrap_or(BLACK)
+    }
+
+    /// The top and bottom colors of `background_gradient`, if it has two.
+    pub fn gradient_colors(&self) -> Option<(Color, Color)> {
+	parse_gradient(&self.background_gradient)
+    }
+
+    /// Store the config, warning when it could not be.
+    pub fn save(&self) {
+	if let Err(err) = self.try_save() {
+	    warn!("{err}");
+	}
+    }
+
+    /// Store the config with its `config_backend`, falling back on the file
+    /// when the registry cannot be written.
+    pub fn try_save(&self) -> Result<(), String> {
+	let content = self.serialize();
+	if self.config_backend == ConfigBackend::Registry {
+	    match write_registry(&content) {
+		Ok(()) => {
+		    info!("config: saved to the registry");
+		    return Ok(());
+		}
+		Err(err) => warn!("could not save config to the registry, using the file: {err}"),
+	    }
+	}
+	let path = write_first_writable(&Self::config_paths(), &content).ok_or("could not save config to any location")?;
+	info!("config: saved to {}", path.display());
+	Ok(())
+    }
+
+    /// Render every field as the TOML document read by `load`.
+    pub fn serialize(&self) -> String {
+	toml::to_string(self).expect("config always serializes")
+    }
+
+    /// The config with its exit passphrases, the profiles' and schedule's
+    /// too, shown as `HIDDEN`, for showing outside the screensaver.
+    pub(crate) fn redacted(&self) -> Self {
+	let hide = |table: &mut toml::Table| {
+	    if let Some(passphrase) = table.get_mut("exit_passphrase") {
+		*passphrase = toml::Value::String(HIDDEN.to_string());
+	    }
+	};
+	let mut config = self.clone();
+	if !config.exit_passphrase.is_empty() {
+	    config.exit_passphrase = HIDDEN.to_string();
+	}
+	config.profiles.values_mut().chain(config.schedule.values_mut()).for_each(hide);
+	config
+    }
+
+    /// Locations to read and write the config, in order of preference: the
+    /// per-user config directory (APPDATA on Windows, `$XDG_CONFIG_HOME` on
+    /// Linux, Application Support on macOS), then the current directory,
+    /// then the temp directory for locked-down systems.
+    fn config_paths() -> Vec<PathBuf> {
+	Self::paths_with_extension("toml")
+    }
+
+    /// Write `content` beside the config, in a file ending in `extension`,
+    /// returning where it went.
+    pub(crate) fn save_beside(extension: &str, content: &str) -> Option<PathBuf> {
+	write_first_writable(&Self::paths_with_extension(extension), content)
+    }
+
+    /// Directories holding the config, searched for files it refers to.
+    pub(crate) fn config_dirs() -> Vec<PathBuf> {
+	Self::config_paths()
+	    .into_iter()
+	    .map(|path| path.parent().map(Path::to_path_buf).unwrap_or_default())
+	    .collect()
+    }
+
+    /// Resolve a path named in the config. Relative paths are looked up in
+    /// the config directories and otherwise left relative to the current one.
+    pub(crate) fn find_file(path: &Path) -> PathBuf {
+	if path.is_absolute() {
+	    return path.to_path_buf();
+	}
+	Self::config_dirs()
+	    .into_iter()
+	    .map(|dir| dir.join(path))
+	    .find(|candidate| candidate.is_file())
+	    .unwrap_or_else(|| path.to_path_buf())
+    }
+
+    fn legacy_config_paths() -> Vec<PathBuf> {
+	Self::paths_with_extension("txt")
+    }
+
+    /// Apply `command_line` to the config reloaded when the file changes,
+    /// as well as to the one it starts with.
+    pub fn set_command_line(command_line: CommandLine) {
+	*COMMAND_LINE.lock().unwrap_or_else(|err| err.into_inner()) = command_line;
+    }
+
+    /// Keep the config in a `config.toml` beside the executable and nowhere
+    /// else, so nothing is left behind on the host machine.
+    pub fn set_portable() {
+	PORTABLE.store(true, Ordering::Relaxed);
+    }
+
+    /// Directory of the executable when running in portable mode.
+    fn portable_dir() -> Option<PathBuf> {
+	let exe_dir = env::current_exe().ok()?.parent()?.to_path_buf();
+	let marked = exe_dir.join("portable.txt").is_file();
+	(PORTABLE.load(Ordering::Relaxed) || marked).then_some(exe_dir)
+    }
+
+    fn paths_with_extension(extension: &str) -> Vec<PathBuf> {
+	// A page has no files to keep settings in
+	if cfg!(target_arch = "wasm32") {
+	    return Vec::new();
+	}
+	if let Some(dir) = Self::portable_dir() {
+	    let name = if extension == "toml" { "config.toml".to_string() } else { format!("screensaver_config.{}", extension) };
+	    return vec![dir.join(name)];
+	}
+	let mut paths = Vec::new();
+	if let Ok(appdata) = env::var("APPDATA") {
+	    let mut path = PathBuf::from(appdata);
+	    path.push("HeartScreensaver");
+	    path.push("config");
+	    path.set_extension(extension);
+	    paths.push(path);
+	} else if let Some(config_dir) = dirs::config_dir() {
+	    paths.push(config_dir.join("hexagon_screensaver").join(format!("config.{}", extension)));
+	}
+	paths.push(PathBuf::from(format!("screensaver_config.{}", extension)));
+	paths.push(env::temp_dir().join(format!("screensaver_config.{}", extension)));
+	paths
+    }
+}
+
+/// Notices when the config file is rewritten while the screensaver runs.
+/// The `--profile` selection and `--KEY VALUE` overrides given on the
+/// command line.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommandLine {
+    /// Replaces the saved profile selection.
+    pub profile: Option<String>,
+    /// Config overrides, in order.
+    pub overrides: Vec<(String, String)>,
+}
+
+impl CommandLine {
+    /// The settings to run with from the saved `file`.
+    pub fn apply(&self, mut file: Config) -> Config {
+	if let Some(profile) = &self.profile {
+	    file.profile = profile.clone();
+	}
+	let mut config = file.effective();
+	for (key, value) in &self.overrides {
+	    match config.clone().with_override(key, value) {
+		Ok(overridden) => config = overridden,
+		Err(err) => warn!("{err}"),
+	    }
+	}
+	config
+    }
+}
+
+pub(crate) struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    since_check: f32,
+    /// Applied again to each reload so the command line keeps winning.
+    command_line: CommandLine,
+}
+
+impl ConfigWatcher {
+    /// Watch the file `Config::load` reads, or the one `save` would create.
+    pub(crate) fn new() -> Self {
+	Self::watching(Config::file_path())
+    }
+
+    fn watching(path: PathBuf) -> Self {
+	let modified = modified_time(&path);
+	let command_line = COMMAND_LINE.lock().unwrap_or_else(|err| err.into_inner()).clone();
+	Self { path, modified, since_check: 0.0, command_line }
+    }
+
+    /// Advance by `dt` seconds. About once a second, checks the file and
+    /// returns the new config if it changed since the last check. An edit
+    /// that loses a setting, such as one saved half-written, is skipped so
+    /// the running config keeps its `kiosk` and `exit_passphrase`.
+    pub(crate) fn poll(&mut self, dt: f32) -> Option<Config> {
+	self.since_check += dt;
+	if self.since_check < 1.0 {
+	    return None;
+	}
+	self.since_check = 0.0;
+	let modified = modified_time(&self.path);
+	if modified == self.modified {
+	    return None;
+	}
+	self.modified = modified;
+	let content = fs::read_to_string(&self.path).ok()?;
+	let (config, problems, lost) = Config::parse_checked(&content);
+	for problem in &problems {
+	    warn!("{}: {problem}", self.path.display());
+	}
+	if lost {
+	    warn!("config: not reloading {} until it is fixed", self.path.display());
+	    return None;
+	}
+	info!("config: reloaded {}", self.path.display());
+	Some(self.command_line.apply(config))
+    }
+}
+
+/// Run `change` on `table` and each of its profiles and schedule entries.
+fn each_table(table: &mut toml::Table, change: fn(&mut toml::Table)) {
+    change(table);
+    for name in ["profiles", "schedule"] {
+	if let Some(toml::Value::Table(tables)) = table.get_mut(name) {
+	    for (_, entry) in tables.iter_mut() {
+		if let toml::Value::Table(entry) = entry {
+		    change(entry);
+		}
+	    }
+	}
+    }
+}
+
+/// Bring `table` up to `CONFIG_VERSION`, returning the version it was
+/// written for and whether any migration changed it.
+fn upgrade(table: &mut toml::Table) -> (u32, bool) {
+    let version = table.remove("version").and_then(|version| version.as_integer()).map_or(1, |version| version.clamp(1, u32::MAX.into()) as u32);
+    let before = table.clone();
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+	each_table(table, *migration);
+    }
+    let changed = *table != before;
+    table.insert("version".to_string(), toml::Value::Integer(CONFIG_VERSION.max(version).into()));
+    (version, changed)
+}
+
+/// `content` in the current version and the version it was written for,
+/// when upgrading it changed anything.
+fn upgraded(content: &str) -> Option<(u32, String)> {
+    let mut table: toml::Table = content.parse().ok()?;
+    let (version, changed) = upgrade(&mut table);
+    changed.then(|| (version, table.to_string()))
+}
+
+/// Version 2: `threshold` became `density`, its opposite, and no longer
+/// sets how fast shapes twinkle; `speed` keeps the old pace instead.
+fn density_from_threshold(table: &mut toml::Table) {
+    if let Some(threshold) = table.remove("threshold")
+	&& !table.contains_key("density")
+    {
+	match threshold.as_float().or_else(|| threshold.as_integer().map(|threshold| threshold as f64)) {
+	    Some(threshold) => {
+		table.insert("density".to_string(), toml::Value::Float(1.0 - threshold));
+		let speed = table.get("speed").and_then(|speed| speed.as_float().or_else(|| speed.as_integer().map(|speed| speed as f64))).unwrap_or(1.0);
+		let speed = speed * speed_for_threshold(threshold as f32) as f64;
+		table.insert("speed".to_string(), toml::Value::Float(speed.clamp(0.0, 10.0)));
+	    }
+	    None => warn!("ignoring invalid threshold {threshold}"),
+	}
+    }
+}
+
+/// The `speed` factor giving the twinkle the pace `threshold` gave it
+/// before `density` replaced it, `(1 - threshold) * 10` radians a second.
+fn speed_for_threshold(threshold: f32) -> f32 {
+    (1.0 - threshold) * 10.0 / TWINKLE_SPEED
+}
+
+/// Split the `image:<path>` and `harmony:<rule>` shorthands of
+/// `color_mode`.
+fn migrate(table: &mut toml::Table) {
+    if let Some(toml::Value::String(mode)) = table.get("color_mode")
+	&& let Some(path) = mode.strip_prefix("image:")
+    {
+	let path = toml::Value::String(path.to_string());
+	table.insert("color_image".to_string(), path);
+	table.insert("color_mode".to_string(), toml::Value::String("image".to_string()));
+    }
+    if let Some(toml::Value::String(mode)) = table.get("color_mode")
+	&& let Some(rule) = mode.strip_prefix("harmony:")
+    {
+	let rule = toml::Value::String(rule.to_string());
+	table.insert("harmony".to_string(), rule);
+	table.insert("color_mode".to_string(), toml::Value::String("harmony".to_string()));
+    }
+}
+
+/// A schedule time, `"HH:MM"`.
+fn schedule_time(value: &toml::Value) -> Option<NaiveTime> {
+    let (hours, minutes) = value.as_str()?.split_once(':')?;
+    NaiveTime::from_hms_opt(hours.trim().parse().ok()?, minutes.trim().parse().ok()?, 0)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The keys `sanitized` changed from `raw` to `used`, with both values.
+fn changed_keys(raw: &Config, used: &Config) -> Vec<(String, toml::Value, toml::Value)> {
+    let (Ok(raw), Ok(mut used)) = (toml::Table::try_from(raw), toml::Table::try_from(used)) else {
+	return Vec::new();
+    };
+    raw.into_iter()
+	.filter_map(|(key, value)| {
+	    let used = used.remove(&key)?;
+	    (used != value).then_some((key, value, used))
+	})
+	.collect()
+}
+
+/// The config's keys as registry values: strings as they are, unless they
+/// would read back as another type, and anything else as TOML.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn registry_values(content: &str) -> Vec<(String, String)> {
+    let table: toml::Table = content.parse().unwrap_or_default();
+    table
+	.into_iter()
+	.map(|(key, value)| {
+	    let data = match &value {
+		toml::Value::String(text) if registry_value(text).is_none() => text.clone(),
+		value => value.to_string(),
+	    };
+	    (key, data)
+	})
+	.collect()
+}
+
+/// The TOML value `data` reads as, if any.
+fn registry_value(data: &str) -> Option<toml::Value> {
+    format!("value = {data}").parse::<toml::Table>().ok()?.remove("value")
+}
+
+/// A config document of registry `values`, any that are not TOML taken as
+/// strings.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn from_registry_values(values: Vec<(String, String)>) -> String {
+    let table: toml::Table = values
+	.into_iter()
+	.map(|(key, data)| {
+	    let value = registry_value(&data).unwrap_or(toml::Value::String(data));
+	    (key, value)
+	})
+	.collect();
+    table.to_string()
+}
+
+/// The config kept in the registry, if there are any values.
+fn read_registry() -> Option<String> {
+    #[cfg(windows)]
+    {
+	let values = crate::win32::registry_values(REGISTRY_KEY)?;
+	(!values.is_empty()).then(|| from_registry_values(values))
+    }
+    #[cfg(not(windows))]
+    None
+}
+
+/// Replaces the config kept in the registry with `content`.
+fn write_registry(content: &str) -> Result<(), String> {
+    #[cfg(windows)]
+    {
+	crate::win32::set_registry_values(REGISTRY_KEY, &registry_values(content))
+    }
+    #[cfg(not(windows))]
+    {
+	let _ = content;
+	Err("there is no registry outside Windows".to_string())
+    }
+}
+
+/// The first of `paths` that can be read, noting in `problems` the ones
+/// there that could not.
+fn read_first(paths: &[PathBuf], problems: &mut Vec<String>) -> Option<(String, PathBuf)> {
+    paths.iter().filter(|path| path.exists()).find_map(|path| match fs::read_to_string(path) {
+	Ok(content) => Some((content, path.clone())),
+	Err(err) => {
+	    problems.push(format!("cannot read {}: {err}", path.display()));
+	    None
+	}
+    })
+}
+
+/// Serde adapter storing a key set as a sorted list of key names.
+mod key_set {
+    use super::{key_name, parse_key_names};
+    use macroquad::input::KeyCode;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::collections::HashSet;
+
+    pub fn serialize<S: Serializer>(keys: &HashSet<KeyCode>, serializer: S) -> Result<S::Ok, S::Error> {
+	let mut names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+	names.sort();
+	serializer.collect_seq(names)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashSet<KeyCode>, D::Error> {
+	let names = Vec::<String>::deserialize(deserializer)?;
+	Ok(parse_key_names(&names))
+    }
+}
+
+/// Serde adapter storing a `Vec2` as a two-element array.
+mod vec2_array {
+    use macroquad::math::Vec2;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Vec2, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.collect_seq([value.x, value.y])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec2, D::Error> {
+	let [x, y] = <[f32; 2]>::deserialize(deserializer)?;
+	Ok(Vec2::new(x, y))
+    }
+}
+
+/// Write `content` to the first of `paths` that can be written, creating
+/// missing parent directories. Returns the path that was used.
+fn write_first_writable(paths: &[PathBuf], content: &str) -> Option<PathBuf> {
+    for path in paths {
+	if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty())
+	    && let Err(err) = fs::create_dir_all(parent)
+	{
+	    warn!("cannot create {}: {}", parent.display(), err);
+	    continue;
+	}
+	match fs::write(path, content) {
+	    Ok(()) => return Some(path.clone()),
+	    Err(err) => warn!("cannot write {}: {}", path.display(), err),
+	}
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watcher_reports_rewritten_configs() {
+	let path = env::temp_dir().join(format!("hexagon_screensaver_watch_{}.toml", std::process::id()));
+	fs::write(&path, "density = 0.5\n").unwrap();
+	let mut watcher = ConfigWatcher::watching(path.clone());
+	assert!(watcher.poll(2.0).is_none());
+
+	fs::write(&path, "density = 0.25\n").unwrap();
+	let later = SystemTime::now() + std::time::Duration::from_secs(5);
+	fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+	assert!(watcher.poll(0.5).is_none(), "checks at most once a second");
+	assert_eq!(watcher.poll(0.5).map(|config| config.density), Some(0.25));
+	assert!(watcher.poll(2.0).is_none());
+
+	// The command line still wins over the reloaded file
+	watcher.command_line = CommandLine { profile: Some("calm".to_string()), overrides: vec![("shape".to_string(), "\"heart\"".to_string())] };
+	fs::write(&path, "shape = \"star\"\n[profiles.calm]\ndensity = 0.75\n").unwrap();
+	let later = SystemTime::now() + std::time::Duration::from_secs(10);
+	fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+	let reloaded = watcher.poll(1.0).unwrap();
+	assert_eq!((reloaded.shape.as_str(), reloaded.density), ("heart", 0.75));
+
+	// A half-written edit keeps the running config
+	fs::write(&path, "kiosk = tr").unwrap();
+	let later = SystemTime::now() + std::time::Duration::from_secs(20);
+	fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+	assert!(watcher.poll(1.0).is_none());
+	fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn imports_report_what_loading_would_skip() {
+	assert!(Config::parse_reporting(&Config::default().serialize()).1.is_empty());
+	let (_, problems) = Config::parse_reporting("shape = \"heart\"\nshpae = \"star\"\n");
+	assert_eq!(problems, ["ignoring unknown config key 'shpae'"]);
+	// A value of the wrong type loses only itself
+	let (config, problems) = Config::parse_reporting("density = \"high\"\nshape = 3\nkiosk = true\n");
+	assert_eq!(problems.len(), 2);
+	assert!(problems[0].starts_with("density = \"high\" is not valid") && problems[1].starts_with("shape = 3 is not valid"));
+	assert_eq!((config.density, config.shape, config.kiosk), (Config::default().density, Config::default().shape, true));
+	let (config, problems) = Config::parse_reporting("density = 2.0");
+	assert_eq!((config.density, problems), (1.0, vec!["density = 2.0 is out of range or invalid, using 1.0".to_string()]));
+	assert_eq!(Config::parse_reporting("not toml [").1.len(), 1);
+    }
+
+    #[test]
+    fn save_falls_back_when_primary_is_unwritable() {
+	let dir = env::temp_dir().join(format!("hexagon_screensaver_test_{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	// A regular file where the config directory should be makes it uncreatable
+	let blocker = dir.join("blocker");
+	fs::write(&blocker, "").unwrap();
+	let primary = blocker.join("HeartScreensaver").join("config.txt");
+	let fallback = dir.join("fallback_config.txt");
+
+	let used = write_first_writable(&[primary, fallback.clone()], "shape=heart\n");
+	assert_eq!(used, Some(fallback.clone()));
+	assert_eq!(fs::read_to_string(&fallback).unwrap(), "shape=heart\n");
+	fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serialize_writes_every_key() {
+	let serialized = Config::default().serialize();
+	let table: toml::Table = serialized.parse().unwrap();
+	let mut keys: Vec<&str> = table.keys().map(String::as_str).collect();
+	let mut expected = [
+	    "version", "shape", "palette", "palette_image", "random_saturation", "random_value", "color_mode", "color_cycle_period", "harmony", "harmony_minutes", "color_image", "webcam_device", "hue_shift", "density", "twinkle_duty", "twinkle_stagger", "pattern", "ripple_speed", "ripple_decay", "noise_scale", "noise_speed", "life_tick", "automaton", "ants", "ant_rule", "reaction", "feed", "kill", "rain_color", "script", "static_color", "ignore_keys", "exit_grace", "exit_distance", "exit_on", "lock_on_exit", "hide_cursor", "spotlight", "spotlight_radius", "click_rings", "kiosk", "kiosk_exit", "exit_passphrase", "kiosk_hide", "widget_region", "focus", "focus_pos", "clock", "message", "message_speed", "date_overlay", "date_corner", "weather_url", "weather_overlay", "media_overlay", "media_tint", "debug_overlay", "share_texture", "control_port", "osc_port", "osc_network", "midi_device", "live_smoothing", "live_map", "sync", "sync_group", "ticker", "ticker_speed", "timer", "timer_color", "qr",
+	    "audio_reactive", "grid_rotation", "rotation_speed", "random_spin", "breathing", "fade_easing", "color_easing", "color_transition_seconds", "color_hold_seconds", "morph_period", "layout", "motion", "flow_speed", "flow_scale", "orientation", "corner_radius", "shape_quality", "speed", "flash_limit", "reduced_motion", "max_fps", "wallpaper_fps", "vsync", "samples", "smooth_edges", "gpu_animation", "battery", "radius", "gap", "radius_jitter", "pixel_shift", "camera_drift", "background", "background_gradient", "background_image", "background_fit", "photo_folder", "photo_minutes", "bloom", "bloom_intensity", "bloom_radius", "trails", "post_effect", "render_mode", "kaleidoscope", "stars", "star_density", "star_speed", "idle_minutes", "start_delay", "spiral_start", "fade_in", "fade_out", "fade_to", "seed", "temperature_shift", "day_temperature",
+	    "night_temperature", "sunset_hour", "sunrise_hour", "night_brightness", "dim_after_minutes", "dim_to", "dim", "mode", "attract_minutes", "surprise", "surprise_shapes", "surprise_palettes", "surprise_patterns", "auto_theme", "language", "config_backend", "profile",
+	];
+	keys.sort();
+	expected.sort();
+	assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn registry_values_read_back_as_the_config() {
+	let config = Config { shape: "heart".to_string(), message: "true".to_string(), config_backend: ConfigBackend::Registry, ..Default::default() };
+	let values = registry_values(&config.serialize());
+	let value = |key: &str| values.iter().find(|(name, _)| name == key).map(|(_, data)| data.as_str());
+	// Plain strings as group policy would set them
+	assert_eq!(value("shape"), Some("heart"));
+	assert_eq!(value("message"), Some("\"true\""));
+	assert_eq!(value("density"), Some("1.0"));
+	assert_eq!(Config::parse(&from_registry_values(values)).serialize(), config.serialize());
+    }
+
+    #[test]
+    fn config_round_trips_and_skips_unknown_keys() {
+	let mut config = Config { shape: "heart".to_string(), density: 0.05, ..Default::default() };
+	config.ignore_keys.insert(KeyCode::Space);
+	let parsed = Config::parse(&format!("{}unknown = 1\n", config.serialize()));
+	assert_eq!(parsed.serialize(), config.serialize());
+	assert_eq!(Config::parse("density = \"high\"").serialize(), Config::default().serialize());
+	assert_eq!(Config::parse("post_effect = \"crt\"").post_effect, PostEffect::Crt);
+	assert_eq!(Config::default().with_override("render_mode", "3d").unwrap().render_mode, RenderMode::Prisms);
+	assert_eq!(Config::default().with_override("kaleidoscope", "6").unwrap().kaleidoscope, 6);
+	assert_eq!(Config::parse("kaleidoscope = 5").kaleidoscope, 0);
+	assert_eq!([0, 3, 64].map(|samples| Config::parse(&format!("samples = {samples}")).samples), [1, 4, 16]);
+	assert_eq!(Config::parse("mode = \"attract\"").mode, Mode::Attract);
+	let mosaic = Config::parse("color_mode = \"image:photo.png\"");
+	assert_eq!((mosaic.color_mode, mosaic.color_image.as_str()), (ColorMode::Image, "photo.png"));
+	let harmony = Config::default().with_override("color_mode", "harmony:triadic").unwrap();
+	assert_eq!((harmony.color_mode, harmony.harmony), (ColorMode::Harmony, Harmony::Triadic));
+	let gradient = Config::parse("background_gradient = \"#000000, #101040\"").gradient_colors();
+	assert_eq!(gradient, Some((BLACK, Color::from_rgba(0x10, 0x10, 0x40, 255))));
+	assert_eq!(Config::parse("background_gradient = \"#000000\"").background_gradient, "");
+	assert_eq!(Config::default().with_override("timer", "25m,5m").unwrap().timer, "25m,5m");
+	assert_eq!(Config::parse("timer = \"soon\"").timer, "");
+	assert_eq!(Config::parse(&format!("qr = \"{}\"", "x".repeat(300))).qr, "");
+    }
+
+    #[test]
+    fn schedule_entries_take_over_at_their_times() {
+	let config = Config::parse(
+	    "[schedule.night]\nfrom = \"22:00\"\nshape = \"hexagon\"\npalette = \"monochrome\"\ndim = 0.3\n\
+	     [schedule.lunch]\nfrom = \"12:00\"\nto = \"13:30\"\npalette = \"sunset\"\n\
+	     [schedule.day]\nfrom = \"7:00\"\n\
+	     [schedule.broken]\nfrom = \"noon\"\n",
+	);
+	assert!(!config.schedule.contains_key("broken"));
+	let at = |hour, minute| config.schedule_entry(NaiveTime::from_hms_opt(hour, minute, 0).unwrap());
+	assert_eq!([at(23, 0), at(3, 0), at(7, 0), at(12, 30), at(14, 0)], [Some("night"), Some("night"), Some("day"), Some("lunch"), None]);
+	let night = config.scheduled("night");
+	assert_eq!((night.palette.as_str(), night.dim, night.schedule.len()), ("monochrome", 0.3, 3));
+	assert!(!Config::parse(&night.serialize()).schedule.is_empty());
+    }
+
+    #[test]
+    fn profiles_override_and_store_their_differences() {
+	let file = Config::parse("density = 0.5\nprofile = \"party\"\n[profiles.party]\npalette = \"neon\"\n");
+	let mut config = file.effective();
+	assert_eq!((config.density, config.palette.as_str()), (0.5, "neon"));
+
+	config.density = 0.25;
+	let stored = file.with_profile_edits(&config);
+	assert_eq!(stored.density, 0.5);
+	let party = &stored.profiles["party"];
+	assert_eq!(party.keys().collect::<Vec<_>>(), ["density", "palette"]);
+	assert_eq!(Config::parse(&stored.serialize()).effective().density, 0.25);
+    }
+
+    #[test]
+    fn legacy_config_is_migrated() {
+	let config = Config::parse_legacy("shape=heart\nthreshold=0.97\nstatic_color=true\nignore_keys=Escape,Space\nfocus=true\nfocus_pos=0.25,0.75\naudio_reactive=true\ngrid_rotation=30\nstart_delay=2.5\n");
+	assert_eq!(config.shape, "heart");
+	assert!((config.threshold() - 0.97).abs() < 1e-6);
+	assert!((config.speed - 0.03).abs() < 1e-6);
+	assert!(config.static_color && config.focus && config.audio_reactive);
+	assert_eq!(config.ignore_keys, parse_key_names(&["Escape", "Space"]));
+	assert_eq!((config.focus_pos, config.grid_rotation, config.start_delay), (Vec2::new(0.25, 0.75), 30.0, 2.5));
+	let defaults = Config::parse_legacy("focus_pos=left\ngrid_rotation=lots\n");
+	assert_eq!((defaults.focus_pos, defaults.grid_rotation), (Config::default().focus_pos, Config::default().grid_rotation));
+    }
+
+    #[test]
+    fn battery_caps_frame_rate_and_density() {
+	let config = Config { density: 0.1, max_fps: 60.0, ..Default::default() };
+	let saving = config.for_power(true);
+	assert_eq!((saving.max_fps, saving.density), (30.0, 0.05));
+	assert_eq!(config.for_power(false).max_fps, 60.0);
+	let disabled = Config { battery: BatterySaver { enabled: false, ..Default::default() }, ..config.clone() };
+	assert_eq!(disabled.for_power(true).density, 0.1);
+	assert_eq!(Config::default().for_power(true).max_fps, 30.0);
+    }
+
+    #[test]
+    fn exit_triggers_default_per_entry() {
+	let config = Config::parse("[exit_on]\nscroll = false\n");
+	assert_eq!(config.exit_on, ExitTriggers { scroll: false, ..Default::default() });
+	assert_eq!(Config::parse(&config.serialize()).exit_on, config.exit_on);
+    }
+
+    #[test]
+    fn threshold_is_migrated_to_density() {
+	let config = Config::parse("threshold = 0.75\nprofile = \"calm\"\n[profiles.calm]\nthreshold = 0.5\n");
+	assert_eq!((config.density, config.speed), (0.25, 0.25));
+	assert_eq!((config.effective().density, config.effective().speed), (0.5, 0.5));
+	assert!(!config.serialize().contains("threshold"));
+	assert_eq!(Config::parse("threshold = 0.5\nspeed = 3\n").speed, 1.5);
+	let overridden = Config::default().with_override("threshold", "0.75").unwrap();
+	assert_eq!((overridden.density, overridden.speed), (0.25, 0.25));
+	assert!(Config::default().with_override("threshold", "high").is_err());
+    }
+
+    #[test]
+    fn old_configs_are_upgraded_to_the_current_version() {
+	let (version, content) = upgraded("threshold = 0.75\nshape = \"heart\"\n").unwrap();
+	assert_eq!(version, 1);
+	let table: toml::Table = content.parse().unwrap();
+	assert_eq!(table.get("version").and_then(toml::Value::as_integer), Some(CONFIG_VERSION.into()));
+	assert_eq!(table.get("density").and_then(toml::Value::as_float), Some(0.25));
+	assert!(table.contains_key("shape") && !table.contains_key("threshold"));
+	// Only rewritten when something changed, and never downgraded
+	assert_eq!(upgraded("shape = \"heart\"\n"), None);
+	assert_eq!(upgraded(&Config::default().serialize()), None);
+	assert_eq!(Config::parse("version = 2\nthreshold = 0.75").density, Config::default().density);
+	let (config, problems) = Config::parse_reporting("version = 99");
+	assert_eq!((config.version, problems.len()), (99, 1));
+    }
+}
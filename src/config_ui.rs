@@ -0,0 +1,994 @@
+use crate::config::Config;
+use crate::i18n::{self, tr};
+use crate::layout::Layout;
+use crate::palette::{Deficiency, Palette, hex_color, hsv_to_rgb, parse_gradient, parse_hex_color, rgb_to_hsv, simulate};
+use crate::pattern::Pattern;
+use crate::post::PostEffect;
+use crate::screensaver::Screensaver;
+use crate::shapes::ShapeRegistry;
+#[cfg(windows)]
+use crate::win32;
+use macroquad::prelude::*;
+
+/// The system DPI factor: pixels per point, which the dialog and macroquad's
+/// screen size and mouse position are in.
+fn ui_scale() -> f32 {
+    screen_dpi_scale()
+}
+
+/// Map the dialog's points onto the window.
+fn set_ui_camera() {
+    set_camera(&Camera2D::from_display_rect(Rect::new(0.0, 0.0, screen_width(), screen_height())));
+}
+
+/// The cursor position in points.
+fn ui_mouse() -> Vec2 {
+    Vec2::from(mouse_position())
+}
+
+thread_local! {
+    /// A system font with the letters of the dialog's language, when the
+    /// built-in one lacks them.
+    static FONT: Option<Font> = load_font();
+}
+
+fn load_font() -> Option<Font> {
+    let fonts = i18n::select("").fonts();
+    let font = fonts.iter().find_map(|path| {
+	let bytes = std::fs::read(path).ok()?;
+	load_ttf_font_from_bytes(&bytes).map_err(|err| log::warn!("cannot load the font {path}: {err}")).ok()
+    });
+    if font.is_none() && !fonts.is_empty() {
+	log::warn!("no font found for the dialog's language, some letters may be missing");
+    }
+    font
+}
+
+/// Draw text sized in points, rasterized at the screen's resolution so it
+/// stays sharp when scaled.
+fn draw_text_points(text: &str, x: f32, y: f32, font_size: f32, color: Color) {
+    let scale = ui_scale();
+    FONT.with(|font| {
+	let params = TextParams { font: font.as_ref(), font_size: (font_size * scale) as u16, font_scale: 1.0 / scale, color, ..Default::default() };
+	draw_text_ex(text, x, y, params);
+    });
+}
+
+/// Draw `text` translated, as `draw_text_points` does.
+fn draw_label(text: &str, x: f32, y: f32, font_size: f32, color: Color) {
+    draw_text_points(tr(text), x, y, font_size, color);
+}
+
+/// How wide `draw_label` draws `text`, in points.
+fn label_width(text: &str, font_size: u16) -> f32 {
+    FONT.with(|font| measure_text(tr(text), font.as_ref(), font_size, 1.0).width)
+}
+
+/// Longest problem shown in the banner, in characters.
+const BANNER_TEXT: usize = 48;
+
+/// The banner beside the title listing what was wrong with the loaded config.
+fn draw_problems(problems: &[String]) {
+    let Some(first) = problems.first() else {
+	return;
+    };
+    let rect = Rect::new(390.0, 10.0, 350.0, 42.0);
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::from_rgba(255, 236, 179, 255));
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, Color::from_rgba(200, 140, 0, 255));
+    let heading = match problems.len() {
+	1 => tr("1 problem in the config, see config.log:").to_string(),
+	count => tr("{count} problems in the config, see config.log:").replace("{count}", &count.to_string()),
+    };
+    draw_label(&heading, rect.x + 8.0, rect.y + 17.0, 16.0, BLACK);
+    // Its end says what is wrong, the start is mostly the path
+    let length = first.chars().count();
+    let shown: String = match length > BANNER_TEXT {
+	true => "...".chars().chain(first.chars().skip(length + 3 - BANNER_TEXT)).collect(),
+	false => first.clone(),
+    };
+    draw_text_points(&shown, rect.x + 8.0, rect.y + 35.0, 16.0, DARKGRAY);
+}
+
+/// Draw a horizontal slider and handle its input.
+///
+/// `value` is normalized to 0..1. Pressing anywhere on the track (or the
+/// handle) jumps the handle to the cursor and starts a drag, which continues
+/// until the button is released even if the cursor leaves the track.
+/// Returns the new value and whether the slider is still being dragged.
+fn slider(rect: Rect, value: f32, dragging: bool) -> (f32, bool) {
+    let mouse_pos = ui_mouse();
+    let handle_x = rect.x + value.clamp(0.0, 1.0) * rect.w;
+    let handle = Rect::new(handle_x - 8.0, rect.y - 5.0, 16.0, 20.0);
+
+    let mut dragging = dragging && is_mouse_button_down(MouseButton::Left);
+    if is_mouse_button_pressed(MouseButton::Left) && (rect.contains(mouse_pos) || handle.contains(mouse_pos)) {
+	dragging = true;
+    }
+
+    let value = if dragging {
+	((mouse_pos.x - rect.x) / rect.w).clamp(0.0, 1.0)
+    } else {
+	value
+    };
+
+    let handle_x = rect.x + value.clamp(0.0, 1.0) * rect.w;
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, LIGHTGRAY);
+    draw_rectangle(handle_x - 8.0, rect.y - 5.0, 16.0, 20.0, DARKBLUE);
+
+    (value, dragging)
+}
+
+/// The part of a `color_picker` being dragged.
+#[derive(Clone, Copy)]
+enum PickerDrag {
+    SaturationValue,
+    Hue,
+}
+
+/// Draw an HSV color picker and handle its input: a saturation/value
+/// square filling the left of `rect` and a hue strip on the right.
+///
+/// Like `slider`, a press jumps to the cursor and drags until released.
+/// Returns the new `(hue, saturation, value)` and the ongoing drag.
+fn color_picker(rect: Rect, hsv: (f32, f32, f32), drag: Option<PickerDrag>) -> ((f32, f32, f32), Option<PickerDrag>) {
+    const CELLS: usize = 16;
+    let square = Rect::new(rect.x, rect.y, rect.h, rect.h);
+    let strip = Rect::new(rect.x + rect.h + 10.0, rect.y, rect.w - rect.h - 10.0, rect.h);
+    let mouse_pos = ui_mouse();
+
+    let mut drag = drag.filter(|_| is_mouse_button_down(MouseButton::Left));
+    if is_mouse_button_pressed(MouseButton::Left) {
+	if square.contains(mouse_pos) {
+	    drag = Some(PickerDrag::SaturationValue);
+	} else if strip.contains(mouse_pos) {
+	    drag = Some(PickerDrag::Hue);
+	}
+    }
+    let (mut hue, mut saturation, mut value) = hsv;
+    match drag {
+	Some(PickerDrag::SaturationValue) => {
+	    saturation = ((mouse_pos.x - square.x) / square.w).clamp(0.0, 1.0);
+	    value = 1.0 - ((mouse_pos.y - square.y) / square.h).clamp(0.0, 1.0);
+	}
+	Some(PickerDrag::Hue) => hue = ((mouse_pos.y - strip.y) / strip.h).clamp(0.0, 1.0),
+	None => {}
+    }
+
+    let cell = square.w / CELLS as f32;
+    for row in 0..CELLS {
+	for column in 0..CELLS {
+	    let color = hsv_to_rgb(hue, column as f32 / (CELLS - 1) as f32, 1.0 - row as f32 / (CELLS - 1) as f32);
+	    draw_rectangle(square.x + column as f32 * cell, square.y + row as f32 * cell, cell + 0.5, cell + 0.5, color);
+	}
+    }
+    let band = strip.h / 36.0;
+    for index in 0..36 {
+	draw_rectangle(strip.x, strip.y + index as f32 * band, strip.w, band + 0.5, hsv_to_rgb(index as f32 / 36.0, 1.0, 1.0));
+    }
+    draw_rectangle_lines(square.x, square.y, square.w, square.h, 2.0, BLACK);
+    draw_rectangle_lines(strip.x, strip.y, strip.w, strip.h, 2.0, BLACK);
+    draw_circle_lines(square.x + saturation * square.w, square.y + (1.0 - value) * square.h, 5.0, 2.0, WHITE);
+    draw_rectangle_lines(strip.x - 2.0, strip.y + hue * strip.h - 3.0, strip.w + 4.0, 6.0, 2.0, WHITE);
+
+    ((hue, saturation, value), drag)
+}
+
+/// Color a `color_picker` is editing.
+#[derive(Clone, Copy, PartialEq)]
+enum ColorTarget {
+    Background,
+    /// An entry of the palette, turning it into a hex list.
+    PaletteColor(usize),
+}
+
+/// Most colors a palette can be given here.
+const MAX_PALETTE_COLORS: usize = 10;
+
+/// Draw a single line text box and handle its input: while `focused`
+/// typed characters are appended to `text` and Backspace deletes.
+fn text_field(rect: Rect, text: &mut String, max_len: usize, focused: bool) {
+    if focused {
+	while let Some(c) = get_char_pressed() {
+	    if !c.is_control() && text.chars().count() < max_len {
+		text.push(c);
+	    }
+	}
+	if is_key_pressed(KeyCode::Backspace) {
+	    text.pop();
+	}
+    }
+
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, WHITE);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, if focused { DARKBLUE } else { BLACK });
+    let shown = if focused { format!("{text}_") } else { text.clone() };
+    draw_text_points(&shown, rect.x + 8.0, rect.y + rect.h - 8.0, 20.0, BLACK);
+}
+
+/// Draw a dropdown showing `options[selected]` and handle its input.
+///
+/// While `open` the options are listed above the box, or below it when
+/// there is no room above, so call this after drawing everything it may
+/// cover. Returns the new selection, whether the list is still open, and
+/// whether this frame's click was used up.
+fn dropdown(rect: Rect, options: &[&str], selected: usize, open: bool) -> (usize, bool, bool) {
+    let mouse_pos = ui_mouse();
+    let clicked = is_mouse_button_pressed(MouseButton::Left);
+    let list_top = if rect.y >= options.len() as f32 * rect.h {
+	rect.y - options.len() as f32 * rect.h
+    } else {
+	rect.y + rect.h
+    };
+    let option_rect = |index: usize| Rect::new(rect.x, list_top + index as f32 * rect.h, rect.w, rect.h);
+
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, WHITE);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, BLACK);
+    draw_label(options[selected], rect.x + 8.0, rect.y + rect.h - 8.0, 20.0, BLACK);
+    draw_label("v", rect.x + rect.w - 18.0, rect.y + rect.h - 8.0, 20.0, BLACK);
+
+    if open {
+	for (index, option) in options.iter().enumerate() {
+	    let item = option_rect(index);
+	    let background = if item.contains(mouse_pos) { LIGHTGRAY } else { WHITE };
+	    draw_rectangle(item.x, item.y, item.w, item.h, background);
+	    draw_rectangle_lines(item.x, item.y, item.w, item.h, 1.0, GRAY);
+	    draw_label(option, item.x + 8.0, item.y + item.h - 8.0, 20.0, BLACK);
+	}
+    }
+
+    if !clicked {
+	return (selected, open, false);
+    }
+    if rect.contains(mouse_pos) {
+	return (selected, !open, true);
+    }
+    if open {
+	let picked = (0..options.len()).find(|&index| option_rect(index).contains(mouse_pos));
+	return (picked.unwrap_or(selected), false, true);
+    }
+    (selected, false, false)
+}
+
+/// Pages of the dialog.
+#[derive(Clone, Copy, PartialEq)]
+enum Tab {
+    Shape,
+    Colors,
+    Behavior,
+    Advanced,
+}
+
+const TABS: [(Tab, &str); 4] = [(Tab::Shape, "Shape"), (Tab::Colors, "Colors"), (Tab::Behavior, "Behavior"), (Tab::Advanced, "Advanced")];
+
+impl Tab {
+    /// The widgets reachable with Tab on this page, in order.
+    fn fields(self) -> Vec<Field> {
+	let page: &[Field] = match self {
+	    Tab::Shape => &[Field::Shape, Field::Size, Field::Gap, Field::Layout, Field::Spin, Field::RandomSpin],
+	    Tab::Colors => &[Field::Palette, Field::Background, Field::Gradient, Field::Stars, Field::PostEffect, Field::Simulation, Field::ColorFade, Field::ColorHold],
+	    Tab::Behavior => &[Field::Density, Field::Speed, Field::Focus, Field::StaticColor, Field::Pattern, Field::NoiseScale, Field::NoiseSpeed, Field::ReducedMotion, Field::Clock],
+	    Tab::Advanced => &[Field::Rotation, Field::Delay, Field::Audio, Field::Temperature, Field::RippleSpeed, Field::RippleDecay, Field::LifeTick],
+	};
+	[Field::Tabs, Field::Profile].into_iter().chain(page.iter().copied()).chain([Field::Restore, Field::Ok, Field::Cancel]).collect()
+    }
+}
+
+/// Widgets that can have the keyboard focus.
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Tabs,
+    Profile,
+    Shape,
+    Size,
+    Gap,
+    Layout,
+    Spin,
+    RandomSpin,
+    Palette,
+    Background,
+    Gradient,
+    Stars,
+    PostEffect,
+    Simulation,
+    ColorFade,
+    ColorHold,
+    Density,
+    Speed,
+    Focus,
+    StaticColor,
+    Pattern,
+    NoiseScale,
+    NoiseSpeed,
+    ReducedMotion,
+    Clock,
+    Rotation,
+    Delay,
+    Audio,
+    Temperature,
+    RippleSpeed,
+    RippleDecay,
+    LifeTick,
+    Restore,
+    Ok,
+    Cancel,
+}
+
+/// Choices of the layout dropdown.
+const LAYOUTS: [(Layout, &str); 5] = [
+    (Layout::Hex, "Hexagons"),
+    (Layout::Square, "Squares"),
+    (Layout::Triangle, "Triangles"),
+    (Layout::Voronoi, "Voronoi"),
+    (Layout::Penrose, "Penrose"),
+];
+
+/// Choices of the blink pattern dropdown.
+const PATTERNS: [(Pattern, &str); 10] = [
+    (Pattern::Random, "Twinkle"),
+    (Pattern::Wave, "Waves"),
+    (Pattern::Ripple, "Ripples"),
+    (Pattern::Noise, "Clouds"),
+    (Pattern::Life, "Life"),
+    (Pattern::Ant, "Ants"),
+    (Pattern::Reaction, "Reaction"),
+    (Pattern::Rain, "Rain"),
+    (Pattern::Monitor, "System load"),
+    (Pattern::Script, "Script"),
+];
+
+/// The setting a checkbox `field` toggles.
+fn checkbox_value(config: &mut Config, field: Field) -> &mut bool {
+    match field {
+	Field::Focus => &mut config.focus,
+	Field::StaticColor => &mut config.static_color,
+	Field::Audio => &mut config.audio_reactive,
+	Field::Temperature => &mut config.temperature_shift,
+	Field::Stars => &mut config.stars,
+	Field::RandomSpin => &mut config.random_spin,
+	Field::ReducedMotion => &mut config.reduced_motion,
+	Field::Clock => &mut config.clock,
+	_ => unreachable!("not a checkbox"),
+    }
+}
+
+/// The effect after clicking the tube screen checkbox with `effect` set.
+fn toggle_crt(effect: PostEffect) -> PostEffect {
+    if effect == PostEffect::Crt { PostEffect::None } else { PostEffect::Crt }
+}
+
+/// Random colors the preview shows a deficiency with, the random palette
+/// having none to simulate.
+const SIMULATED_RANDOM_COLORS: usize = 12;
+
+/// `config` with its palette of `colors` and its background as they look
+/// with `deficiency`, to preview. Gradient and picture colors are left as
+/// they are.
+fn simulated(config: &Config, colors: &[Color], deficiency: Deficiency) -> Config {
+    if deficiency == Deficiency::None {
+	return config.clone();
+    }
+    let colors: Vec<Color> = if colors.is_empty() {
+	(0..SIMULATED_RANDOM_COLORS).map(|index| Palette::random().nth(index)).collect()
+    } else {
+	colors.to_vec()
+    };
+    let colors = colors.into_iter().map(|color| simulate(color, deficiency)).collect();
+    Config {
+	palette: Palette::from_colors(colors).to_hex_list(),
+	background: hex_color(simulate(config.background_color(), deficiency)),
+	..config.clone()
+    }
+}
+
+/// 1 if `forward` was pressed this frame, -1 for `back`, otherwise 0.
+fn key_step(back: KeyCode, forward: KeyCode) -> i32 {
+    is_key_pressed(forward) as i32 - is_key_pressed(back) as i32
+}
+
+/// Move `index` by `step` within `0..len`, wrapping around.
+fn cycle(index: usize, len: usize, step: i32) -> usize {
+    (index as i32 + step).rem_euclid(len as i32) as usize
+}
+
+/// A focused slider's normalized `value` moved by the Left and Right keys,
+/// if either was pressed.
+fn slider_key_step(value: f32) -> Option<f32> {
+    let step = key_step(KeyCode::Left, KeyCode::Right);
+    (step != 0).then(|| (value + step as f32 * 0.05).clamp(0.0, 1.0))
+}
+
+/// The area around a slider track that its focus indicator outlines.
+fn slider_focus_rect(track: Rect) -> Rect {
+    Rect::new(track.x - 10.0, track.y - 7.0, track.w + 20.0, track.h + 14.0)
+}
+
+/// A box at the left of `rect`, ticked when `checked`, with `label` beside it.
+fn draw_checkbox(rect: Rect, label: &str, checked: bool) {
+    draw_rectangle(rect.x, rect.y, 20.0, 20.0, WHITE);
+    draw_rectangle_lines(rect.x, rect.y, 20.0, 20.0, 2.0, BLACK);
+    if checked {
+	draw_rectangle(rect.x + 4.0, rect.y + 4.0, 12.0, 12.0, DARKBLUE);
+    }
+    draw_label(label, rect.x + 30.0, rect.y + 16.0, 20.0, BLACK);
+}
+
+fn draw_button(rect: Rect, label: &str, hovered: bool) {
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, if hovered { DARKGRAY } else { GRAY });
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, BLACK);
+    let width = label_width(label, 22);
+    draw_label(label, rect.x + (rect.w - width) / 2.0, rect.y + rect.h / 2.0 + 7.0, 22.0, WHITE);
+}
+
+fn draw_focus(rect: Rect) {
+    draw_rectangle_lines(rect.x - 3.0, rect.y - 3.0, rect.w + 6.0, rect.h + 6.0, 2.0, ORANGE);
+}
+
+/// Area of the dialog showing the live preview.
+const PREVIEW_RECT: Rect = Rect { x: 500.0, y: 110.0, w: 240.0, h: 180.0 };
+/// Area of the dialog showing the color picker while a color is edited.
+const PICKER_RECT: Rect = Rect { x: 500.0, y: 305.0, w: 190.0, h: 150.0 };
+
+/// Advance `preview` and draw it clipped to `rect`, whose grid it was laid
+/// out for at the origin.
+fn draw_preview(preview: &mut Screensaver, rect: Rect, dt: f32) {
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, preview.config().background_color());
+    let commands = preview.step(dt, &[]);
+    // The viewport clips shapes at the edges; its origin is the bottom left
+    let scale = ui_scale();
+    set_camera(&Camera2D {
+	viewport: Some((
+	    (rect.x * scale) as i32,
+	    ((screen_height() - rect.bottom()) * scale) as i32,
+	    (rect.w * scale) as i32,
+	    (rect.h * scale) as i32,
+	)),
+	..Camera2D::from_display_rect(Rect::new(0.0, 0.0, rect.w, rect.h))
+    });
+    preview.draw(&commands);
+    set_ui_camera();
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, BLACK);
+}
+
+/// Show the settings dialog. `owner` is the Display Properties window
+/// passed as `/c:<hwnd>`, which the dialog attaches to as a modal child.
+pub async fn run_config_ui(owner: Option<isize>) {
+    #[cfg(windows)]
+    if let Some(owner) = owner {
+	// The window only exists once the first frame has been presented
+	next_frame().await;
+	win32::set_owner(owner);
+    }
+
+    // Edits apply to the selected profile, stored back into `file` on save
+    let (mut file, problems) = Config::load_reporting();
+    let mut config = file.effective();
+    i18n::select(&config.language);
+    let registry = ShapeRegistry::with_builtins();
+    let shapes: Vec<(&str, &str)> = registry.iter().map(|(_, renderer)| (renderer.name(), renderer.label())).collect();
+    let mut tab = Tab::Shape;
+    let mut focus = Field::Tabs;
+    let mut dragging: Option<Field> = None;
+    let mut background_text = config.background.clone();
+    let mut gradient_text = config.background_gradient.clone();
+    let mut palette_names: Vec<&str> = Palette::names().collect();
+    // Hex palettes, from the config file or edited below, stay selectable
+    let mut custom_palette = config.palette.clone();
+    if Palette::named(&custom_palette).is_none() {
+	palette_names.push("custom");
+    }
+    let mut palette_open = false;
+    let mut layout_open = false;
+    let mut pattern_open = false;
+    let mut profile_open = false;
+    let mut palette_source = String::new();
+    let mut palette_colors = Vec::new();
+    let mut picking = None;
+    let mut picker_hsv = (0.0, 0.0, 0.0);
+    let mut picker_drag = None;
+    let mut simulation = Deficiency::None;
+    // Sized like a thumbnail of a 1080p screen
+    let preview_size = PREVIEW_RECT.size();
+    let mut preview = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, preview_size.x, preview_size.y)],
+				       (config.radius * preview_size.y / 1080.0).max(3.0));
+
+    loop {
+	set_ui_camera();
+	clear_background(Color::from_rgba(240, 240, 240, 255));
+	let fields = tab.fields();
+	let mut focus_rects = Vec::new();
+	let mut checkboxes = Vec::new();
+	let mouse = ui_mouse();
+
+	// Tab and Shift+Tab move the focus, closing any open list
+	if is_key_pressed(KeyCode::Tab) {
+	    let back = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+	    let index = fields.iter().position(|field| *field == focus).unwrap_or(0);
+	    focus = fields[cycle(index, fields.len(), if back { -1 } else { 1 })];
+	    palette_open = false;
+	    layout_open = false;
+	    pattern_open = false;
+	    profile_open = false;
+	}
+	let activated = is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space);
+
+	// Title
+	draw_label("Screensaver Configuration", 20.0, 40.0, 30.0, BLACK);
+	draw_problems(&problems);
+
+	// Tab bar; the arrow keys switch tabs while it has the focus
+	if focus == Field::Tabs {
+	    let index = TABS.iter().position(|(other, _)| *other == tab).unwrap_or(0);
+	    tab = TABS[cycle(index, TABS.len(), key_step(KeyCode::Left, KeyCode::Right))].0;
+	}
+	let tab_rect = |index: usize| Rect::new(20.0 + index as f32 * 115.0, 60.0, 110.0, 30.0);
+	for (index, (other, label)) in TABS.iter().enumerate() {
+	    let rect = tab_rect(index);
+	    let background = if *other == tab { WHITE } else { LIGHTGRAY };
+	    draw_rectangle(rect.x, rect.y, rect.w, rect.h, background);
+	    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, BLACK);
+	    draw_label(label, rect.x + 12.0, rect.y + 21.0, 20.0, BLACK);
+	}
+	focus_rects.push((Field::Tabs, Rect::new(20.0, 60.0, TABS.len() as f32 * 115.0 - 5.0, 30.0)));
+	draw_line(20.0, 90.0, 480.0, 90.0, 2.0, BLACK);
+
+	// A slider over `range`, dragged or stepped with the arrow keys
+	let mut range_slider = |focus_rects: &mut Vec<(Field, Rect)>, field: Field, track: Rect, value: f32, min: f32, max: f32| {
+	    focus_rects.push((field, slider_focus_rect(track)));
+	    let (normalized, still_dragging) = slider(track, (value - min) / (max - min), dragging == Some(field));
+	    if still_dragging {
+		dragging = Some(field);
+	    } else if dragging == Some(field) {
+		dragging = None;
+	    }
+	    let key_value = if focus == field { slider_key_step(normalized) } else { None };
+	    match key_value {
+		Some(normalized) => min + normalized * (max - min),
+		None if still_dragging => min + normalized * (max - min),
+		None => value,
+	    }
+	};
+
+	let mut shape_boxes = Vec::new();
+	let mut swatches = Vec::new();
+	let mut add_swatch = None;
+	let mut background_swatch = None;
+	let mut crt_box = None;
+	let mut simulation_button = None;
+	match tab {
+	    Tab::Shape => {
+		draw_label("Choose Shape:", 20.0, 130.0, 25.0, BLACK);
+
+		// One radio button per registered shape, in columns of three;
+		// the arrow keys step through them
+		if focus == Field::Shape {
+		    let selected =
+			shapes.iter().position(|(name, _)| name.eq_ignore_ascii_case(&config.shape)).unwrap_or(0);
+		    let step = key_step(KeyCode::Left, KeyCode::Right) + key_step(KeyCode::Up, KeyCode::Down);
+		    config.shape = shapes[cycle(selected, shapes.len(), step)].0.to_string();
+		}
+		for (index, &(name, label)) in shapes.iter().enumerate() {
+		    let radio_box = Rect::new(40.0 + (index / 3) as f32 * 150.0, 150.0 + (index % 3) as f32 * 30.0, 20.0, 20.0);
+		    draw_rectangle(radio_box.x, radio_box.y, radio_box.w, radio_box.h, WHITE);
+		    draw_rectangle_lines(radio_box.x, radio_box.y, radio_box.w, radio_box.h, 2.0, BLACK);
+		    if name.eq_ignore_ascii_case(&config.shape) {
+			draw_rectangle(radio_box.x + 4.0, radio_box.y + 4.0, 12.0, 12.0, DARKBLUE);
+		    }
+		    draw_label(label, radio_box.x + 30.0, radio_box.y + 18.0, 20.0, BLACK);
+		    shape_boxes.push((radio_box, name));
+		}
+		let columns = shapes.len().div_ceil(3) as f32;
+		focus_rects.push((Field::Shape, Rect::new(34.0, 144.0, columns * 150.0 - 10.0, 92.0)));
+
+		// Shape size slider, 10 to 100 pixels
+		draw_label("Shape size:", 20.0, 280.0, 20.0, BLACK);
+		config.radius = range_slider(&mut focus_rects, Field::Size, Rect::new(40.0, 295.0, 340.0, 10.0), config.radius, 10.0, 100.0).round();
+		draw_label(&format!("{:.0}px", config.radius), 400.0, 305.0, 18.0, BLACK);
+
+		// Gap slider, 0 to 40 pixels
+		draw_label("Gap between shapes:", 20.0, 335.0, 20.0, BLACK);
+		config.gap = range_slider(&mut focus_rects, Field::Gap, Rect::new(40.0, 350.0, 340.0, 10.0), config.gap, 0.0, 40.0).round();
+		draw_label(&format!("{:.0}px", config.gap), 400.0, 360.0, 18.0, BLACK);
+
+		// Spin slider, either way up to half a turn a second
+		draw_label("Spin:", 20.0, 440.0, 20.0, BLACK);
+		config.rotation_speed =
+		    range_slider(&mut focus_rects, Field::Spin, Rect::new(40.0, 455.0, 340.0, 10.0), config.rotation_speed, -180.0, 180.0).round();
+		draw_label(&format!("{:.0} deg/s", config.rotation_speed), 400.0, 465.0, 18.0, BLACK);
+		checkboxes.push((Field::RandomSpin, Rect::new(40.0, 475.0, 300.0, 20.0), "Spin some shapes the other way"));
+	    }
+	    Tab::Colors => {
+		// Palette colors; click one to edit it, right click to remove it
+		draw_label("Colors:", 20.0, 178.0, 20.0, BLACK);
+		// Resolved only on changes so an invalid palette warns once
+		if palette_source != config.palette {
+		    palette_source = config.palette.clone();
+		    palette_colors = Palette::resolve(&palette_source).colors().to_vec();
+		}
+		let swatch = |index: usize| Rect::new(120.0 + index as f32 * 30.0, 160.0, 26.0, 26.0);
+		for (index, &color) in palette_colors.iter().enumerate() {
+		    let rect = swatch(index);
+		    draw_rectangle(rect.x, rect.y, rect.w, rect.h, simulate(color, simulation));
+		    let selected = picking == Some(ColorTarget::PaletteColor(index));
+		    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, if selected { 4.0 } else { 2.0 }, BLACK);
+		    swatches.push(rect);
+		}
+		add_swatch = (palette_colors.len() < MAX_PALETTE_COLORS).then(|| swatch(palette_colors.len()));
+		if let Some(rect) = add_swatch {
+		    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, GRAY);
+		    draw_label("+", rect.x + 7.0, rect.y + 20.0, 25.0, GRAY);
+		}
+
+		// Background color as hex, applied once it parses
+		draw_label("Background:", 20.0, 218.0, 20.0, BLACK);
+		let background_rect = Rect::new(140.0, 200.0, 120.0, 26.0);
+		focus_rects.push((Field::Background, background_rect));
+		text_field(background_rect, &mut background_text, 7, focus == Field::Background);
+		if parse_hex_color(&background_text).is_some() {
+		    config.background = background_text.clone();
+		}
+		let rect = Rect::new(270.0, 200.0, 26.0, 26.0);
+		draw_rectangle(rect.x, rect.y, rect.w, rect.h, simulate(config.background_color(), simulation));
+		let selected = picking == Some(ColorTarget::Background);
+		draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, if selected { 4.0 } else { 2.0 }, BLACK);
+		background_swatch = Some(rect);
+
+		// Top and bottom colors, or empty for the plain background
+		draw_label("Gradient:", 20.0, 258.0, 20.0, BLACK);
+		let gradient_rect = Rect::new(140.0, 240.0, 160.0, 26.0);
+		focus_rects.push((Field::Gradient, gradient_rect));
+		text_field(gradient_rect, &mut gradient_text, 15, focus == Field::Gradient);
+		if gradient_text.is_empty() || parse_gradient(&gradient_text).is_some() {
+		    config.background_gradient = gradient_text.clone();
+		}
+
+		checkboxes.push((Field::Stars, Rect::new(40.0, 290.0, 300.0, 20.0), "Drifting stars behind the shapes"));
+
+		// Not a plain setting, so toggled apart from the checkboxes
+		let rect = Rect::new(40.0, 320.0, 300.0, 20.0);
+		focus_rects.push((Field::PostEffect, rect));
+		if focus == Field::PostEffect && activated {
+		    config.post_effect = toggle_crt(config.post_effect);
+		}
+		draw_checkbox(rect, "Retro tube screen", config.post_effect == PostEffect::Crt);
+		crt_box = Some(rect);
+
+		// Shows the colors as they look with a color vision deficiency,
+		// without changing them
+		let rect = Rect::new(40.0, 350.0, 300.0, 30.0);
+		focus_rects.push((Field::Simulation, rect));
+		let index = Deficiency::ALL.iter().position(|other| *other == simulation).unwrap_or(0);
+		let step = if focus == Field::Simulation { key_step(KeyCode::Left, KeyCode::Right) + activated as i32 } else { 0 };
+		simulation = Deficiency::ALL[cycle(index, Deficiency::ALL.len(), step)];
+		draw_button(rect, &format!("{} {}", tr("Preview as:"), tr(simulation.label())), rect.contains(mouse));
+		simulation_button = Some(rect);
+
+		draw_label("Color fade:", 20.0, 410.0, 20.0, BLACK);
+		config.color_transition_seconds =
+		    range_slider(&mut focus_rects, Field::ColorFade, Rect::new(40.0, 425.0, 340.0, 10.0), config.color_transition_seconds, 0.5, 20.0);
+		draw_label(&format!("{:.1}s", config.color_transition_seconds), 400.0, 435.0, 18.0, BLACK);
+
+		draw_label("Hold each color:", 20.0, 455.0, 20.0, BLACK);
+		config.color_hold_seconds =
+		    range_slider(&mut focus_rects, Field::ColorHold, Rect::new(40.0, 470.0, 340.0, 10.0), config.color_hold_seconds, 0.0, 30.0);
+		draw_label(&format!("{:.1}s", config.color_hold_seconds), 400.0, 480.0, 18.0, BLACK);
+	    }
+	    Tab::Behavior => {
+		// Density slider, spread over the sparse tenth where single
+		// shapes twinkle
+		draw_label("Density (fewer <- -> more):", 20.0, 130.0, 20.0, BLACK);
+		let density = range_slider(&mut focus_rects, Field::Density, Rect::new(40.0, 150.0, 340.0, 10.0), config.density * 10., 0.0, 1.0);
+		config.density = density / 10.;
+		draw_label(&format!("{:.0}%", density * 100.0), 400.0, 160.0, 18.0, BLACK);
+
+		// Speed slider, 0.1x to 3x
+		draw_label("Speed:", 20.0, 190.0, 20.0, BLACK);
+		config.speed = range_slider(&mut focus_rects, Field::Speed, Rect::new(40.0, 205.0, 340.0, 10.0), config.speed, 0.1, 3.0);
+		draw_label(&format!("{:.1}x", config.speed), 400.0, 215.0, 18.0, BLACK);
+
+		checkboxes.push((Field::Focus, Rect::new(40.0, 240.0, 300.0, 20.0), "Highlight one shape"));
+		checkboxes.push((Field::StaticColor, Rect::new(40.0, 270.0, 300.0, 20.0), "Keep each shape's color"));
+
+		// Cloud pattern sliders, below the pattern dropdown
+		draw_label("Cloud size:", 20.0, 375.0, 20.0, BLACK);
+		config.noise_scale =
+		    range_slider(&mut focus_rects, Field::NoiseScale, Rect::new(40.0, 390.0, 340.0, 10.0), config.noise_scale, 2.0, 40.0);
+		draw_label(&format!("{:.0}", config.noise_scale), 400.0, 400.0, 18.0, BLACK);
+
+		draw_label("Cloud speed:", 20.0, 430.0, 20.0, BLACK);
+		config.noise_speed =
+		    range_slider(&mut focus_rects, Field::NoiseSpeed, Rect::new(40.0, 445.0, 340.0, 10.0), config.noise_speed, 0.0, 1.0);
+		draw_label(&format!("{:.2}", config.noise_speed), 400.0, 455.0, 18.0, BLACK);
+
+		checkboxes.push((Field::ReducedMotion, Rect::new(40.0, 475.0, 200.0, 20.0), "Reduce motion"));
+		checkboxes.push((Field::Clock, Rect::new(250.0, 475.0, 200.0, 20.0), "Show the time"));
+	    }
+	    Tab::Advanced => {
+		draw_label("Grid rotation:", 20.0, 130.0, 20.0, BLACK);
+		config.grid_rotation =
+		    range_slider(&mut focus_rects, Field::Rotation, Rect::new(40.0, 150.0, 340.0, 10.0), config.grid_rotation, 0.0, 360.0).round();
+		draw_label(&format!("{:.0} deg", config.grid_rotation), 400.0, 160.0, 18.0, BLACK);
+
+		draw_label("Start delay:", 20.0, 190.0, 20.0, BLACK);
+		config.start_delay =
+		    range_slider(&mut focus_rects, Field::Delay, Rect::new(40.0, 205.0, 340.0, 10.0), config.start_delay, 0.0, 10.0);
+		draw_label(&format!("{:.1}s", config.start_delay), 400.0, 215.0, 18.0, BLACK);
+
+		checkboxes.push((Field::Audio, Rect::new(40.0, 240.0, 300.0, 20.0), "React to audio"));
+		checkboxes.push((Field::Temperature, Rect::new(40.0, 270.0, 300.0, 20.0), "Warmer colors at night"));
+
+		// Ripple pattern sliders
+		draw_label("Ripple speed:", 20.0, 320.0, 20.0, BLACK);
+		config.ripple_speed =
+		    range_slider(&mut focus_rects, Field::RippleSpeed, Rect::new(40.0, 335.0, 340.0, 10.0), config.ripple_speed, 1.0, 30.0);
+		draw_label(&format!("{:.1}", config.ripple_speed), 400.0, 345.0, 18.0, BLACK);
+
+		draw_label("Ripple fade:", 20.0, 375.0, 20.0, BLACK);
+		config.ripple_decay =
+		    range_slider(&mut focus_rects, Field::RippleDecay, Rect::new(40.0, 390.0, 340.0, 10.0), config.ripple_decay, 0.0, 3.0);
+		draw_label(&format!("{:.2}", config.ripple_decay), 400.0, 400.0, 18.0, BLACK);
+
+		draw_label("Life generation:", 20.0, 430.0, 20.0, BLACK);
+		config.life_tick =
+		    range_slider(&mut focus_rects, Field::LifeTick, Rect::new(40.0, 445.0, 340.0, 10.0), config.life_tick, 0.05, 2.0);
+		draw_label(&format!("{:.2}s", config.life_tick), 400.0, 455.0, 18.0, BLACK);
+	    }
+	}
+	for &(field, rect, label) in &checkboxes {
+	    focus_rects.push((field, rect));
+	    let value = checkbox_value(&mut config, field);
+	    if focus == field && activated {
+		*value = !*value;
+	    }
+	    draw_checkbox(rect, label, *value);
+	}
+
+	// Color picker for the selected swatch
+	let done_button = Rect::new(PICKER_RECT.x, PICKER_RECT.bottom() + 10.0, 80.0, 30.0);
+	if tab != Tab::Colors {
+	    picking = None;
+	    simulation = Deficiency::None;
+	}
+	if let Some(target) = picking {
+	    let (hsv, drag) = color_picker(PICKER_RECT, picker_hsv, picker_drag);
+	    picker_drag = drag;
+	    if drag.is_some() {
+		picker_hsv = hsv;
+		let color = hsv_to_rgb(hsv.0, hsv.1, hsv.2);
+		match target {
+		    ColorTarget::Background => {
+			config.background = hex_color(color);
+			background_text = config.background.clone();
+		    }
+		    ColorTarget::PaletteColor(index) => {
+			palette_colors[index] = color;
+			config.palette = Palette::from_colors(palette_colors.clone()).to_hex_list();
+		    }
+		}
+	    }
+	    draw_button(done_button, "Done", false);
+	}
+
+	preview.set_config(simulated(&config, &palette_colors, simulation));
+	draw_preview(&mut preview, PREVIEW_RECT, get_frame_time());
+
+	// Dialog buttons
+	let restore_button = Rect::new(20.0, 505.0, 170.0, 40.0);
+	let ok_button = Rect::new(530.0, 505.0, 100.0, 40.0);
+	let cancel_button = Rect::new(640.0, 505.0, 100.0, 40.0);
+	for (field, rect, label) in
+	    [(Field::Restore, restore_button, "Restore Defaults"), (Field::Ok, ok_button, "OK"), (Field::Cancel, cancel_button, "Cancel")]
+	{
+	    focus_rects.push((field, rect));
+	    draw_button(rect, label, rect.contains(mouse));
+	}
+
+	// Layout dropdown, drawn last so its open list covers the rest
+	let mut layout_click_used = false;
+	if tab == Tab::Shape {
+	    draw_label("Layout:", 20.0, 400.0, 20.0, BLACK);
+	    let layout_rect = Rect::new(120.0, 382.0, 150.0, 26.0);
+	    focus_rects.push((Field::Layout, layout_rect));
+	    let selected_layout = LAYOUTS.iter().position(|(layout, _)| *layout == config.layout).unwrap_or(0);
+	    if focus == Field::Layout && activated {
+		layout_open = !layout_open;
+	    }
+	    let names = LAYOUTS.map(|(_, name)| name);
+	    let (mut picked_layout, open, click_used) = dropdown(layout_rect, &names, selected_layout, layout_open);
+	    layout_open = open;
+	    layout_click_used = click_used;
+	    if focus == Field::Layout {
+		picked_layout = cycle(picked_layout, LAYOUTS.len(), key_step(KeyCode::Up, KeyCode::Down));
+	    }
+	    config.layout = LAYOUTS[picked_layout].0;
+	}
+
+	// Pattern dropdown, likewise
+	let mut pattern_click_used = false;
+	if tab == Tab::Behavior {
+	    draw_label("Blinking:", 20.0, 330.0, 20.0, BLACK);
+	    let pattern_rect = Rect::new(120.0, 312.0, 150.0, 26.0);
+	    focus_rects.push((Field::Pattern, pattern_rect));
+	    let selected_pattern = PATTERNS.iter().position(|(pattern, _)| *pattern == config.pattern).unwrap_or(0);
+	    if focus == Field::Pattern && activated {
+		pattern_open = !pattern_open;
+	    }
+	    let names = PATTERNS.map(|(_, name)| name);
+	    let (mut picked_pattern, open, click_used) = dropdown(pattern_rect, &names, selected_pattern, pattern_open);
+	    pattern_open = open;
+	    pattern_click_used = click_used;
+	    if focus == Field::Pattern {
+		picked_pattern = cycle(picked_pattern, PATTERNS.len(), key_step(KeyCode::Up, KeyCode::Down));
+	    }
+	    config.pattern = PATTERNS[picked_pattern].0;
+	}
+
+	// Palette dropdown, drawn last so its open list covers the rest
+	let mut palette_click_used = false;
+	if tab == Tab::Colors {
+	    draw_label("Palette:", 20.0, 130.0, 20.0, BLACK);
+	    let palette_rect = Rect::new(120.0, 112.0, 200.0, 26.0);
+	    focus_rects.push((Field::Palette, palette_rect));
+	    let selected_palette = palette_names
+		.iter()
+		.position(|name| name.eq_ignore_ascii_case(&config.palette))
+		.unwrap_or(palette_names.len() - 1);
+	    if focus == Field::Palette && activated {
+		palette_open = !palette_open;
+	    }
+	    let (mut picked_palette, open, click_used) = dropdown(palette_rect, &palette_names, selected_palette, palette_open);
+	    palette_open = open;
+	    palette_click_used = click_used;
+	    if focus == Field::Palette {
+		picked_palette = cycle(picked_palette, palette_names.len(), key_step(KeyCode::Up, KeyCode::Down));
+	    }
+	    if picked_palette != selected_palette {
+		config.palette = match palette_names[picked_palette] {
+		    "custom" => custom_palette.clone(),
+		    name => name.to_string(),
+		};
+	    }
+	}
+
+	// Profile dropdown; switching keeps the edits made to the previous one
+	draw_label("Profile:", 520.0, 80.0, 20.0, BLACK);
+	let profile_rect = Rect::new(590.0, 62.0, 150.0, 26.0);
+	focus_rects.push((Field::Profile, profile_rect));
+	let profile_names: Vec<&str> = std::iter::once("(none)").chain(file.profiles.keys().map(String::as_str)).collect();
+	let selected_profile = profile_names.iter().position(|name| *name == file.profile).unwrap_or(0);
+	if focus == Field::Profile && activated {
+	    profile_open = !profile_open;
+	}
+	let (mut picked_profile, open, profile_click_used) =
+	    dropdown(profile_rect, &profile_names, selected_profile, profile_open);
+	profile_open = open;
+	if focus == Field::Profile {
+	    picked_profile = cycle(picked_profile, profile_names.len(), key_step(KeyCode::Up, KeyCode::Down));
+	}
+	if picked_profile != selected_profile {
+	    let profile = if picked_profile == 0 { String::new() } else { profile_names[picked_profile].to_string() };
+	    file = file.with_profile_edits(&config);
+	    file.profile = profile;
+	    config = file.effective();
+	    background_text = config.background.clone();
+	    gradient_text = config.background_gradient.clone();
+	}
+
+	// Focus indicator, and clicks moving the focus to what they hit
+	if let Some((_, rect)) = focus_rects.iter().find(|(field, _)| *field == focus) {
+	    draw_focus(*rect);
+	}
+	if layout_click_used {
+	    focus = Field::Layout;
+	} else if pattern_click_used {
+	    focus = Field::Pattern;
+	} else if palette_click_used {
+	    focus = Field::Palette;
+	} else if profile_click_used {
+	    focus = Field::Profile;
+	} else if is_mouse_button_pressed(MouseButton::Left)
+	    && let Some((field, _)) = focus_rects.iter().find(|(_, rect)| rect.contains(mouse))
+	{
+	    focus = *field;
+	}
+	// The focus stays within the current tab
+	if !fields.contains(&focus) {
+	    focus = Field::Tabs;
+	}
+
+	// Handle mouse input
+	let mouse_clicked =
+	    is_mouse_button_pressed(MouseButton::Left) && !layout_click_used && !pattern_click_used && !palette_click_used && !profile_click_used;
+	let clicked_swatch = swatches.iter().position(|rect| rect.contains(mouse));
+
+	if is_mouse_button_pressed(MouseButton::Right)
+	    && let Some(index) = clicked_swatch
+	{
+	    palette_colors.remove(index);
+	    config.palette = if palette_colors.is_empty() {
+		"random".to_string()
+	    } else {
+		Palette::from_colors(palette_colors.clone()).to_hex_list()
+	    };
+	    picking = None;
+	}
+
+	let mut pressed = if activated { Some(focus) } else { None };
+	if mouse_clicked {
+	    let mut edit_color = |target, color| {
+		picking = Some(target);
+		picker_hsv = rgb_to_hsv(color);
+	    };
+	    if let Some(index) = (0..TABS.len()).find(|&index| tab_rect(index).contains(mouse)) {
+		tab = TABS[index].0;
+	    } else if let Some((_, name)) = shape_boxes.iter().find(|(radio_box, _)| radio_box.contains(mouse)) {
+		config.shape = name.to_string();
+	    } else if let Some(&(field, _, _)) = checkboxes.iter().find(|(_, rect, _)| rect.contains(mouse)) {
+		let value = checkbox_value(&mut config, field);
+		*value = !*value;
+	    } else if let Some(index) = clicked_swatch {
+		edit_color(ColorTarget::PaletteColor(index), palette_colors[index]);
+	    } else if add_swatch.is_some_and(|rect| rect.contains(mouse)) {
+		palette_colors.push(WHITE);
+		config.palette = Palette::from_colors(palette_colors.clone()).to_hex_list();
+		edit_color(ColorTarget::PaletteColor(palette_colors.len() - 1), WHITE);
+	    } else if crt_box.is_some_and(|rect| rect.contains(mouse)) {
+		config.post_effect = toggle_crt(config.post_effect);
+	    } else if let Some(rect) = simulation_button
+		&& rect.contains(mouse)
+	    {
+		let index = Deficiency::ALL.iter().position(|other| *other == simulation).unwrap_or(0);
+		simulation = Deficiency::ALL[cycle(index, Deficiency::ALL.len(), 1)];
+	    } else if background_swatch.is_some_and(|rect| rect.contains(mouse)) {
+		edit_color(ColorTarget::Background, config.background_color());
+	    } else if picking.is_some() && done_button.contains(mouse) {
+		picking = None;
+	    } else if restore_button.contains(mouse) {
+		pressed = Some(Field::Restore);
+	    } else if ok_button.contains(mouse) {
+		pressed = Some(Field::Ok);
+	    } else if cancel_button.contains(mouse) {
+		pressed = Some(Field::Cancel);
+	    }
+	}
+	match pressed {
+	    Some(Field::Ok) => {
+		// What loading skipped would be lost for good otherwise
+		if !problems.is_empty() {
+		    file.back_up();
+		}
+		file.with_profile_edits(&config).save();
+		break;
+	    }
+	    Some(Field::Cancel) => break,
+	    // Nothing is saved until OK
+	    Some(Field::Restore) => {
+		config = Config { profile: config.profile.clone(), profiles: config.profiles.clone(), ..Config::default() };
+		background_text = config.background.clone();
+		gradient_text = config.background_gradient.clone();
+		picking = None;
+	    }
+	    _ => {}
+	}
+
+	// A palette edited here becomes the custom entry
+	if Palette::named(&config.palette).is_none() {
+	    custom_palette = config.palette.clone();
+	    if !palette_names.contains(&"custom") {
+		palette_names.push("custom");
+	    }
+	}
+
+	// Escape closes the color picker first, then cancels
+	if is_key_pressed(KeyCode::Escape) && picking.take().is_none() {
+	    break;
+	}
+
+	next_frame().await
+    }
+
+    #[cfg(windows)]
+    if let Some(owner) = owner {
+	win32::release_owner(owner);
+    }
+    #[cfg(not(windows))]
+    let _ = owner;
+}
@@ -0,0 +1,348 @@
+//! The HTTP control API `control_port` opens on localhost, for scripts and
+//! home automation to read and change the settings live and set off
+//! effects, e.g. `curl -X POST 'localhost:8686/settings?palette=%23ff0000'`.
+//! Only requests naming localhost as their host and sent from outside a
+//! browser page are taken, so web sites cannot reach it, and only the
+//! settings in `REMOTE_KEYS` can be changed.
+
+use crate::config::Config;
+use crate::net::form_pairs;
+use log::{info, warn};
+use macroquad::math::Vec2;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+/// Time between checks for a connection, and whether the API is still wanted.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+/// Time a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Largest request body read.
+const MAX_BODY: usize = 64 * 1024;
+
+/// The settings requests can change: how the animation looks, and nothing
+/// reading files, reaching the network or guarding the exit.
+const REMOTE_KEYS: [&str; 58] = [
+    "shape",
+    "palette",
+    "random_saturation",
+    "random_value",
+    "color_mode",
+    "color_cycle_period",
+    "harmony",
+    "hue_shift",
+    "density",
+    "threshold",
+    "twinkle_duty",
+    "twinkle_stagger",
+    "pattern",
+    "ripple_speed",
+    "ripple_decay",
+    "noise_scale",
+    "noise_speed",
+    "life_tick",
+    "automaton",
+    "ants",
+    "ant_rule",
+    "reaction",
+    "feed",
+    "kill",
+    "rain_color",
+    "static_color",
+    "focus",
+    "clock",
+    "message",
+    "date_overlay",
+    "grid_rotation",
+    "rotation_speed",
+    "random_spin",
+    "breathing",
+    "fade_easing",
+    "color_easing",
+    "color_transition_seconds",
+    "color_hold_seconds",
+    "morph_period",
+    "layout",
+    "motion",
+    "orientation",
+    "corner_radius",
+    "speed",
+    "radius",
+    "gap",
+    "background",
+    "background_gradient",
+    "bloom",
+    "bloom_intensity",
+    "trails",
+    "post_effect",
+    "render_mode",
+    "kaleidoscope",
+    "stars",
+    "star_density",
+    "star_speed",
+    "seed",
+];
+
+const HELP: &str = "\
+GET  /settings             every setting, as the config's TOML
+GET  /settings/KEY         one setting
+POST /settings?KEY=VALUE   change how it looks until the next restart (or in the body)
+POST /settings/reset       back to the config's settings
+POST /effects/ring?x=&y=   a ring from a point, 0..1 across the first display
+POST /effects/pause        hold the animation still
+POST /effects/resume       and start it again
+POST /effects/screenshot   save a screenshot
+";
+
+/// What a request asks of the running screensaver.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Command {
+    /// Config overrides to apply on top of the config, in order.
+    Set(Vec<(String, String)>),
+    /// Drop every override.
+    Reset,
+    /// A ring from this point, as a fraction of the first display.
+    Ring(Vec2),
+    Pause(bool),
+    Screenshot,
+}
+
+/// The settings running, to answer and check requests against, and the
+/// commands not taken yet.
+struct Shared {
+    live: Config,
+    commands: Vec<Command>,
+}
+
+/// The API served in the background.
+pub(crate) struct Control {
+    port: u16,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Control {
+    /// Serve on `config.control_port`, or nothing when it is 0 or taken.
+    pub(crate) fn start(config: &Config) -> Option<Self> {
+	if config.control_port == 0 {
+	    return None;
+	}
+	let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, config.control_port))
+	    .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+	    .map_err(|err| warn!("cannot serve the control API on port {}: {err}", config.control_port))
+	    .ok()?;
+	info!("control: listening on http://localhost:{}", config.control_port);
+	let shared = Arc::new(Mutex::new(Shared { live: config.clone(), commands: Vec::new() }));
+	let weak = Arc::downgrade(&shared);
+	let port = config.control_port;
+	std::thread::spawn(move || serve(&listener, port, &weak));
+	Some(Self { port: config.control_port, shared })
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+	self.port
+    }
+
+    /// Answer with `config` from now on.
+    pub(crate) fn publish(&self, config: &Config) {
+	self.shared.lock().unwrap().live = config.clone();
+    }
+
+    /// The commands received since the last call.
+    pub(crate) fn poll(&self) -> Vec<Command> {
+	std::mem::take(&mut self.shared.lock().unwrap().commands)
+    }
+}
+
+/// Answer connections until the `Control` is dropped, which frees the port.
+fn serve(listener: &TcpListener, port: u16, shared: &Weak<Mutex<Shared>>) {
+    loop {
+	match listener.accept() {
+	    Ok((stream, _)) => {
+		let Some(shared) = shared.upgrade() else {
+		    break;
+		};
+		if let Err(err) = answer(stream, port, &shared) {
+		    warn!("control: {err}");
+		}
+	    }
+	    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+		if shared.strong_count() == 0 {
+		    break;
+		}
+		std::thread::sleep(ACCEPT_INTERVAL);
+	    }
+	    Err(err) => warn!("control: {err}"),
+	}
+    }
+}
+
+/// Read one request to `port` from `stream` and answer it.
+fn answer(stream: TcpStream, port: u16, shared: &Mutex<Shared>) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let (mut length, mut host, mut origin) = (0, None, None);
+    loop {
+	let mut header = String::new();
+	if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+	    break;
+	}
+	let Some((name, value)) = header.split_once(':') else {
+	    continue;
+	};
+	let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().to_string());
+	match name.as_str() {
+	    "content-length" => length = value.parse().unwrap_or(0),
+	    "host" => host = Some(value),
+	    "origin" => origin = Some(value),
+	    _ => {}
+	}
+    }
+    let mut body = vec![0; length.min(MAX_BODY)];
+    reader.read_exact(&mut body)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let (status, text) = match refusal(host.as_deref(), origin.is_some(), port) {
+	Some(refusal) => (403, refusal),
+	None => respond(&mut shared.lock().unwrap(), method, target, &String::from_utf8_lossy(&body)),
+    };
+    let reason = match status {
+	200 => "OK",
+	400 => "Bad Request",
+	403 => "Forbidden",
+	404 => "Not Found",
+	_ => "Method Not Allowed",
+    };
+    let mut stream = &stream;
+    write!(stream, "HTTP/1.1 {status} {reason}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{text}", text.len())
+}
+
+/// Why a request with the `Host` header `host` to `port` is turned away,
+/// if it is. Pages can send requests here too: one from a page carries its
+/// `Origin`, and one from a site that has pointed its own name at this
+/// machine names that site as the host.
+fn refusal(host: Option<&str>, has_origin: bool, port: u16) -> Option<String> {
+    if has_origin {
+	return Some("requests from web pages are not taken\n".to_string());
+    }
+    let local = host.is_some_and(|host| [format!("localhost:{port}"), format!("127.0.0.1:{port}")].iter().any(|local| host.eq_ignore_ascii_case(local)));
+    (!local).then(|| format!("the host must be localhost:{port} or 127.0.0.1:{port}\n"))
+}
+
+/// The running settings as requests see them, the exit passphrase hidden.
+fn visible(config: &Config) -> toml::Table {
+    toml::Table::try_from(config.redacted()).unwrap_or_default()
+}
+
+/// The status and text answering `method` on `target`, queuing whatever it
+/// asks of the screensaver.
+fn respond(shared: &mut Shared, method: &str, target: &str, body: &str) -> (u16, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = path.trim_end_matches('/');
+    let mut params = form_pairs(query);
+    params.extend(form_pairs(body.trim()));
+    let command = match (method, path) {
+	("GET", "" | "/help") => return (200, HELP.to_string()),
+	("GET", "/settings") => return (200, visible(&shared.live).to_string()),
+	("GET", key) if let Some(key) = key.strip_prefix("/settings/") => {
+	    return match visible(&shared.live).get(&key.replace('-', "_")) {
+		Some(toml::Value::String(value)) => (200, format!("{value}\n")),
+		Some(value) => (200, format!("{value}\n")),
+		None => (404, format!("unknown setting `{key}`\n")),
+	    };
+	}
+	("POST", "/settings") => {
+	    // Checked here so the caller hears about bad values
+	    let mut live = shared.live.clone();
+	    for (key, value) in &params {
+		if !REMOTE_KEYS.contains(&key.as_str()) || (key == "post_effect" && value.starts_with("file:")) {
+		    return (403, format!("`{key}` cannot be changed over the control API\n"));
+		}
+		match live.with_override(key, value) {
+		    Ok(changed) => live = changed,
+		    Err(err) => return (400, format!("{err}\n")),
+		}
+	    }
+	    shared.live = live;
+	    Command::Set(params)
+	}
+	("POST", "/settings/reset") => Command::Reset,
+	("POST", "/effects/ring") => {
+	    let fraction = |name: &str| params.iter().find(|(key, _)| key == name).and_then(|(_, value)| value.parse::<f32>().ok());
+	    Command::Ring(Vec2::new(fraction("x").unwrap_or(0.5), fraction("y").unwrap_or(0.5)).clamp(Vec2::ZERO, Vec2::ONE))
+	}
+	("POST", "/effects/pause") => Command::Pause(true),
+	("POST", "/effects/resume") => Command::Pause(false),
+	("POST", "/effects/screenshot") => Command::Screenshot,
+	(_, "" | "/help" | "/settings" | "/settings/reset" | "/effects/ring" | "/effects/pause" | "/effects/resume" | "/effects/screenshot") => {
+	    return (405, format!("{method} is not supported on {path}\n"));
+	}
+	_ => return (404, format!("nothing at {path}; try GET /help\n")),
+    };
+    shared.commands.push(command);
+    (200, "ok\n".to_string())
+}
+
+/// Add `overrides` to those received so far, each replacing any earlier
+/// one for its key, so there are never more than there are settings.
+pub(crate) fn add_overrides(received: &mut Vec<(String, String)>, overrides: Vec<(String, String)>) {
+    for (key, value) in overrides {
+	received.retain(|(earlier, _)| *earlier != key);
+	received.push((key, value));
+    }
+}
+
+/// `config` with the overrides received so far applied in order.
+pub(crate) fn with_overrides(config: Config, overrides: &[(String, String)]) -> Config {
+    overrides.iter().fold(config, |config, (key, value)| config.clone().with_override(key, value).unwrap_or(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HIDDEN;
+
+    #[test]
+    fn requests_change_settings_and_queue_commands() {
+	let mut shared = Shared { live: Config::default(), commands: Vec::new() };
+	assert_eq!(respond(&mut shared, "POST", "/settings?shape=heart", "speed=2"), (200, "ok\n".to_string()));
+	assert_eq!(respond(&mut shared, "GET", "/settings/shape", ""), (200, "heart\n".to_string()));
+	assert_eq!(respond(&mut shared, "GET", "/settings/speed", ""), (200, "2.0\n".to_string()));
+	assert_eq!(respond(&mut shared, "POST", "/settings?speed=fast", "").0, 400);
+	assert_eq!(respond(&mut shared, "POST", "/effects/ring?x=0.25&y=3", "").0, 200);
+	assert_eq!(respond(&mut shared, "GET", "/effects/ring", "").0, 405);
+	assert_eq!(respond(&mut shared, "GET", "/nothing", "").0, 404);
+	let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+	assert_eq!(shared.commands, [Command::Set(vec![pair("shape", "heart"), pair("speed", "2")]), Command::Ring(Vec2::new(0.25, 1.0))]);
+	assert_eq!(with_overrides(Config::default(), &[pair("shape", "star"), pair("speed", "fast")]).shape, "star");
+	let mut received = vec![pair("shape", "star"), pair("speed", "2")];
+	add_overrides(&mut received, vec![pair("shape", "heart"), pair("shape", "hexagon")]);
+	assert_eq!(received, [pair("speed", "2"), pair("shape", "hexagon")]);
+    }
+
+    #[test]
+    fn requests_stay_away_from_files_secrets_and_other_sites() {
+	let mut live = Config { exit_passphrase: "open sesame".to_string(), ..Config::default() };
+	live.profiles.insert("night".to_string(), toml::Table::from_iter([("exit_passphrase".to_string(), toml::Value::from("night owl"))]));
+	let mut shared = Shared { live, commands: Vec::new() };
+	for forbidden in ["/settings?exit_passphrase=x", "/settings?control_port=1", "/settings?script=other.script", "/settings?post_effect=file:evil.frag", "/settings?photo_folder=/"] {
+	    assert_eq!(respond(&mut shared, "POST", forbidden, "").0, 403, "{forbidden}");
+	}
+	assert!(shared.commands.is_empty());
+	assert!(REMOTE_KEYS.iter().all(|key| Config::is_key(key)));
+	assert_eq!(respond(&mut shared, "POST", "/settings?post_effect=gameboy", "").0, 200);
+	let settings = respond(&mut shared, "GET", "/settings", "").1;
+	assert!(!settings.contains("open sesame") && !settings.contains("night owl"));
+	assert_eq!(respond(&mut shared, "GET", "/settings/exit_passphrase", ""), (200, format!("{HIDDEN}\n")));
+
+	assert_eq!(refusal(Some("localhost:8686"), false, 8686), None);
+	assert_eq!(refusal(Some("127.0.0.1:8686"), false, 8686), None);
+	assert!(refusal(Some("evil.example:8686"), false, 8686).is_some());
+	assert!(refusal(Some("localhost:9000"), false, 8686).is_some());
+	assert!(refusal(None, false, 8686).is_some());
+	assert!(refusal(Some("localhost:8686"), true, 8686).is_some());
+    }
+}
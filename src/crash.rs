@@ -0,0 +1,65 @@
+//! The panic hook: a crash report in the config directory, then the desktop
+//! given back rather than a frozen full screen window left up.
+
+use crate::config::Config;
+use chrono::Local;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::sync::Mutex;
+
+/// The settings running, as TOML, for the report.
+static CONFIG: Mutex<String> = Mutex::new(String::new());
+
+/// Keep `config` for the report of a later crash, without the exit
+/// passphrase since reports get attached to bug reports.
+pub(crate) fn note_config(config: &Config) {
+    if let Ok(mut snapshot) = CONFIG.lock() {
+	*snapshot = config.redacted().serialize();
+    }
+}
+
+/// Write a report of any panic beside the config. A panic on the main
+/// thread also ends the process once the window is out of the way.
+pub fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+	let thread = std::thread::current();
+	// The lock may be held by the panicking code itself
+	let config = CONFIG.try_lock().map(|config| config.clone()).unwrap_or_default();
+	let content = report(thread.name().unwrap_or("unnamed"), info, &Backtrace::force_capture().to_string(), &config);
+	match write_report(&content) {
+	    Some(path) => eprintln!("error: crashed, report saved to {}", path.display()),
+	    None => eprintln!("{content}"),
+	}
+	if thread.name() != Some("main") {
+	    default(info);
+	    return;
+	}
+	#[cfg(windows)]
+	crate::win32::restore_desktop();
+	std::process::exit(101);
+    }));
+}
+
+/// The crash report for a panic on `thread`.
+fn report(thread: &str, info: &PanicHookInfo, backtrace: &str, config: &str) -> String {
+    let location = info.location().map(|location| location.to_string()).unwrap_or_default();
+    let message = info.payload_as_str().unwrap_or("(no message)");
+    format!(
+	"hexagon_screensaver {} crashed at {}\n\nthread '{thread}' panicked at {location}:\n{message}\n\nBacktrace:\n{backtrace}\n\nConfig:\n{config}",
+	env!("CARGO_PKG_VERSION"),
+	Local::now().format("%Y-%m-%d %H:%M:%S"),
+    )
+}
+
+/// Save `content` as a new report in the first config directory that can
+/// be written, returning where it went.
+fn write_report(content: &str) -> Option<std::path::PathBuf> {
+    let name = format!("crash-{}.txt", Local::now().format("%Y%m%d-%H%M%S"));
+    Config::config_dirs().into_iter().find_map(|dir| {
+	fs::create_dir_all(&dir).ok()?;
+	let path = dir.join(&name);
+	fs::write(&path, content).ok().map(|()| path)
+    })
+}
@@ -0,0 +1,103 @@
+//! The `debug_overlay` readout: the frame rate, a graph of recent frame
+//! times and how much gets drawn, for telling how a machine copes.
+
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+
+/// Frames the graph and the frame rate cover.
+const HISTORY: usize = 120;
+/// Frame time at the top of the graph, in seconds.
+const GRAPH_CEILING: f32 = 1.0 / 20.0;
+/// Frame times up to these draw green, then yellow, then red.
+const SMOOTH_FRAME: f32 = 1.0 / 55.0;
+const SLOW_FRAME: f32 = 1.0 / 28.0;
+/// Height of the graph, in pixels; each frame is a pixel wide.
+const GRAPH_HEIGHT: f32 = 60.0;
+const FONT_SIZE: f32 = 16.0;
+/// Space around the readout and its lines, in pixels.
+const MARGIN: f32 = 8.0;
+
+/// How much was drawn in a frame.
+pub(crate) struct FrameStats {
+    pub(crate) shapes: usize,
+    /// Shapes drawn, unknown while the shader animates the grid.
+    pub(crate) visible: Option<usize>,
+    /// Meshes submitted for the shapes.
+    pub(crate) draws: usize,
+}
+
+/// The frame times of the last `HISTORY` frames, oldest first.
+pub(crate) struct DebugOverlay {
+    frame_times: VecDeque<f32>,
+}
+
+impl DebugOverlay {
+    pub(crate) fn new() -> Self {
+	Self { frame_times: VecDeque::with_capacity(HISTORY) }
+    }
+
+    pub(crate) fn step(&mut self, dt: f32) {
+	if self.frame_times.len() == HISTORY {
+	    self.frame_times.pop_front();
+	}
+	self.frame_times.push_back(dt);
+    }
+
+    /// Frames a second over the recorded frames.
+    fn fps(&self) -> f32 {
+	let total: f32 = self.frame_times.iter().sum();
+	if total > 0.0 { self.frame_times.len() as f32 / total } else { 0.0 }
+    }
+
+    fn lines(&self, stats: &FrameStats) -> [String; 3] {
+	let last = self.frame_times.back().copied().unwrap_or(0.0);
+	let shapes = match stats.visible {
+	    Some(visible) => format!("{visible} of {} shapes drawn", stats.shapes),
+	    None => format!("{} shapes, animated on the GPU", stats.shapes),
+	};
+	[format!("{:.0} fps, {:.1} ms", self.fps(), last * 1000.0), shapes, format!("{} mesh draws", stats.draws)]
+    }
+
+    /// Draw the readout in the top left corner of `display`.
+    pub(crate) fn draw(&self, display: Rect, stats: &FrameStats) {
+	let lines = self.lines(stats);
+	let line_height = FONT_SIZE * 1.2;
+	let width = lines.iter().map(|line| measure_text(line, None, FONT_SIZE as u16, 1.0).width).fold(HISTORY as f32, f32::max);
+	let height = line_height * lines.len() as f32 + MARGIN + GRAPH_HEIGHT;
+	let corner = display.point() + Vec2::splat(MARGIN);
+	draw_rectangle(corner.x - MARGIN / 2.0, corner.y - MARGIN / 2.0, width + MARGIN, height + MARGIN, Color::new(0.0, 0.0, 0.0, 0.7));
+	for (index, line) in lines.iter().enumerate() {
+	    draw_text(line, corner.x, corner.y + (index as f32 + 0.8) * line_height, FONT_SIZE, WHITE);
+	}
+	let bottom = corner.y + height;
+	for (index, dt) in self.frame_times.iter().enumerate() {
+	    let bar = (dt / GRAPH_CEILING).min(1.0) * GRAPH_HEIGHT;
+	    let color = match *dt {
+		dt if dt <= SMOOTH_FRAME => GREEN,
+		dt if dt <= SLOW_FRAME => YELLOW,
+		_ => RED,
+	    };
+	    draw_rectangle(corner.x + index as f32, bottom - bar, 1.0, bar, color);
+	}
+	// Where a frame at 60 fps reaches
+	let target = bottom - GRAPH_HEIGHT / (GRAPH_CEILING * 60.0);
+	draw_line(corner.x, target, corner.x + HISTORY as f32, target, 1.0, GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readout_covers_the_recent_frames() {
+	let mut overlay = DebugOverlay::new();
+	overlay.step(1.0);
+	for _ in 0..HISTORY {
+	    overlay.step(0.02);
+	}
+	let lines = overlay.lines(&FrameStats { shapes: 300, visible: Some(42), draws: 2 });
+	assert_eq!(lines, ["50 fps, 20.0 ms", "42 of 300 shapes drawn", "2 mesh draws"].map(String::from));
+	assert!(overlay.lines(&FrameStats { shapes: 300, visible: None, draws: 1 })[1].contains("GPU"));
+    }
+}
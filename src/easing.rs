@@ -0,0 +1,73 @@
+//! Easing curves shaping fades and color transitions.
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Maps progress 0..1 to eased progress, starting at 0 and ending at 1.
+/// The polynomial and sine curves start slow; `elastic` and `bounce`
+/// settle at the end, `elastic` overshooting on the way.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Easing {
+    #[default]
+    Linear,
+    Quad,
+    Cubic,
+    Sine,
+    Elastic,
+    Bounce,
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+	let t = t.clamp(0.0, 1.0);
+	match self {
+	    Easing::Linear => t,
+	    Easing::Quad => t * t,
+	    Easing::Cubic => t * t * t,
+	    Easing::Sine => 1.0 - (t * PI / 2.0).cos(),
+	    Easing::Elastic if t == 0.0 || t == 1.0 => t,
+	    Easing::Elastic => 2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0,
+	    Easing::Bounce => {
+		let (n, d) = (7.5625, 2.75);
+		if t < 1.0 / d {
+		    n * t * t
+		} else if t < 2.0 / d {
+		    let t = t - 1.5 / d;
+		    n * t * t + 0.75
+		} else if t < 2.5 / d {
+		    let t = t - 2.25 / d;
+		    n * t * t + 0.9375
+		} else {
+		    let t = t - 2.625 / d;
+		    n * t * t + 0.984375
+		}
+	    }
+	}
+    }
+
+    /// The steepest slope of the curve, sampled.
+    pub(crate) fn max_slope(self) -> f32 {
+	let steps = 1000;
+	(0..steps)
+	    .map(|step| (self.apply((step + 1) as f32 / steps as f32) - self.apply(step as f32 / steps as f32)) * steps as f32)
+	    .fold(0.0, |steepest, slope| slope.abs().max(steepest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_run_from_zero_to_one() {
+	for easing in [Easing::Linear, Easing::Quad, Easing::Cubic, Easing::Sine, Easing::Elastic, Easing::Bounce] {
+	    assert!(easing.apply(0.0).abs() < 1e-5, "{easing:?}");
+	    assert!((easing.apply(1.0) - 1.0).abs() < 1e-5, "{easing:?}");
+	}
+	assert_eq!(Easing::Quad.apply(0.5), 0.25);
+	assert!((Easing::Linear.max_slope() - 1.0).abs() < 1e-3);
+	assert!((Easing::Quad.max_slope() - 2.0).abs() < 1e-2);
+	assert!(Easing::Elastic.apply(0.1) > 1.0);
+    }
+}
@@ -0,0 +1,190 @@
+//! The motions taking shapes out of their cells: in `flow` they wander
+//! along a curl noise field that slowly changes, coming back in at the far
+//! edge of their display, and in `fall` lit ones now and then drop to the
+//! bottom, fading, and come back to their cells.
+
+use crate::pattern::noise;
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, Serialize};
+
+/// How the shapes move about.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Motion {
+    /// Each stays in its cell.
+    #[default]
+    Grid,
+    /// Each drifts along the flow field.
+    Flow,
+    /// Lit ones now and then fall out of their cells.
+    Fall,
+}
+
+/// How fast the field changes, in noise units a second.
+const FLOW_CHANGE: f32 = 0.05;
+/// How quickly shapes take up the field's velocity where they are, per
+/// second.
+const FLOW_EASE: f32 = 2.0;
+
+/// The field's velocity at `pos` after `time` seconds, for features `scale`
+/// pixels across, seldom over 1 long. Being a curl it neither gathers
+/// shapes nor spreads them apart.
+pub(crate) fn curl(pos: Vec2, time: f32, scale: f32) -> Vec2 {
+    let point = pos / scale;
+    let potential = |offset: Vec2| noise(Vec3::new(point.x + offset.x, point.y + offset.y, time * FLOW_CHANGE));
+    const STEP: f32 = 0.01;
+    let dx = (potential(Vec2::X * STEP) - potential(-Vec2::X * STEP)) / (2.0 * STEP);
+    let dy = (potential(Vec2::Y * STEP) - potential(-Vec2::Y * STEP)) / (2.0 * STEP);
+    // Noise slopes seldom pass 2
+    Vec2::new(dy, -dx) / 2.0
+}
+
+/// Move `shapes` along the field for `dt` seconds, `time` seconds in, at
+/// up to `speed` pixels a second, wrapping them around the one of
+/// `displays` they are on.
+pub(crate) fn drift(shapes: &mut [Shape], displays: &[Rect], time: f32, dt: f32, speed: f32, scale: f32) {
+    for shape in shapes {
+	let pos = Vec2::new(shape.x, shape.y);
+	let Some(display) = displays.iter().min_by(|a, b| outside(**a, pos).total_cmp(&outside(**b, pos))) else {
+	    return;
+	};
+	let target = curl(pos, time, scale) * speed;
+	shape.velocity += (target - shape.velocity) * (dt * FLOW_EASE).min(1.0);
+	let moved = pos + shape.velocity * dt - display.point();
+	shape.x = display.x + moved.x.rem_euclid(display.w.max(1.0));
+	shape.y = display.y + moved.y.rem_euclid(display.h.max(1.0));
+    }
+}
+
+/// Chance a second of a lit shape starting to fall.
+const FALL_CHANCE: f32 = 0.05;
+/// Least opacity of a shape that can fall.
+const FALL_OPACITY: f32 = 0.5;
+/// Pull on falling shapes, in pixels a second squared at full scale.
+const GRAVITY: f32 = 300.0;
+/// Pixels falling shapes sway either way by at full scale, and how fast,
+/// in radians a second.
+const SWAY: f32 = 12.0;
+const SWAY_SPEED: f32 = 3.0;
+/// Pixels above the bottom of the display that falling shapes fade out
+/// over, at full scale.
+const FALL_FADE: f32 = 150.0;
+
+/// A shape on the way down from its cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Fall {
+    /// Where its cell is.
+    home: Vec2,
+    /// The bottom of its display, and the pixels above it it fades over.
+    floor: f32,
+    fade: f32,
+    /// Pixels a second it falls at.
+    speed: f32,
+    /// Seconds since it left its cell.
+    age: f32,
+    /// Its opacity as it left.
+    opacity: f32,
+}
+
+impl Fall {
+    /// How visible the shape is at `y`, fading out toward the floor.
+    pub(crate) fn opacity(&self, y: f32) -> f32 {
+	self.opacity * ((self.floor - y) / self.fade).clamp(0.0, 1.0)
+    }
+}
+
+/// Let lit `shapes` drop out of their cells now and then, moving those
+/// falling on `dt` seconds and putting back those at the bottom of the
+/// one of `displays` they are on, distances scaled by `scale`.
+pub(crate) fn fall(shapes: &mut [Shape], displays: &[Rect], dt: f32, scale: f32, rng: &RandGenerator) {
+    for shape in shapes {
+	let pos = Vec2::new(shape.x, shape.y);
+	match &mut shape.fall {
+	    None => {
+		if shape.opacity >= FALL_OPACITY && !shape.focus && !shape.lit && rng.gen_range(0.0, 1.0) < FALL_CHANCE * dt {
+		    let Some(display) = displays.iter().min_by(|a, b| outside(**a, pos).total_cmp(&outside(**b, pos))) else {
+			return;
+		    };
+		    let fade = FALL_FADE * scale;
+		    shape.fall = Some(Fall { home: pos, floor: display.bottom(), fade, speed: 0.0, age: 0.0, opacity: shape.opacity });
+		}
+	    }
+	    Some(fall) => {
+		fall.speed += GRAVITY * scale * dt;
+		fall.age += dt;
+		shape.y += fall.speed * dt;
+		shape.x = fall.home.x + SWAY * scale * (fall.age * SWAY_SPEED + shape.phase_offset).sin();
+		if shape.y >= fall.floor {
+		    // Back in its cell, to fade in again
+		    (shape.x, shape.y) = fall.home.into();
+		    shape.fall = None;
+		    shape.opacity = 0.0;
+		}
+	    }
+	}
+    }
+}
+
+/// How far `pos` is outside `display`, 0 on it.
+fn outside(display: Rect, pos: Vec2) -> f32 {
+    (pos.clamp(display.point(), display.point() + display.size()) - pos).length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+    use macroquad::rand::RandGenerator;
+
+    #[test]
+    fn the_field_neither_gathers_nor_spreads() {
+	// The divergence of a curl is zero
+	let divergence = |pos: Vec2| {
+	    let step = 0.5;
+	    let dx = (curl(pos + Vec2::X * step, 3.0, 100.0).x - curl(pos - Vec2::X * step, 3.0, 100.0).x) / (2.0 * step);
+	    let dy = (curl(pos + Vec2::Y * step, 3.0, 100.0).y - curl(pos - Vec2::Y * step, 3.0, 100.0).y) / (2.0 * step);
+	    dx + dy
+	};
+	for pos in [Vec2::new(37.0, 52.0), Vec2::new(410.0, 95.0), Vec2::new(-230.0, 600.0)] {
+	    assert!(divergence(pos).abs() < 1e-3, "{pos}");
+	}
+    }
+
+    #[test]
+    fn falling_shapes_drop_and_come_back() {
+	let display = [Rect::new(0.0, 0.0, 200.0, 100.0)];
+	let rng = RandGenerator::new();
+	let mut shapes = vec![Shape::new(50.0, 20.0, 5.0, false, &Palette::random(), &rng)];
+	shapes[0].opacity = 1.0;
+	// Sooner or later it lets go
+	let mut frames = 0;
+	while shapes[0].fall.is_none() {
+	    fall(&mut shapes, &display, 0.5, 1.0, &rng);
+	    frames += 1;
+	    assert!(frames < 10_000);
+	}
+	let mut last = 20.0;
+	while let Some(falling) = shapes[0].fall {
+	    assert!(shapes[0].y >= last && (shapes[0].x - 50.0).abs() <= SWAY + 1e-3);
+	    assert!(falling.opacity(shapes[0].y) <= 1.0);
+	    last = shapes[0].y;
+	    fall(&mut shapes, &display, 0.05, 1.0, &rng);
+	}
+	assert_eq!((shapes[0].x, shapes[0].y, shapes[0].opacity), (50.0, 20.0, 0.0));
+    }
+
+    #[test]
+    fn drifting_shapes_wrap_around_their_display() {
+	let displays = [Rect::new(0.0, 0.0, 200.0, 100.0), Rect::new(200.0, 0.0, 200.0, 100.0)];
+	let rng = RandGenerator::new();
+	let mut shapes: Vec<Shape> = (0..20).map(|index| Shape::new(index as f32 * 20.0 + 10.0, 50.0, 5.0, false, &Palette::random(), &rng)).collect();
+	let halves: Vec<bool> = shapes.iter().map(|shape| shape.x < 200.0).collect();
+	for frame in 0..600 {
+	    drift(&mut shapes, &displays, frame as f32 * 0.1, 0.1, 80.0, 50.0);
+	}
+	assert!(shapes.iter().zip(halves).all(|(shape, left)| displays[!left as usize].contains(Vec2::new(shape.x, shape.y))));
+	assert!(shapes.iter().any(|shape| shape.velocity.length() > 1.0));
+    }
+}
@@ -0,0 +1,108 @@
+//! A five pixel high bitmap font for spelling text out in the grid, one
+//! shape or more per font pixel.
+
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+
+pub(crate) const GLYPH_HEIGHT: usize = 5;
+
+/// The width of `character` and its rows, top to bottom, the lowest
+/// `width` bits lighting its columns with the leftmost highest. Lowercase
+/// letters are drawn as capitals.
+fn glyph(character: char) -> Option<(usize, [u8; GLYPH_HEIGHT])> {
+    Some(match character.to_ascii_uppercase() {
+	'0' => (3, [0b111, 0b101, 0b101, 0b101, 0b111]),
+	'1' => (3, [0b010, 0b110, 0b010, 0b010, 0b111]),
+	'2' => (3, [0b111, 0b001, 0b111, 0b100, 0b111]),
+	'3' => (3, [0b111, 0b001, 0b011, 0b001, 0b111]),
+	'4' => (3, [0b101, 0b101, 0b111, 0b001, 0b001]),
+	'5' => (3, [0b111, 0b100, 0b111, 0b001, 0b111]),
+	'6' => (3, [0b111, 0b100, 0b111, 0b101, 0b111]),
+	'7' => (3, [0b111, 0b001, 0b010, 0b010, 0b010]),
+	'8' => (3, [0b111, 0b101, 0b111, 0b101, 0b111]),
+	'9' => (3, [0b111, 0b101, 0b111, 0b001, 0b111]),
+	'A' => (3, [0b010, 0b101, 0b111, 0b101, 0b101]),
+	'B' => (3, [0b110, 0b101, 0b110, 0b101, 0b110]),
+	'C' => (3, [0b011, 0b100, 0b100, 0b100, 0b011]),
+	'D' => (3, [0b110, 0b101, 0b101, 0b101, 0b110]),
+	'E' => (3, [0b111, 0b100, 0b110, 0b100, 0b111]),
+	'F' => (3, [0b111, 0b100, 0b110, 0b100, 0b100]),
+	'G' => (3, [0b011, 0b100, 0b101, 0b101, 0b011]),
+	'H' => (3, [0b101, 0b101, 0b111, 0b101, 0b101]),
+	'I' => (3, [0b111, 0b010, 0b010, 0b010, 0b111]),
+	'J' => (3, [0b001, 0b001, 0b001, 0b101, 0b010]),
+	'K' => (3, [0b101, 0b101, 0b110, 0b101, 0b101]),
+	'L' => (3, [0b100, 0b100, 0b100, 0b100, 0b111]),
+	'M' => (5, [0b10001, 0b11011, 0b10101, 0b10001, 0b10001]),
+	'N' => (4, [0b1001, 0b1101, 0b1011, 0b1001, 0b1001]),
+	'O' => (3, [0b010, 0b101, 0b101, 0b101, 0b010]),
+	'P' => (3, [0b110, 0b101, 0b110, 0b100, 0b100]),
+	'Q' => (3, [0b010, 0b101, 0b101, 0b110, 0b011]),
+	'R' => (3, [0b110, 0b101, 0b110, 0b101, 0b101]),
+	'S' => (3, [0b011, 0b100, 0b010, 0b001, 0b110]),
+	'T' => (3, [0b111, 0b010, 0b010, 0b010, 0b010]),
+	'U' => (3, [0b101, 0b101, 0b101, 0b101, 0b111]),
+	'V' => (3, [0b101, 0b101, 0b101, 0b101, 0b010]),
+	'W' => (5, [0b10001, 0b10001, 0b10101, 0b11011, 0b10001]),
+	'X' => (3, [0b101, 0b101, 0b010, 0b101, 0b101]),
+	'Y' => (3, [0b101, 0b101, 0b010, 0b010, 0b010]),
+	'Z' => (3, [0b111, 0b001, 0b010, 0b100, 0b111]),
+	' ' => (2, [0; GLYPH_HEIGHT]),
+	':' => (1, [0, 1, 0, 1, 0]),
+	'.' => (1, [0, 0, 0, 0, 1]),
+	',' => (1, [0, 0, 0, 1, 1]),
+	'!' => (1, [1, 1, 1, 0, 1]),
+	'\'' => (1, [1, 1, 0, 0, 0]),
+	'-' => (3, [0b000, 0b000, 0b111, 0b000, 0b000]),
+	'?' => (3, [0b111, 0b001, 0b010, 0b000, 0b010]),
+	_ => return None,
+    })
+}
+
+/// Which font pixels of `text` are lit, by row and then column, with a
+/// blank column between characters. Characters the font lacks are left
+/// out.
+pub(crate) fn rasterize_text(text: &str) -> Vec<Vec<bool>> {
+    let glyphs: Vec<_> = text.chars().filter_map(glyph).collect();
+    (0..GLYPH_HEIGHT)
+	.map(|row| {
+	    let mut pixels = Vec::new();
+	    for (index, (width, rows)) in glyphs.iter().enumerate() {
+		if index > 0 {
+		    pixels.push(false);
+		}
+		pixels.extend((0..*width).rev().map(|column| rows[row] >> column & 1 == 1));
+	    }
+	    pixels
+	})
+	.collect()
+}
+
+/// Light the `shapes` on `display` under the lit `pixels`, drawn from
+/// `corner` with font pixels `pixel` wide. Shapes elsewhere are left as
+/// they are.
+pub(crate) fn light_pixels(shapes: &mut [Shape], display: Rect, pixels: &[Vec<bool>], corner: Vec2, pixel: f32) {
+    let size = Vec2::new(pixels[0].len() as f32, GLYPH_HEIGHT as f32);
+    for shape in shapes.iter_mut().filter(|shape| display.contains(Vec2::new(shape.x, shape.y))) {
+	let cell = ((Vec2::new(shape.x, shape.y) - corner) / pixel).floor();
+	if cell.cmpge(Vec2::ZERO).all() && cell.cmplt(size).all() {
+	    shape.lit = pixels[cell.y as usize][cell.x as usize];
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str) -> Vec<String> {
+	rasterize_text(text).iter().map(|row| row.iter().map(|lit| if *lit { '#' } else { '.' }).collect()).collect()
+    }
+
+    #[test]
+    fn characters_rasterize_side_by_side() {
+	assert_eq!(rows("1:7"), [".#....###", "##..#...#", ".#.....#.", ".#..#..#.", "###....#."]);
+	assert_eq!(rows("Hm"), ["#.#.#...#", "#.#.##.##", "###.#.#.#", "#.#.#...#", "#.#.#...#"]);
+	assert_eq!(rows("~"), ["", "", "", "", ""]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Game controller wake-ups, which macroquad doesn't report: XInput pads on
+//! Windows and joystick devices on Linux.
+
+/// Stick deflection, out of 32767, that counts as pushing it.
+#[cfg(any(windows, target_os = "linux"))]
+const STICK_DEADZONE: i32 = 8000;
+
+/// Connected controllers, polled once a frame.
+pub(crate) struct Gamepads {
+    /// Whether each XInput pad had a button down or a stick pushed.
+    #[cfg(windows)]
+    engaged: [bool; 4],
+    #[cfg(target_os = "linux")]
+    devices: Vec<std::fs::File>,
+}
+
+impl Gamepads {
+    /// Controllers connected now; on Linux later ones are not picked up.
+    pub(crate) fn new() -> Self {
+	#[cfg(windows)]
+	{
+	    let mut gamepads = Self { engaged: [false; 4] };
+	    // Buttons already held at the start don't count
+	    gamepads.poll();
+	    gamepads
+	}
+	#[cfg(target_os = "linux")]
+	{
+	    use std::os::unix::fs::OpenOptionsExt;
+	    const O_NONBLOCK: i32 = 0o4000;
+	    let devices = (0..8)
+		.filter_map(|index| std::fs::File::options().read(true).custom_flags(O_NONBLOCK).open(format!("/dev/input/js{index}")).ok())
+		.collect();
+	    Self { devices }
+	}
+	#[cfg(not(any(windows, target_os = "linux")))]
+	Self {}
+    }
+
+    /// Whether a button was pressed or a stick pushed since the last poll.
+    pub(crate) fn poll(&mut self) -> bool {
+	#[cfg(windows)]
+	{
+	    use windows_sys::Win32::UI::Input::XboxController::{
+		XINPUT_GAMEPAD_TRIGGER_THRESHOLD, XINPUT_STATE, XInputGetState,
+	    };
+	    let mut woke = false;
+	    for (index, engaged) in self.engaged.iter_mut().enumerate() {
+		let mut state: XINPUT_STATE = unsafe { std::mem::zeroed() };
+		let connected = unsafe { XInputGetState(index as u32, &mut state) } == 0;
+		let pad = state.Gamepad;
+		let now = connected
+		    && (pad.wButtons != 0
+			|| pad.bLeftTrigger as u16 > XINPUT_GAMEPAD_TRIGGER_THRESHOLD
+			|| pad.bRightTrigger as u16 > XINPUT_GAMEPAD_TRIGGER_THRESHOLD
+			|| [pad.sThumbLX, pad.sThumbLY, pad.sThumbRX, pad.sThumbRY].iter().any(|axis| (*axis as i32).abs() > STICK_DEADZONE));
+		woke |= now && !*engaged;
+		*engaged = now;
+	    }
+	    woke
+	}
+	#[cfg(target_os = "linux")]
+	{
+	    use std::io::{ErrorKind, Read};
+	    let mut woke = false;
+	    // Each js_event is a u32 timestamp, an i16 value, a u8 type and a u8 number
+	    self.devices.retain_mut(|device| {
+		let mut event = [0u8; 8];
+		loop {
+		    match device.read_exact(&mut event) {
+			Ok(()) => woke |= event_wakes(&event),
+			Err(err) => return err.kind() == ErrorKind::WouldBlock,
+		    }
+		}
+	    });
+	    woke
+	}
+	#[cfg(not(any(windows, target_os = "linux")))]
+	false
+    }
+}
+
+/// Whether a Linux joystick event is a button press or a pushed stick,
+/// skipping the synthetic events describing the initial state.
+#[cfg(target_os = "linux")]
+fn event_wakes(event: &[u8; 8]) -> bool {
+    const BUTTON: u8 = 0x01;
+    const AXIS: u8 = 0x02;
+    let value = i16::from_ne_bytes([event[4], event[5]]) as i32;
+    match event[6] {
+	BUTTON => value == 1,
+	AXIS => value.abs() > STICK_DEADZONE,
+	_ => false,
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joystick_presses_and_pushes_wake() {
+	let event = |value: i16, kind: u8| {
+	    let [low, high] = value.to_ne_bytes();
+	    [0, 0, 0, 0, low, high, kind, 0]
+	};
+	assert!(event_wakes(&event(1, 0x01)));
+	assert!(!event_wakes(&event(0, 0x01)));
+	assert!(event_wakes(&event(-20000, 0x02)));
+	assert!(!event_wakes(&event(3000, 0x02)));
+	// Initial state, flagged with 0x80
+	assert!(!event_wakes(&event(1, 0x81)));
+    }
+}
@@ -0,0 +1,302 @@
+//! Custom shapes cut from a character of a font, such as `glyph:❄`.
+
+use crate::config::Config;
+use crate::render::MAX_MESH_INDICES;
+use crate::shapes::{ShapeRenderer, Tessellation};
+use crate::svg::{cubic, quadratic};
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use ttf_parser::{Face, OutlineBuilder};
+
+/// Line segments used to flatten each curve; shapes are small, and every
+/// segment is drawn in every cell.
+const CURVE_SEGMENTS: usize = 4;
+
+/// A character of a font, selected with `shape = "glyph:<character>"`.
+///
+/// The outline is filled holes and all, scaled to fit the unit circle and
+/// tessellated once on load; the `ShapeRegistry` caches it like any other
+/// shape and the renderer scales it to each radius.
+pub struct GlyphShape {
+    name: String,
+    tessellation: Tessellation,
+}
+
+impl GlyphShape {
+    /// Load the character named by `name`, `glyph:` followed by it, from
+    /// the first font having it: `glyph.ttf` in the config directories,
+    /// then common fonts of the system.
+    pub fn load(name: &str) -> io::Result<Self> {
+	let text = name.strip_prefix("glyph:").unwrap_or(name);
+	let mut chars = text.chars();
+	let (Some(character), None) = (chars.next(), chars.next()) else {
+	    return Err(io::Error::new(io::ErrorKind::InvalidInput, "expected a single character"));
+	};
+	let contours = font_paths()
+	    .iter()
+	    .filter_map(|path| fs::read(path).ok())
+	    .find_map(|data| outline(&Face::parse(&data, 0).ok()?, character))
+	    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no font found drawing '{character}'")))?;
+	let tessellation = tessellate(contours).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+	Ok(Self { name: name.to_string(), tessellation })
+    }
+}
+
+impl ShapeRenderer for GlyphShape {
+    fn name(&self) -> &str {
+	&self.name
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	self.tessellation.clone()
+    }
+}
+
+/// Fonts to look in, in order; missing ones are skipped.
+fn font_paths() -> Vec<PathBuf> {
+    let mut paths = vec![Config::find_file(Path::new("glyph.ttf"))];
+    if cfg!(windows) {
+	let fonts = PathBuf::from(std::env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into())).join("Fonts");
+	paths.extend(["seguisym.ttf", "seguiemj.ttf", "segoeui.ttf", "arial.ttf"].map(|name| fonts.join(name)));
+    } else if cfg!(target_os = "macos") {
+	paths.extend(
+	    ["/System/Library/Fonts/Apple Symbols.ttf", "/System/Library/Fonts/Supplemental/Arial Unicode.ttf", "/Library/Fonts/Arial Unicode.ttf"]
+		.map(PathBuf::from),
+	);
+    } else {
+	paths.extend(
+	    [
+		"/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+		"/usr/share/fonts/TTF/DejaVuSans.ttf",
+		"/usr/share/fonts/dejavu/DejaVuSans.ttf",
+		"/usr/share/fonts/truetype/noto/NotoSansSymbols2-Regular.ttf",
+		"/usr/share/fonts/noto/NotoSansSymbols2-Regular.ttf",
+	    ]
+	    .map(PathBuf::from),
+	);
+    }
+    paths
+}
+
+/// The closed, flattened contours of `character` in `face`, if it has an
+/// outline there.
+fn outline(face: &Face, character: char) -> Option<Vec<Vec<Vec2>>> {
+    let mut contours = Contours::default();
+    face.outline_glyph(face.glyph_index(character)?, &mut contours)?;
+    contours.contours.retain(|contour| contour.len() >= 3);
+    Some(contours.contours).filter(|contours| !contours.is_empty())
+}
+
+/// Collects a glyph's outline as polylines, flipped to point y down.
+#[derive(Default)]
+struct Contours {
+    contours: Vec<Vec<Vec2>>,
+    pos: Vec2,
+}
+
+impl OutlineBuilder for Contours {
+    fn move_to(&mut self, x: f32, y: f32) {
+	self.pos = Vec2::new(x, -y);
+	self.contours.push(vec![self.pos]);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+	self.pos = Vec2::new(x, -y);
+	self.push(self.pos);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+	let (start, control, end) = (self.pos, Vec2::new(x1, -y1), Vec2::new(x, -y));
+	for i in 1..=CURVE_SEGMENTS {
+	    self.push(quadratic(start, control, end, i as f32 / CURVE_SEGMENTS as f32));
+	}
+	self.pos = end;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+	let (start, c1, c2, end) = (self.pos, Vec2::new(x1, -y1), Vec2::new(x2, -y2), Vec2::new(x, -y));
+	for i in 1..=CURVE_SEGMENTS {
+	    self.push(cubic(start, c1, c2, end, i as f32 / CURVE_SEGMENTS as f32));
+	}
+	self.pos = end;
+    }
+
+    fn close(&mut self) {
+	// The closing point repeats the first
+	if let Some(contour) = self.contours.last_mut()
+	    && contour.len() > 1
+	    && contour.first() == contour.last()
+	{
+	    contour.pop();
+	}
+    }
+}
+
+impl Contours {
+    fn push(&mut self, point: Vec2) {
+	if let Some(contour) = self.contours.last_mut()
+	    && contour.last() != Some(&point)
+	{
+	    contour.push(point);
+	}
+    }
+}
+
+/// Fill `contours` into one unit-sized shape: those inside an odd number
+/// of others are holes, joined to the contour around them and cut out.
+fn tessellate(contours: Vec<Vec<Vec2>>) -> Result<Tessellation, String> {
+    let min = contours.iter().flatten().fold(Vec2::splat(f32::MAX), |min, point| min.min(*point));
+    let max = contours.iter().flatten().fold(Vec2::splat(f32::MIN), |max, point| max.max(*point));
+    let center = (min + max) / 2.0;
+    let extent = contours.iter().flatten().map(|point| point.distance(center)).fold(0.0, f32::max);
+    let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+    let contours: Vec<Vec<Vec2>> = contours.into_iter().map(|contour| contour.into_iter().map(|point| (point - center) * scale).collect()).collect();
+
+    let depth = |index: usize| contours.iter().enumerate().filter(|(other, contour)| *other != index && contains(contour, contours[index][0])).count();
+    let depths: Vec<usize> = (0..contours.len()).map(depth).collect();
+    let mut tessellation = Tessellation::default();
+    for (index, outer) in contours.iter().enumerate().filter(|(index, _)| depths[*index].is_multiple_of(2)) {
+	let mut polygon = oriented(outer.clone(), true);
+	let mut holes: Vec<Vec<Vec2>> = contours
+	    .iter()
+	    .enumerate()
+	    .filter(|(hole, contour)| depths[*hole] == depths[index] + 1 && contains(outer, contour[0]))
+	    .map(|(_, contour)| oriented(contour.clone(), false))
+	    .collect();
+	// Rightmost first, so later bridges never cross earlier holes
+	holes.sort_by(|a, b| rightmost(b).1.x.total_cmp(&rightmost(a).1.x));
+	for (hole_index, hole) in holes.iter().enumerate() {
+	    bridge(&mut polygon, hole, &holes[hole_index + 1..]);
+	}
+	let base = tessellation.vertices.len();
+	if base + polygon.len() > u16::MAX as usize {
+	    return Err("too detailed".to_string());
+	}
+	tessellation.indices.extend(clip_ears(&polygon).into_iter().map(|vertex| (base + vertex) as u16));
+	tessellation.vertices.extend(polygon);
+    }
+    if tessellation.indices.len() > MAX_MESH_INDICES {
+	return Err(format!("too detailed: {} triangles, at most {} supported", tessellation.indices.len() / 3, MAX_MESH_INDICES / 3));
+    }
+    Ok(tessellation)
+}
+
+/// Twice the signed area of `polygon`, positive when counterclockwise on
+/// screen.
+fn signed_area(polygon: &[Vec2]) -> f32 {
+    polygon.iter().zip(polygon.iter().cycle().skip(1)).map(|(a, b)| b.x * a.y - a.x * b.y).sum()
+}
+
+/// `polygon` turned counterclockwise on screen, or clockwise.
+fn oriented(mut polygon: Vec<Vec2>, counterclockwise: bool) -> Vec<Vec2> {
+    if (signed_area(&polygon) > 0.0) != counterclockwise {
+	polygon.reverse();
+    }
+    polygon
+}
+
+/// Whether `point` is inside `polygon`, by the even-odd rule.
+fn contains(polygon: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+	if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+	    inside = !inside;
+	}
+    }
+    inside
+}
+
+fn rightmost(polygon: &[Vec2]) -> (usize, Vec2) {
+    polygon.iter().copied().enumerate().max_by(|(_, a), (_, b)| a.x.total_cmp(&b.x)).unwrap_or_default()
+}
+
+/// Join `hole` into `polygon` along a cut from its rightmost point to the
+/// nearest point of `polygon` seen without crossing an edge, leaving one
+/// outline around both.
+fn bridge(polygon: &mut Vec<Vec2>, hole: &[Vec2], other_holes: &[Vec<Vec2>]) {
+    let (start, from) = rightmost(hole);
+    let edges: Vec<(Vec2, Vec2)> = [polygon.as_slice(), hole]
+	.into_iter()
+	.chain(other_holes.iter().map(Vec::as_slice))
+	.flat_map(|outline| outline.iter().zip(outline.iter().cycle().skip(1)).map(|(a, b)| (*a, *b)))
+	.collect();
+    let visible = |to: Vec2| edges.iter().all(|(a, b)| !crosses(from, to, *a, *b));
+    let target = (0..polygon.len())
+	.filter(|&index| visible(polygon[index]))
+	.min_by(|&a, &b| from.distance_squared(polygon[a]).total_cmp(&from.distance_squared(polygon[b])))
+	.unwrap_or_else(|| (0..polygon.len()).min_by(|&a, &b| from.distance_squared(polygon[a]).total_cmp(&from.distance_squared(polygon[b]))).unwrap_or(0));
+    // Out along the cut, around the hole, and back
+    let around = hole[start..].iter().chain(&hole[..=start]).copied();
+    let spliced: Vec<Vec2> = around.chain([polygon[target]]).collect();
+    polygon.splice(target + 1..target + 1, spliced);
+}
+
+/// Whether segments `a`-`b` and `c`-`d` cross, not counting shared ends.
+fn crosses(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    if a == c || a == d || b == c || b == d {
+	return false;
+    }
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2, d3, d4) = (side(c, d, a), side(c, d, b), side(a, b, c), side(a, b, d));
+    (d1 > 0.0) != (d2 > 0.0) && (d3 > 0.0) != (d4 > 0.0) && d1 != 0.0 && d2 != 0.0 && d3 != 0.0 && d4 != 0.0
+}
+
+/// Triangles filling `polygon`, given counterclockwise on screen, as
+/// indices into it: each step cuts off a corner with nothing inside it.
+fn clip_ears(polygon: &[Vec2]) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+	let count = remaining.len();
+	let corner = |at: usize| (remaining[(at + count - 1) % count], remaining[at], remaining[(at + 1) % count]);
+	let is_ear = |at: usize| {
+	    let (a, b, c) = corner(at);
+	    let (pa, pb, pc) = (polygon[a], polygon[b], polygon[c]);
+	    // Counterclockwise on screen, which points y down, turns negative
+	    (pb - pa).perp_dot(pc - pb) < 0.0
+		&& remaining.iter().map(|&index| polygon[index]).all(|point| point == pa || point == pb || point == pc || !in_triangle(point, pa, pb, pc))
+	};
+	// A degenerate outline may have no ear left; cut a corner anyway
+	let at = (0..count).find(|&at| is_ear(at)).unwrap_or(0);
+	let (a, b, c) = corner(at);
+	triangles.extend([a, b, c]);
+	remaining.remove(at);
+    }
+    if let [a, b, c] = remaining[..] {
+	triangles.extend([a, b, c]);
+    }
+    triangles
+}
+
+fn in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2| (q - p).perp_dot(point - p);
+    let (ab, bc, ca) = (side(a, b), side(b, c), side(c, a));
+    (ab <= 0.0 && bc <= 0.0 && ca <= 0.0) || (ab >= 0.0 && bc >= 0.0 && ca >= 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Total area of the triangles of `tessellation`.
+    fn area(tessellation: &Tessellation) -> f32 {
+	tessellation
+	    .indices
+	    .chunks(3)
+	    .map(|triangle| signed_area(&triangle.iter().map(|&index| tessellation.vertices[index as usize]).collect::<Vec<_>>()).abs() / 2.0)
+	    .sum()
+    }
+
+    #[test]
+    fn holes_are_cut_out_of_the_fill() {
+	let square = |half: f32| vec![vec2(-half, -half), vec2(half, -half), vec2(half, half), vec2(-half, half)];
+	// A frame around a hole, the same way round as its outside
+	let frame = tessellate(vec![square(2.0), square(1.0)]).unwrap();
+	let scale = 1.0 / 8.0_f32.sqrt();
+	assert!((area(&frame) - 12.0 * scale * scale).abs() < 1e-4);
+	assert!(frame.vertices.iter().all(|vertex| vertex.length() <= 1.0 + 1e-4));
+	assert!((area(&tessellate(vec![square(2.0)]).unwrap()) - 2.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,204 @@
+//! The twinkle animated in a shader: the grid is uploaded once and each
+//! frame only sets a handful of uniforms.
+
+use crate::config::Config;
+use crate::pattern::TWINKLE_SPEED;
+use crate::render::{MAX_MESH_INDICES, MAX_MESH_VERTICES};
+use crate::shapes::{COLOR_JITTER, Envelope, MAX_FLASH_HZ, Shape, ShapeId, ShapeRegistry};
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams, UniformType};
+use macroquad::models::{Mesh, Vertex, draw_mesh};
+use macroquad::prelude::*;
+
+// Each vertex carries its shape's state: `position` is the offset from the
+// center in `texcoord`, `color0` the first color with the color fade offset
+// in alpha, and `normal` the second color with the wave phase in w.
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+attribute vec4 normal;
+
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+uniform float time;
+uniform float twinkle;
+uniform float threshold;
+uniform float duty;
+uniform float stagger;
+uniform float breathing;
+uniform float fade_easing;
+uniform float color_easing;
+uniform float color_speed;
+uniform float color_jitter;
+uniform vec3 tint;
+uniform vec2 offset;
+
+const float PI = 3.14159265;
+
+// Easing::apply, picked by the index of the curve
+float ease(float kind, float t) {
+    t = clamp(t, 0.0, 1.0);
+    if (kind < 0.5) return t;
+    if (kind < 1.5) return t * t;
+    if (kind < 2.5) return t * t * t;
+    if (kind < 3.5) return 1.0 - cos(t * PI / 2.0);
+    if (kind < 4.5) {
+	if (t >= 1.0) return 1.0;
+	return pow(2.0, -10.0 * t) * sin((t * 10.0 - 0.75) * (2.0 * PI / 3.0)) + 1.0;
+    }
+    float n = 7.5625;
+    float d = 2.75;
+    if (t < 1.0 / d) return n * t * t;
+    if (t < 2.0 / d) { t -= 1.5 / d; return n * t * t + 0.75; }
+    if (t < 2.5 / d) { t -= 2.25 / d; return n * t * t + 0.9375; }
+    t -= 2.625 / d;
+    return n * t * t + 0.984375;
+}
+
+void main() {
+    // Envelope::opacity
+    float wave = sin(time * twinkle + normal.w * stagger);
+    float opacity = 0.0;
+    if (wave > threshold && threshold < 1.0) {
+	opacity = clamp(ease(fade_easing, (wave - threshold) / ((1.0 - threshold) * (1.0 - duty))), 0.0, 1.0);
+    }
+    if (opacity <= 0.01) {
+	opacity = 0.0;
+    }
+    // Back and forth between the two colors, which come in as bytes
+    vec4 first = color0 / 255.0;
+    // Each at its own pace, as Shape::color_pace has it
+    float pace = 1.0 + color_jitter * sin(normal.w * 3.0);
+    float cycle = fract(time * color_speed * 0.5 / pace + first.a);
+    float progress = ease(color_easing, 1.0 - abs(cycle * 2.0 - 1.0));
+    color = vec4(mix(first.rgb, normal.rgb, progress) * tint, opacity);
+    vec2 point = texcoord + offset + position.xy * (1.0 + breathing * opacity);
+    gl_Position = Projection * Model * vec4(point, 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+
+void main() {
+    gl_FragColor = color;
+}
+"#;
+
+/// A grid uploaded once for the shader to animate.
+pub(crate) struct GpuGrid {
+    meshes: Vec<Mesh>,
+    material: Material,
+}
+
+impl GpuGrid {
+    /// Upload `shapes`, drawn as `shape` unless they have their own, or the
+    /// reason the shader could not be built.
+    pub(crate) fn new(registry: &ShapeRegistry, shapes: &[Shape], shape: ShapeId) -> Result<Self, String> {
+	let material = load_material(
+	    ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+	    MaterialParams {
+		uniforms: ["time", "twinkle", "threshold", "duty", "stagger", "breathing", "fade_easing", "color_easing", "color_speed", "color_jitter"]
+		    .into_iter()
+		    .map(|name| UniformDesc::new(name, UniformType::Float1))
+		    .chain([UniformDesc::new("tint", UniformType::Float3), UniformDesc::new("offset", UniformType::Float2)])
+		    .collect(),
+		pipeline_params: PipelineParams {
+		    color_blend: Some(BlendState::new(
+			Equation::Add,
+			BlendFactor::Value(BlendValue::SourceAlpha),
+			BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+		    )),
+		    ..Default::default()
+		},
+		..Default::default()
+	    },
+	)
+	.map_err(|err| err.to_string())?;
+
+	let mut meshes = Vec::new();
+	let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new(), texture: None };
+	for cell in shapes {
+	    let tessellation = registry.tessellation(cell.own_shape.unwrap_or(shape));
+	    if mesh.vertices.len() + tessellation.vertices.len() > MAX_MESH_VERTICES
+		|| mesh.indices.len() + tessellation.indices.len() > MAX_MESH_INDICES
+	    {
+		meshes.push(std::mem::replace(&mut mesh, Mesh { vertices: Vec::new(), indices: Vec::new(), texture: None }));
+	    }
+	    let base = mesh.vertices.len() as u16;
+	    let rotation = Vec2::from_angle(cell.rotation);
+	    // Halfway through the cycle is the second color
+	    let color_offset = (cell.transition_progress * 0.5 * 255.0).round() as u8;
+	    let [r, g, b, _] = cell.color.into();
+	    for vertex in &tessellation.vertices {
+		let point = rotation.rotate(*vertex) * cell.radius;
+		mesh.vertices.push(Vertex {
+		    position: Vec3::new(point.x, point.y, 0.0),
+		    uv: Vec2::new(cell.x, cell.y),
+		    color: [r, g, b, color_offset],
+		    normal: Vec4::new(cell.next_color.r, cell.next_color.g, cell.next_color.b, cell.phase_offset),
+		});
+	    }
+	    mesh.indices.extend(tessellation.indices.iter().map(|index| base + index));
+	}
+	if !mesh.indices.is_empty() {
+	    meshes.push(mesh);
+	}
+	Ok(Self { meshes, material })
+    }
+
+    /// Draw the grid at animation `time` as `config` has it, colors scaled
+    /// by `tint` and moved by `offset` pixels.
+    pub(crate) fn draw(&self, time: f32, config: &Config, tint: Vec3, offset: Vec2) {
+	let material = &self.material;
+	material.set_uniform("time", time);
+	material.set_uniform("twinkle", twinkle_speed(config));
+	material.set_uniform("threshold", config.threshold());
+	material.set_uniform("duty", config.twinkle_duty);
+	material.set_uniform("stagger", config.twinkle_stagger);
+	material.set_uniform("breathing", if config.reduced_motion { 0.0 } else { config.breathing });
+	material.set_uniform("fade_easing", config.fade_easing as i32 as f32);
+	material.set_uniform("color_easing", config.color_easing as i32 as f32);
+	material.set_uniform("color_speed", 1.0 / config.color_transition_seconds);
+	material.set_uniform("color_jitter", COLOR_JITTER);
+	material.set_uniform("tint", tint.to_array());
+	material.set_uniform("offset", offset.to_array());
+	gl_use_material(material);
+	for mesh in &self.meshes {
+	    draw_mesh(mesh);
+	}
+	gl_use_default_material();
+    }
+}
+
+/// Radians per animation second the wave advances. With `flash_limit` it
+/// slows down until no shape fades faster than the limit allows, which the
+/// CPU does per shape instead.
+fn twinkle_speed(config: &Config) -> f32 {
+    if !config.flash_limit {
+	return TWINKLE_SPEED;
+    }
+    // Opacity per real second at the steepest point of the fade
+    let steepest = TWINKLE_SPEED * config.animation_speed() * Envelope::new(config).max_slope();
+    let limit = 2.0 * MAX_FLASH_HZ;
+    if steepest > limit { TWINKLE_SPEED * limit / steepest } else { TWINKLE_SPEED }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flash_limit_slows_the_twinkle_down() {
+	let config = Config { speed: 100.0, ..Config::default() };
+	let slowed = twinkle_speed(&config);
+	assert!(slowed < TWINKLE_SPEED);
+	let steepest = slowed * config.animation_speed() * config.fade_easing.max_slope() / (1.0 - config.threshold());
+	assert!((steepest - 2.0 * MAX_FLASH_HZ).abs() < 1e-3);
+
+	assert_eq!(twinkle_speed(&Config { speed: 0.1, ..Config::default() }), TWINKLE_SPEED);
+	assert_eq!(twinkle_speed(&Config { flash_limit: false, ..config }), TWINKLE_SPEED);
+    }
+}
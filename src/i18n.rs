@@ -0,0 +1,497 @@
+//! Translations of the settings dialog, into the language the system is set
+//! to unless `language` picks one. The English text is the key, so text
+//! without a translation shows as it is.
+
+use std::sync::OnceLock;
+
+/// The languages the dialog is translated into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Russian,
+    Japanese,
+}
+
+/// Each language's ISO 639-1 code, as locales start with.
+const CODES: [(Language, &str); 6] = [
+    (Language::English, "en"),
+    (Language::German, "de"),
+    (Language::French, "fr"),
+    (Language::Spanish, "es"),
+    (Language::Russian, "ru"),
+    (Language::Japanese, "ja"),
+];
+
+/// The language `tr` translates into, once `select` has picked it.
+static SELECTED: OnceLock<Language> = OnceLock::new();
+
+impl Language {
+    /// The language of a locale such as `de_DE.UTF-8`, `ja-JP` or `fr`.
+    pub(crate) fn from_locale(locale: &str) -> Option<Self> {
+	let code = locale.split(['_', '-', '.', '@']).next()?.to_ascii_lowercase();
+	CODES.iter().find(|(_, other)| *other == code).map(|(language, _)| *language)
+    }
+
+    /// The system's language, or English when it is none of ours.
+    pub(crate) fn system() -> Self {
+	system_locale().as_deref().and_then(Self::from_locale).unwrap_or(Language::English)
+    }
+
+    /// `text` in this language, or as it is without a translation.
+    pub(crate) fn translate(self, text: &str) -> &str {
+	self.table().iter().find(|(english, _)| *english == text).map_or(text, |(_, translated)| translated)
+    }
+
+    /// Fonts with this language's letters, the first one found being used,
+    /// for those macroquad's built-in font lacks; empty when it has them.
+    pub(crate) fn fonts(self) -> &'static [&'static str] {
+	match self {
+	    Language::English => &[],
+	    Language::German | Language::French | Language::Spanish | Language::Russian => &[
+		"C:\\Windows\\Fonts\\segoeui.ttf",
+		"/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+		"/usr/share/fonts/TTF/DejaVuSans.ttf",
+		"/usr/share/fonts/dejavu-sans-fonts/DejaVuSans.ttf",
+		"/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+		"/System/Library/Fonts/Supplemental/Arial.ttf",
+	    ],
+	    Language::Japanese => &[
+		"C:\\Windows\\Fonts\\YuGothM.ttc",
+		"C:\\Windows\\Fonts\\meiryo.ttc",
+		"C:\\Windows\\Fonts\\msgothic.ttc",
+		"/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+		"/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+		"/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+		"/usr/share/fonts/opentype/ipafont-gothic/ipag.ttf",
+		"/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+		"/System/Library/Fonts/Supplemental/Arial Unicode.ttf",
+	    ],
+	}
+    }
+
+    fn table(self) -> &'static [(&'static str, &'static str)] {
+	match self {
+	    Language::English => &[],
+	    Language::German => &GERMAN,
+	    Language::French => &FRENCH,
+	    Language::Spanish => &SPANISH,
+	    Language::Russian => &RUSSIAN,
+	    Language::Japanese => &JAPANESE,
+	}
+    }
+}
+
+/// The user's locale, such as `de_DE.UTF-8` or `ja-JP`.
+fn system_locale() -> Option<String> {
+    #[cfg(windows)]
+    return crate::win32::user_locale();
+    #[cfg(not(windows))]
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+	.into_iter()
+	.filter_map(|name| std::env::var(name).ok())
+	.find(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
+
+/// Translate into `language`, a code such as `de`, or the system's language
+/// when it is empty or not one of ours. Only the first call picks.
+pub(crate) fn select(language: &str) -> Language {
+    *SELECTED.get_or_init(|| Language::from_locale(language).unwrap_or_else(Language::system))
+}
+
+/// `text` in the selected language.
+pub(crate) fn tr(text: &str) -> &str {
+    SELECTED.get().copied().unwrap_or(Language::English).translate(text)
+}
+
+const GERMAN: [(&str, &str); 70] = [
+    ("Screensaver Configuration", "Bildschirmschoner-Einstellungen"),
+    ("Shape", "Form"),
+    ("Colors", "Farben"),
+    ("Behavior", "Verhalten"),
+    ("Advanced", "Erweitert"),
+    ("Choose Shape:", "Form wählen:"),
+    ("Shape size:", "Formgröße:"),
+    ("Gap between shapes:", "Abstand zwischen Formen:"),
+    ("Spin:", "Drehung:"),
+    ("Spin some shapes the other way", "Manche Formen andersherum drehen"),
+    ("Colors:", "Farben:"),
+    ("Background:", "Hintergrund:"),
+    ("Gradient:", "Verlauf:"),
+    ("Drifting stars behind the shapes", "Treibende Sterne hinter den Formen"),
+    ("Retro tube screen", "Retro-Röhrenbildschirm"),
+    ("Preview as:", "Vorschau als:"),
+    ("Density (fewer <- -> more):", "Dichte (weniger <- -> mehr):"),
+    ("Speed:", "Geschwindigkeit:"),
+    ("Highlight one shape", "Eine Form hervorheben"),
+    ("Keep each shape's color", "Farbe jeder Form beibehalten"),
+    ("Cloud size:", "Wolkengröße:"),
+    ("Cloud speed:", "Wolkentempo:"),
+    ("Reduce motion", "Bewegung reduzieren"),
+    ("Show the time", "Uhrzeit anzeigen"),
+    ("Grid rotation:", "Gitterdrehung:"),
+    ("Start delay:", "Startverzögerung:"),
+    ("React to audio", "Auf Ton reagieren"),
+    ("Warmer colors at night", "Wärmere Farben bei Nacht"),
+    ("Ripple speed:", "Wellentempo:"),
+    ("Ripple fade:", "Wellen-Ausklang:"),
+    ("Life generation:", "Life-Generation:"),
+    ("Done", "Fertig"),
+    ("Restore Defaults", "Standardwerte"),
+    ("OK", "OK"),
+    ("Cancel", "Abbrechen"),
+    ("Layout:", "Anordnung:"),
+    ("Blinking:", "Blinken:"),
+    ("Palette:", "Palette:"),
+    ("Profile:", "Profil:"),
+    ("(none)", "(keins)"),
+    ("custom", "eigene"),
+    ("1 problem in the config, see config.log:", "1 Problem in der Konfiguration, siehe config.log:"),
+    ("{count} problems in the config, see config.log:", "{count} Probleme in der Konfiguration, siehe config.log:"),
+    ("Hexagons", "Sechsecke"),
+    ("Squares", "Quadrate"),
+    ("Triangles", "Dreiecke"),
+    ("Voronoi", "Voronoi"),
+    ("Penrose", "Penrose"),
+    ("Twinkle", "Funkeln"),
+    ("Waves", "Wellen"),
+    ("Ripples", "Kreiswellen"),
+    ("Clouds", "Wolken"),
+    ("Life", "Life"),
+    ("Ants", "Ameisen"),
+    ("Reaction", "Reaktion"),
+    ("Rain", "Regen"),
+    ("System load", "Systemlast"),
+    ("Script", "Skript"),
+    ("Hearts", "Herzen"),
+    ("Stars", "Sterne"),
+    ("Circles", "Kreise"),
+    ("Pumpkins", "Kürbisse"),
+    ("Snowflakes", "Schneeflocken"),
+    ("Morphing", "Verwandlung"),
+    ("Normal vision", "Normales Sehen"),
+    ("Deuteranopia", "Deuteranopie"),
+    ("Protanopia", "Protanopie"),
+    ("Tritanopia", "Tritanopie"),
+    ("Color fade:", "Farbwechsel:"),
+    ("Hold each color:", "Jede Farbe halten:"),
+];
+
+const FRENCH: [(&str, &str); 70] = [
+    ("Screensaver Configuration", "Configuration de l'économiseur"),
+    ("Shape", "Forme"),
+    ("Colors", "Couleurs"),
+    ("Behavior", "Comportement"),
+    ("Advanced", "Avancé"),
+    ("Choose Shape:", "Choisir la forme :"),
+    ("Shape size:", "Taille des formes :"),
+    ("Gap between shapes:", "Espace entre les formes :"),
+    ("Spin:", "Rotation :"),
+    ("Spin some shapes the other way", "Tourner certaines formes à l'envers"),
+    ("Colors:", "Couleurs :"),
+    ("Background:", "Arrière-plan :"),
+    ("Gradient:", "Dégradé :"),
+    ("Drifting stars behind the shapes", "Étoiles dérivant derrière les formes"),
+    ("Retro tube screen", "Écran cathodique rétro"),
+    ("Preview as:", "Voir comme :"),
+    ("Density (fewer <- -> more):", "Densité (moins <- -> plus) :"),
+    ("Speed:", "Vitesse :"),
+    ("Highlight one shape", "Mettre une forme en avant"),
+    ("Keep each shape's color", "Garder la couleur de chaque forme"),
+    ("Cloud size:", "Taille des nuages :"),
+    ("Cloud speed:", "Vitesse des nuages :"),
+    ("Reduce motion", "Réduire les animations"),
+    ("Show the time", "Afficher l'heure"),
+    ("Grid rotation:", "Rotation de la grille :"),
+    ("Start delay:", "Délai de démarrage :"),
+    ("React to audio", "Réagir au son"),
+    ("Warmer colors at night", "Couleurs plus chaudes la nuit"),
+    ("Ripple speed:", "Vitesse des ondes :"),
+    ("Ripple fade:", "Atténuation des ondes :"),
+    ("Life generation:", "Génération de Life :"),
+    ("Done", "Terminé"),
+    ("Restore Defaults", "Par défaut"),
+    ("OK", "OK"),
+    ("Cancel", "Annuler"),
+    ("Layout:", "Disposition :"),
+    ("Blinking:", "Clignotement :"),
+    ("Palette:", "Palette :"),
+    ("Profile:", "Profil :"),
+    ("(none)", "(aucun)"),
+    ("custom", "personnalisée"),
+    ("1 problem in the config, see config.log:", "1 problème dans la configuration, voir config.log :"),
+    ("{count} problems in the config, see config.log:", "{count} problèmes dans la configuration, voir config.log :"),
+    ("Hexagons", "Hexagones"),
+    ("Squares", "Carrés"),
+    ("Triangles", "Triangles"),
+    ("Voronoi", "Voronoï"),
+    ("Penrose", "Penrose"),
+    ("Twinkle", "Scintillement"),
+    ("Waves", "Vagues"),
+    ("Ripples", "Ondes"),
+    ("Clouds", "Nuages"),
+    ("Life", "Life"),
+    ("Ants", "Fourmis"),
+    ("Reaction", "Réaction"),
+    ("Rain", "Pluie"),
+    ("System load", "Charge système"),
+    ("Script", "Script"),
+    ("Hearts", "Cœurs"),
+    ("Stars", "Étoiles"),
+    ("Circles", "Cercles"),
+    ("Pumpkins", "Citrouilles"),
+    ("Snowflakes", "Flocons"),
+    ("Morphing", "Métamorphose"),
+    ("Normal vision", "Vision normale"),
+    ("Deuteranopia", "Deutéranopie"),
+    ("Protanopia", "Protanopie"),
+    ("Tritanopia", "Tritanopie"),
+    ("Color fade:", "Fondu des couleurs :"),
+    ("Hold each color:", "Garder chaque couleur :"),
+];
+
+const SPANISH: [(&str, &str); 70] = [
+    ("Screensaver Configuration", "Configuración del salvapantallas"),
+    ("Shape", "Forma"),
+    ("Colors", "Colores"),
+    ("Behavior", "Comportamiento"),
+    ("Advanced", "Avanzado"),
+    ("Choose Shape:", "Elegir forma:"),
+    ("Shape size:", "Tamaño de las formas:"),
+    ("Gap between shapes:", "Espacio entre formas:"),
+    ("Spin:", "Giro:"),
+    ("Spin some shapes the other way", "Girar algunas formas al revés"),
+    ("Colors:", "Colores:"),
+    ("Background:", "Fondo:"),
+    ("Gradient:", "Degradado:"),
+    ("Drifting stars behind the shapes", "Estrellas a la deriva tras las formas"),
+    ("Retro tube screen", "Pantalla de tubo retro"),
+    ("Preview as:", "Ver como:"),
+    ("Density (fewer <- -> more):", "Densidad (menos <- -> más):"),
+    ("Speed:", "Velocidad:"),
+    ("Highlight one shape", "Resaltar una forma"),
+    ("Keep each shape's color", "Mantener el color de cada forma"),
+    ("Cloud size:", "Tamaño de las nubes:"),
+    ("Cloud speed:", "Velocidad de las nubes:"),
+    ("Reduce motion", "Reducir el movimiento"),
+    ("Show the time", "Mostrar la hora"),
+    ("Grid rotation:", "Rotación de la cuadrícula:"),
+    ("Start delay:", "Retraso de inicio:"),
+    ("React to audio", "Reaccionar al sonido"),
+    ("Warmer colors at night", "Colores más cálidos de noche"),
+    ("Ripple speed:", "Velocidad de las ondas:"),
+    ("Ripple fade:", "Desvanecido de las ondas:"),
+    ("Life generation:", "Generación de Life:"),
+    ("Done", "Listo"),
+    ("Restore Defaults", "Restaurar"),
+    ("OK", "Aceptar"),
+    ("Cancel", "Cancelar"),
+    ("Layout:", "Disposición:"),
+    ("Blinking:", "Parpadeo:"),
+    ("Palette:", "Paleta:"),
+    ("Profile:", "Perfil:"),
+    ("(none)", "(ninguno)"),
+    ("custom", "personalizada"),
+    ("1 problem in the config, see config.log:", "1 problema en la configuración, ver config.log:"),
+    ("{count} problems in the config, see config.log:", "{count} problemas en la configuración, ver config.log:"),
+    ("Hexagons", "Hexágonos"),
+    ("Squares", "Cuadrados"),
+    ("Triangles", "Triángulos"),
+    ("Voronoi", "Voronoi"),
+    ("Penrose", "Penrose"),
+    ("Twinkle", "Centelleo"),
+    ("Waves", "Olas"),
+    ("Ripples", "Ondas"),
+    ("Clouds", "Nubes"),
+    ("Life", "Life"),
+    ("Ants", "Hormigas"),
+    ("Reaction", "Reacción"),
+    ("Rain", "Lluvia"),
+    ("System load", "Carga del sistema"),
+    ("Script", "Script"),
+    ("Hearts", "Corazones"),
+    ("Stars", "Estrellas"),
+    ("Circles", "Círculos"),
+    ("Pumpkins", "Calabazas"),
+    ("Snowflakes", "Copos de nieve"),
+    ("Morphing", "Metamorfosis"),
+    ("Normal vision", "Visión normal"),
+    ("Deuteranopia", "Deuteranopía"),
+    ("Protanopia", "Protanopía"),
+    ("Tritanopia", "Tritanopía"),
+    ("Color fade:", "Cambio de color:"),
+    ("Hold each color:", "Mantener cada color:"),
+];
+
+const RUSSIAN: [(&str, &str); 70] = [
+    ("Screensaver Configuration", "Настройки заставки"),
+    ("Shape", "Фигуры"),
+    ("Colors", "Цвета"),
+    ("Behavior", "Поведение"),
+    ("Advanced", "Ещё"),
+    ("Choose Shape:", "Выберите фигуру:"),
+    ("Shape size:", "Размер фигур:"),
+    ("Gap between shapes:", "Промежуток между фигурами:"),
+    ("Spin:", "Вращение:"),
+    ("Spin some shapes the other way", "Вращать часть фигур в другую сторону"),
+    ("Colors:", "Цвета:"),
+    ("Background:", "Фон:"),
+    ("Gradient:", "Градиент:"),
+    ("Drifting stars behind the shapes", "Плывущие звёзды за фигурами"),
+    ("Retro tube screen", "Ретро-кинескоп"),
+    ("Preview as:", "Показать как:"),
+    ("Density (fewer <- -> more):", "Плотность (меньше <- -> больше):"),
+    ("Speed:", "Скорость:"),
+    ("Highlight one shape", "Выделять одну фигуру"),
+    ("Keep each shape's color", "Сохранять цвет каждой фигуры"),
+    ("Cloud size:", "Размер облаков:"),
+    ("Cloud speed:", "Скорость облаков:"),
+    ("Reduce motion", "Меньше движения"),
+    ("Show the time", "Показывать время"),
+    ("Grid rotation:", "Поворот сетки:"),
+    ("Start delay:", "Задержка запуска:"),
+    ("React to audio", "Реагировать на звук"),
+    ("Warmer colors at night", "Тёплые цвета ночью"),
+    ("Ripple speed:", "Скорость волн:"),
+    ("Ripple fade:", "Затухание волн:"),
+    ("Life generation:", "Поколение «Жизни»:"),
+    ("Done", "Готово"),
+    ("Restore Defaults", "Сбросить"),
+    ("OK", "ОК"),
+    ("Cancel", "Отмена"),
+    ("Layout:", "Раскладка:"),
+    ("Blinking:", "Мерцание:"),
+    ("Palette:", "Палитра:"),
+    ("Profile:", "Профиль:"),
+    ("(none)", "(нет)"),
+    ("custom", "своя"),
+    ("1 problem in the config, see config.log:", "1 ошибка в настройках, см. config.log:"),
+    ("{count} problems in the config, see config.log:", "Ошибок в настройках: {count}, см. config.log:"),
+    ("Hexagons", "Шестиугольники"),
+    ("Squares", "Квадраты"),
+    ("Triangles", "Треугольники"),
+    ("Voronoi", "Вороной"),
+    ("Penrose", "Пенроуз"),
+    ("Twinkle", "Искры"),
+    ("Waves", "Волны"),
+    ("Ripples", "Рябь"),
+    ("Clouds", "Облака"),
+    ("Life", "«Жизнь»"),
+    ("Ants", "Муравьи"),
+    ("Reaction", "Реакция"),
+    ("Rain", "Дождь"),
+    ("System load", "Нагрузка системы"),
+    ("Script", "Скрипт"),
+    ("Hearts", "Сердца"),
+    ("Stars", "Звёзды"),
+    ("Circles", "Круги"),
+    ("Pumpkins", "Тыквы"),
+    ("Snowflakes", "Снежинки"),
+    ("Morphing", "Превращения"),
+    ("Normal vision", "Обычное зрение"),
+    ("Deuteranopia", "Дейтеранопия"),
+    ("Protanopia", "Протанопия"),
+    ("Tritanopia", "Тританопия"),
+    ("Color fade:", "Смена цвета:"),
+    ("Hold each color:", "Держать каждый цвет:"),
+];
+
+const JAPANESE: [(&str, &str); 70] = [
+    ("Screensaver Configuration", "スクリーンセーバーの設定"),
+    ("Shape", "図形"),
+    ("Colors", "色"),
+    ("Behavior", "動作"),
+    ("Advanced", "詳細"),
+    ("Choose Shape:", "図形を選択:"),
+    ("Shape size:", "図形の大きさ:"),
+    ("Gap between shapes:", "図形の間隔:"),
+    ("Spin:", "回転:"),
+    ("Spin some shapes the other way", "一部の図形を逆回転"),
+    ("Colors:", "色:"),
+    ("Background:", "背景:"),
+    ("Gradient:", "グラデーション:"),
+    ("Drifting stars behind the shapes", "図形の後ろに流れる星"),
+    ("Retro tube screen", "レトロなブラウン管"),
+    ("Preview as:", "プレビュー:"),
+    ("Density (fewer <- -> more):", "密度 (少 <- -> 多):"),
+    ("Speed:", "速さ:"),
+    ("Highlight one shape", "図形をひとつ強調"),
+    ("Keep each shape's color", "図形ごとの色を保つ"),
+    ("Cloud size:", "雲の大きさ:"),
+    ("Cloud speed:", "雲の速さ:"),
+    ("Reduce motion", "動きを減らす"),
+    ("Show the time", "時刻を表示"),
+    ("Grid rotation:", "グリッドの回転:"),
+    ("Start delay:", "開始までの時間:"),
+    ("React to audio", "音に反応"),
+    ("Warmer colors at night", "夜は暖かい色に"),
+    ("Ripple speed:", "波紋の速さ:"),
+    ("Ripple fade:", "波紋の減衰:"),
+    ("Life generation:", "ライフの世代:"),
+    ("Done", "完了"),
+    ("Restore Defaults", "既定値に戻す"),
+    ("OK", "OK"),
+    ("Cancel", "キャンセル"),
+    ("Layout:", "配置:"),
+    ("Blinking:", "点滅:"),
+    ("Palette:", "パレット:"),
+    ("Profile:", "プロファイル:"),
+    ("(none)", "(なし)"),
+    ("custom", "カスタム"),
+    ("1 problem in the config, see config.log:", "設定に問題が1件あります。config.log を参照:"),
+    ("{count} problems in the config, see config.log:", "設定に問題が{count}件あります。config.log を参照:"),
+    ("Hexagons", "六角形"),
+    ("Squares", "正方形"),
+    ("Triangles", "三角形"),
+    ("Voronoi", "ボロノイ"),
+    ("Penrose", "ペンローズ"),
+    ("Twinkle", "きらめき"),
+    ("Waves", "波"),
+    ("Ripples", "波紋"),
+    ("Clouds", "雲"),
+    ("Life", "ライフゲーム"),
+    ("Ants", "アリ"),
+    ("Reaction", "反応拡散"),
+    ("Rain", "雨"),
+    ("System load", "システム負荷"),
+    ("Script", "スクリプト"),
+    ("Hearts", "ハート"),
+    ("Stars", "星"),
+    ("Circles", "円"),
+    ("Pumpkins", "かぼちゃ"),
+    ("Snowflakes", "雪の結晶"),
+    ("Morphing", "変形"),
+    ("Normal vision", "通常の色覚"),
+    ("Deuteranopia", "2型色覚"),
+    ("Protanopia", "1型色覚"),
+    ("Tritanopia", "3型色覚"),
+    ("Color fade:", "色の切り替え:"),
+    ("Hold each color:", "各色の保持:"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_pick_their_language() {
+	assert_eq!(Language::from_locale("de_DE.UTF-8"), Some(Language::German));
+	assert_eq!(Language::from_locale("ja-JP"), Some(Language::Japanese));
+	assert_eq!(Language::from_locale("FR"), Some(Language::French));
+	assert_eq!(Language::from_locale("pt_BR"), None);
+	assert_eq!(Language::from_locale(""), None);
+    }
+
+    #[test]
+    fn every_language_translates_the_same_text() {
+	let english: Vec<&str> = GERMAN.iter().map(|(english, _)| *english).collect();
+	for language in [Language::French, Language::Spanish, Language::Russian, Language::Japanese] {
+	    assert_eq!(language.table().iter().map(|(english, _)| *english).collect::<Vec<_>>(), english, "{language:?}");
+	}
+	assert_eq!(Language::German.translate("Cancel"), "Abbrechen");
+	assert_eq!(Language::Japanese.translate("#ff0000"), "#ff0000");
+	assert_eq!(Language::English.translate("Cancel"), "Cancel");
+    }
+}
@@ -0,0 +1,189 @@
+use crate::config::ExitTriggers;
+use log::warn;
+use macroquad::prelude::*;
+use std::collections::HashSet;
+
+/// Keys that can be named in the config, spelled as their lowercased variant name.
+pub(crate) const KEY_CODES: &[KeyCode] = &[
+    KeyCode::Space, KeyCode::Apostrophe, KeyCode::Comma, KeyCode::Minus,
+    KeyCode::Period, KeyCode::Slash, KeyCode::Key0, KeyCode::Key1, KeyCode::Key2,
+    KeyCode::Key3, KeyCode::Key4, KeyCode::Key5, KeyCode::Key6, KeyCode::Key7,
+    KeyCode::Key8, KeyCode::Key9, KeyCode::Semicolon, KeyCode::Equal, KeyCode::A,
+    KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M,
+    KeyCode::N, KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S,
+    KeyCode::T, KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y,
+    KeyCode::Z, KeyCode::LeftBracket, KeyCode::Backslash, KeyCode::RightBracket,
+    KeyCode::GraveAccent, KeyCode::World1, KeyCode::World2, KeyCode::Escape,
+    KeyCode::Enter, KeyCode::Tab, KeyCode::Backspace, KeyCode::Insert,
+    KeyCode::Delete, KeyCode::Right, KeyCode::Left, KeyCode::Down, KeyCode::Up,
+    KeyCode::PageUp, KeyCode::PageDown, KeyCode::Home, KeyCode::End,
+    KeyCode::CapsLock, KeyCode::ScrollLock, KeyCode::NumLock, KeyCode::PrintScreen,
+    KeyCode::Pause, KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4,
+    KeyCode::F5, KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10,
+    KeyCode::F11, KeyCode::F12, KeyCode::F13, KeyCode::F14, KeyCode::F15,
+    KeyCode::F16, KeyCode::F17, KeyCode::F18, KeyCode::F19, KeyCode::F20,
+    KeyCode::F21, KeyCode::F22, KeyCode::F23, KeyCode::F24, KeyCode::F25,
+    KeyCode::Kp0, KeyCode::Kp1, KeyCode::Kp2, KeyCode::Kp3, KeyCode::Kp4,
+    KeyCode::Kp5, KeyCode::Kp6, KeyCode::Kp7, KeyCode::Kp8, KeyCode::Kp9,
+    KeyCode::KpDecimal, KeyCode::KpDivide, KeyCode::KpMultiply,
+    KeyCode::KpSubtract, KeyCode::KpAdd, KeyCode::KpEnter, KeyCode::KpEqual,
+    KeyCode::LeftShift, KeyCode::LeftControl, KeyCode::LeftAlt, KeyCode::LeftSuper,
+    KeyCode::RightShift, KeyCode::RightControl, KeyCode::RightAlt,
+    KeyCode::RightSuper, KeyCode::Menu,
+];
+
+/// Keys that dismiss the screensaver unless listed in `ignore_keys`.
+pub(crate) const EXIT_KEYS: &[KeyCode] = &[KeyCode::Escape];
+
+/// Saves a screenshot instead of dismissing the screensaver.
+pub(crate) const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+
+/// Pauses and resumes the animation when running in a window.
+pub(crate) const PAUSE_KEY: KeyCode = KeyCode::Space;
+
+/// Advances a paused animation by one frame.
+pub(crate) const STEP_KEY: KeyCode = KeyCode::Period;
+
+/// Shows and hides the debug overlay when running in a window.
+pub(crate) const DEBUG_OVERLAY_KEY: KeyCode = KeyCode::F3;
+
+pub(crate) fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key).to_lowercase()
+}
+
+pub(crate) fn parse_key_name(name: &str) -> Option<KeyCode> {
+    let name = name.trim().to_lowercase();
+    KEY_CODES.iter().copied().find(|key| key_name(*key) == name)
+}
+
+pub(crate) fn parse_key_names<S: AsRef<str>>(names: &[S]) -> HashSet<KeyCode> {
+    let mut keys = HashSet::new();
+    for name in names.iter().map(AsRef::as_ref) {
+	match parse_key_name(name) {
+	    Some(key) => {
+		keys.insert(key);
+	    }
+	    None => warn!("ignoring unknown key name '{}' in ignore_keys", name.trim()),
+	}
+    }
+    keys
+}
+
+/// A key pressed while holding modifiers, such as `ctrl+shift+q`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct KeyCombo {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    key: KeyCode,
+}
+
+impl KeyCombo {
+    /// Whether the key went down this frame with exactly these modifiers.
+    pub(crate) fn pressed(&self) -> bool {
+	let down = |left, right| is_key_down(left) || is_key_down(right);
+	is_key_pressed(self.key)
+	    && down(KeyCode::LeftControl, KeyCode::RightControl) == self.ctrl
+	    && down(KeyCode::LeftShift, KeyCode::RightShift) == self.shift
+	    && down(KeyCode::LeftAlt, KeyCode::RightAlt) == self.alt
+    }
+}
+
+/// Parse `+` separated modifiers (`ctrl`, `shift`, `alt`) ending in a key
+/// name.
+pub(crate) fn parse_key_combo(text: &str) -> Option<KeyCombo> {
+    let mut parts: Vec<String> = text.split('+').map(|part| part.trim().to_lowercase()).collect();
+    let key = parse_key_name(&parts.pop()?)?;
+    let mut combo = KeyCombo { ctrl: false, shift: false, alt: false, key };
+    for modifier in parts {
+	match modifier.as_str() {
+	    "ctrl" | "control" => combo.ctrl = true,
+	    "shift" => combo.shift = true,
+	    "alt" => combo.alt = true,
+	    _ => return None,
+	}
+    }
+    Some(combo)
+}
+
+/// Whether a key that should dismiss the screensaver was pressed this
+/// frame: any but the screenshot key with `any_key`, else just `EXIT_KEYS`.
+pub(crate) fn exit_key_pressed(ignore_keys: &HashSet<KeyCode>, any_key: bool) -> bool {
+    get_keys_pressed().iter().any(|key| {
+	let exits = if any_key { *key != SCREENSHOT_KEY } else { EXIT_KEYS.contains(key) };
+	exits && !ignore_keys.contains(key)
+    })
+}
+
+/// Whether a key, button, scroll or touch enabled in `triggers` happened
+/// this frame.
+pub(crate) fn exit_input(triggers: &ExitTriggers, ignore_keys: &HashSet<KeyCode>) -> bool {
+    let buttons = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+    exit_key_pressed(ignore_keys, triggers.keys)
+	|| (triggers.buttons && buttons.into_iter().any(is_mouse_button_pressed))
+	|| (triggers.scroll && mouse_wheel() != (0.0, 0.0))
+	|| (triggers.touch && touches().iter().any(|touch| touch.phase == TouchPhase::Started))
+}
+
+/// Decides when the mouse has moved enough to dismiss the screensaver,
+/// ignoring everything during a grace period after starting.
+pub(crate) struct ExitWatch {
+    grace: f32,
+    distance: f32,
+    /// Where the mouse was when the grace period ended.
+    anchor: Option<Vec2>,
+}
+
+impl ExitWatch {
+    pub(crate) fn new(grace: f32, distance: f32) -> Self {
+	Self { grace, distance, anchor: None }
+    }
+
+    /// Whether input is still being ignored.
+    pub(crate) fn in_grace(&self) -> bool {
+	self.grace > 0.0
+    }
+
+    /// Advance by `dt` seconds with the mouse at `pos`, returning whether
+    /// it has now moved further than the exit distance.
+    pub(crate) fn mouse_moved(&mut self, pos: Vec2, dt: f32) -> bool {
+	if self.in_grace() {
+	    self.grace -= dt;
+	    return false;
+	}
+	let anchor = *self.anchor.get_or_insert(pos);
+	pos.distance(anchor) > self.distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_list_skips_unknown_names() {
+	let keys = parse_key_names(&["Space", " f1", "notakey", "escape"]);
+	assert_eq!(keys, HashSet::from([KeyCode::Space, KeyCode::F1, KeyCode::Escape]));
+    }
+
+    #[test]
+    fn key_combos_name_modifiers_and_a_key() {
+	assert_eq!(parse_key_combo("Ctrl+Shift+Q"), Some(KeyCombo { ctrl: true, shift: true, alt: false, key: KeyCode::Q }));
+	assert_eq!(parse_key_combo("f10"), Some(KeyCombo { ctrl: false, shift: false, alt: false, key: KeyCode::F10 }));
+	assert_eq!(parse_key_combo("hyper+q"), None);
+	assert_eq!(parse_key_combo("ctrl+"), None);
+    }
+
+    #[test]
+    fn mouse_exit_waits_out_grace_and_jitter() {
+	let mut watch = ExitWatch::new(1.0, 10.0);
+	assert!(watch.in_grace());
+	assert!(!watch.mouse_moved(Vec2::new(500.0, 500.0), 0.6));
+	assert!(!watch.mouse_moved(Vec2::ZERO, 0.6));
+	assert!(!watch.in_grace());
+	assert!(!watch.mouse_moved(Vec2::new(100.0, 100.0), 0.1));
+	assert!(!watch.mouse_moved(Vec2::new(106.0, 106.0), 0.1));
+	assert!(watch.mouse_moved(Vec2::new(108.0, 108.0), 0.1));
+    }
+}
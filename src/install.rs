@@ -0,0 +1,66 @@
+//! `install` and `uninstall`: copying the executable where Windows finds
+//! screensavers and selecting it, or undoing both.
+
+use std::env;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+#[cfg(windows)]
+use crate::win32;
+
+/// Name of the installed copy; Windows lists `.scr` files as screensavers.
+const SCR_NAME: &str = "hexagon_screensaver.scr";
+
+/// Where `install` copies the screensaver: under the user's programs, or
+/// beside the system's own when `system`.
+fn target(system: bool) -> Option<PathBuf> {
+    let dir = if system {
+	env::var_os("SystemRoot").map(|root| PathBuf::from(root).join("System32"))
+    } else {
+	dirs::data_local_dir().map(|dir| dir.join("Programs").join("hexagon_screensaver"))
+    };
+    dir.map(|dir| dir.join(SCR_NAME))
+}
+
+/// Copy this executable into place and select it as the screensaver,
+/// returning where it went.
+pub fn install(system: bool) -> Result<PathBuf, String> {
+    if !cfg!(windows) {
+	return Err("install is only supported on Windows".to_string());
+    }
+    let exe = env::current_exe().map_err(|err| format!("cannot find the executable: {err}"))?;
+    let path = target(system).ok_or("cannot find the install folder")?;
+    if let Some(dir) = path.parent() {
+	std::fs::create_dir_all(dir).map_err(|err| format!("cannot create {}: {err}", dir.display()))?;
+    }
+    // Installing from the installed copy keeps it as it is
+    if exe != path {
+	std::fs::copy(&exe, &path).map_err(|err| match err.kind() {
+	    ErrorKind::PermissionDenied if system => format!("cannot write {}: run from an elevated prompt or install per user", path.display()),
+	    _ => format!("cannot copy to {}: {err}", path.display()),
+	})?;
+    }
+    #[cfg(windows)]
+    win32::set_screensaver(Some(&path))?;
+    Ok(path)
+}
+
+/// Remove the installed copies and deselect the screensaver if it was one
+/// of them, returning what was removed.
+pub fn uninstall() -> Result<Vec<PathBuf>, String> {
+    if !cfg!(windows) {
+	return Err("uninstall is only supported on Windows".to_string());
+    }
+    let installed: Vec<PathBuf> = [false, true].into_iter().filter_map(target).filter(|path| path.exists()).collect();
+    #[cfg(windows)]
+    if win32::screensaver().is_some_and(|selected| installed.iter().any(|path| selected.as_os_str().eq_ignore_ascii_case(path))) {
+	win32::set_screensaver(None)?;
+    }
+    for path in &installed {
+	std::fs::remove_file(path).map_err(|err| match err.kind() {
+	    ErrorKind::PermissionDenied => format!("cannot remove {}: run from an elevated prompt", path.display()),
+	    _ => format!("cannot remove {}: {err}", path.display()),
+	})?;
+    }
+    Ok(installed)
+}
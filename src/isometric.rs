@@ -0,0 +1,52 @@
+//! The `isometric` render mode: the grid tilted back into a surface seen
+//! at an angle, each shape lifted by how visible it is.
+
+use crate::render::MeshBatch;
+use crate::shapes::{DrawCommand, ShapeRegistry};
+use macroquad::prelude::*;
+use std::f32::consts::FRAC_1_SQRT_2;
+
+/// Height a fully visible shape rises to, in shape radii.
+const LIFT: f32 = 1.5;
+
+/// Where `point` on the grid of `display` shows once tilted, raised by
+/// `lift` pixels. The grid turns by 45 degrees and squashes to half its
+/// height around the display's center.
+pub(crate) fn project(point: Vec2, display: Rect, lift: f32) -> Vec2 {
+    let offset = point - display.center();
+    display.center() + Vec2::new(offset.x - offset.y, (offset.x + offset.y) / 2.0) * FRAC_1_SQRT_2 - Vec2::new(0.0, lift)
+}
+
+/// Add `commands` on each of `displays` to `batch` tilted, from the back
+/// forward so nearer shapes cover those behind them.
+pub(crate) fn draw(batch: &mut MeshBatch, registry: &ShapeRegistry, commands: &[DrawCommand], displays: &[Rect]) {
+    for display in displays {
+	let mut shown: Vec<&DrawCommand> = commands.iter().filter(|command| display.contains(Vec2::new(command.x, command.y))).collect();
+	shown.sort_by(|a, b| (a.x + a.y).total_cmp(&(b.x + b.y)));
+	for command in shown {
+	    let (center, rotation) = (Vec2::new(command.x, command.y), Vec2::from_angle(command.rotation));
+	    let lift = command.color.a * LIFT * command.radius;
+	    let place = |vertex: Vec2| project(center + rotation.rotate(vertex) * command.radius, *display, lift);
+	    batch.add_mapped(registry.tessellation(command.shape), command.color, place);
+	}
+    }
+    batch.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_grid_tilts_around_the_display_center() {
+	let display = Rect::new(100.0, 0.0, 200.0, 100.0);
+	let center = display.center();
+	assert_eq!(project(center, display, 0.0), center);
+	assert_eq!(project(center, display, 10.0), center - Vec2::new(0.0, 10.0));
+	// Right on the grid is right and down on the screen, down is left and down
+	let right = project(center + Vec2::new(10.0, 0.0), display, 0.0) - center;
+	let down = project(center + Vec2::new(0.0, 10.0), display, 0.0) - center;
+	assert!(right.x > 0.0 && right.y > 0.0 && down.x < 0.0 && down.y > 0.0);
+	assert!((right.length() - down.length()).abs() < 1e-4 && right.length() < 10.0);
+    }
+}
@@ -0,0 +1,139 @@
+//! The kaleidoscope: each display's frame drawn into a texture, then one
+//! wedge of it mirrored and turned around the display's center.
+
+use crate::offscreen::Offscreen;
+use macroquad::miniquad::{PipelineParams, UniformType};
+use macroquad::prelude::*;
+
+/// Most wedges a kaleidoscope can have.
+pub(crate) const MAX_WEDGES: u32 = 16;
+
+/// Whether `wedges` make up a kaleidoscope, 0 being none. Mirrored pairs
+/// need an even count.
+pub(crate) fn valid_wedges(wedges: u32) -> bool {
+    wedges == 0 || ((2..=MAX_WEDGES).contains(&wedges) && wedges.is_multiple_of(2))
+}
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying lowp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 center;
+uniform vec2 resolution;
+uniform float wedges;
+uniform float reach;
+
+void main() {
+    vec2 offset = (uv - center) * resolution;
+    float wedge = 6.28318531 / wedges;
+    // Every other wedge is the first one mirrored, so their edges meet
+    float angle = mod(atan(offset.y, offset.x), 2.0 * wedge);
+    angle = min(angle, 2.0 * wedge - angle);
+    vec2 folded = vec2(cos(angle), sin(angle)) * length(offset) * reach;
+    gl_FragColor = texture2D(Texture, center + folded / resolution);
+}
+"#;
+
+/// The texture the frame is drawn into and the shader folding it.
+pub(crate) struct Kaleidoscope {
+    offscreen: Offscreen,
+    material: Material,
+}
+
+impl Kaleidoscope {
+    /// A kaleidoscope over `bounds`, or the reason its shader could not be
+    /// built.
+    pub(crate) fn new(bounds: Rect) -> Result<Self, String> {
+	let material = load_material(
+	    ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+	    MaterialParams {
+		uniforms: vec![
+		    UniformDesc::new("center", UniformType::Float2),
+		    UniformDesc::new("resolution", UniformType::Float2),
+		    UniformDesc::new("wedges", UniformType::Float1),
+		    UniformDesc::new("reach", UniformType::Float1),
+		],
+		pipeline_params: PipelineParams::default(),
+		..Default::default()
+	    },
+	)
+	.map_err(|err| err.to_string())?;
+	Ok(Self { offscreen: Offscreen::new(bounds, 1.0, FilterMode::Linear), material })
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.offscreen.fits(bounds)
+    }
+
+    /// Capture what is drawn next over `bounds`, starting from
+    /// `background`; `finish` folds it.
+    pub(crate) fn capture(&mut self, bounds: Rect, background: Color) {
+	self.offscreen.begin(bounds);
+	clear_background(background);
+    }
+
+    /// Draw the captured frame onto each of `displays`, which span
+    /// `bounds`, folded into `wedges`.
+    pub(crate) fn finish(&self, bounds: Rect, displays: &[Rect], wedges: u32) {
+	self.offscreen.end();
+	gl_use_material(&self.material);
+	let resolution = Vec2::new(self.offscreen.size.0 as f32, self.offscreen.size.1 as f32);
+	self.material.set_uniform("resolution", resolution);
+	self.material.set_uniform("wedges", wedges as f32);
+	for display in displays {
+	    let (source, reach) = fold(*display, bounds);
+	    self.material.set_uniform("center", source.center() / resolution);
+	    self.material.set_uniform("reach", reach);
+	    let params = DrawTextureParams { dest_size: Some(display.size()), source: Some(source), flip_y: true, ..Default::default() };
+	    draw_texture_ex(&self.offscreen.target.texture, display.x, display.y, WHITE, params);
+	}
+	gl_use_default_material();
+    }
+}
+
+/// The pixels of the texture `display` is drawn in, with the texture held
+/// upside down, and the factor on distances from its center keeping every
+/// corner's sample inside the circle that fits the display.
+fn fold(display: Rect, bounds: Rect) -> (Rect, f32) {
+    let source = Rect::new(display.x - bounds.x, bounds.bottom() - display.bottom(), display.w, display.h);
+    let reach = display.w.min(display.h) / display.size().length().max(1.0);
+    (source, reach)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wedges_fold_inside_their_display() {
+	assert!([0, 2, 4, 6, 8, 16].into_iter().all(valid_wedges));
+	assert!(![1, 3, 7, 18].into_iter().any(valid_wedges));
+	let bounds = Rect::new(-1920.0, 0.0, 3840.0, 1200.0);
+	let (source, reach) = fold(Rect::new(0.0, 0.0, 1920.0, 1080.0), bounds);
+	assert_eq!(source, Rect::new(1920.0, 120.0, 1920.0, 1080.0));
+	// The farthest sample, from a corner, lands on the edge's midpoint
+	assert!((Vec2::new(960.0, 540.0).length() * reach - 540.0).abs() < 1e-3);
+    }
+}
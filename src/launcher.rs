@@ -0,0 +1,123 @@
+//! The `--launch` and `--tray` companions, for systems where no screensaver
+//! host runs us: they wait for the machine to sit idle for `idle_minutes`,
+//! run the screensaver full screen, and start waiting again once it quits.
+
+use log::{error, warn};
+use std::env;
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+#[cfg(windows)]
+use crate::win32;
+#[cfg(target_os = "linux")]
+use crate::x11;
+
+/// Longest wait between looks at the idle time.
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// What the tray icon's menu asks for.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) enum TrayCommand {
+    Start,
+    Quit,
+}
+
+/// Seconds since the last keyboard, mouse or touch input.
+#[cfg(target_os = "macos")]
+fn idle_seconds() -> Option<f64> {
+    #[link(name = "CoreGraphics", kind = "framework")]
+    unsafe extern "C" {
+	fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+    }
+    // The combined session state, and any input event type
+    Some(unsafe { CGEventSourceSecondsSinceLastEventType(0, u32::MAX) })
+}
+
+#[cfg(windows)]
+fn idle_seconds() -> Option<f64> {
+    Some(win32::idle_seconds())
+}
+
+#[cfg(target_os = "linux")]
+fn idle_seconds() -> Option<f64> {
+    x11::idle_seconds()
+}
+
+#[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+fn idle_seconds() -> Option<f64> {
+    None
+}
+
+/// Show the tray icon sending `commands`, false when there is no tray.
+fn spawn_tray(commands: Sender<TrayCommand>) -> bool {
+    #[cfg(windows)]
+    {
+	win32::spawn_tray("Hexagon Screensaver", commands)
+    }
+    #[cfg(not(windows))]
+    {
+	let _ = commands;
+	false
+    }
+}
+
+/// How long to wait once idle for `idle` seconds before `threshold` can be
+/// reached, or None when it has been. Input in between only pushes it back.
+fn next_check(idle: f64, threshold: f64) -> Option<Duration> {
+    (idle < threshold).then(|| Duration::from_secs_f64(threshold - idle).min(MAX_CHECK_INTERVAL))
+}
+
+/// Run the screensaver with `args` after every `idle_minutes` of idle time,
+/// until killed or, with the `tray` icon, told to quit from it.
+pub fn run(idle_minutes: f32, args: &[String], tray: bool) -> ! {
+    if idle_seconds().is_none() {
+	error!("cannot read the idle time; --launch and --tray need Windows, macOS or X11");
+	std::process::exit(2);
+    }
+    let exe = env::current_exe().unwrap_or_else(|err| {
+	error!("cannot find the screensaver executable: {err}");
+	std::process::exit(1);
+    });
+    let (sender, commands) = mpsc::channel();
+    if tray && !spawn_tray(sender.clone()) {
+	warn!("tray: no tray icon on this system, waiting for idle time without one");
+    }
+    let threshold = f64::from(idle_minutes) * 60.0;
+    // Only once input comes after a run, so one that fails straight away
+    // does not start over and over
+    let mut armed = true;
+    loop {
+	let idle = idle_seconds().unwrap_or(0.0);
+	armed |= idle < threshold;
+	let wait = if armed { next_check(idle, threshold) } else { Some(MAX_CHECK_INTERVAL) };
+	let start = match wait {
+	    Some(wait) => match commands.recv_timeout(wait) {
+		Ok(TrayCommand::Start) => true,
+		Ok(TrayCommand::Quit) => std::process::exit(0),
+		Err(_) => false,
+	    },
+	    None => true,
+	};
+	if start {
+	    armed = false;
+	    // It quits on input by itself
+	    if let Err(err) = Command::new(&exe).args(args).status() {
+		error!("launch: could not start the screensaver: {err}");
+		std::process::exit(1);
+	    }
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_wait_out_the_idle_time_left() {
+	assert_eq!(next_check(290.0, 300.0), Some(Duration::from_secs(10)));
+	assert_eq!(next_check(0.0, 300.0), Some(MAX_CHECK_INTERVAL));
+	assert_eq!(next_check(300.0, 300.0), None);
+    }
+}
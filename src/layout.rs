@@ -0,0 +1,638 @@
+use crate::shapes::{ShapeRenderer, Tessellation, hexagon_outline, round_corners, square_outline, triangle_outline};
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+static SIN_60: f32 = 0.866;
+
+/// Which way hexagons, and the grid tiling them, face.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// Flat edges on top, in columns offset by half a cell.
+    #[default]
+    Flat,
+    /// Corners on top, in rows offset by half a cell.
+    Pointy,
+}
+
+/// The lattice shapes are placed on.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Hex,
+    Square,
+    /// Alternating up and down triangles.
+    Triangle,
+    /// The Voronoi cells of randomly scattered points, each its own polygon.
+    Voronoi,
+    /// An aperiodic Penrose tiling of thick and thin rhombi.
+    Penrose,
+}
+
+/// One place on a lattice, with the rotation in radians its tile needs to
+/// fit its neighbors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cell {
+    pub pos: Vec2,
+    pub rotation: f32,
+    /// The cell's own polygon in pixels around `pos`, for layouts whose
+    /// cells differ; empty when the layout's tile fills it.
+    pub outline: Vec<Vec2>,
+}
+
+/// Cells covering a screen and the tile, of radius 1, that fills each one.
+pub struct Tiling {
+    pub cells: Vec<Cell>,
+    pub tile: Tessellation,
+    /// Indices of the cells bordering each cell.
+    pub neighbors: Vec<Vec<usize>>,
+}
+
+impl Layout {
+    /// The cell radius leaving `gap` pixels between the edges of
+    /// neighboring tiles of `radius`.
+    pub fn cell_radius(self, radius: f32, gap: f32) -> f32 {
+	// Neighboring centers are sqrt(3), sqrt(2) and 1 cell radii apart
+	let spacing = match self {
+	    Layout::Hex | Layout::Voronoi | Layout::Penrose => 3f32.sqrt(),
+	    Layout::Square => 2f32.sqrt(),
+	    Layout::Triangle => 1.0,
+	};
+	radius + gap / spacing
+    }
+
+    /// How far apart the centers of bordering cells of `cell_radius` are,
+    /// at most. Irregular cells get a typical distance instead.
+    pub fn neighbor_distance(self, cell_radius: f32) -> f32 {
+	let distance = match self {
+	    Layout::Hex => 3f32.sqrt(),
+	    Layout::Square => 2f32.sqrt(),
+	    Layout::Triangle => 1.0,
+	    Layout::Voronoi => 2.0,
+	    Layout::Penrose => 1.7,
+	};
+	// Slack for rounding errors
+	distance * cell_radius * 1.1
+    }
+
+    /// The tile filling a cell of radius 1, unrotated. Voronoi and Penrose
+    /// cells have their own outlines; hexagons stand in for them.
+    pub fn tessellation(self, orientation: Orientation) -> Tessellation {
+	Tessellation::fan(&self.outline(orientation))
+    }
+
+    /// The outline of `tessellation`.
+    fn outline(self, orientation: Orientation) -> Vec<Vec2> {
+	match self {
+	    Layout::Hex | Layout::Voronoi | Layout::Penrose => hexagon_outline(orientation),
+	    Layout::Square => square_outline(),
+	    Layout::Triangle => triangle_outline(),
+	}
+    }
+
+    /// Cells of `cell_radius` covering a `width` x `height` screen.
+    /// `orientation` only affects the hex lattice and `rng` only the
+    /// Voronoi points.
+    pub fn cells(self, cell_radius: f32, width: f32, height: f32, orientation: Orientation, rng: &RandGenerator) -> Vec<Cell> {
+	let unrotated = |pos| Cell { pos, rotation: 0.0, outline: Vec::new() };
+	match self {
+	    Layout::Hex => HexGrid::new(cell_radius, width, height, orientation).positions().into_iter().map(unrotated).collect(),
+	    Layout::Square => {
+		let side = cell_radius * 2f32.sqrt();
+		let (columns, rows) = ((width / side) as i32 + 2, (height / side) as i32 + 2);
+		(0..rows)
+		    .flat_map(|row| (0..columns).map(move |col| unrotated(Vec2::new(col as f32, row as f32) * side)))
+		    .collect()
+	    }
+	    Layout::Triangle => {
+		// Rows of triangles half a side apart, alternating up and down,
+		// with the flat sides shared between rows
+		let side = cell_radius * 3f32.sqrt();
+		let row_height = cell_radius * 1.5;
+		let (columns, rows) = ((width / side * 2.) as i32 + 3, (height / row_height) as i32 + 2);
+		(0..rows)
+		    .flat_map(|row| {
+			(0..columns).map(move |col| {
+			    let up = (row + col) % 2 == 0;
+			    let y = row as f32 * row_height + if up { cell_radius } else { cell_radius * 0.5 };
+			    let pos = Vec2::new(col as f32 * side * 0.5, y);
+			    Cell { pos, rotation: if up { 0.0 } else { PI }, outline: Vec::new() }
+			})
+		    })
+		    .collect()
+	    }
+	    Layout::Voronoi => {
+		// As many points as hexagons of the same radius would need
+		let bounds = Rect::new(-cell_radius, -cell_radius, width + cell_radius * 2., height + cell_radius * 2.);
+		let count = (bounds.w * bounds.h / (2.6 * cell_radius * cell_radius)).ceil() as usize;
+		let sites = (0..count)
+		    .map(|_| Vec2::new(rng.gen_range(bounds.left(), bounds.right()), rng.gen_range(bounds.top(), bounds.bottom())))
+		    .collect();
+		voronoi_cells(sites, bounds)
+	    }
+	    Layout::Penrose => {
+		let bounds = Rect::new(-cell_radius, -cell_radius, width + cell_radius * 2., height + cell_radius * 2.);
+		penrose_cells(Vec2::new(width, height) * 0.5, bounds.size().length() * 0.5, cell_radius * 1.6)
+		    .into_iter()
+		    .filter(|cell| bounds.contains(cell.pos))
+		    .collect()
+	    }
+	}
+    }
+}
+
+/// The golden ratio, the scale between Penrose subdivision steps.
+const PHI: f32 = 1.618034;
+
+/// Rhombi of a Penrose P3 tiling covering the circle of `radius` around
+/// `center`, with edges about `edge` long.
+///
+/// Starts from a wheel of ten thick Robinson triangles and subdivides until
+/// the edges are short enough. Each rhombus is the pair of triangles sharing
+/// a base; triangles left unpaired at the rim are kept as they are.
+fn penrose_cells(center: Vec2, radius: f32, edge: f32) -> Vec<Cell> {
+    // The wheel's rim is a decagon whose edges are cos(18°) from its center
+    let wheel = radius / (0.1 * PI).cos();
+    let steps = ((wheel / edge).ln() / PHI.ln()).ceil().max(0.0) as u32;
+
+    // (thin, apex, base corners)
+    let mut triangles: Vec<(bool, Vec2, Vec2, Vec2)> = (0..10)
+	.map(|i| {
+	    let mut b = Vec2::from_angle((2 * i - 1) as f32 * PI / 10.) * wheel;
+	    let mut c = Vec2::from_angle((2 * i + 1) as f32 * PI / 10.) * wheel;
+	    if i % 2 == 0 {
+		std::mem::swap(&mut b, &mut c);
+	    }
+	    (false, Vec2::ZERO, b, c)
+	})
+	.collect();
+    for _ in 0..steps {
+	triangles = triangles
+	    .into_iter()
+	    .flat_map(|(thin, a, b, c)| {
+		if thin {
+		    let q = b + (a - b) / PHI;
+		    let r = b + (c - b) / PHI;
+		    vec![(true, r, c, a), (true, q, r, b), (false, r, q, a)]
+		} else {
+		    let p = a + (b - a) / PHI;
+		    vec![(false, c, p, b), (true, p, c, a)]
+		}
+	    })
+	    .collect();
+    }
+
+    // Pair triangles up by their shared base, in either direction
+    let key = |p: Vec2, q: Vec2| {
+	let [p, q] = [p, q].map(|point| ((point.x * 16.).round() as i64, (point.y * 16.).round() as i64));
+	if p < q { (p, q) } else { (q, p) }
+    };
+    let mut halves: std::collections::HashMap<_, Vec<(Vec2, Vec2, Vec2)>> = std::collections::HashMap::new();
+    for (_, a, b, c) in triangles {
+	halves.entry(key(b, c)).or_default().push((a, b, c));
+    }
+    let mut outlines: Vec<Vec<Vec2>> = halves
+	.into_values()
+	.map(|pair| match pair[..] {
+	    [(a, b, c), (other, ..)] => vec![a, b, other, c],
+	    _ => pair.into_iter().flat_map(|(a, b, c)| [a, b, c]).collect(),
+	})
+	.collect();
+    // The map's order is random; keep the layout reproducible
+    outlines.sort_by(|p, q| (p[0].x, p[0].y).partial_cmp(&(q[0].x, q[0].y)).unwrap());
+
+    outlines
+	.into_iter()
+	.map(|outline| {
+	    let centroid = outline.iter().copied().sum::<Vec2>() / outline.len() as f32;
+	    let outline = outline.into_iter().map(|point| point - centroid).collect();
+	    Cell { pos: center + centroid, rotation: 0.0, outline }
+	})
+	.collect()
+}
+
+/// The Voronoi cell of each of `sites` within `bounds`: the bounds clipped
+/// by the half-plane closer to the site than to each other site.
+///
+/// The sites are bucketed in a grid about one site apart, and each cell is
+/// clipped by the rings of buckets around its own until the next ring is
+/// too far away to matter, so a full screen of small cells stays quick.
+fn voronoi_cells(sites: Vec<Vec2>, bounds: Rect) -> Vec<Cell> {
+    let corners = [bounds.point(), Vec2::new(bounds.right(), bounds.top()), bounds.point() + bounds.size(), Vec2::new(bounds.left(), bounds.bottom())];
+    let size = (bounds.w * bounds.h / sites.len().max(1) as f32).sqrt().max(f32::EPSILON);
+    let (columns, rows) = ((bounds.w / size).ceil().max(1.0) as i64, (bounds.h / size).ceil().max(1.0) as i64);
+    let bucket = |point: Vec2| {
+	let at = (point - bounds.point()) / size;
+	((at.x as i64).clamp(0, columns - 1), (at.y as i64).clamp(0, rows - 1))
+    };
+    let mut buckets = vec![Vec::new(); (columns * rows) as usize];
+    for &site in &sites {
+	let (column, row) = bucket(site);
+	buckets[(row * columns + column) as usize].push(site);
+    }
+    sites
+	.iter()
+	.map(|&site| {
+	    let (column, row) = bucket(site);
+	    let mut outline: Vec<Vec2> = corners.iter().map(|corner| *corner - site).collect();
+	    for ring in 0..columns.max(rows) {
+		// Sites in this ring are at least one bucket less than it away
+		let reach = outline.iter().map(|point| point.length()).fold(0.0, f32::max);
+		if (ring - 1) as f32 * size > reach * 2.0 {
+		    break;
+		}
+		for y in (row - ring).max(0)..=(row + ring).min(rows - 1) {
+		    for x in (column - ring).max(0)..=(column + ring).min(columns - 1) {
+			if (y - row).abs().max((x - column).abs()) != ring {
+			    continue;
+			}
+			for &other in buckets[(y * columns + x) as usize].iter().filter(|other| **other != site) {
+			    let normal = other - site;
+			    outline = clip_half_plane(&outline, normal, normal.length_squared() * 0.5);
+			}
+		    }
+		}
+	    }
+	    Cell { pos: site, rotation: 0.0, outline }
+	})
+	.collect()
+}
+
+/// The part of the convex `polygon` where `point.dot(normal) <= limit`.
+fn clip_half_plane(polygon: &[Vec2], normal: Vec2, limit: f32) -> Vec<Vec2> {
+    let mut clipped = Vec::new();
+    for (index, &start) in polygon.iter().enumerate() {
+	let end = polygon[(index + 1) % polygon.len()];
+	let (start_side, end_side) = (start.dot(normal) - limit, end.dot(normal) - limit);
+	if start_side <= 0.0 {
+	    clipped.push(start);
+	}
+	if (start_side <= 0.0) != (end_side <= 0.0) {
+	    clipped.push(start + (end - start) * (start_side / (start_side - end_side)));
+	}
+    }
+    clipped
+}
+
+/// The `layout` cells covering a `width` x `height` screen after rotating
+/// them by `rotation` degrees around the screen center, and their tile.
+///
+/// The unrotated lattice is generated over a square as wide as the screen
+/// diagonal so the rotated field still reaches every corner. A rotation of
+/// zero returns exactly the `Layout::cells` lattice.
+pub fn create_tiling(
+    layout: Layout,
+    cell_radius: f32,
+    width: f32,
+    height: f32,
+    rotation: f32,
+    orientation: Orientation,
+    rng: &RandGenerator,
+) -> Tiling {
+    let tile = layout.tessellation(orientation);
+    if rotation == 0.0 {
+	let cells = layout.cells(cell_radius, width, height, orientation, rng);
+	let neighbors = neighbors(&cells, layout.neighbor_distance(cell_radius));
+	return Tiling { cells, tile, neighbors };
+    }
+
+    let center = Vec2::new(width, height) * 0.5;
+    let diagonal = center.length() * 2.;
+    let offset = center - Vec2::splat(diagonal * 0.5);
+    let turn = Vec2::from_angle(rotation.to_radians());
+    let bounds = Rect::new(-cell_radius, -cell_radius, width + cell_radius * 2., height + cell_radius * 2.);
+
+    let cells: Vec<Cell> = layout
+	.cells(cell_radius, diagonal, diagonal, orientation, rng)
+	.into_iter()
+	.map(|cell| Cell { pos: center + turn.rotate(cell.pos + offset - center), ..cell })
+	.filter(|cell| bounds.contains(cell.pos))
+	.collect();
+    let neighbors = neighbors(&cells, layout.neighbor_distance(cell_radius));
+    Tiling { cells, tile, neighbors }
+}
+
+/// For each of `cells`, the others with centers within `distance`.
+fn neighbors(cells: &[Cell], distance: f32) -> Vec<Vec<usize>> {
+    // Buckets a cell wide, so neighbors are in the surrounding nine
+    let bucket = |pos: Vec2| ((pos.x / distance).floor() as i32, (pos.y / distance).floor() as i32);
+    let mut buckets: std::collections::HashMap<(i32, i32), Vec<usize>> = std::collections::HashMap::new();
+    for (index, cell) in cells.iter().enumerate() {
+	buckets.entry(bucket(cell.pos)).or_default().push(index);
+    }
+    cells
+	.iter()
+	.enumerate()
+	.map(|(index, cell)| {
+	    let (x, y) = bucket(cell.pos);
+	    let mut near: Vec<usize> = (-1..=1)
+		.flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+		.filter_map(|key| buckets.get(&key))
+		.flatten()
+		.copied()
+		.filter(|&other| other != index && cells[other].pos.distance(cell.pos) <= distance)
+		.collect();
+	    near.sort_unstable();
+	    near
+	})
+	.collect()
+}
+
+/// A cell of a hex grid in axial coordinates: `q` counts the columns of a
+/// flat grid, or the rows of a pointy one, and `r` runs along them,
+/// leaning back half a cell per step of `q`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl Axial {
+    /// Steps to the six bordering cells.
+    pub const DIRECTIONS: [Axial; 6] =
+	[Axial { q: 1, r: 0 }, Axial { q: 1, r: -1 }, Axial { q: 0, r: -1 }, Axial { q: -1, r: 0 }, Axial { q: -1, r: 1 }, Axial { q: 0, r: 1 }];
+
+    pub fn new(q: i32, r: i32) -> Self {
+	Self { q, r }
+    }
+
+    /// The six bordering cells, whether or not on a grid.
+    pub fn neighbors(self) -> [Axial; 6] {
+	Self::DIRECTIONS.map(|step| Axial::new(self.q + step.q, self.r + step.r))
+    }
+
+    /// Steps between the two cells.
+    pub fn distance(self, other: Axial) -> i32 {
+	let (dq, dr) = (self.q - other.q, self.r - other.r);
+	(dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+    }
+}
+
+/// Hexagons of `radius` covering a screen from the origin, facing
+/// `orientation`, with their axial coordinates. Those with centers more
+/// than a radius past the far edges, which could not reach the screen, are
+/// left out. A pointy grid is the flat one with its axes swapped.
+#[derive(Clone, Debug)]
+pub struct HexGrid {
+    radius: f32,
+    orientation: Orientation,
+    cells: Vec<Axial>,
+    indices: HashMap<Axial, usize>,
+}
+
+impl HexGrid {
+    /// The grid covering a `width` x `height` screen.
+    pub fn new(radius: f32, width: f32, height: f32, orientation: Orientation) -> Self {
+	let (width, height) = match orientation {
+	    Orientation::Flat => (width, height),
+	    Orientation::Pointy => (height, width),
+	};
+	let hex_height = SIN_60 * radius * 2.;
+	let (right, bottom) = (width + radius, height + radius);
+	let num_cols = (right / (radius * 3.)) as i32 + 1;
+	let num_rows = (bottom / hex_height) as i32 + 1;
+	// Rows of a column pair at a time: one on the row, then the one
+	// half a cell down and across from it, if it still reaches the screen
+	let mut cells = Vec::new();
+	for row in 0..num_rows {
+	    for col in 0..num_cols {
+		cells.push(Axial::new(col * 2, row - col));
+		let (x, y) = (col as f32 * radius * 3. + radius * 1.5, (row as f32 + 0.5) * hex_height);
+		if x <= right && y <= bottom {
+		    cells.push(Axial::new(col * 2 + 1, row - col));
+		}
+	    }
+	}
+	let indices = cells.iter().enumerate().map(|(index, cell)| (*cell, index)).collect();
+	Self { radius, orientation, cells, indices }
+    }
+
+    /// Every cell, in the order of `positions`.
+    pub fn cells(&self) -> &[Axial] {
+	&self.cells
+    }
+
+    /// The center of every cell, in pixels.
+    pub fn positions(&self) -> Vec<Vec2> {
+	self.cells.iter().map(|cell| self.to_pixel(*cell)).collect()
+    }
+
+    /// Where `cell` is among `cells`, if it is on the grid.
+    pub fn index(&self, cell: Axial) -> Option<usize> {
+	self.indices.get(&cell).copied()
+    }
+
+    /// Indices of the cells on the grid bordering the `index`th.
+    pub fn neighbors(&self, index: usize) -> Vec<usize> {
+	self.cells[index].neighbors().into_iter().filter_map(|cell| self.index(cell)).collect()
+    }
+
+    /// Steps between the `a`th and `b`th cells.
+    pub fn distance(&self, a: usize, b: usize) -> i32 {
+	self.cells[a].distance(self.cells[b])
+    }
+
+    /// The center of `cell` in pixels, on the grid or not.
+    pub fn to_pixel(&self, cell: Axial) -> Vec2 {
+	let hex_height = SIN_60 * self.radius * 2.;
+	let (x, y) = (cell.q as f32 * self.radius * 1.5, (cell.r as f32 + cell.q as f32 * 0.5) * hex_height);
+	match self.orientation {
+	    Orientation::Flat => Vec2::new(x, y),
+	    Orientation::Pointy => Vec2::new(y, x),
+	}
+    }
+
+    /// The cell covering the pixel `pos`, on the grid or not.
+    pub fn cell_at(&self, pos: Vec2) -> Axial {
+	let pos = match self.orientation {
+	    Orientation::Flat => pos,
+	    Orientation::Pointy => Vec2::new(pos.y, pos.x),
+	};
+	let q = pos.x / (self.radius * 1.5);
+	let r = pos.y / (SIN_60 * self.radius * 2.) - q * 0.5;
+	// Round as cube coordinates, fixing up the one rounded furthest
+	let s = -q - r;
+	let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+	let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+	if dq > dr && dq > ds {
+	    rq = -rr - rs;
+	} else if dr > ds {
+	    rr = -rq - rs;
+	}
+	Axial::new(rq as i32, rr as i32)
+    }
+}
+
+/// The tile of the configured layout, selectable as the `tile` shape so
+/// the cells are filled edge to edge.
+#[derive(Default)]
+pub struct Tile {
+    pub layout: Layout,
+    pub orientation: Orientation,
+    /// Radius of the rounded corners, as a share of the tile's.
+    pub corner_radius: f32,
+}
+
+impl ShapeRenderer for Tile {
+    fn name(&self) -> &str {
+	"tile"
+    }
+
+    fn label(&self) -> &str {
+	"Layout tiles"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&round_corners(&self.layout.outline(self.orientation), self.corner_radius))
+    }
+}
+
+/// A cell's own polygon, registered as a shape named after the cell.
+pub(crate) struct CellShape {
+    pub(crate) name: String,
+    /// The outline scaled to the radius the shape is drawn at.
+    pub(crate) outline: Vec<Vec2>,
+}
+
+impl ShapeRenderer for CellShape {
+    fn name(&self) -> &str {
+	&self.name
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&self.outline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiling(layout: Layout, rotation: f32) -> Tiling {
+	create_tiling(layout, 40.0, 800.0, 600.0, rotation, Orientation::Flat, &RandGenerator::new())
+    }
+
+    fn positions(tiling: Tiling) -> Vec<Vec2> {
+	tiling.cells.into_iter().map(|cell| cell.pos).collect()
+    }
+
+    #[test]
+    fn rotated_grid_covers_corners() {
+	let (width, height) = (800.0, 600.0);
+	for layout in [Layout::Hex, Layout::Square, Layout::Triangle, Layout::Voronoi] {
+	    let cells = positions(tiling(layout, 30.0));
+	    for corner in [Vec2::ZERO, Vec2::new(width, 0.0), Vec2::new(0.0, height), Vec2::new(width, height)] {
+		assert!(cells.iter().any(|cell| cell.distance(corner) <= 40.0), "{layout:?}");
+	    }
+	}
+	let unrotated = positions(tiling(Layout::Hex, 0.0));
+	assert_eq!(unrotated, HexGrid::new(40.0, width, height, Orientation::Flat).positions());
+    }
+
+    #[test]
+    fn hex_grid_only_covers_the_screen() {
+	let (radius, width, height) = (40.0, 800.0, 600.0);
+	let cells = HexGrid::new(radius, width, height, Orientation::Flat).positions();
+	assert!(cells.iter().all(|cell| cell.x <= width + radius && cell.y <= height + radius));
+	// Every point of the screen is near enough to be inside a hexagon
+	for x in (0..=80).map(|step| step as f32 * width / 80.0) {
+	    for y in (0..=60).map(|step| step as f32 * height / 60.0) {
+		assert!(cells.iter().any(|cell| cell.distance(Vec2::new(x, y)) <= radius), "{x}, {y}");
+	    }
+	}
+    }
+
+    #[test]
+    fn pointy_grid_offsets_rows() {
+	let cells = HexGrid::new(40.0, 800.0, 600.0, Orientation::Pointy).positions();
+	// Neighbors in a row sit side by side; the next row is shifted by half
+	assert!(cells.contains(&Vec2::new(0.0, 0.0)) && cells.contains(&Vec2::new(SIN_60 * 80.0, 0.0)));
+	assert!(cells.contains(&Vec2::new(SIN_60 * 40.0, 60.0)));
+	assert!(cells.iter().any(|cell| cell.distance(Vec2::new(800.0, 600.0)) <= 40.0));
+    }
+
+    #[test]
+    fn triangles_alternate_and_share_edges() {
+	let cells = Layout::Triangle.cells(40.0, 400.0, 300.0, Orientation::Flat, &RandGenerator::new());
+	let (first, second) = (&cells[0], &cells[1]);
+	assert_eq!((first.rotation, second.rotation), (0.0, PI));
+	// Neighbors' centroids are two inradii apart, touching along an edge
+	assert!((first.pos.distance(second.pos) - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn inner_cells_have_a_full_neighborhood() {
+	for (layout, count) in [(Layout::Hex, 6), (Layout::Square, 4), (Layout::Triangle, 3)] {
+	    for rotation in [0.0, 30.0] {
+		let tiling = create_tiling(layout, 20.0, 400.0, 300.0, rotation, Orientation::Flat, &RandGenerator::new());
+		let inner = Rect::new(60.0, 60.0, 280.0, 180.0);
+		for (cell, neighbors) in tiling.cells.iter().zip(&tiling.neighbors) {
+		    if inner.contains(cell.pos) {
+			assert_eq!(neighbors.len(), count, "{layout:?} at {rotation} degrees");
+		    }
+		}
+	    }
+	}
+    }
+
+    #[test]
+    fn hex_grids_know_their_neighbors() {
+	for orientation in [Orientation::Flat, Orientation::Pointy] {
+	    let grid = HexGrid::new(20.0, 400.0, 300.0, orientation);
+	    let tiling = create_tiling(Layout::Hex, 20.0, 400.0, 300.0, 0.0, orientation, &RandGenerator::new());
+	    for (index, near) in tiling.neighbors.iter().enumerate() {
+		let mut axial = grid.neighbors(index);
+		axial.sort_unstable();
+		assert_eq!(&axial, near, "{orientation:?}");
+		assert!(axial.iter().all(|&other| grid.distance(index, other) == 1));
+	    }
+	    // Pixels round to the cell they are in
+	    for (cell, pos) in grid.cells().iter().zip(grid.positions()) {
+		assert_eq!(grid.to_pixel(*cell), pos);
+		assert_eq!(grid.cell_at(pos + Vec2::new(7.0, -5.0)), *cell);
+	    }
+	}
+	assert_eq!(Axial::new(0, 0).distance(Axial::new(3, -1)), 3);
+	assert_eq!(Axial::new(2, 1).distance(Axial::new(-1, 3)), 3);
+    }
+
+    #[test]
+    fn penrose_rhombi_have_equal_sides() {
+	let cells = Layout::Penrose.cells(20.0, 400.0, 300.0, Orientation::Flat, &RandGenerator::new());
+	assert!(cells.len() > 50);
+	let edge = cells[0].outline[0].distance(cells[0].outline[1]);
+	for cell in &cells {
+	    assert_eq!(cell.outline.len(), 4);
+	    for i in 0..4 {
+		let side = cell.outline[i].distance(cell.outline[(i + 1) % 4]);
+		assert!((side - edge).abs() < 0.01 * edge, "{side} vs {edge}");
+	    }
+	}
+    }
+
+    #[test]
+    fn voronoi_cells_cover_the_bounds_once() {
+	let bounds = Rect::new(0.0, 0.0, 400.0, 300.0);
+	let rng = RandGenerator::new();
+	rng.srand(3);
+	// Shoelace areas of the cells add up to the bounds without overlaps
+	let area = |cells: &[Cell]| -> f32 {
+	    cells
+		.iter()
+		.map(|cell| {
+		    let outline = &cell.outline;
+		    (0..outline.len()).map(|i| outline[i].perp_dot(outline[(i + 1) % outline.len()])).sum::<f32>() * 0.5
+		})
+		.sum()
+	};
+	for count in [1, 60, 20_000] {
+	    let sites = (0..count).map(|_| Vec2::new(rng.gen_range(0.0, 400.0), rng.gen_range(0.0, 300.0))).collect();
+	    let total = area(&voronoi_cells(sites, bounds));
+	    assert!((total - bounds.w * bounds.h).abs() < bounds.w * bounds.h * 1e-3, "{count}: {total}");
+	}
+    }
+}
@@ -0,0 +1,78 @@
+//! A twinkling hexagon (or heart) grid screensaver built on macroquad.
+//!
+//! The binary handles the Windows screensaver command line; everything else
+//! lives here so the animation can be embedded in other apps and its
+//! simulation tested without opening a window.
+
+pub mod attract;
+mod audio;
+mod automaton;
+mod background;
+pub mod bench;
+mod bloom;
+mod capture;
+mod clock;
+pub mod flow;
+mod font;
+mod gamepad;
+mod glyph;
+mod gpu;
+pub mod cli;
+pub mod config;
+pub mod config_ui;
+mod control;
+pub mod crash;
+mod debug_overlay;
+pub mod easing;
+mod i18n;
+mod input;
+mod isometric;
+mod kaleidoscope;
+pub mod install;
+pub mod launcher;
+pub mod layout;
+mod live;
+pub mod logging;
+mod marquee;
+mod media;
+mod monitor;
+mod net;
+mod offscreen;
+mod overlay;
+pub mod palette;
+mod passphrase;
+pub mod pattern;
+pub mod post;
+pub mod prisms;
+mod power;
+mod qr;
+mod rain;
+mod reaction;
+mod rings;
+mod script;
+mod seasons;
+mod share;
+mod slideshow;
+mod smoothing;
+mod sync;
+pub mod record;
+pub mod render;
+pub mod screensaver;
+pub mod shapes;
+pub mod svg;
+mod temperature;
+mod ticker;
+mod timer;
+mod trails;
+mod turmite;
+mod weather;
+mod webcam;
+pub mod web;
+#[cfg(windows)]
+mod win32;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use config::Config;
+pub use layout::{Axial, HexGrid};
+pub use screensaver::Screensaver;
@@ -0,0 +1,473 @@
+//! Live input for VJ use: OSC messages on `osc_port`, from this machine
+//! unless `osc_network` lets the network in, and a MIDI controller on
+//! `midi_device`, mapped by `live_map` onto the density, speed and hue
+//! shift, which glide to where they are sent, and onto effects.
+
+use crate::config::Config;
+use crate::control::Command;
+use log::{info, warn};
+use macroquad::math::Vec2;
+use macroquad::rand::gen_range;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::sync::mpsc::{Receiver, channel};
+
+/// `live_map` when the config has none.
+pub(crate) const DEFAULT_MAP: [(&str, &str); 10] = [
+    ("/hexagon/density", "density"),
+    ("/hexagon/speed", "speed"),
+    ("/hexagon/hue", "hue_shift"),
+    ("/hexagon/ring", "ring"),
+    ("/hexagon/pause", "pause"),
+    ("/hexagon/resume", "resume"),
+    ("cc:1", "density"),
+    ("cc:2", "speed"),
+    ("cc:3", "hue_shift"),
+    ("note:36", "ring"),
+];
+/// Largest OSC packet read.
+const MAX_PACKET: usize = 4096;
+
+/// A setting live input can move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Param {
+    Density,
+    Speed,
+    HueShift,
+}
+
+impl Param {
+    /// The span 0..1 values map onto without a range in the mapping.
+    fn range(self) -> (f32, f32) {
+	match self {
+	    Param::Density => (0.0, 1.0),
+	    Param::Speed => (0.0, 4.0),
+	    Param::HueShift => (0.0, 360.0),
+	}
+    }
+
+    fn value(self, config: &Config) -> f32 {
+	match self {
+	    Param::Density => config.density,
+	    Param::Speed => config.speed,
+	    Param::HueShift => config.hue_shift,
+	}
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Set(Param, f32, f32),
+    Ring,
+    Pause(bool),
+    Screenshot,
+}
+
+/// A `live_map` value: the setting and range, or the effect.
+fn parse_mapping(text: &str) -> Option<Action> {
+    let (name, range) = text.split_once(':').map_or((text, None), |(name, range)| (name, Some(range)));
+    let param = match name.trim() {
+	"density" => Param::Density,
+	"speed" => Param::Speed,
+	"hue_shift" => Param::HueShift,
+	effect => {
+	    return match effect {
+		"ring" => Some(Action::Ring),
+		"pause" => Some(Action::Pause(true)),
+		"resume" => Some(Action::Pause(false)),
+		"screenshot" => Some(Action::Screenshot),
+		_ => None,
+	    }
+	    .filter(|_| range.is_none());
+	}
+    };
+    let (low, high) = match range {
+	Some(range) => {
+	    let (low, high) = range.split_once("..")?;
+	    (low.trim().parse().ok()?, high.trim().parse().ok()?)
+	}
+	None => param.range(),
+    };
+    Some(Action::Set(param, low, high))
+}
+
+/// The parsed `live_map`, warning of entries that cannot be.
+fn parse_map(config: &Config) -> Vec<(String, Action)> {
+    config
+	.live_map
+	.iter()
+	.filter_map(|(key, target)| match parse_mapping(target) {
+	    Some(action) => Some((key.clone(), action)),
+	    None => {
+		warn!("invalid live map target '{target}' for `{key}`");
+		None
+	    }
+	})
+	.collect()
+}
+
+/// The null-terminated string at `at`, moving past its padding.
+fn osc_string(packet: &[u8], at: &mut usize) -> Option<String> {
+    let rest = packet.get(*at..)?;
+    let end = rest.iter().position(|byte| *byte == 0)?;
+    let text = std::str::from_utf8(&rest[..end]).ok()?.to_string();
+    *at += (end + 4) & !3;
+    Some(text)
+}
+
+fn osc_bytes<const N: usize>(packet: &[u8], at: &mut usize) -> Option<[u8; N]> {
+    let bytes = packet.get(*at..at.checked_add(N)?)?.try_into().ok()?;
+    *at += N;
+    Some(bytes)
+}
+
+/// The address and numeric arguments of each message in an OSC packet, a
+/// message or a bundle of them.
+fn osc_messages(packet: &[u8]) -> Vec<(String, Vec<f32>)> {
+    let mut at = 0;
+    if packet.starts_with(b"#bundle\0") {
+	// Past the time tag; everything is taken as it comes
+	at = 16;
+	let mut messages = Vec::new();
+	while let Some(size) = osc_bytes::<4>(packet, &mut at) {
+	    let size = u32::from_be_bytes(size) as usize;
+	    let Some(element) = at.checked_add(size).and_then(|end| packet.get(at..end)) else {
+		break;
+	    };
+	    messages.extend(osc_messages(element));
+	    at += size;
+	}
+	return messages;
+    }
+    let Some(address) = osc_string(packet, &mut at).filter(|address| address.starts_with('/')) else {
+	return Vec::new();
+    };
+    let tags = osc_string(packet, &mut at).unwrap_or_default();
+    let mut values = Vec::new();
+    for tag in tags.chars().skip(1) {
+	let value = match tag {
+	    'f' => osc_bytes(packet, &mut at).map(f32::from_be_bytes),
+	    'i' => osc_bytes(packet, &mut at).map(|bytes| i32::from_be_bytes(bytes) as f32),
+	    'd' => osc_bytes(packet, &mut at).map(|bytes| f64::from_be_bytes(bytes) as f32),
+	    'h' => osc_bytes(packet, &mut at).map(|bytes| i64::from_be_bytes(bytes) as f32),
+	    'T' => Some(1.0),
+	    'F' => Some(0.0),
+	    's' | 'S' => {
+		osc_string(packet, &mut at);
+		continue;
+	    }
+	    'b' => {
+		let size = osc_bytes(packet, &mut at).map_or(0, u32::from_be_bytes) as usize;
+		// A size near the top of the range would wrap around on 32-bit
+		match size.checked_add(3).and_then(|padded| at.checked_add(padded & !3)) {
+		    Some(next) => at = next,
+		    None => break,
+		}
+		continue;
+	    }
+	    _ => continue,
+	};
+	match value {
+	    Some(value) => values.push(value),
+	    None => break,
+	}
+    }
+    vec![(address, values)]
+}
+
+/// The `live_map` key and 0..1 value of a controller change or a note.
+fn midi_event([status, number, value]: [u8; 3]) -> Option<(String, f32)> {
+    let value = f32::from(value) / 127.0;
+    match status & 0xf0 {
+	0xb0 => Some((format!("cc:{number}"), value)),
+	0x90 => Some((format!("note:{number}"), value)),
+	0x80 => Some((format!("note:{number}"), 0.0)),
+	_ => None,
+    }
+}
+
+/// Channel messages out of a raw MIDI byte stream, which leaves out a
+/// status byte repeating the last one.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct MidiStream {
+    status: u8,
+    data: Vec<u8>,
+}
+
+#[cfg(target_os = "linux")]
+impl MidiStream {
+    fn feed(&mut self, byte: u8) -> Option<[u8; 3]> {
+	match byte {
+	    // Clock and other real time bytes can come between any others
+	    0xf8.. => return None,
+	    0x80.. => {
+		// System messages are not channel messages
+		self.status = if byte < 0xf0 { byte } else { 0 };
+		self.data.clear();
+		return None;
+	    }
+	    _ if self.status == 0 => return None,
+	    _ => self.data.push(byte),
+	}
+	let length = if matches!(self.status & 0xf0, 0xc0 | 0xd0) { 1 } else { 2 };
+	if self.data.len() < length {
+	    return None;
+	}
+	let message = [self.status, self.data[0], self.data.get(1).copied().unwrap_or(0)];
+	self.data.clear();
+	Some(message)
+    }
+}
+
+/// Read the first ALSA raw MIDI device with `name` in its path or card
+/// name, or the first of all for `first`, on a thread of its own.
+#[cfg(target_os = "linux")]
+fn open_midi(name: &str, messages: std::sync::mpsc::Sender<[u8; 3]>) -> Result<(), String> {
+    use std::io::Read;
+
+    let mut devices: Vec<_> = std::fs::read_dir("/dev/snd")
+	.map_err(|err| err.to_string())?
+	.flatten()
+	.map(|entry| entry.path())
+	.filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("midiC")))
+	.collect();
+    devices.sort();
+    let card_name = |path: &std::path::Path| {
+	let card = path.file_name()?.to_str()?.strip_prefix("midiC")?.split('D').next()?.to_string();
+	std::fs::read_to_string(format!("/proc/asound/card{card}/id")).ok()
+    };
+    let name = name.to_lowercase();
+    let path = devices
+	.into_iter()
+	.find(|path| name == "first" || path.display().to_string().contains(&name) || card_name(path).is_some_and(|card| card.trim().to_lowercase().contains(&name)))
+	.ok_or_else(|| format!("no MIDI device named `{name}`"))?;
+    let mut device = std::fs::File::open(&path).map_err(|err| format!("cannot open {}: {err}", path.display()))?;
+    info!("live: reading MIDI from {}", path.display());
+    // Holds the device until the next byte after the input is dropped
+    std::thread::spawn(move || {
+	let (mut stream, mut buffer) = (MidiStream::default(), [0; 64]);
+	while let Ok(read @ 1..) = device.read(&mut buffer) {
+	    for message in buffer[..read].iter().filter_map(|byte| stream.feed(*byte)) {
+		if messages.send(message).is_err() {
+		    return;
+		}
+	    }
+	}
+    });
+    Ok(())
+}
+
+/// The MIDI input, its messages arriving on `messages`.
+struct Midi {
+    messages: Receiver<[u8; 3]>,
+    #[cfg(windows)]
+    _input: crate::win32::MidiInput,
+}
+
+impl Midi {
+    fn open(name: &str) -> Result<Self, String> {
+	let (sender, messages) = channel::<[u8; 3]>();
+	#[cfg(windows)]
+	return Ok(Self { messages, _input: crate::win32::open_midi_input(name, sender)? });
+	#[cfg(target_os = "linux")]
+	return open_midi(name, sender).map(|()| Self { messages });
+	#[cfg(not(any(windows, target_os = "linux")))]
+	{
+	    let _ = (name, sender, messages);
+	    Err("MIDI input is not supported on this system".to_string())
+	}
+    }
+}
+
+/// A setting on its way to where it was sent.
+#[derive(Clone, Copy)]
+struct Glide {
+    value: f32,
+    target: f32,
+}
+
+/// The settings live input has taken over, `None` for those left to the
+/// config.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Tuning {
+    pub(crate) density: Option<f32>,
+    pub(crate) speed: Option<f32>,
+    pub(crate) hue_shift: Option<f32>,
+}
+
+/// The open inputs and where they have sent the settings.
+pub(crate) struct Live {
+    osc: Option<UdpSocket>,
+    midi: Option<Midi>,
+    map: Vec<(String, Action)>,
+    smoothing: f32,
+    density: Option<Glide>,
+    speed: Option<Glide>,
+    hue_shift: Option<Glide>,
+}
+
+impl Live {
+    /// Open the inputs `config` asks for, or nothing when it asks for none
+    /// or none can be opened.
+    pub(crate) fn start(config: &Config) -> Option<Self> {
+	let osc = (config.osc_port != 0)
+	    .then(|| {
+		let address = if config.osc_network { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+		let socket = UdpSocket::bind((address, config.osc_port)).and_then(|socket| socket.set_nonblocking(true).map(|()| socket));
+		socket.map_err(|err| warn!("cannot take OSC on port {}: {err}", config.osc_port)).ok()
+	    })
+	    .flatten();
+	if osc.is_some() {
+	    info!("live: taking OSC on port {}", config.osc_port);
+	}
+	let midi = (!config.midi_device.is_empty()).then(|| Midi::open(&config.midi_device).map_err(|err| warn!("{err}")).ok()).flatten();
+	if osc.is_none() && midi.is_none() {
+	    return None;
+	}
+	Some(Self { osc, midi, map: parse_map(config), smoothing: config.live_smoothing, density: None, speed: None, hue_shift: None })
+    }
+
+    /// Whether `start` would open the same inputs for `config`.
+    pub(crate) fn same_inputs(a: &Config, b: &Config) -> bool {
+	a.osc_port == b.osc_port && a.osc_network == b.osc_network && a.midi_device == b.midi_device
+    }
+
+    /// Map and glide the way `config` says from now on.
+    pub(crate) fn reconfigure(&mut self, config: &Config) {
+	self.map = parse_map(config);
+	self.smoothing = config.live_smoothing;
+    }
+
+    fn glide(&mut self, param: Param) -> &mut Option<Glide> {
+	match param {
+	    Param::Density => &mut self.density,
+	    Param::Speed => &mut self.speed,
+	    Param::HueShift => &mut self.hue_shift,
+	}
+    }
+
+    /// What `key` received with `values` asks for, setting off gliding
+    /// from where `config` has the setting the first time.
+    fn handle(&mut self, key: &str, values: &[f32], config: &Config) -> Vec<Command> {
+	let actions: Vec<Action> = self.map.iter().filter(|(mapped, _)| mapped == key).map(|(_, action)| *action).collect();
+	let pressed = values.first().is_none_or(|value| *value > 0.0);
+	let mut commands = Vec::new();
+	for action in actions {
+	    match action {
+		Action::Set(param, low, high) => {
+		    let Some(value) = values.first() else {
+			continue;
+		    };
+		    let target = low + value.clamp(0.0, 1.0) * (high - low);
+		    let glide = self.glide(param).get_or_insert(Glide { value: param.value(config), target });
+		    glide.target = target;
+		}
+		Action::Ring if values.len() >= 2 => commands.push(Command::Ring(Vec2::new(values[0], values[1]).clamp(Vec2::ZERO, Vec2::ONE))),
+		Action::Ring if pressed => commands.push(Command::Ring(Vec2::new(gen_range(0.0, 1.0), gen_range(0.0, 1.0)))),
+		Action::Pause(pause) if pressed => commands.push(Command::Pause(pause)),
+		Action::Screenshot if pressed => commands.push(Command::Screenshot),
+		_ => {}
+	    }
+	}
+	commands
+    }
+
+    /// Take what arrived since the last call and glide the settings `dt`
+    /// seconds further, returning the effects set off.
+    pub(crate) fn poll(&mut self, dt: f32, config: &Config) -> Vec<Command> {
+	let mut received = Vec::new();
+	if let Some(osc) = &self.osc {
+	    let mut packet = [0; MAX_PACKET];
+	    while let Ok(length) = osc.recv(&mut packet) {
+		received.extend(osc_messages(&packet[..length]));
+	    }
+	}
+	if let Some(midi) = &self.midi {
+	    received.extend(midi.messages.try_iter().filter_map(midi_event).map(|(key, value)| (key, vec![value])));
+	}
+	let commands = received.iter().flat_map(|(key, values)| self.handle(key, values, config)).collect();
+	let blend = if self.smoothing > 0.0 { 1.0 - (-dt / self.smoothing).exp() } else { 1.0 };
+	for param in [Param::Density, Param::Speed, Param::HueShift] {
+	    if let Some(glide) = self.glide(param) {
+		// The short way around the color wheel
+		let gap = match param {
+		    Param::HueShift => (glide.target - glide.value + 180.0).rem_euclid(360.0) - 180.0,
+		    _ => glide.target - glide.value,
+		};
+		glide.value += gap * blend;
+	    }
+	}
+	commands
+    }
+
+    pub(crate) fn tuning(&self) -> Tuning {
+	let value = |glide: Option<Glide>| glide.map(|glide| glide.value);
+	Tuning { density: value(self.density), speed: value(self.speed), hue_shift: value(self.hue_shift).map(|hue| hue.rem_euclid(360.0)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc_packet(address: &str, values: &[f32]) -> Vec<u8> {
+	let mut packet = Vec::new();
+	let push_string = |packet: &mut Vec<u8>, text: &str| {
+	    packet.extend(text.as_bytes());
+	    packet.resize((packet.len() + 4) & !3, 0);
+	};
+	push_string(&mut packet, address);
+	push_string(&mut packet, &format!(",{}", "f".repeat(values.len())));
+	for value in values {
+	    packet.extend(value.to_be_bytes());
+	}
+	packet
+    }
+
+    #[test]
+    fn osc_packets_and_bundles_are_read() {
+	let message = osc_packet("/hexagon/ring", &[0.25, 0.75]);
+	assert_eq!(osc_messages(&message), [("/hexagon/ring".to_string(), vec![0.25, 0.75])]);
+	let mut bundle = b"#bundle\0\0\0\0\0\0\0\0\x01".to_vec();
+	for message in [osc_packet("/a", &[1.0]), osc_packet("/b", &[])] {
+	    bundle.extend((message.len() as u32).to_be_bytes());
+	    bundle.extend(message);
+	}
+	assert_eq!(osc_messages(&bundle), [("/a".to_string(), vec![1.0]), ("/b".to_string(), vec![])]);
+	assert!(osc_messages(b"nonsense").is_empty());
+	// What follows a blob claiming more than the packet holds is not read
+	let mut blob = b"/a\0\0,bf\0".to_vec();
+	blob.extend(u32::MAX.to_be_bytes());
+	blob.extend(1.0f32.to_be_bytes());
+	assert_eq!(osc_messages(&blob), [("/a".to_string(), vec![])]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn midi_bytes_become_controller_changes() {
+	let mut stream = MidiStream::default();
+	// The second change leaves out the status, and a clock byte comes between
+	let messages: Vec<_> = [0xb0, 1, 127, 0xf8, 1, 0].into_iter().filter_map(|byte| stream.feed(byte)).collect();
+	assert_eq!(messages, [[0xb0, 1, 127], [0xb0, 1, 0]]);
+	assert_eq!(midi_event([0x91, 36, 127]), Some(("note:36".to_string(), 1.0)));
+	assert_eq!(midi_event([0xc0, 5, 0]), None);
+    }
+
+    #[test]
+    fn mapped_values_glide_to_their_targets() {
+	assert_eq!(parse_mapping("speed:0.5..4"), Some(Action::Set(Param::Speed, 0.5, 4.0)));
+	assert_eq!(parse_mapping("ring:0..1"), None);
+	let config = Config { live_map: [("/s", "speed:0..4"), ("/h", "hue_shift"), ("/r", "ring")].map(|(key, target)| (key.to_string(), target.to_string())).into(), hue_shift: 350.0, ..Default::default() };
+	let mut live = Live { osc: None, midi: None, map: parse_map(&config), smoothing: 1.0, density: None, speed: None, hue_shift: None };
+	assert!(live.handle("/s", &[0.5], &config).is_empty());
+	live.handle("/h", &[10.0 / 360.0], &config);
+	assert_eq!(live.handle("/r", &[0.5, 2.0], &config), [Command::Ring(Vec2::new(0.5, 1.0))]);
+	assert!(live.handle("/r", &[0.0], &config).is_empty());
+	live.poll(1.0, &config);
+	let tuning = live.tuning();
+	assert!(tuning.speed.is_some_and(|speed| speed > 1.0 && speed < 2.0));
+	// Through 0 rather than back across the wheel
+	assert!(tuning.hue_shift.is_some_and(|hue| hue < 10.0));
+	assert_eq!(tuning.density, None);
+    }
+}
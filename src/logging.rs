@@ -0,0 +1,248 @@
+//! Diagnostics through the `log` macros: printed to stderr as before and
+//! kept in a rotating log file beside the config. `HEXAGON_LOG` picks the
+//! levels, such as `debug` or `info,config=trace`. Embedders that skip
+//! `init` can install their own logger instead.
+
+use crate::config::Config;
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The environment variable with the levels to log.
+const LOG_VARIABLE: &str = "HEXAGON_LOG";
+/// Name of the log file in the config directory.
+const LOG_NAME: &str = "hexagon_screensaver.log";
+/// Size a log file grows to before it is rotated.
+const MAX_LOG_BYTES: u64 = 1 << 20;
+/// Rotated files kept, as `.log.1` (the newest) to `.log.3`.
+const KEPT_LOGS: u32 = 3;
+
+/// Frames taking this many times their interval, and at least
+/// `SLOW_FRAME_SECONDS`, count as slow.
+const SLOW_FRAME_FACTOR: f32 = 4.0;
+const SLOW_FRAME_SECONDS: f32 = 0.25;
+/// Seconds between slow frame warnings; the ones in between are counted.
+const SLOW_FRAME_REPORT_INTERVAL: f32 = 10.0;
+
+/// The level to log for each module, the longest matching name winning.
+struct Filter {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// Parse comma separated `level` and `module=level` entries, modules
+    /// named without the crate prefix. A bare level replaces `level`.
+    fn parse(spec: &str, level: LevelFilter) -> Result<Self, String> {
+	let mut filter = Self { level, modules: Vec::new() };
+	for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+	    let parse_level = |level: &str| level.trim().parse::<LevelFilter>().map_err(|_| format!("invalid log level `{level}`"));
+	    match entry.split_once('=') {
+		Some((module, level)) => filter.modules.push((module.trim().to_string(), parse_level(level)?)),
+		None => filter.level = parse_level(entry)?,
+	    }
+	}
+	Ok(filter)
+    }
+
+    fn level(&self, target: &str) -> LevelFilter {
+	let module = target.strip_prefix(concat!(env!("CARGO_CRATE_NAME"), "::")).unwrap_or(target);
+	self.modules
+	    .iter()
+	    .filter(|(name, _)| module == name || module.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with("::")))
+	    .max_by_key(|(name, _)| name.len())
+	    .map_or(self.level, |(_, level)| *level)
+    }
+
+    /// The most detailed level any module logs at.
+    fn max(&self) -> LevelFilter {
+	self.modules.iter().map(|(_, level)| *level).fold(self.level, Ord::max)
+    }
+}
+
+/// The open log file and how much it holds.
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    /// Append to the log in the first of `dirs` it can be written in.
+    fn open(dirs: &[PathBuf]) -> Option<Self> {
+	dirs.iter().find_map(|dir| {
+	    fs::create_dir_all(dir).ok()?;
+	    let path = dir.join(LOG_NAME);
+	    let file = OpenOptions::new().create(true).append(true).open(&path).ok()?;
+	    let size = file.metadata().map_or(0, |metadata| metadata.len());
+	    Some(Self { path, file, size })
+	})
+    }
+
+    fn write(&mut self, line: &str) {
+	if self.size + line.len() as u64 > MAX_LOG_BYTES {
+	    self.rotate();
+	}
+	if self.file.write_all(line.as_bytes()).is_ok() {
+	    self.size += line.len() as u64;
+	}
+    }
+
+    /// Move each kept log up one, dropping the oldest, and start afresh.
+    fn rotate(&mut self) {
+	// Nowhere to report failures to; the log just keeps growing then
+	for kept in (1..KEPT_LOGS).rev() {
+	    let _ = fs::rename(rotated(&self.path, kept), rotated(&self.path, kept + 1));
+	}
+	let _ = fs::rename(&self.path, rotated(&self.path, 1));
+	if let Ok(file) = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path) {
+	    self.file = file;
+	    self.size = 0;
+	}
+    }
+}
+
+/// The `kept`th rotated copy of the log at `path`.
+fn rotated(path: &Path, kept: u32) -> PathBuf {
+    path.with_extension(format!("log.{kept}"))
+}
+
+struct Logger {
+    filter: Filter,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+	metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+	if !self.enabled(record.metadata()) {
+	    return;
+	}
+	let message = record.args().to_string();
+	#[cfg(not(target_arch = "wasm32"))]
+	match record.level() {
+	    Level::Error => eprintln!("error: {message}"),
+	    Level::Warn => eprintln!("warning: {message}"),
+	    Level::Info => eprintln!("{message}"),
+	    level => eprintln!("{}: {message}", level.as_str().to_ascii_lowercase()),
+	}
+	// A page has the browser console in place of stderr
+	#[cfg(target_arch = "wasm32")]
+	match record.level() {
+	    Level::Error => macroquad::miniquad::error!("{message}"),
+	    Level::Warn => macroquad::miniquad::warn!("{message}"),
+	    Level::Info => macroquad::miniquad::info!("{message}"),
+	    _ => macroquad::miniquad::debug!("{message}"),
+	}
+	if let Ok(mut file) = self.file.lock()
+	    && let Some(file) = file.as_mut()
+	{
+	    let time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+	    file.write(&format!("{time} {:<5} {}: {message}\n", record.level(), record.target()));
+	}
+    }
+
+    fn flush(&self) {
+	if let Ok(mut file) = self.file.lock()
+	    && let Some(file) = file.as_mut()
+	{
+	    let _ = file.file.flush();
+	}
+    }
+}
+
+/// Log to stderr and the log file at the levels `HEXAGON_LOG` sets, by
+/// default info and up, or debug too when `verbose`. Call it once the
+/// config location is settled.
+pub fn init(verbose: bool) {
+    let level = if verbose { LevelFilter::Debug } else { LevelFilter::Info };
+    let spec = env::var(LOG_VARIABLE).unwrap_or_default();
+    let filter = Filter::parse(&spec, level).unwrap_or_else(|err| {
+	eprintln!("warning: {LOG_VARIABLE}: {err}, logging at {level}");
+	Filter { level, modules: Vec::new() }
+    });
+    let max = filter.max();
+    let logger = Logger { filter, file: Mutex::new(LogFile::open(&Config::config_dirs())) };
+    if log::set_logger(Box::leak(Box::new(logger))).is_ok() {
+	log::set_max_level(max);
+    }
+}
+
+/// Notices frames that took far longer than the frame rate allows.
+pub(crate) struct FrameWatch {
+    since_report: f32,
+    slow: u32,
+}
+
+impl FrameWatch {
+    pub(crate) fn new() -> Self {
+	Self { since_report: SLOW_FRAME_REPORT_INTERVAL, slow: 0 }
+    }
+
+    /// Count a frame of `dt` seconds paced at `max_fps`, 0 for unpaced,
+    /// returning a warning when it was slow and one is due.
+    pub(crate) fn step(&mut self, dt: f32, max_fps: f32) -> Option<String> {
+	self.since_report += dt;
+	let interval = if max_fps > 0.0 { 1.0 / max_fps } else { 0.0 };
+	if dt < (SLOW_FRAME_FACTOR * interval).max(SLOW_FRAME_SECONDS) {
+	    return None;
+	}
+	self.slow += 1;
+	if self.since_report < SLOW_FRAME_REPORT_INTERVAL {
+	    return None;
+	}
+	let report = match self.slow {
+	    1 => format!("a frame took {:.0} ms", dt * 1000.0),
+	    slow => format!("a frame took {:.0} ms, {slow} slow frames in the last {:.0}s", dt * 1000.0, self.since_report),
+	};
+	self.since_report = 0.0;
+	self.slow = 0;
+	Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_pick_the_closest_module() {
+	let filter = Filter::parse("warn, config=debug ,config::watch=trace", LevelFilter::Info).unwrap();
+	assert_eq!(filter.level("hexagon_screensaver::screensaver"), LevelFilter::Warn);
+	assert_eq!(filter.level("hexagon_screensaver::config"), LevelFilter::Debug);
+	assert_eq!(filter.level("hexagon_screensaver::config::watch"), LevelFilter::Trace);
+	assert_eq!(filter.level("hexagon_screensaver::config_ui"), LevelFilter::Warn);
+	assert_eq!(filter.max(), LevelFilter::Trace);
+	assert_eq!(Filter::parse("", LevelFilter::Debug).unwrap().max(), LevelFilter::Debug);
+	assert!(Filter::parse("loud", LevelFilter::Info).is_err());
+    }
+
+    #[test]
+    fn full_logs_are_rotated() {
+	let dir = env::temp_dir().join(format!("hexagon_log_test_{}", std::process::id()));
+	let mut log = LogFile::open(std::slice::from_ref(&dir)).unwrap();
+	log.size = MAX_LOG_BYTES;
+	log.write("after\n");
+	assert_eq!(fs::read_to_string(dir.join(LOG_NAME)).unwrap(), "after\n");
+	assert!(rotated(&log.path, 1).is_file());
+	fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn slow_frames_are_reported_now_and_then() {
+	let mut frames = FrameWatch::new();
+	assert_eq!(frames.step(0.02, 60.0), None);
+	// Paced at one frame a second, a second is on time
+	assert_eq!(frames.step(1.0, 1.0), None);
+	assert_eq!(frames.step(0.5, 60.0).as_deref(), Some("a frame took 500 ms"));
+	assert_eq!(frames.step(0.5, 60.0), None);
+	assert!(frames.step(9.5, 0.0).unwrap().contains("2 slow frames"));
+    }
+}
@@ -0,0 +1,60 @@
+//! A message scrolling across each display in lit shapes, right to left.
+
+use crate::font::{GLYPH_HEIGHT, light_pixels};
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+
+/// Share of a display's height the text takes.
+const HEIGHT_SHARE: f32 = 0.3;
+
+/// Mark the `shapes` under the message `pixels`, `seconds` into its
+/// scrolling at `speed` font pixels a second, as lit and every other shape
+/// as not. Each pass starts with the message just off the right edge and
+/// ends once it has left at the left. Font pixels are at least
+/// `cell_radius * 2` wide so each one covers a shape.
+pub(crate) fn light_marquee(shapes: &mut [Shape], displays: &[Rect], pixels: &[Vec<bool>], cell_radius: f32, seconds: f32, speed: f32) {
+    for shape in shapes.iter_mut() {
+	shape.lit = false;
+    }
+    for display in displays {
+	let pixel = (display.h * HEIGHT_SHARE / GLYPH_HEIGHT as f32).max(cell_radius * 2.0);
+	let travel = display.w + pixels[0].len() as f32 * pixel;
+	let x = display.right() - (seconds * speed * pixel).rem_euclid(travel);
+	let corner = Vec2::new(x, display.center().y - GLYPH_HEIGHT as f32 * pixel / 2.0);
+	light_pixels(shapes, *display, pixels, corner, pixel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::rasterize_text;
+    use crate::palette::Palette;
+    use macroquad::rand::RandGenerator;
+
+    #[test]
+    fn messages_scroll_in_from_the_right() {
+	let (palette, rng) = (Palette::random(), RandGenerator::new());
+	let display = Rect::new(0.0, 0.0, 100.0, 50.0);
+	let mut shapes: Vec<Shape> = (0..10)
+	    .flat_map(|x| (0..5).map(move |y| (x, y)))
+	    .map(|(x, y)| Shape::new(x as f32 * 10.0 + 5.0, y as f32 * 10.0 + 5.0, 4.0, false, &palette, &rng))
+	    .collect();
+	let pixels = rasterize_text("I");
+	let lit_columns = |shapes: &[Shape]| {
+	    let mut columns: Vec<f32> = shapes.iter().filter(|shape| shape.lit).map(|shape| shape.x).collect();
+	    columns.dedup();
+	    columns
+	};
+	light_marquee(&mut shapes, &[display], &pixels, 5.0, 0.0, 1.0);
+	assert!(lit_columns(&shapes).is_empty());
+	// One font pixel in, the left of the I shows in the last column
+	light_marquee(&mut shapes, &[display], &pixels, 5.0, 1.0, 1.0);
+	assert_eq!(lit_columns(&shapes), [95.0]);
+	// After a whole pass it starts over
+	light_marquee(&mut shapes, &[display], &pixels, 5.0, 13.0, 1.0);
+	assert!(lit_columns(&shapes).is_empty());
+	light_marquee(&mut shapes, &[display], &pixels, 5.0, 14.0, 1.0);
+	assert_eq!(lit_columns(&shapes), [95.0]);
+    }
+}
@@ -0,0 +1,129 @@
+//! The track playing in the system's media player, from MPRIS through
+//! `playerctl` on Linux and the media session controls through PowerShell
+//! on Windows, polled on a background thread.
+
+use crate::net::percent_decode;
+use crate::webcam::hide_console;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time between asking the player. PowerShell takes a while to start.
+const POLL_INTERVAL: Duration = Duration::from_secs(if cfg!(windows) { 5 } else { 2 });
+
+// Prints the current session as `status<TAB>artist<TAB>title<TAB>`, the
+// format asked of playerctl, awaiting the WinRT calls through AsTask
+const SESSION_SCRIPT: &str = r#"
+Add-Type -AssemblyName System.Runtime.WindowsRuntime
+$asTask = [System.WindowsRuntimeSystemExtensions].GetMethods() | Where-Object { $_.Name -eq 'AsTask' -and $_.GetParameters().Count -eq 1 -and $_.GetParameters()[0].ParameterType.Name -eq 'IAsyncOperation`1' } | Select-Object -First 1
+function Await($operation, $type) {
+    $task = $asTask.MakeGenericMethod($type).Invoke($null, @($operation))
+    $task.Wait(-1) | Out-Null
+    $task.Result
+}
+$managerType = [Windows.Media.Control.GlobalSystemMediaTransportControlsSessionManager, Windows.Media.Control, ContentType = WindowsRuntime]
+$manager = Await ($managerType::RequestAsync()) $managerType
+$session = $manager.GetCurrentSession()
+if ($session) {
+    $propertiesType = [Windows.Media.Control.GlobalSystemMediaTransportControlsSessionMediaProperties, Windows.Media.Control, ContentType = WindowsRuntime]
+    $properties = Await ($session.TryGetMediaPropertiesAsync()) $propertiesType
+    "$($session.GetPlaybackInfo().PlaybackStatus)`t$($properties.Artist)`t$($properties.Title)`t"
+}
+"#;
+
+/// A track being played.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Track {
+    pub(crate) artist: String,
+    pub(crate) title: String,
+    /// The album art, when the player has it in a file.
+    pub(crate) art: Option<PathBuf>,
+}
+
+impl Track {
+    /// The track as the overlay shows it.
+    pub(crate) fn label(&self) -> String {
+	if self.artist.is_empty() { self.title.clone() } else { format!("{} - {}", self.artist, self.title) }
+    }
+}
+
+/// Asking the player in the background, and what changed since last taken.
+pub(crate) struct Media {
+    /// The track playing, or `None` once nothing is, when that changed.
+    changed: Arc<Mutex<Option<Option<Track>>>>,
+}
+
+impl Media {
+    pub(crate) fn start() -> Self {
+	let changed = Arc::new(Mutex::new(None));
+	let weak = Arc::downgrade(&changed);
+	std::thread::spawn(move || {
+	    let mut playing = None;
+	    loop {
+		let track = now_playing();
+		// Stop once the screensaver no longer wants to know
+		let Some(changed) = weak.upgrade() else {
+		    break;
+		};
+		if track != playing {
+		    playing = track.clone();
+		    *changed.lock().unwrap() = Some(track);
+		}
+		drop(changed);
+		std::thread::sleep(POLL_INTERVAL);
+	    }
+	});
+	Self { changed }
+    }
+
+    /// The track playing now if that changed since the last call, `None`
+    /// inside for nothing playing anymore.
+    pub(crate) fn poll(&self) -> Option<Option<Track>> {
+	self.changed.lock().unwrap().take()
+    }
+}
+
+/// The track the system's player is playing, if any.
+fn now_playing() -> Option<Track> {
+    let mut command = if cfg!(windows) {
+	let mut command = Command::new("powershell");
+	command.args(["-NoProfile", "-NonInteractive", "-Command", SESSION_SCRIPT]);
+	command
+    } else {
+	let mut command = Command::new("playerctl");
+	command.args(["metadata", "--format", "{{status}}\t{{artist}}\t{{title}}\t{{mpris:artUrl}}"]);
+	command
+    };
+    hide_console(&mut command);
+    let output = command.output().ok()?;
+    parse_status(String::from_utf8_lossy(&output.stdout).lines().next()?)
+}
+
+/// A `status<TAB>artist<TAB>title<TAB>art url` line, a track only while
+/// playing.
+fn parse_status(line: &str) -> Option<Track> {
+    let mut fields = line.split('\t');
+    if fields.next()? != "Playing" {
+	return None;
+    }
+    let artist = fields.next()?.trim().to_string();
+    let title = fields.next()?.trim().to_string();
+    let art = fields.next().and_then(|url| url.trim().strip_prefix("file://")).map(|path| PathBuf::from(percent_decode(path, false)));
+    (!title.is_empty()).then_some(Track { artist, title, art })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playing_tracks_parse_with_their_art() {
+	let track = parse_status("Playing\tDaft Punk\tOne More Time\tfile:///home/me/Album%20Art/cover.png").unwrap();
+	assert_eq!(track.label(), "Daft Punk - One More Time");
+	assert_eq!(track.art, Some(PathBuf::from("/home/me/Album Art/cover.png")));
+	assert_eq!(parse_status("Playing\t\tRadio\thttps://example.com/art.jpg").map(|track| (track.label(), track.art)), Some(("Radio".to_string(), None)));
+	assert_eq!(parse_status("Paused\tDaft Punk\tOne More Time\t"), None);
+	assert_eq!(percent_decode("100%25%2", false), "100%%2");
+    }
+}
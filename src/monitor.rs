@@ -0,0 +1,170 @@
+//! The machine's load in the `monitor` pattern: the grid is split into
+//! columns, one per CPU core and a last one for memory, each lit as
+//! brightly as that core or the memory is busy.
+
+use crate::config::Config;
+use crate::pattern::Pattern;
+use crate::shapes::Shape;
+use log::warn;
+
+/// Seconds between samples of the load.
+const SAMPLE_INTERVAL: f32 = 1.0;
+
+/// The latest load of each column and how lit it leaves each shape.
+#[derive(Default)]
+pub(crate) struct Monitor {
+    /// Busy and total time of each core at the last sample.
+    previous: Vec<(u64, u64)>,
+    /// Share of each core in use, then of memory, 0..1.
+    loads: Vec<f32>,
+    levels: Vec<f32>,
+    since_sample: f32,
+    warned: bool,
+}
+
+impl Monitor {
+    /// Sample the load once `SAMPLE_INTERVAL` has passed over `dt` second
+    /// steps and light `shapes` by it.
+    pub(crate) fn update(&mut self, shapes: &[Shape], dt: f32, config: &Config) {
+	if config.pattern != Pattern::Monitor || shapes.is_empty() {
+	    *self = Monitor { warned: self.warned, ..Default::default() };
+	    return;
+	}
+	self.since_sample += dt;
+	if self.since_sample < SAMPLE_INTERVAL && self.levels.len() == shapes.len() {
+	    return;
+	}
+	self.since_sample = 0.0;
+	let times = cpu_times();
+	self.loads = loads_since(&self.previous, &times);
+	self.loads.extend(memory_use());
+	self.previous = times;
+	if self.loads.is_empty() && !self.warned {
+	    warn!("the system load cannot be read on this platform");
+	    self.warned = true;
+	}
+	self.levels = shapes.iter().map(|shape| column_load(&self.loads, shape.audio_pos)).collect();
+    }
+
+    /// How lit the `index`th shape is, the load of its column.
+    pub(crate) fn level(&self, index: usize) -> f32 {
+	self.levels.get(index).copied().unwrap_or(0.0)
+    }
+}
+
+/// The load of the column at horizontal position `pos`, 0..1, across a
+/// display with one column per entry of `loads`.
+fn column_load(loads: &[f32], pos: f32) -> f32 {
+    if loads.is_empty() {
+	return 0.0;
+    }
+    loads[((pos * loads.len() as f32) as usize).min(loads.len() - 1)]
+}
+
+/// The busy share of each core between the `previous` sample and
+/// `current`, or since boot when there was none.
+fn loads_since(previous: &[(u64, u64)], current: &[(u64, u64)]) -> Vec<f32> {
+    current
+	.iter()
+	.enumerate()
+	.map(|(index, &(busy, total))| {
+	    let (busy_before, total_before) = previous.get(index).copied().unwrap_or((0, 0));
+	    let total = total.saturating_sub(total_before);
+	    if total == 0 { 0.0 } else { busy.saturating_sub(busy_before) as f32 / total as f32 }
+	})
+	.collect()
+}
+
+/// Busy and total time of each core since boot, in the platform's ticks.
+/// Windows only reports all cores together.
+fn cpu_times() -> Vec<(u64, u64)> {
+    #[cfg(windows)]
+    {
+	use windows_sys::Win32::Foundation::FILETIME;
+	use windows_sys::Win32::System::Threading::GetSystemTimes;
+	let mut times: [FILETIME; 3] = unsafe { std::mem::zeroed() };
+	let [idle, kernel, user] = &mut times;
+	if unsafe { GetSystemTimes(idle, kernel, user) } == 0 {
+	    return Vec::new();
+	}
+	let ticks = |time: &FILETIME| (time.dwHighDateTime as u64) << 32 | time.dwLowDateTime as u64;
+	// Kernel time includes the idle time
+	let total = ticks(&times[1]) + ticks(&times[2]);
+	vec![(total - ticks(&times[0]), total)]
+    }
+    #[cfg(not(windows))]
+    {
+	std::fs::read_to_string("/proc/stat").map(|stat| parse_proc_stat(&stat)).unwrap_or_default()
+    }
+}
+
+/// The per core lines of Linux's `/proc/stat`.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_proc_stat(stat: &str) -> Vec<(u64, u64)> {
+    stat.lines()
+	.filter(|line| line.starts_with("cpu") && line.as_bytes().get(3).is_some_and(u8::is_ascii_digit))
+	.map(|line| {
+	    // user, nice, system, idle, iowait, irq, softirq, steal
+	    let ticks: Vec<u64> = line.split_whitespace().skip(1).take(8).filter_map(|field| field.parse().ok()).collect();
+	    let total = ticks.iter().sum::<u64>();
+	    let idle = ticks.iter().skip(3).take(2).sum::<u64>();
+	    (total - idle, total)
+	})
+	.collect()
+}
+
+/// The share of memory in use, if known.
+fn memory_use() -> Option<f32> {
+    #[cfg(windows)]
+    {
+	use windows_sys::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+	let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+	status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+	(unsafe { GlobalMemoryStatusEx(&mut status) } != 0).then(|| status.dwMemoryLoad as f32 / 100.0)
+    }
+    #[cfg(not(windows))]
+    {
+	parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+    }
+}
+
+/// The share of memory in use from Linux's `/proc/meminfo`.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_meminfo(meminfo: &str) -> Option<f32> {
+    let field = |name: &str| {
+	meminfo
+	    .lines()
+	    .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+	    .and_then(|value| value.split_whitespace().next()?.parse::<f32>().ok())
+    };
+    let total = field("MemTotal").filter(|total| *total > 0.0)?;
+    Some(1.0 - field("MemAvailable")? / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linux_load_is_read_per_core() {
+	let stat = "cpu  30 0 10 60 0 0 0 0 0 0\n\
+		    cpu0 20 0 5 20 5 0 0 0 0 0\n\
+		    cpu1 10 0 5 40 0 0 0 0 0 0\n\
+		    intr 12345\n";
+	let times = parse_proc_stat(stat);
+	assert_eq!(times, [(25, 50), (15, 55)]);
+	assert_eq!(loads_since(&[(20, 40), (15, 45)], &times), [0.5, 0.0]);
+	let meminfo = "MemTotal:       1000 kB\nMemFree:         100 kB\nMemAvailable:    250 kB\n";
+	assert_eq!(parse_meminfo(meminfo), Some(0.75));
+	assert_eq!(parse_meminfo("MemFree: 1 kB"), None);
+    }
+
+    #[test]
+    fn columns_split_the_display_between_loads() {
+	let loads = [0.1, 0.5, 0.9];
+	assert_eq!(column_load(&loads, 0.0), 0.1);
+	assert_eq!(column_load(&loads, 0.5), 0.5);
+	assert_eq!(column_load(&loads, 1.0), 0.9);
+	assert_eq!(column_load(&[], 0.5), 0.0);
+    }
+}
@@ -0,0 +1,70 @@
+//! Fetching over HTTP with `curl`, which every desktop platform now ships.
+
+use crate::webcam::hide_console;
+use std::process::Command;
+
+/// Seconds a fetch may take.
+const TIMEOUT: &str = "20";
+
+/// The body at `url`, or why it could not be fetched.
+pub(crate) fn fetch(url: &str) -> Result<String, String> {
+    let mut command = Command::new("curl");
+    // A URL starting with a dash is still a URL, not an option
+    command.args(["--silent", "--show-error", "--fail", "--location", "--max-time", TIMEOUT, "--", url]);
+    hide_console(&mut command);
+    let output = command.output().map_err(|err| format!("cannot run curl: {err}"))?;
+    if !output.status.success() {
+	return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The `key=value` pairs of a URL query or form body, joined by `&` and
+/// with or without a leading `?`. Keys are given with underscores for
+/// dashes, and a bare key is set to true.
+pub(crate) fn form_pairs(query: &str) -> Vec<(String, String)> {
+    query
+	.trim_start_matches('?')
+	.split('&')
+	.filter(|pair| !pair.is_empty())
+	.map(|pair| {
+	    let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+	    (percent_decode(key, true).replace('-', "_"), percent_decode(value, true))
+	})
+	.collect()
+}
+
+/// `text` with its `%XX` escapes decoded, and each `+` as a space when
+/// `plus_is_space`, as in forms rather than file URLs.
+pub(crate) fn percent_decode(text: &str, plus_is_space: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+	// Two hex digits exactly; `from_str_radix` alone would take a sign
+	let escaped = text.get(index + 1..index + 3).filter(|hex| hex.bytes().all(|digit| digit.is_ascii_hexdigit()));
+	match bytes[index] {
+	    b'+' if plus_is_space => decoded.push(b' '),
+	    b'%' if let Some(byte) = escaped.and_then(|hex| u8::from_str_radix(hex, 16).ok()) => {
+		decoded.push(byte);
+		index += 2;
+	    }
+	    byte => decoded.push(byte),
+	}
+	index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_pairs_are_decoded() {
+	let pairs = form_pairs("?palette=%23ff0000&message=Hello+w%C3%B6rld&random-spin&&bad=100%");
+	let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+	assert_eq!(pairs, [pair("palette", "#ff0000"), pair("message", "Hello wörld"), pair("random_spin", "true"), pair("bad", "100%")]);
+	assert_eq!(percent_decode("a+b%2B%+1", false), "a+b+%+1");
+    }
+}
@@ -0,0 +1,65 @@
+//! A texture the frame is drawn into on its way to the screen, for the
+//! effects that work on the whole frame at once.
+
+use macroquad::prelude::*;
+
+/// A texture sized for one set of bounds, and the viewport to go back to
+/// once drawing into it ends.
+pub(crate) struct Offscreen {
+    pub(crate) target: RenderTarget,
+    pub(crate) size: (u32, u32),
+    /// Texture pixels per pixel of the bounds.
+    scale: f32,
+    /// The viewport to go back to, which the camera state leaves out.
+    viewport: (i32, i32, i32, i32),
+}
+
+impl Offscreen {
+    /// A texture for `bounds` with `scale` of their pixels, sampled with
+    /// `filter`.
+    pub(crate) fn new(bounds: Rect, scale: f32, filter: FilterMode) -> Self {
+	let size = size_for(bounds, scale);
+	let target = render_target(size.0, size.1);
+	target.texture.set_filter(filter);
+	Self { target, size, scale, viewport: (0, 0, 0, 0) }
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.size == size_for(bounds, self.scale)
+    }
+
+    /// Send what is drawn next, in the coordinates of `bounds`, into the
+    /// texture, as it was left; `end` switches back.
+    pub(crate) fn begin(&mut self, bounds: Rect) {
+	// A preview clips to its viewport
+	self.viewport = unsafe { get_internal_gl() }.quad_gl.get_viewport();
+	push_camera_state();
+	// Set so passes nested inside can tell the texture's size
+	let viewport = Some((0, 0, self.size.0 as i32, self.size.1 as i32));
+	set_camera(&Camera2D { render_target: Some(self.target.clone()), viewport, ..Camera2D::from_display_rect(bounds) });
+    }
+
+    /// Go back to drawing where `begin` left off.
+    pub(crate) fn end(&self) {
+	pop_camera_state();
+	unsafe { get_internal_gl() }.quad_gl.viewport(Some(self.viewport));
+    }
+}
+
+/// The texture size for `bounds` with `scale` of their pixels, at least one
+/// pixel each way.
+fn size_for(bounds: Rect, scale: f32) -> (u32, u32) {
+    ((bounds.w * scale).ceil().max(1.0) as u32, (bounds.h * scale).ceil().max(1.0) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_round_up_to_whole_pixels() {
+	assert_eq!(size_for(Rect::new(10.0, 20.0, 801.0, 600.0), 0.5), (401, 300));
+	assert_eq!(size_for(Rect::new(0.0, 0.0, 0.0, 0.4), 1.0), (1, 1));
+    }
+}
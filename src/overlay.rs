@@ -0,0 +1,90 @@
+//! Faint readouts in a corner of each display, today's weekday and date,
+//! the weather and the track playing, so the screensaver doubles as an
+//! ambient info display.
+
+use crate::clock::local_now;
+use crate::config::Corner;
+use chrono::NaiveDate;
+use macroquad::prelude::*;
+
+/// Opacity of the text, to stay in the background.
+const OPACITY: f32 = 0.35;
+
+/// The date shown and its text, kept until the day changes.
+pub(crate) struct Overlay {
+    date: Option<NaiveDate>,
+    text: String,
+}
+
+impl Overlay {
+    pub(crate) fn new() -> Self {
+	Self { date: None, text: String::new() }
+    }
+
+    /// Today's date, moving on to the new day past midnight.
+    pub(crate) fn date(&mut self) -> &str {
+	let today = local_now().date();
+	if self.date != Some(today) {
+	    self.date = Some(today);
+	    self.text = date_text(today);
+	}
+	&self.text
+    }
+
+    /// Draw `lines`, each with a factor on its opacity, top to bottom in
+    /// `corner` of each of `displays`, dimmed by `brightness`.
+    pub(crate) fn draw(&self, displays: &[Rect], corner: Corner, brightness: f32, lines: &[(&str, f32)]) {
+	for display in displays {
+	    // Small next to the shapes, whatever the display's size
+	    let font_size = (display.h / 30.0).max(8.0).round() as u16;
+	    let dimensions: Vec<_> = lines.iter().map(|(line, _)| measure_text(line, None, font_size, 1.0)).collect();
+	    let line_height = font_size as f32 * 1.2;
+	    let width = dimensions.iter().map(|dimensions| dimensions.width).fold(0.0, f32::max);
+	    let size = Vec2::new(width, line_height * lines.len() as f32);
+	    let top_left = text_position(*display, corner, size, font_size as f32);
+	    for (index, ((line, opacity), dimensions)) in lines.iter().zip(&dimensions).enumerate() {
+		// Lines on the right keep to the right edge
+		let x = match corner {
+		    Corner::TopRight | Corner::BottomRight => top_left.x + width - dimensions.width,
+		    Corner::TopLeft | Corner::BottomLeft => top_left.x,
+		};
+		let color = Color::new(brightness, brightness, brightness, OPACITY * opacity);
+		draw_text(line, x, top_left.y + index as f32 * line_height + dimensions.offset_y, font_size as f32, color);
+	    }
+	}
+    }
+}
+
+/// `date` as the overlay shows it, such as `Tuesday, 14 October 2026`.
+fn date_text(date: NaiveDate) -> String {
+    date.format("%A, %-d %B %Y").to_string()
+}
+
+/// The top left of text `size` pixels big in `corner` of `display`,
+/// `margin` pixels in from both edges.
+fn text_position(display: Rect, corner: Corner, size: Vec2, margin: f32) -> Vec2 {
+    let left = display.left() + margin;
+    let right = display.right() - margin - size.x;
+    let top = display.top() + margin;
+    let bottom = display.bottom() - margin - size.y;
+    match corner {
+	Corner::TopLeft => Vec2::new(left, top),
+	Corner::TopRight => Vec2::new(right, top),
+	Corner::BottomLeft => Vec2::new(left, bottom),
+	Corner::BottomRight => Vec2::new(right, bottom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_read_in_full_in_their_corner() {
+	assert_eq!(date_text(NaiveDate::from_ymd_opt(2026, 10, 4).unwrap()), "Sunday, 4 October 2026");
+	let display = Rect::new(100.0, 0.0, 800.0, 600.0);
+	let size = Vec2::new(200.0, 20.0);
+	assert_eq!(text_position(display, Corner::TopLeft, size, 10.0), Vec2::new(110.0, 10.0));
+	assert_eq!(text_position(display, Corner::BottomRight, size, 10.0), Vec2::new(690.0, 570.0));
+    }
+}
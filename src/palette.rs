@@ -0,0 +1,562 @@
+use crate::config::Config;
+use log::warn;
+use macroquad::color::hsl_to_rgb;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Colors extracted from a `palette_image`.
+const IMAGE_COLORS: usize = 8;
+/// Pixels sampled from a `palette_image`, spread evenly over the image.
+const IMAGE_SAMPLES: usize = 4096;
+
+/// Built-in palettes as `(name, colors)`, colors given as `0xRRGGBB`.
+const PRESETS: &[(&str, &[u32])] = &[
+    ("neon", &[0xff00ff, 0x00ffff, 0x39ff14, 0xffff00, 0xff073a, 0xbc13fe]),
+    ("ocean", &[0x03045e, 0x0077b6, 0x00b4d8, 0x48cae4, 0x90e0ef, 0xcaf0f8]),
+    ("sunset", &[0x355c7d, 0x6c5b7b, 0xc06c84, 0xf67280, 0xf8b195]),
+    ("monochrome", &[0xffffff, 0xcccccc, 0x999999, 0x666666, 0x444444]),
+    ("vaporwave", &[0xff71ce, 0x01cdfe, 0x05ffa1, 0xb967ff, 0xfffb96]),
+    ("winter", &[0xffffff, 0xe0f7ff, 0xa8d8f0, 0x5fa8d3, 0x1b4f8c]),
+    ("valentine", &[0xd00000, 0xff0a54, 0xff477e, 0xff7096, 0xfbb1bd]),
+    ("halloween", &[0xff7518, 0xff9a00, 0xf26419, 0x9d4edd, 0x6a0dad, 0x3c096c]),
+    // Flags, their stripes top to bottom for the `bands` color mode
+    ("rainbow", &[0xe40303, 0xff8c00, 0xffed00, 0x008026, 0x004dff, 0x750787]),
+    ("trans", &[0x5bcefa, 0xf5a9b8, 0xffffff, 0xf5a9b8, 0x5bcefa]),
+    ("bi", &[0xd60270, 0xd60270, 0x9b4f96, 0x0038a8, 0x0038a8]),
+    ("pan", &[0xff218c, 0xffd800, 0x21b1ff]),
+    ("lesbian", &[0xd52d00, 0xef7627, 0xff9a56, 0xffffff, 0xd162a4, 0xb55690, 0xa30262]),
+    ("nonbinary", &[0xfcf434, 0xffffff, 0x9c59d1, 0x2c2c2c]),
+    ("ace", &[0x000000, 0xa3a3a3, 0xffffff, 0x800080]),
+    // Colors that stay apart with a color vision deficiency
+    ("okabe-ito", &[0xe69f00, 0x56b4e9, 0x009e73, 0xf0e442, 0x0072b2, 0xd55e00, 0xcc79a7]),
+    ("deuteranopia", &[0x648fff, 0x785ef0, 0xdc267f, 0xfe6100, 0xffb000]),
+    ("protanopia", &[0x004488, 0x6699cc, 0xddaa33, 0xffee99, 0xeeeeee]),
+    ("tritanopia", &[0xcc0000, 0xff7777, 0x007777, 0x55cccc, 0xffffff]),
+];
+
+/// Hue change per second of a gradient field.
+const GRADIENT_DRIFT: f32 = 1.0 / 60.0;
+/// Furthest a shape's hue strays from the shared one in `synced_cycle`, as
+/// a part of the color wheel.
+const SYNCED_SPREAD: f32 = 0.05;
+/// Seconds a `harmony` takes to turn to its next base hue.
+const HARMONY_TRANSITION: f32 = 10.0;
+
+/// How shapes choose their colors.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Each shape fades between colors picked from the palette.
+    #[default]
+    Random,
+    /// Hue follows a diagonal across each display and drifts over time.
+    Gradient,
+    /// Hue follows the distance from each display's center.
+    Radial,
+    /// Each shape takes the color of the picture under it.
+    Image,
+    /// Each shape takes the color of the webcam's live picture under it.
+    Webcam,
+    /// Stripes of the palette's colors in order, top to bottom of each
+    /// display, like a flag.
+    Bands,
+    /// One hue for every shape, give or take a little, going around the
+    /// color wheel once every `color_cycle_period` seconds.
+    #[serde(rename = "synced_cycle")]
+    SyncedCycle,
+    /// Colors in the `harmony` rule with a random base hue, a new one every
+    /// `harmony_minutes`. `harmony:<rule>` sets both.
+    Harmony,
+}
+
+impl ColorMode {
+    /// Position along the gradient, 0..1, of a point `offset` into a display
+    /// of `size`. Only meaningful for the gradient modes and `bands`.
+    pub(crate) fn gradient_pos(self, offset: Vec2, size: Vec2) -> f32 {
+	let normalized = (offset / size.max(Vec2::ONE)).clamp(Vec2::ZERO, Vec2::ONE);
+	match self {
+	    ColorMode::Random | ColorMode::Image | ColorMode::Webcam | ColorMode::SyncedCycle | ColorMode::Harmony => 0.0,
+	    ColorMode::Gradient => (normalized.x + normalized.y) / 2.0,
+	    ColorMode::Bands => normalized.y,
+	    ColorMode::Radial => (normalized - Vec2::splat(0.5)).length() / 0.5f32.hypot(0.5),
+	}
+    }
+
+    /// Whether hues follow a color field, `gradient` or `radial`.
+    pub(crate) fn is_gradient(self) -> bool {
+	matches!(self, ColorMode::Gradient | ColorMode::Radial)
+    }
+
+    /// Gradient color at `gradient_pos` after `time` seconds of drift.
+    pub(crate) fn gradient_color(gradient_pos: f32, time: f32) -> Color {
+	hsl_to_rgb((gradient_pos + time * GRADIENT_DRIFT).fract(), 0.8, 0.55)
+    }
+
+    /// The `synced_cycle` color after `time` seconds of a cycle lasting
+    /// `period`, of a shape straying by `variation`, -1..1.
+    pub(crate) fn synced_color(variation: f32, time: f32, period: f32) -> Color {
+	hsl_to_rgb((time / period + variation * SYNCED_SPREAD).rem_euclid(1.0), 0.8, 0.55)
+    }
+}
+
+/// How the `harmony` color mode spreads its colors around the color wheel.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Harmony {
+    /// The base hue and the one across from it.
+    #[default]
+    Complementary,
+    /// The base hue and its neighbors on either side.
+    Analogous,
+    /// Three hues a third of the wheel apart.
+    Triadic,
+}
+
+impl Harmony {
+    /// The rule's hues, as parts of the wheel away from the base hue.
+    fn offsets(self) -> &'static [f32] {
+	match self {
+	    Harmony::Complementary => &[0.0, 0.5],
+	    Harmony::Analogous => &[-1.0 / 12.0, 0.0, 1.0 / 12.0],
+	    Harmony::Triadic => &[0.0, 1.0 / 3.0, 2.0 / 3.0],
+	}
+    }
+
+    /// The rule's colors around `base`, a hue 0..1.
+    pub(crate) fn palette(self, base: f32) -> Palette {
+	Palette::from_colors(self.offsets().iter().map(|offset| hsl_to_rgb((base + offset).rem_euclid(1.0), 0.8, 0.55)).collect())
+    }
+
+    /// The base hue after `time` seconds, picked from `seed` anew every
+    /// `period` seconds and turning the short way round to the next one
+    /// at the end of each.
+    pub(crate) fn base_hue(seed: u64, time: f32, period: f32) -> f32 {
+	let hue = |index: u64| -> f32 {
+	    let rng = RandGenerator::new();
+	    rng.srand(seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+	    rng.gen_range(0.0, 1.0)
+	};
+	let index = (time / period).max(0.0) as u64;
+	let (from, to) = (hue(index), hue(index + 1));
+	let transition = HARMONY_TRANSITION.min(period);
+	let t = ((time - (index + 1) as f32 * period + transition) / transition).clamp(0.0, 1.0);
+	let turn = (to - from + 0.5).rem_euclid(1.0) - 0.5;
+	(from + turn * t * t * (3.0 - 2.0 * t)).rem_euclid(1.0)
+    }
+}
+
+/// Colors shapes pick from when they appear and when they change color.
+/// An empty palette picks random colors, of any hue and of saturation and
+/// value within its ranges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Color>,
+    saturation: Vec2,
+    value: Vec2,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+	Self { colors: Vec::new(), saturation: Vec2::new(0.0, 1.0), value: Vec2::new(0.0, 1.0) }
+    }
+}
+
+impl Palette {
+    /// Fully random colors, the original look.
+    pub fn random() -> Self {
+	Self::default()
+    }
+
+    pub fn from_colors(colors: Vec<Color>) -> Self {
+	Self { colors, ..Self::default() }
+    }
+
+    /// Random colors keep their saturation and value between the lowest
+    /// and highest of each range, both 0..1.
+    pub fn with_random_ranges(self, saturation: Vec2, value: Vec2) -> Self {
+	Self { saturation, value, ..self }
+    }
+
+    /// A built-in palette, or `random`.
+    pub fn named(name: &str) -> Option<Self> {
+	if name.eq_ignore_ascii_case("random") {
+	    return Some(Self::random());
+	}
+	let (_, colors) = PRESETS.iter().find(|(preset, _)| preset.eq_ignore_ascii_case(name))?;
+	let colors = colors.iter().map(|&rgb| Color::from_hex(rgb)).collect();
+	Some(Self::from_colors(colors))
+    }
+
+    /// The dominant colors of an image, found by median-cut quantization.
+    pub fn from_image(path: &Path) -> io::Result<Self> {
+	let bytes = fs::read(Config::find_file(path))?;
+	let image = Image::from_file_with_format(&bytes, None)
+	    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+	let pixels: Vec<[u8; 4]> = image.get_image_data().to_vec();
+	let step = (pixels.len() / IMAGE_SAMPLES).max(1);
+	let samples = pixels
+	    .iter()
+	    .step_by(step)
+	    .filter(|pixel| pixel[3] > 0)
+	    .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+	    .collect();
+	let colors = median_cut(samples, IMAGE_COLORS);
+	if colors.is_empty() {
+	    return Err(io::Error::new(io::ErrorKind::InvalidData, "image has no opaque pixels"));
+	}
+	Ok(Self::from_colors(colors))
+    }
+
+    /// Parse a comma separated list of `#rrggbb` or `#rgb` colors, skipping
+    /// invalid entries with a warning. `None` if no entry was valid.
+    pub fn from_hex_list(list: &str) -> Option<Self> {
+	let colors: Vec<Color> = list
+	    .split(',')
+	    .map(str::trim)
+	    .filter(|entry| !entry.is_empty())
+	    .filter_map(|entry| {
+		let color = parse_hex_color(entry);
+		if color.is_none() {
+		    warn!("ignoring invalid palette color `{entry}`");
+		}
+		color
+	    })
+	    .collect();
+	if colors.is_empty() { None } else { Some(Self::from_colors(colors)) }
+    }
+
+    /// A built-in palette name or a list of hex colors, falling back to
+    /// random colors with a warning.
+    pub fn resolve(name: &str) -> Self {
+	let palette = if name.trim_start().starts_with('#') { Self::from_hex_list(name) } else { Self::named(name) };
+	palette.unwrap_or_else(|| {
+	    warn!("unknown palette `{name}`, using random colors");
+	    Self::random()
+	})
+    }
+
+    /// Names accepted by `named`, starting with `random`.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+	std::iter::once("random").chain(PRESETS.iter().map(|(name, _)| *name))
+    }
+
+    pub fn colors(&self) -> &[Color] {
+	&self.colors
+    }
+
+    /// The colors as the list read by `from_hex_list`.
+    pub fn to_hex_list(&self) -> String {
+	self.colors.iter().map(|&color| hex_color(color)).collect::<Vec<_>>().join(",")
+    }
+
+    pub fn pick(&self, rng: &RandGenerator) -> Color {
+	if self.colors.is_empty() {
+	    let range = |range: Vec2| rng.gen_range(range.x, range.y);
+	    return hsv_to_rgb(rng.gen_range(0.0, 1.0), range(self.saturation), range(self.value));
+	}
+	self.colors[rng.gen_range(0, self.colors.len())]
+    }
+
+    /// The color of the stripe at `pos`, 0..1 from top to bottom, the
+    /// random palette striped in six hues.
+    pub(crate) fn band(&self, pos: f32) -> Color {
+	let stripes = if self.colors.is_empty() { 6 } else { self.colors.len() };
+	self.nth(((pos * stripes as f32) as usize).min(stripes - 1))
+    }
+
+    /// The `index`th color, wrapping around; the random palette spreads
+    /// hues by the golden ratio instead.
+    pub(crate) fn nth(&self, index: usize) -> Color {
+	if self.colors.is_empty() {
+	    return hsl_to_rgb((index as f32 * 0.618_034).fract(), 0.8, 0.55);
+	}
+	self.colors[index % self.colors.len()]
+    }
+}
+
+/// Reduce `pixels` to at most `count` colors by repeatedly splitting the
+/// box with the widest channel range at its median, then averaging each box.
+fn median_cut(pixels: Vec<[u8; 3]>, count: usize) -> Vec<Color> {
+    let range = |pixels: &[[u8; 3]], channel: usize| {
+	let (min, max) = pixels.iter().fold((u8::MAX, 0), |(min, max), pixel| (min.min(pixel[channel]), max.max(pixel[channel])));
+	max.saturating_sub(min)
+    };
+    let widest = |pixels: &[[u8; 3]]| (0..3).max_by_key(|&channel| range(pixels, channel)).unwrap();
+
+    let mut boxes = vec![pixels];
+    boxes.retain(|pixels| !pixels.is_empty());
+    while boxes.len() < count {
+	let Some((index, _)) = boxes
+	    .iter()
+	    .enumerate()
+	    .map(|(index, pixels)| (index, range(pixels, widest(pixels))))
+	    .filter(|(_, range)| *range > 0)
+	    .max_by_key(|(_, range)| *range)
+	else {
+	    break;
+	};
+	let mut pixels = boxes.swap_remove(index);
+	let channel = widest(&pixels);
+	pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+	let upper = pixels.split_off(pixels.len() / 2);
+	boxes.push(pixels);
+	boxes.push(upper);
+    }
+
+    boxes
+	.iter()
+	.map(|pixels| {
+	    let sum = pixels.iter().fold([0u32; 3], |sum, pixel| [0, 1, 2].map(|c| sum[c] + pixel[c] as u32));
+	    let [r, g, b] = sum.map(|channel| (channel / pixels.len() as u32) as u8);
+	    Color::from_rgba(r, g, b, 255)
+	})
+	.collect()
+}
+
+/// Parse `#rrggbb` or the `#rgb` shorthand.
+pub(crate) fn parse_hex_color(text: &str) -> Option<Color> {
+    let digits = text.strip_prefix('#')?;
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+	return None;
+    }
+    let rgb = match digits.len() {
+	6 => u32::from_str_radix(digits, 16).ok()?,
+	3 => digits
+	    .chars()
+	    .map(|c| c.to_digit(16).unwrap() * 0x11)
+	    .fold(0, |rgb, channel| rgb << 8 | channel),
+	_ => return None,
+    };
+    Some(Color::from_hex(rgb))
+}
+
+/// The average color of the pixels of `image` within `region`, black where
+/// it reaches past the image.
+pub(crate) fn average_color(image: &Image, region: Rect) -> Color {
+    let (width, height) = (image.width as i32, image.height as i32);
+    let (left, top) = (region.x.floor() as i32, region.y.floor() as i32);
+    let (right, bottom) = ((region.right().ceil() as i32).max(left + 1), (region.bottom().ceil() as i32).max(top + 1));
+    let pixels = image.get_image_data();
+    let mut sum = Vec3::ZERO;
+    for y in top..bottom {
+	for x in left..right {
+	    if (0..width).contains(&x) && (0..height).contains(&y) {
+		let [r, g, b, _] = pixels[(y * width + x) as usize];
+		sum += Vec3::new(r as f32, g as f32, b as f32);
+	    }
+	}
+    }
+    let average = sum / ((right - left) * (bottom - top)) as f32 / 255.0;
+    Color::new(average.x, average.y, average.z, 1.0)
+}
+
+/// Two colors written `#top,#bottom`.
+pub(crate) fn parse_gradient(text: &str) -> Option<(Color, Color)> {
+    let (top, bottom) = text.split_once(',')?;
+    Some((parse_hex_color(top.trim())?, parse_hex_color(bottom.trim())?))
+}
+
+/// Format as `#rrggbb`, dropping alpha.
+pub(crate) fn hex_color(color: Color) -> String {
+    let [r, g, b] = [color.r, color.g, color.b].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+/// Convert hue, saturation and value, each 0..1, to an opaque color.
+pub(crate) fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = hue.rem_euclid(1.0) * 6.0;
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+	0 => (chroma, x, 0.0),
+	1 => (x, chroma, 0.0),
+	2 => (0.0, chroma, x),
+	3 => (0.0, x, chroma),
+	4 => (x, 0.0, chroma),
+	_ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    Color::new(r + m, g + m, b + m, 1.0)
+}
+
+/// The inverse of `hsv_to_rgb`; hue is 0 for grays.
+pub(crate) fn rgb_to_hsv(color: Color) -> (f32, f32, f32) {
+    let max = color.r.max(color.g).max(color.b);
+    let chroma = max - color.r.min(color.g).min(color.b);
+    let hue = if chroma == 0.0 {
+	0.0
+    } else if max == color.r {
+	((color.g - color.b) / chroma).rem_euclid(6.0)
+    } else if max == color.g {
+	(color.b - color.r) / chroma + 2.0
+    } else {
+	(color.r - color.g) / chroma + 4.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+    (hue / 6.0, saturation, max)
+}
+
+/// A color vision deficiency the settings preview can simulate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Deficiency {
+    None,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Deficiency {
+    pub(crate) const ALL: [Deficiency; 4] = [Self::None, Self::Deuteranopia, Self::Protanopia, Self::Tritanopia];
+
+    pub(crate) fn label(self) -> &'static str {
+	match self {
+	    Self::None => "Normal vision",
+	    Self::Deuteranopia => "Deuteranopia",
+	    Self::Protanopia => "Protanopia",
+	    Self::Tritanopia => "Tritanopia",
+	}
+    }
+
+    /// How the deficiency mixes linear red, green and blue, row by row,
+    /// from Machado et al. (2009) at full severity.
+    fn matrix(self) -> Option<[[f32; 3]; 3]> {
+	match self {
+	    Self::None => None,
+	    Self::Deuteranopia => Some([[0.367322, 0.860646, -0.227968], [0.280085, 0.672501, 0.047413], [-0.011820, 0.042940, 0.968881]]),
+	    Self::Protanopia => Some([[0.152286, 1.052583, -0.204868], [0.114503, 0.786281, 0.099216], [-0.003882, -0.048116, 1.051998]]),
+	    Self::Tritanopia => Some([[1.255528, -0.076749, -0.178779], [-0.078411, 0.930809, 0.147602], [0.004733, 0.691367, 0.303900]]),
+	}
+    }
+}
+
+/// `color` as it looks with `deficiency`.
+pub(crate) fn simulate(color: Color, deficiency: Deficiency) -> Color {
+    let Some(matrix) = deficiency.matrix() else {
+	return color;
+    };
+    let linear = [color.r, color.g, color.b].map(|channel| channel.clamp(0.0, 1.0).powf(2.2));
+    let [r, g, b] = matrix.map(|row| (row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]).clamp(0.0, 1.0).powf(1.0 / 2.2));
+    Color::new(r, g, b, color.a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_color_counts_pixels_past_the_edge_as_black() {
+	let mut image = Image::gen_image_color(4, 2, Color::from_rgba(200, 100, 0, 255));
+	image.set_pixel(0, 0, WHITE);
+	let color = average_color(&image, Rect::new(0.0, 0.0, 2.0, 1.0));
+	assert_eq!(color, Color::new(227.5 / 255.0, 177.5 / 255.0, 127.5 / 255.0, 1.0));
+	let half = average_color(&image, Rect::new(3.0, 0.0, 2.0, 2.0));
+	assert_eq!(half, Color::new(100.0 / 255.0, 50.0 / 255.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn presets_only_pick_their_own_colors() {
+	let rng = RandGenerator::new();
+	for name in Palette::names().skip(1) {
+	    let palette = Palette::named(name).unwrap();
+	    assert!(!palette.colors().is_empty());
+	    for _ in 0..20 {
+		assert!(palette.colors().contains(&palette.pick(&rng)), "{name}");
+	    }
+	}
+	assert_eq!(Palette::named("Random"), Some(Palette::random()));
+	assert_eq!(Palette::named("plaid"), None);
+    }
+
+    #[test]
+    fn harmonies_hold_their_hue_then_turn_smoothly() {
+	let hue = |time| Harmony::base_hue(5, time, 60.0);
+	assert_eq!(hue(0.0), hue(49.0));
+	assert_ne!(hue(49.0), hue(61.0));
+	assert_eq!(hue(60.0), hue(61.0));
+	// No jumps on the way
+	let apart = |a: f32, b: f32| (a - b).abs().min(1.0 - (a - b).abs());
+	assert!((490..600).all(|step| apart(hue(step as f32 / 10.0), hue((step + 1) as f32 / 10.0)) < 0.02));
+	let triad: Vec<f32> = Harmony::Triadic.palette(0.1).colors().iter().map(|color| rgb_to_hsv(*color).0).collect();
+	assert!(triad.iter().zip([0.1, 0.1 + 1.0 / 3.0, 0.1 + 2.0 / 3.0]).all(|(hue, expected)| (hue - expected).abs() < 1e-3), "{triad:?}");
+    }
+
+    #[test]
+    fn synced_hues_stay_together_as_they_cycle() {
+	let hue = |color| rgb_to_hsv(color).0;
+	let apart = |a: f32, b: f32| (a - b).abs().min(1.0 - (a - b).abs());
+	for time in [0.0, 10.0, 59.0] {
+	    assert!(apart(hue(ColorMode::synced_color(-1.0, time, 60.0)), hue(ColorMode::synced_color(1.0, time, 60.0))) <= 2.0 * SYNCED_SPREAD + 1e-3);
+	}
+	// Half a cycle later, across the wheel
+	assert!((apart(hue(ColorMode::synced_color(0.0, 0.0, 60.0)), hue(ColorMode::synced_color(0.0, 30.0, 60.0))) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn random_colors_keep_to_their_ranges() {
+	let rng = RandGenerator::new();
+	let pastel = Palette::random().with_random_ranges(Vec2::new(0.2, 0.4), Vec2::new(0.9, 1.0));
+	for _ in 0..100 {
+	    let (_, saturation, value) = rgb_to_hsv(pastel.pick(&rng));
+	    assert!((0.2 - 1e-4..=0.4 + 1e-4).contains(&saturation) && (0.9 - 1e-4..=1.0 + 1e-4).contains(&value));
+	}
+    }
+
+    #[test]
+    fn simulated_deficiencies_confuse_their_colors() {
+	let distance = |a: Color, b: Color| Vec3::new(a.r - b.r, a.g - b.g, a.b - b.b).length();
+	for deficiency in Deficiency::ALL {
+	    assert!(distance(simulate(WHITE, deficiency), WHITE) < 1e-3, "{deficiency:?}");
+	}
+	assert_eq!(simulate(RED, Deficiency::None), RED);
+	// Red and green run together without red or green cones, blue and
+	// green without blue ones
+	let (red, green, blue) = (Color::new(0.8, 0.2, 0.1, 1.0), Color::new(0.4, 0.6, 0.1, 1.0), Color::new(0.1, 0.5, 0.8, 1.0));
+	for deficiency in [Deficiency::Deuteranopia, Deficiency::Protanopia] {
+	    assert!(distance(simulate(red, deficiency), simulate(green, deficiency)) < distance(red, green) * 0.6);
+	}
+	assert!(distance(simulate(blue, Deficiency::Tritanopia), simulate(green, Deficiency::Tritanopia)) < distance(blue, green));
+    }
+
+    #[test]
+    fn bands_stripe_the_palette_in_order() {
+	let pan = Palette::named("pan").unwrap();
+	let bands: Vec<Color> = [0.0, 0.3, 0.5, 0.99, 1.0].into_iter().map(|pos| pan.band(pos)).collect();
+	assert_eq!(bands, [0xff218c, 0xff218c, 0xffd800, 0x21b1ff, 0x21b1ff].map(Color::from_hex));
+	assert_eq!(ColorMode::Bands.gradient_pos(Vec2::new(50.0, 75.0), Vec2::new(100.0, 100.0)), 0.75);
+    }
+
+    #[test]
+    fn hex_palettes_skip_invalid_colors() {
+	let palette = Palette::resolve("#ff0066, #0fc,nope,#12345,#ffffff");
+	assert_eq!(palette.colors(), &[Color::from_hex(0xff0066), Color::from_hex(0x00ffcc), WHITE]);
+	assert_eq!(Palette::resolve("#zzz"), Palette::random());
+    }
+
+    #[test]
+    fn hsv_and_hex_round_trip() {
+	for rgb in [0xff0066, 0x00ffcc, 0x123456, 0x808080, 0x000000, 0xffffff] {
+	    let color = Color::from_hex(rgb);
+	    let (hue, saturation, value) = rgb_to_hsv(color);
+	    assert_eq!(hex_color(hsv_to_rgb(hue, saturation, value)), format!("#{rgb:06x}"));
+	}
+	assert_eq!(hex_color(hsv_to_rgb(1.0 / 3.0, 1.0, 1.0)), "#00ff00");
+	let palette = Palette::resolve("#ff0066,#0fc");
+	assert_eq!(palette.to_hex_list(), "#ff0066,#00ffcc");
+    }
+
+    #[test]
+    fn median_cut_finds_dominant_colors() {
+	let mut pixels = vec![[250, 10, 10]; 50];
+	pixels.extend(vec![[10, 10, 250]; 50]);
+	let mut colors = median_cut(pixels, 8);
+	colors.sort_by(|a, b| a.r.total_cmp(&b.r));
+	// Uniform boxes are never split, so two colors stay two colors
+	assert_eq!(colors, vec![Color::from_rgba(10, 10, 250, 255), Color::from_rgba(250, 10, 10, 255)]);
+	assert_eq!(median_cut(vec![[1, 2, 3]; 10], 8).len(), 1);
+	assert!(median_cut(Vec::new(), 8).is_empty());
+    }
+}
@@ -0,0 +1,130 @@
+//! The prompt `exit_passphrase` puts in front of quitting: input opens it
+//! over the running animation, and only the passphrase gets past it.
+
+use macroquad::prelude::*;
+
+/// Seconds without typing before the prompt closes again.
+const PROMPT_TIMEOUT: f32 = 15.0;
+/// Longest wait after wrong passphrases, in seconds.
+const MAX_LOCKOUT: f32 = 60.0;
+/// Size of the prompt box, in display heights.
+const PROMPT_SIZE: Vec2 = Vec2::new(0.5, 0.12);
+
+/// Seconds to wait after `failures` wrong passphrases in a row, doubling
+/// from one.
+fn lockout(failures: u32) -> f32 {
+    match failures {
+	0 => 0.0,
+	failures => 2f32.powi(failures.min(16) as i32 - 1).min(MAX_LOCKOUT),
+    }
+}
+
+/// What was typed and how long the prompt has to wait.
+#[derive(Default)]
+pub(crate) struct Prompt {
+    open: bool,
+    typed: String,
+    failures: u32,
+    locked_for: f32,
+    idle: f32,
+}
+
+impl Prompt {
+    pub(crate) fn open(&mut self) {
+	self.open = true;
+	self.idle = 0.0;
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+	self.open
+    }
+
+    /// Close the prompt and forget what was typed, keeping any lockout.
+    pub(crate) fn close(&mut self) {
+	self.open = false;
+	self.typed.clear();
+    }
+
+    /// Count down the lockout, and close once left alone for a while.
+    pub(crate) fn step(&mut self, dt: f32) {
+	self.locked_for = (self.locked_for - dt).max(0.0);
+	self.idle += dt;
+	if self.open && self.idle >= PROMPT_TIMEOUT {
+	    self.close();
+	}
+    }
+
+    /// Take `key` typed into the prompt, ignored while locked out.
+    pub(crate) fn type_char(&mut self, key: char) {
+	self.idle = 0.0;
+	if self.locked_for == 0.0 && !key.is_control() {
+	    self.typed.push(key);
+	}
+    }
+
+    pub(crate) fn backspace(&mut self) {
+	self.idle = 0.0;
+	self.typed.pop();
+    }
+
+    /// Check what was typed against `passphrase`, true when it matches. A
+    /// wrong one locks the prompt for longer each time.
+    pub(crate) fn submit(&mut self, passphrase: &str) -> bool {
+	self.idle = 0.0;
+	if self.locked_for > 0.0 || self.typed.is_empty() {
+	    return false;
+	}
+	let typed = std::mem::take(&mut self.typed);
+	if typed == passphrase {
+	    self.failures = 0;
+	    return true;
+	}
+	self.failures += 1;
+	self.locked_for = lockout(self.failures);
+	false
+    }
+
+    /// Draw the prompt in the middle of each of `displays`.
+    pub(crate) fn draw(&self, displays: &[Rect]) {
+	if !self.open {
+	    return;
+	}
+	let text = if self.locked_for > 0.0 {
+	    format!("Wrong passphrase, try again in {}s", self.locked_for.ceil())
+	} else {
+	    format!("Passphrase: {}", "*".repeat(self.typed.chars().count()))
+	};
+	for display in displays {
+	    let size = PROMPT_SIZE * display.h;
+	    let corner = display.center() - size / 2.0;
+	    draw_rectangle(corner.x, corner.y, size.x, size.y, Color::new(0.0, 0.0, 0.0, 0.8));
+	    draw_rectangle_lines(corner.x, corner.y, size.x, size.y, 2.0, GRAY);
+	    let font_size = (size.y / 3.0).round() as u16;
+	    let dimensions = measure_text(&text, None, font_size, 1.0);
+	    let x = display.center().x - dimensions.width / 2.0;
+	    draw_text(&text, x, display.center().y - dimensions.height / 2.0 + dimensions.offset_y, font_size as f32, WHITE);
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrong_passphrases_lock_the_prompt_for_longer() {
+	assert_eq!([0, 1, 2, 3, 10].map(lockout), [0.0, 1.0, 2.0, 4.0, MAX_LOCKOUT]);
+	let mut prompt = Prompt::default();
+	prompt.open();
+	"guess".chars().for_each(|key| prompt.type_char(key));
+	assert!(!prompt.submit("secret"));
+	// Typing is ignored until the lockout runs out
+	"secret".chars().for_each(|key| prompt.type_char(key));
+	assert!(!prompt.submit("secret"));
+	prompt.step(1.0);
+	"secret".chars().for_each(|key| prompt.type_char(key));
+	assert!(prompt.submit("secret"));
+	prompt.step(PROMPT_TIMEOUT);
+	assert!(!prompt.is_open());
+    }
+}
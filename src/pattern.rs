@@ -0,0 +1,323 @@
+//! How shapes time their blinking.
+
+use crate::automaton::Automaton;
+use crate::config::Config;
+use crate::monitor::Monitor;
+use crate::palette::{Palette, parse_hex_color};
+use crate::rain::Rain;
+use crate::reaction::Reaction;
+use crate::script::Scripted;
+use crate::shapes::Shape;
+use crate::turmite::Turmites;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// Radians per second the wave direction turns.
+const WAVE_TURN: f32 = 0.01;
+/// Radians per second the `random` and `wave` patterns advance at `speed`
+/// 1: the pace of the full grid when `threshold` still set it.
+pub(crate) const TWINKLE_SPEED: f32 = 10.0;
+/// Distance between wave crests, in shape radii.
+const WAVELENGTH: f32 = 12.0;
+/// Seconds between new ripples.
+const RIPPLE_INTERVAL: f32 = 3.0;
+/// Width of a ripple's ring, in shape radii.
+const RING_WIDTH: f32 = 3.0;
+/// Ripples are dropped once their ring this far out, in pixels.
+const RIPPLE_REACH: f32 = 10000.0;
+/// Colors of idle and fully busy columns in the `monitor` pattern.
+const IDLE_COLOR: Color = Color::new(0.2, 0.75, 0.4, 1.0);
+const BUSY_COLOR: Color = Color::new(1.0, 0.25, 0.2, 1.0);
+
+/// What times each shape's opacity wave.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    /// Each shape twinkles on its own random phase.
+    #[default]
+    Random,
+    /// Lit bands sweep across the screen along a slowly turning direction.
+    Wave,
+    /// Rings spread out from a random shape every few seconds, at
+    /// `ripple_speed` and fading by `ripple_decay`.
+    Ripple,
+    /// Cloud-like regions, `noise_scale` shape radii across, light up and
+    /// fade at `noise_speed`.
+    Noise,
+    /// Shapes are the cells of the cellular `automaton`, stepped every
+    /// `life_tick` seconds.
+    Life,
+    /// `ants` turmites walk the cells, each turning by `ant_rule` and
+    /// recoloring the cells it leaves behind.
+    Ant,
+    /// Shapes show a Gray-Scott reaction-diffusion simulation, lit and
+    /// colored by how much of its second chemical they are over. The
+    /// `reaction` preset, or `feed` and `kill` rates, shape its patterns.
+    Reaction,
+    /// Drops in `rain_color` fall down the columns, leaving fading trails.
+    Rain,
+    /// Columns across each display show the load of each CPU core and,
+    /// last, of memory, lit brighter and redder the busier they are.
+    Monitor,
+    /// The `script` file in the config directory lights, colors and
+    /// sizes each shape.
+    Script,
+}
+
+impl Pattern {
+    /// The opacity wave of `shape`, the `index`th one, at `time`, -1..1; it
+    /// shows above `config.threshold()`.
+    pub(crate) fn wave(self, shape: &Shape, index: usize, time: f32, config: &Config, state: &PatternState) -> f32 {
+	let phase = match self {
+	    Pattern::Random => shape.phase_offset * config.twinkle_stagger,
+	    Pattern::Wave => {
+		let direction = Vec2::from_angle(time * WAVE_TURN);
+		-direction.dot(Vec2::new(shape.x, shape.y)) / (shape.radius * WAVELENGTH) * 2. * PI * config.twinkle_stagger
+	    }
+	    Pattern::Ripple => return state.ripples.level(shape, time, config) * 2. - 1.,
+	    Pattern::Life => return state.automaton.level(index) * 2. - 1.,
+	    Pattern::Ant => return state.turmites.level(index) * 2. - 1.,
+	    Pattern::Reaction => return state.reaction.level(index) * 2. - 1.,
+	    Pattern::Rain => return state.rain.level(index) * 2. - 1.,
+	    Pattern::Monitor | Pattern::Script => {
+		// Past the threshold by the load, so it shows at any density
+		let level = match self {
+		    Pattern::Monitor => state.monitor.level(index),
+		    _ => state.script.output(index).map_or(0.0, |output| output.opacity),
+		};
+		let threshold = config.threshold();
+		return threshold + level * (1.0 - threshold);
+	    }
+	    Pattern::Noise => {
+		let scale = shape.radius * config.noise_scale;
+		// Perlin noise rarely gets near ±1; stretch it so high thresholds still show
+		return (noise(Vec3::new(shape.x / scale, shape.y / scale, time * config.noise_speed)) * 1.5).clamp(-1.0, 1.0);
+	    }
+	};
+	(time * TWINKLE_SPEED + phase).sin()
+    }
+
+    /// When `shape`, hidden at `time`, next rises above the threshold, for
+    /// patterns where that can be worked out ahead; `time` itself while it
+    /// is within the part of its wave that shows.
+    pub(crate) fn next_visible(self, shape: &Shape, time: f32, config: &Config) -> Option<f32> {
+	if self != Pattern::Random {
+	    return None;
+	}
+	let threshold = config.threshold();
+	if threshold >= 1.0 {
+	    return Some(f32::INFINITY);
+	}
+	// The sine is above the threshold from its rise to PI minus that
+	let rise = threshold.asin();
+	let since_rise = (time * TWINKLE_SPEED + shape.phase_offset * config.twinkle_stagger - rise).rem_euclid(2. * PI);
+	if since_rise <= PI - 2. * rise {
+	    return Some(time);
+	}
+	Some(time + (2. * PI - since_rise) / TWINKLE_SPEED)
+    }
+}
+
+/// 3D Perlin noise, about -1..1, zero on integer points.
+pub(crate) fn noise(point: Vec3) -> f32 {
+    let cell = point.floor();
+    let offset = point - cell;
+    let fade = offset * offset * offset * (offset * (offset * 6.0 - 15.0) + 10.0);
+    let corner = |dx: i32, dy: i32, dz: i32| {
+	let hash = lattice_hash(cell.x as i32 + dx, cell.y as i32 + dy, cell.z as i32 + dz);
+	// One of the twelve edge directions of a cube
+	let [a, b, c] = [offset.x - dx as f32, offset.y - dy as f32, offset.z - dz as f32];
+	let (u, v) = match hash % 12 / 4 {
+	    0 => (a, b),
+	    1 => (a, c),
+	    _ => (b, c),
+	};
+	let u = if hash & 1 == 0 { u } else { -u };
+	let v = if hash & 2 == 0 { v } else { -v };
+	u + v
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let x = |dy, dz| lerp(corner(0, dy, dz), corner(1, dy, dz), fade.x);
+    let y = |dz| lerp(x(0, dz), x(1, dz), fade.y);
+    lerp(y(0), y(1), fade.z)
+}
+
+/// Pseudo-random bits for a noise lattice point.
+fn lattice_hash(x: i32, y: i32, z: i32) -> u32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6_b343) ^ (y as u32).wrapping_mul(0xd816_3841) ^ (z as u32).wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^ hash >> 15
+}
+
+/// What the blink patterns keep between frames.
+#[derive(Default)]
+pub(crate) struct PatternState {
+    ripples: Ripples,
+    automaton: Automaton,
+    turmites: Turmites,
+    reaction: Reaction,
+    rain: Rain,
+    monitor: Monitor,
+    script: Scripted,
+}
+
+impl PatternState {
+    /// Advance the pattern selected by `config` to `time`, `dt` seconds on.
+    pub(crate) fn update(&mut self, shapes: &mut [Shape], time: f32, dt: f32, config: &Config, palette: &Palette, rng: &RandGenerator) {
+	self.ripples.update(shapes, time, config, rng);
+	self.automaton.update(shapes, dt, config, rng);
+	self.turmites.update(shapes, dt, config, palette, rng);
+	self.reaction.update(shapes, dt, config, rng);
+	self.rain.update(shapes, dt, config, rng);
+	self.monitor.update(shapes, dt, config);
+	self.script.update(shapes, time, config);
+    }
+
+    /// The color the selected pattern gives the `index`th shape, if any.
+    /// Reactions blend between the first two `palette` colors, rain
+    /// whitens toward the drops' heads, the monitor reddens with load and
+    /// scripts may pick their own.
+    pub(crate) fn color(&self, index: usize, config: &Config, palette: &Palette) -> Option<Color> {
+	let blend = |low: Color, high: Color, level: f32| {
+	    Color::new(low.r + (high.r - low.r) * level, low.g + (high.g - low.g) * level, low.b + (high.b - low.b) * level, 1.0)
+	};
+	match config.pattern {
+	    Pattern::Reaction => Some(blend(palette.nth(0), palette.nth(1), self.reaction.level(index))),
+	    Pattern::Rain => {
+		let color = parse_hex_color(&config.rain_color).unwrap_or(GREEN);
+		Some(blend(color, WHITE, ((self.rain.level(index) - 0.85) / 0.15).max(0.0)))
+	    }
+	    Pattern::Monitor => Some(blend(IDLE_COLOR, BUSY_COLOR, self.monitor.level(index))),
+	    Pattern::Script => self.script.output(index).and_then(|output| output.color),
+	    _ => None,
+	}
+    }
+
+    /// How much the selected pattern scales the `index`th shape.
+    pub(crate) fn scale(&self, index: usize, config: &Config) -> f32 {
+	match config.pattern {
+	    Pattern::Script => self.script.output(index).map_or(1.0, |output| output.scale),
+	    _ => 1.0,
+	}
+    }
+}
+
+/// The ripples spreading in the `ripple` pattern.
+#[derive(Default)]
+struct Ripples {
+    /// Center and start time of each ripple.
+    origins: Vec<(Vec2, f32)>,
+    next_start: f32,
+}
+
+impl Ripples {
+    /// Start ripples at random `shapes` as they become due at `time`, and
+    /// drop the ones that faded or left the screen.
+    fn update(&mut self, shapes: &[Shape], time: f32, config: &Config, rng: &RandGenerator) {
+	if config.pattern != Pattern::Ripple || shapes.is_empty() {
+	    self.origins.clear();
+	    return;
+	}
+	if time >= self.next_start {
+	    let origin = &shapes[rng.gen_range(0, shapes.len())];
+	    self.origins.push((Vec2::new(origin.x, origin.y), time));
+	    self.next_start = time + RIPPLE_INTERVAL;
+	}
+	let speed = config.ripple_speed * shapes[0].radius;
+	self.origins.retain(|(_, start)| {
+	    let age = time - start;
+	    (-config.ripple_decay * age).exp() > 0.01 && age * speed < RIPPLE_REACH
+	});
+    }
+
+    /// How lit `shape` is by the rings passing it, 0..1.
+    fn level(&self, shape: &Shape, time: f32, config: &Config) -> f32 {
+	let pos = Vec2::new(shape.x, shape.y);
+	self.origins
+	    .iter()
+	    .map(|(origin, start)| {
+		let age = time - start;
+		let ring = age * config.ripple_speed * shape.radius;
+		let offset = (pos.distance(*origin) - ring) / (shape.radius * RING_WIDTH);
+		if offset.abs() < 1.0 { (offset * PI / 2.).cos() * (-config.ripple_decay * age).exp() } else { 0.0 }
+	    })
+	    .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+    use macroquad::rand::RandGenerator;
+
+    #[test]
+    fn waves_line_shapes_up_across_the_direction() {
+	let rng = RandGenerator::new();
+	let shape_at = |x, y| Shape::new(x, y, 10.0, false, &Palette::random(), &rng);
+	let config = Config { density: 0.5, ..Default::default() };
+	let wave = |shape: &Shape| Pattern::Wave.wave(shape, 0, 0.0, &config, &PatternState::default());
+	// At time 0 the wave travels along x, so columns share a phase
+	assert!((wave(&shape_at(30.0, 0.0)) - wave(&shape_at(30.0, 500.0))).abs() < 1e-4);
+	// and half a wavelength along it is the opposite phase
+	assert!((wave(&shape_at(30.0, 0.0)) + wave(&shape_at(90.0, 0.0))).abs() < 1e-4);
+
+	// Without stagger everything pulses together
+	let config = Config { twinkle_stagger: 0.0, ..config };
+	for pattern in [Pattern::Wave, Pattern::Random] {
+	    let wave = |shape: &Shape| pattern.wave(shape, 0, 2.0, &config, &PatternState::default());
+	    assert_eq!(wave(&shape_at(30.0, 0.0)), wave(&shape_at(90.0, 40.0)));
+	}
+    }
+
+    #[test]
+    fn next_visible_finds_the_rise_through_the_threshold() {
+	let rng = RandGenerator::new();
+	let config = Config { density: 0.1, ..Default::default() };
+	let state = PatternState::default();
+	for phase in [0.0, 1.0, 2.5, 4.0, 6.0] {
+	    let mut shape = Shape::new(0.0, 0.0, 10.0, false, &Palette::random(), &rng);
+	    shape.phase_offset = phase;
+	    let start = 3.0;
+	    let rise = Pattern::Random.next_visible(&shape, start, &config).unwrap();
+	    let wave = |time| Pattern::Random.wave(&shape, 0, time, &config, &state);
+	    if rise == start {
+		assert!(wave(start) >= config.threshold() - 1e-4);
+		continue;
+	    }
+	    assert!(rise > start && rise < start + 2. * PI / TWINKLE_SPEED);
+	    assert!((wave(rise) - config.threshold()).abs() < 1e-3);
+	    assert!(wave(rise + 0.05 / TWINKLE_SPEED) > config.threshold());
+	    assert!((0..100).all(|step| wave(start + (rise - start) * step as f32 / 100.0) <= config.threshold() + 1e-3));
+	}
+    }
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+	let mut previous = noise(Vec3::new(0.3, 7.1, 2.0));
+	for step in 1..2000 {
+	    let value = noise(Vec3::new(0.3 + step as f32 * 0.01, 7.1, 2.0 + step as f32 * 0.003));
+	    assert!(value.abs() <= 1.0);
+	    assert!((value - previous).abs() < 0.05);
+	    previous = value;
+	}
+	assert_eq!(noise(Vec3::new(3.0, -2.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn ripples_reach_far_shapes_later() {
+	let rng = RandGenerator::new();
+	let config = Config { pattern: Pattern::Ripple, ripple_speed: 8.0, ripple_decay: 0.1, ..Default::default() };
+	let origin = Shape::new(0.0, 0.0, 10.0, false, &Palette::random(), &rng);
+	let far = Shape::new(200.0, 0.0, 10.0, false, &Palette::random(), &rng);
+	let mut ripples = Ripples::default();
+	ripples.update(std::slice::from_ref(&origin), 0.0, &config, &rng);
+	// The ring moves 80 pixels a second
+	assert!(ripples.level(&origin, 0.0, &config) > 0.9);
+	assert_eq!(ripples.level(&far, 0.5, &config), 0.0);
+	assert!(ripples.level(&far, 2.5, &config) > 0.5);
+    }
+}
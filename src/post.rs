@@ -0,0 +1,317 @@
+//! Effects applied to the whole finished frame.
+
+use crate::config::Config;
+use crate::offscreen::Offscreen;
+use crate::palette::parse_hex_color;
+use macroquad::miniquad::{PipelineParams, UniformType};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A look given to the whole frame.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PostEffect {
+    #[default]
+    None,
+    /// An old tube screen: scanlines, a bulging picture and color fringes
+    /// toward the edges.
+    Crt,
+    /// The four greens of the original Game Boy, by lightness.
+    Gameboy,
+    /// The 16 colors of the Commodore 64.
+    C64,
+    /// The 16 colors of EGA graphics.
+    Ega,
+    /// A fragment shader of the user's, written `file:NAME.glsl` and looked
+    /// up in the config directory. It gets the frame as `Texture` at `uv`,
+    /// what it drew the frame before as `previous`, and the seconds since
+    /// it started and the size of the frame in pixels as `time` and
+    /// `resolution`.
+    File(String),
+}
+
+/// The effects with a name of their own.
+const NAMED: [(PostEffect, &str); 5] =
+    [(PostEffect::None, "none"), (PostEffect::Crt, "crt"), (PostEffect::Gameboy, "gameboy"), (PostEffect::C64, "c64"), (PostEffect::Ega, "ega")];
+
+impl TryFrom<String> for PostEffect {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+	if let Some(file) = name.strip_prefix("file:") {
+	    return Ok(PostEffect::File(file.to_string()));
+	}
+	NAMED
+	    .into_iter()
+	    .find(|(_, other)| *other == name)
+	    .map(|(effect, _)| effect)
+	    .ok_or_else(|| format!("unknown post effect `{name}`, expected none, crt, gameboy, c64, ega or file:NAME.glsl"))
+    }
+}
+
+impl From<PostEffect> for String {
+    fn from(effect: PostEffect) -> String {
+	match effect {
+	    PostEffect::File(file) => format!("file:{file}"),
+	    named => NAMED.into_iter().find(|(effect, _)| *effect == named).map_or_else(String::new, |(_, name)| name.to_string()),
+	}
+    }
+}
+
+impl PostEffect {
+    /// The colors of a palette effect, `#rrggbb`.
+    fn palette(&self) -> &'static [&'static str] {
+	match self {
+	    PostEffect::None | PostEffect::Crt | PostEffect::File(_) => &[],
+	    PostEffect::Gameboy => &["#0f380f", "#306230", "#8bac0f", "#9bbc0f"],
+	    PostEffect::C64 => &[
+		"#000000", "#ffffff", "#880000", "#aaffee", "#cc44cc", "#00cc55", "#0000aa", "#eeee77",
+		"#dd8855", "#664400", "#ff7777", "#333333", "#777777", "#aaff66", "#0088ff", "#bbbbbb",
+	    ],
+	    PostEffect::Ega => &[
+		"#000000", "#0000aa", "#00aa00", "#00aaaa", "#aa0000", "#aa00aa", "#aa5500", "#aaaaaa",
+		"#555555", "#5555ff", "#55ff55", "#55ffff", "#ff5555", "#ff55ff", "#ffff55", "#ffffff",
+	    ],
+	}
+    }
+}
+
+/// Most colors a palette effect can have.
+const MAX_PALETTE: usize = 16;
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying lowp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}
+"#;
+
+const CRT_FRAGMENT_SHADER: &str = r#"#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 resolution;
+
+void main() {
+    // Sampling further out toward the edges bulges the picture
+    vec2 centered = uv * 2.0 - 1.0;
+    centered *= 1.0 + dot(centered, centered) * 0.04;
+    vec2 curved = centered * 0.5 + 0.5;
+    if (curved.x < 0.0 || curved.x > 1.0 || curved.y < 0.0 || curved.y > 1.0) {
+	gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+	return;
+    }
+    vec2 fringe = centered * 0.003;
+    vec3 color = vec3(
+	texture2D(Texture, curved + fringe).r,
+	texture2D(Texture, curved).g,
+	texture2D(Texture, curved - fringe).b
+    );
+    // A darker line every other pixel row
+    float scanline = 0.75 + 0.25 * sin(curved.y * resolution.y * 3.14159265);
+    gl_FragColor = vec4(color * scanline, 1.0);
+}
+"#;
+
+// Dithers with a 4x4 Bayer matrix, then picks the nearest palette color, or
+// the one of matching lightness for palettes of shades
+const PALETTE_FRAGMENT_SHADER: &str = r#"#version 100
+precision mediump float;
+
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 resolution;
+uniform vec3 colors[16];
+uniform float count;
+uniform float shades;
+
+float bayer2(vec2 a) {
+    a = floor(a);
+    return fract(dot(a, vec2(0.5, a.y * 0.75)));
+}
+
+void main() {
+    vec3 color = texture2D(Texture, uv).rgb;
+    vec2 pixel = floor(uv * resolution);
+    float dither = bayer2(pixel * 0.5) * 0.25 + bayer2(pixel) - 0.5;
+    vec3 picked = colors[0];
+    if (shades > 0.5) {
+	float lightness = dot(color, vec3(0.299, 0.587, 0.114));
+	float index = clamp(floor(lightness * (count - 1.0) + 0.5 + dither), 0.0, count - 1.0);
+	for (int i = 0; i < 16; i++) {
+	    if (float(i) == index) {
+		picked = colors[i];
+	    }
+	}
+    } else {
+	color += dither * 0.25;
+	float nearest = 100.0;
+	for (int i = 0; i < 16; i++) {
+	    vec3 offset = colors[i] - color;
+	    float distance = dot(offset, offset);
+	    if (float(i) < count && distance < nearest) {
+		nearest = distance;
+		picked = colors[i];
+	    }
+	}
+    }
+    gl_FragColor = vec4(picked, 1.0);
+}
+"#;
+
+/// The texture a frame is drawn into and the shader drawing it out.
+pub(crate) struct PostPass {
+    effect: PostEffect,
+    offscreen: Offscreen,
+    material: Material,
+    /// What a file's shader drew this frame and the one before, taking
+    /// turns, and the one drawn last.
+    history: Option<([Offscreen; 2], usize)>,
+    started: f64,
+}
+
+impl PostPass {
+    /// A pass drawing `effect` over `bounds`, or the reason its shader
+    /// could not be read or built. `PostEffect::None` has no pass.
+    pub(crate) fn new(effect: &PostEffect, bounds: Rect) -> Result<Self, String> {
+	let source;
+	let (fragment, uniforms) = match effect {
+	    PostEffect::None => return Err("no effect".to_string()),
+	    PostEffect::Crt => (CRT_FRAGMENT_SHADER, Vec::new()),
+	    PostEffect::File(file) => {
+		let path = Config::find_file(Path::new(file));
+		source = std::fs::read_to_string(&path).map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+		(source.as_str(), vec![UniformDesc::new("time", UniformType::Float1)])
+	    }
+	    PostEffect::Gameboy | PostEffect::C64 | PostEffect::Ega => (
+		PALETTE_FRAGMENT_SHADER,
+		palette_uniforms(),
+	    ),
+	};
+	let file = matches!(effect, PostEffect::File(_));
+	let material = load_material(
+	    ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment },
+	    MaterialParams {
+		uniforms: [UniformDesc::new("resolution", UniformType::Float2)].into_iter().chain(uniforms).collect(),
+		textures: if file { vec!["previous".to_string()] } else { Vec::new() },
+		pipeline_params: PipelineParams::default(),
+	    },
+	)
+	.map_err(|err| err.to_string())?;
+	// Palettes draw chunky pixels of a lower resolution
+	let (scale, filter) = if effect.palette().is_empty() { (1.0, FilterMode::Linear) } else { (0.5, FilterMode::Nearest) };
+	let offscreen = Offscreen::new(bounds, scale, filter);
+	let history = file.then(|| ([0; 2].map(|_| Offscreen::new(bounds, scale, FilterMode::Linear)), 0));
+	Ok(Self { effect: effect.clone(), offscreen, material, history, started: get_time() })
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.offscreen.fits(bounds)
+    }
+
+    /// Capture what is drawn next over `bounds`, starting from
+    /// `background`; `finish` draws it out.
+    pub(crate) fn capture(&mut self, bounds: Rect, background: Color) {
+	self.offscreen.begin(bounds);
+	clear_background(background);
+    }
+
+    /// Draw the captured frame with the effect over `bounds`.
+    pub(crate) fn finish(&mut self, bounds: Rect) {
+	self.offscreen.end();
+	let params = DrawTextureParams { dest_size: Some(bounds.size()), flip_y: true, ..Default::default() };
+	let resolution = [self.offscreen.size.0 as f32, self.offscreen.size.1 as f32];
+	if let Some((targets, last)) = &mut self.history {
+	    // Drawn aside first, so the next frame can read it back
+	    let (current, previous) = (1 - *last, *last);
+	    targets[current].begin(bounds);
+	    gl_use_material(&self.material);
+	    self.material.set_uniform("resolution", resolution);
+	    self.material.set_uniform("time", (get_time() - self.started) as f32);
+	    self.material.set_texture("previous", targets[previous].target.texture.clone());
+	    draw_texture_ex(&self.offscreen.target.texture, bounds.x, bounds.y, WHITE, params.clone());
+	    gl_use_default_material();
+	    targets[current].end();
+	    draw_texture_ex(&targets[current].target.texture, bounds.x, bounds.y, WHITE, params);
+	    *last = current;
+	    return;
+	}
+	gl_use_material(&self.material);
+	self.material.set_uniform("resolution", resolution);
+	let palette = self.effect.palette();
+	if !palette.is_empty() {
+	    // `set_uniform` leaves arrays unset, which draws all black
+	    self.material.set_uniform_array("colors", &palette_uniform(palette));
+	    self.material.set_uniform("count", palette.len() as f32);
+	    self.material.set_uniform("shades", (self.effect == PostEffect::Gameboy) as u8 as f32);
+	}
+	draw_texture_ex(&self.offscreen.target.texture, bounds.x, bounds.y, WHITE, params);
+	gl_use_default_material();
+    }
+}
+
+/// The uniforms of the palette shader.
+fn palette_uniforms() -> Vec<UniformDesc> {
+    vec![
+	UniformDesc::new("colors", UniformType::Float3).array(MAX_PALETTE),
+	UniformDesc::new("count", UniformType::Float1),
+	UniformDesc::new("shades", UniformType::Float1),
+    ]
+}
+
+/// `palette` as the shader's array of colors, padded with its last color.
+fn palette_uniform(palette: &[&str]) -> [Vec3; MAX_PALETTE] {
+    let colors: Vec<Vec3> = palette
+	.iter()
+	.map(|hex| parse_hex_color(hex).expect("built-in colors parse"))
+	.map(|color| vec3(color.r, color.g, color.b))
+	.collect();
+    std::array::from_fn(|index| colors[index.min(colors.len() - 1)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palettes_fit_the_shader() {
+	for effect in [PostEffect::Gameboy, PostEffect::C64, PostEffect::Ega] {
+	    let palette = effect.palette();
+	    assert!(!palette.is_empty() && palette.len() <= MAX_PALETTE, "{effect:?}");
+	    let colors = palette_uniform(palette);
+	    assert_eq!(colors[MAX_PALETTE - 1], colors[palette.len() - 1]);
+	    let first = parse_hex_color(palette[0]).unwrap();
+	    assert_eq!(colors[0], vec3(first.r, first.g, first.b));
+	    // `set_uniform_array` only uploads data of the uniform's whole size
+	    let uniforms = palette_uniforms();
+	    let desc = uniforms.iter().find(|desc| desc.name == "colors").unwrap();
+	    assert_eq!(std::mem::size_of_val(&colors), desc.uniform_type.size() * desc.array_count);
+	}
+    }
+
+    #[test]
+    fn effects_are_written_by_name() {
+	for effect in [PostEffect::Crt, PostEffect::Ega, PostEffect::File("glow.glsl".to_string())] {
+	    assert_eq!(PostEffect::try_from(String::from(effect.clone())), Ok(effect));
+	}
+	assert_eq!(String::from(PostEffect::File("glow.glsl".to_string())), "file:glow.glsl");
+	assert!(PostEffect::try_from("vhs".to_string()).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+//! Whether the machine is running on battery, so the screensaver can go
+//! easy on it.
+
+/// Whether the computer is running off its battery rather than mains power.
+/// Desktops and unknown platforms count as plugged in.
+pub(crate) fn on_battery() -> bool {
+    #[cfg(windows)]
+    {
+	use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+	let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+	// 0 is offline, 1 online and 255 unknown
+	unsafe { GetSystemPowerStatus(&mut status) != 0 && status.ACLineStatus == 0 }
+    }
+    #[cfg(target_os = "macos")]
+    {
+	std::process::Command::new("pmset")
+	    .args(["-g", "batt"])
+	    .output()
+	    .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+    }
+    #[cfg(target_os = "linux")]
+    {
+	let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+	    return false;
+	};
+	let read = |path: std::path::PathBuf| std::fs::read_to_string(path).map(|text| text.trim().to_string()).unwrap_or_default();
+	supplies
+	    .flatten()
+	    .any(|supply| read(supply.path().join("type")) == "Battery" && read(supply.path().join("status")) == "Discharging")
+    }
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    false
+}
@@ -0,0 +1,228 @@
+//! The `3d` render mode: each shape a prism standing as tall as it is
+//! visible, seen by a camera slowly circling over the grid.
+
+use crate::render::{MAX_MESH_INDICES, MAX_MESH_VERTICES};
+use crate::shapes::{DrawCommand, ShapeId, ShapeRegistry, Tessellation};
+use macroquad::miniquad::PassAction;
+use macroquad::models::{Mesh, Vertex, draw_mesh};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+
+/// How the shapes are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub enum RenderMode {
+    /// Flat on the screen.
+    #[default]
+    #[serde(rename = "2d")]
+    Flat,
+    /// As prisms in 3D.
+    #[serde(rename = "3d")]
+    Prisms,
+    /// Tilted back, rising as they show.
+    #[serde(rename = "isometric")]
+    Isometric,
+}
+
+/// Height of a fully visible prism, in shape radii.
+const PRISM_HEIGHT: f32 = 2.0;
+/// Seconds the camera takes to circle the grid once.
+const ORBIT_SECONDS: f32 = 240.0;
+/// Seconds the camera takes to tilt down and back up.
+const TILT_SECONDS: f32 = 37.0;
+/// Degrees the camera looks down at the grid from, give or take `TILT`.
+const ELEVATION: f32 = 55.0;
+const TILT: f32 = 10.0;
+/// Vertical field of view, in degrees.
+const FOV: f32 = 45.0;
+/// Where the light comes from.
+const LIGHT: Vec3 = Vec3::new(-0.4, 1.0, -0.5);
+/// Brightness of faces turned away from the light.
+const AMBIENT: f32 = 0.35;
+
+/// An edge around a shape's outline, and the way out of the shape from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Edge {
+    start: Vec2,
+    end: Vec2,
+    outward: Vec2,
+}
+
+/// The mesh the prisms are collected in, and each shape's outline.
+pub(crate) struct Prisms {
+    edges: HashMap<ShapeId, Vec<Edge>>,
+    mesh: Mesh,
+}
+
+impl Prisms {
+    pub(crate) fn new() -> Self {
+	Self { edges: HashMap::new(), mesh: Mesh { vertices: Vec::new(), indices: Vec::new(), texture: None } }
+    }
+
+    /// Forget the outlines found, once the registry's shapes may have changed.
+    pub(crate) fn clear(&mut self) {
+	self.edges.clear();
+    }
+
+    /// Draw `commands` as prisms on each of `displays`, which span
+    /// `bounds`, with the camera `seconds` into its orbit.
+    pub(crate) fn draw(&mut self, registry: &ShapeRegistry, commands: &[DrawCommand], displays: &[Rect], bounds: Rect, seconds: f32) {
+	// Each display gets its part of the current viewport, as a preview
+	// clips to its own
+	let viewport = unsafe { get_internal_gl() }.quad_gl.get_viewport();
+	let scale = Vec2::new(viewport.2 as f32 / bounds.w, viewport.3 as f32 / bounds.h);
+	push_camera_state();
+	for (index, display) in displays.iter().enumerate() {
+	    let corner = Vec2::new(display.x - bounds.x, bounds.bottom() - display.bottom()) * scale;
+	    let size = display.size() * scale;
+	    let viewport = Some((viewport.0 + corner.x as i32, viewport.1 + corner.y as i32, size.x as i32, size.y as i32));
+	    set_camera(&Camera3D { viewport, ..camera(*display, seconds) });
+	    if index == 0 {
+		clear_depth();
+	    }
+	    for command in commands.iter().filter(|command| display.contains(Vec2::new(command.x, command.y))) {
+		self.add(registry, command, display.center());
+	    }
+	    flush(&mut self.mesh);
+	}
+	pop_camera_state();
+	let gl = unsafe { get_internal_gl() };
+	gl.quad_gl.depth_test(false);
+	gl.quad_gl.viewport(Some(viewport));
+    }
+
+    /// Add the prism of `command` to the mesh, with `origin` at the center
+    /// of the scene.
+    fn add(&mut self, registry: &ShapeRegistry, command: &DrawCommand, origin: Vec2) {
+	let tessellation = registry.tessellation(command.shape);
+	let edges = self.edges.entry(command.shape).or_insert_with(|| outline_edges(tessellation));
+	let vertices = tessellation.vertices.len() + edges.len() * 4;
+	let indices = tessellation.indices.len() + edges.len() * 6;
+	if self.mesh.vertices.len() + vertices > MAX_MESH_VERTICES || self.mesh.indices.len() + indices > MAX_MESH_INDICES {
+	    flush(&mut self.mesh);
+	}
+
+	let height = command.color.a * PRISM_HEIGHT * command.radius;
+	let rotation = Vec2::from_angle(command.rotation);
+	let center = Vec2::new(command.x, command.y) - origin;
+	let place = |point: Vec2| center + rotation.rotate(point) * command.radius;
+	let vertex = |point: Vec2, y: f32, color: Color| Vertex::new(point.x, y, point.y, 0.0, 0.0, color);
+
+	let base = self.mesh.vertices.len() as u16;
+	let top = shaded(command.color, Vec3::Y);
+	self.mesh.vertices.extend(tessellation.vertices.iter().map(|point| vertex(place(*point), height, top)));
+	self.mesh.indices.extend(tessellation.indices.iter().map(|index| base + index));
+	// The sides down to the ground
+	for edge in edges.iter() {
+	    let outward = rotation.rotate(edge.outward);
+	    let color = shaded(command.color, Vec3::new(outward.x, 0.0, outward.y));
+	    let (start, end) = (place(edge.start), place(edge.end));
+	    let base = self.mesh.vertices.len() as u16;
+	    self.mesh.vertices.extend([vertex(start, height, color), vertex(end, height, color), vertex(end, 0.0, color), vertex(start, 0.0, color)]);
+	    self.mesh.indices.extend([0, 1, 2, 0, 2, 3].map(|index| base + index));
+	}
+    }
+}
+
+/// Start the prisms on an empty depth buffer in the pass being drawn to,
+/// whatever drew there earlier this frame, and test against it.
+fn clear_depth() {
+    let mut gl = unsafe { get_internal_gl() };
+    // Drawing is batched; what came before goes out first, as it would
+    // otherwise be drawn after the clear
+    gl.flush();
+    let clear = PassAction::Clear { color: None, depth: Some(1.0), stencil: None };
+    match gl.quad_gl.get_active_render_pass() {
+	Some(pass) => gl.quad_context.begin_pass(Some(pass), clear),
+	None => gl.quad_context.begin_default_pass(clear),
+    }
+    gl.quad_context.end_render_pass();
+    gl.quad_gl.depth_test(true);
+}
+
+/// Draw what `mesh` collected and empty it.
+fn flush(mesh: &mut Mesh) {
+    if !mesh.indices.is_empty() {
+	draw_mesh(mesh);
+    }
+    mesh.vertices.clear();
+    mesh.indices.clear();
+}
+
+/// The camera over `display`'s grid `seconds` into its orbit, looking at
+/// its center.
+fn camera(display: Rect, seconds: f32) -> Camera3D {
+    let azimuth = seconds / ORBIT_SECONDS * TAU;
+    let elevation = (ELEVATION + TILT * (seconds / TILT_SECONDS * TAU).sin()).to_radians();
+    // Close enough that the grid fills the view all the way around
+    let distance = display.w.min(display.h) * 0.9;
+    let direction = Vec3::new(elevation.cos() * azimuth.sin(), elevation.sin(), elevation.cos() * azimuth.cos());
+    Camera3D {
+	position: direction * distance,
+	target: Vec3::ZERO,
+	up: Vec3::Y,
+	fovy: FOV.to_radians(),
+	aspect: Some(display.w / display.h.max(1.0)),
+	..Default::default()
+    }
+}
+
+/// `color` lit on a face pointing along `normal`.
+fn shaded(color: Color, normal: Vec3) -> Color {
+    let light = AMBIENT + (1.0 - AMBIENT) * normal.dot(LIGHT.normalize()).max(0.0);
+    Color::new(color.r * light, color.g * light, color.b * light, 1.0)
+}
+
+/// The edges of `tessellation` that only one triangle has, which run
+/// around its outline.
+fn outline_edges(tessellation: &Tessellation) -> Vec<Edge> {
+    let Tessellation { vertices, indices } = tessellation;
+    let triangle_edges = || indices.chunks_exact(3).flat_map(|triangle| [(triangle[0], triangle[1], triangle[2]), (triangle[1], triangle[2], triangle[0]), (triangle[2], triangle[0], triangle[1])]);
+    let mut shared: HashMap<(u16, u16), usize> = HashMap::new();
+    for (a, b, _) in triangle_edges() {
+	*shared.entry((a.min(b), a.max(b))).or_default() += 1;
+    }
+    triangle_edges()
+	.filter(|(a, b, _)| shared[&(*a.min(b), *a.max(b))] == 1)
+	.map(|(a, b, across)| (vertices[a as usize], vertices[b as usize], vertices[across as usize]))
+	.filter(|(start, end, _)| start != end)
+	.map(|(start, end, across)| {
+	    // Away from the triangle's third corner
+	    let normal = (end - start).perp().normalize();
+	    let outward = if normal.dot(across - start) > 0.0 { -normal } else { normal };
+	    Edge { start, end, outward }
+	})
+	.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Orientation;
+    use crate::shapes::hexagon_outline;
+
+    #[test]
+    fn prism_sides_face_out_of_the_outline() {
+	let hexagon = Tessellation::fan(&hexagon_outline(Orientation::Flat));
+	let edges = outline_edges(&hexagon);
+	assert_eq!(edges.len(), 6);
+	for edge in edges {
+	    assert!(edge.start.length() > 0.99 && edge.end.length() > 0.99);
+	    assert!(edge.outward.dot((edge.start + edge.end) / 2.0) > 0.0);
+	}
+	// Lit faces are brighter, and never see-through
+	assert!(shaded(WHITE, Vec3::Y).r > shaded(WHITE, Vec3::NEG_Y).r);
+	assert_eq!(shaded(Color::new(1.0, 1.0, 1.0, 0.2), Vec3::Y).a, 1.0);
+    }
+
+    #[test]
+    fn the_camera_stays_above_the_grid() {
+	let display = Rect::new(0.0, 0.0, 1920.0, 1080.0);
+	for seconds in (0..600).map(|step| step as f32) {
+	    let camera = camera(display, seconds);
+	    let elevation = (camera.position.y / camera.position.length()).asin().to_degrees();
+	    assert!((ELEVATION - TILT - 1e-3..=ELEVATION + TILT + 1e-3).contains(&elevation));
+	}
+    }
+}
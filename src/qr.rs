@@ -0,0 +1,406 @@
+//! Text as a QR code drawn in the grid, such as a URL or guest Wi-Fi
+//! credentials for a lobby screen.
+//!
+//! Encoded in byte mode at the medium error correction level, which still
+//! reads with a few shapes off, in versions 1 to 10: up to 213 bytes.
+
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+
+/// Largest version encoded, 57 modules a side.
+const MAX_VERSION: usize = 10;
+/// Error correction codewords per block at the medium level, by version.
+const ECC_PER_BLOCK: [usize; MAX_VERSION + 1] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+/// Blocks the codewords are split into at the medium level, by version.
+const BLOCKS: [usize; MAX_VERSION + 1] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+/// Light modules around the code, which readers need to find it.
+const QUIET_ZONE: usize = 4;
+/// Share of a display's shorter side the code may take, quiet zone included.
+const CODE_SHARE: f32 = 0.9;
+/// A row of modules looking like part of a finder, which readers trip on.
+const FINDER_LIKE: [bool; 11] = [true, false, true, true, true, false, true, false, false, false, false];
+
+/// The modules of `text` as a QR code, row by row and dark as `true`;
+/// `None` when it is too long to encode.
+pub(crate) fn encode(text: &str) -> Option<Vec<Vec<bool>>> {
+    let data = text.as_bytes();
+    let version = (1..=MAX_VERSION).find(|&version| 4 + count_bits(version) + data.len() * 8 <= data_codewords(version) * 8)?;
+    let mut code = Code::new(version);
+    code.draw_codewords(&add_error_correction(&data_codewords_for(data, version), version));
+    // Any mask reads; the one with the fewest confusing features reads best
+    let masked = |mask| {
+	let mut masked = code.clone();
+	masked.apply_mask(mask);
+	masked.draw_format(mask);
+	masked
+    };
+    let code = (0..8).map(masked).min_by_key(Code::penalty)?;
+    Some(code.modules.chunks(code.size).map(<[bool]>::to_vec).collect())
+}
+
+/// Mark the `shapes` under the light modules of `modules` and its quiet
+/// zone as lit, and those under its dark modules as dark, centered on each
+/// of `displays` as large as fits. Modules are at least `cell_radius * 2`
+/// wide so each one covers a shape; shapes around the code animate as usual.
+pub(crate) fn light_code(shapes: &mut [Shape], displays: &[Rect], modules: &[Vec<bool>], cell_radius: f32) {
+    let count = (modules.len() + QUIET_ZONE * 2) as f32;
+    for display in displays {
+	let module = (display.size().min_element() * CODE_SHARE / count).max(cell_radius * 2.0);
+	let corner = display.center() - Vec2::splat(module * count / 2.0);
+	for shape in shapes.iter_mut().filter(|shape| display.contains(Vec2::new(shape.x, shape.y))) {
+	    let cell = ((Vec2::new(shape.x, shape.y) - corner) / module).floor();
+	    let inside = cell.cmpge(Vec2::ZERO).all() && cell.cmplt(Vec2::splat(count)).all();
+	    // The quiet zone is past the ends of the rows
+	    let dark = inside
+		&& modules
+		    .get((cell.y as usize).wrapping_sub(QUIET_ZONE))
+		    .and_then(|row| row.get((cell.x as usize).wrapping_sub(QUIET_ZONE)))
+		    .copied()
+		    .unwrap_or(false);
+	    shape.dark = dark;
+	    shape.lit = inside && !dark;
+	}
+    }
+}
+
+/// Bits of the byte count in `version`.
+fn count_bits(version: usize) -> usize {
+    if version < 10 { 8 } else { 16 }
+}
+
+/// Modules of `version` left for codewords once the patterns are drawn.
+fn raw_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+	let alignments = version / 7 + 2;
+	modules -= (25 * alignments - 10) * alignments - 55;
+	if version >= 7 {
+	    modules -= 36;
+	}
+    }
+    modules
+}
+
+/// Codewords of `version` holding data rather than error correction.
+fn data_codewords(version: usize) -> usize {
+    raw_modules(version) / 8 - ECC_PER_BLOCK[version] * BLOCKS[version]
+}
+
+/// `data` in byte mode, filling all of `version`'s data codewords.
+fn data_codewords_for(data: &[u8], version: usize) -> Vec<u8> {
+    let mut bits = Vec::new();
+    push_bits(&mut bits, 0b0100, 4);
+    push_bits(&mut bits, data.len(), count_bits(version));
+    for &byte in data {
+	push_bits(&mut bits, byte.into(), 8);
+    }
+    // Up to four zeros end the data, then more fill the last byte
+    let capacity = data_codewords(version) * 8;
+    let terminator = (capacity - bits.len()).min(4);
+    push_bits(&mut bits, 0, terminator);
+    let filler = bits.len().wrapping_neg() % 8;
+    push_bits(&mut bits, 0, filler);
+    let mut codewords: Vec<u8> = bits.chunks(8).map(|byte| byte.iter().fold(0, |value, &bit| value << 1 | u8::from(bit))).collect();
+    let padding = data_codewords(version) - codewords.len();
+    codewords.extend([0xec, 0x11].into_iter().cycle().take(padding));
+    codewords
+}
+
+/// Append the low `length` bits of `value`, highest first.
+fn push_bits(bits: &mut Vec<bool>, value: usize, length: usize) {
+    bits.extend((0..length).rev().map(|bit| value >> bit & 1 == 1));
+}
+
+/// `data` split into `version`'s blocks, each followed by its error
+/// correction, interleaved as the code stores them.
+fn add_error_correction(data: &[u8], version: usize) -> Vec<u8> {
+    let (blocks, ecc_length) = (BLOCKS[version], ECC_PER_BLOCK[version]);
+    let raw = raw_modules(version) / 8;
+    // The last blocks take a codeword more when they do not divide evenly
+    let short_blocks = blocks - raw % blocks;
+    let short_length = raw / blocks - ecc_length;
+    let divisor = reed_solomon_divisor(ecc_length);
+    let mut rest = data;
+    let split: Vec<(&[u8], Vec<u8>)> = (0..blocks)
+	.map(|block| {
+	    let (block_data, tail) = rest.split_at(short_length + usize::from(block >= short_blocks));
+	    rest = tail;
+	    (block_data, reed_solomon_remainder(block_data, &divisor))
+	})
+	.collect();
+    let mut codewords = Vec::with_capacity(raw);
+    for index in 0..=short_length {
+	codewords.extend(split.iter().filter_map(|(block_data, _)| block_data.get(index)));
+    }
+    for index in 0..ecc_length {
+	codewords.extend(split.iter().map(|(_, ecc)| ecc[index]));
+    }
+    codewords
+}
+
+/// The generator polynomial for `degree` error correction codewords,
+/// highest coefficient first and the leading 1 left out.
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+	for index in 0..degree {
+	    let next = divisor.get(index + 1).copied().unwrap_or(0);
+	    divisor[index] = gf_multiply(divisor[index], root) ^ next;
+	}
+	root = gf_multiply(root, 2);
+    }
+    divisor
+}
+
+/// The error correction codewords of `data`.
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for &byte in data {
+	let factor = byte ^ remainder.remove(0);
+	remainder.push(0);
+	for (value, &coefficient) in remainder.iter_mut().zip(divisor) {
+	    *value ^= gf_multiply(coefficient, factor);
+	}
+    }
+    remainder
+}
+
+/// `x * y` in GF(256) modulo the polynomial QR codes use.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut product: u16 = 0;
+    for bit in (0..8).rev() {
+	product = (product << 1) ^ ((product >> 7) * 0x11d);
+	product ^= (u16::from(y) >> bit & 1) * u16::from(x);
+    }
+    product as u8
+}
+
+/// The 15 format bits for `mask` at the medium level, with their error
+/// correction.
+fn format_bits(mask: usize) -> usize {
+    // The medium level's own two bits are zero
+    let mut remainder = mask;
+    for _ in 0..10 {
+	remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (mask << 10 | remainder) ^ 0x5412
+}
+
+/// The 18 version bits of `version`, with their error correction.
+fn version_bits(version: usize) -> usize {
+    let mut remainder = version;
+    for _ in 0..12 {
+	remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+    }
+    version << 12 | remainder
+}
+
+/// Centers of the alignment patterns along each side of `version`.
+fn alignment_positions(version: usize, size: usize) -> Vec<usize> {
+    if version == 1 {
+	return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let mut positions: Vec<usize> = (0..count - 1).map(|index| size - 7 - index * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// The modules of a code being built, and which belong to its fixed
+/// patterns rather than the data.
+#[derive(Clone)]
+struct Code {
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl Code {
+    /// An empty `version` code with its fixed patterns drawn.
+    fn new(version: usize) -> Self {
+	let size = version * 4 + 17;
+	let mut code = Self { size, modules: vec![false; size * size], function: vec![false; size * size] };
+	for index in 0..size {
+	    code.set_function(6, index, index % 2 == 0);
+	    code.set_function(index, 6, index % 2 == 0);
+	}
+	// Finders, with the light separator around them
+	for (x, y) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+	    code.draw_square(x, y, 4, |distance| distance != 2 && distance != 4);
+	}
+	let positions = alignment_positions(version, size);
+	let last = positions.last().copied().unwrap_or(0);
+	for &x in &positions {
+	    for &y in &positions {
+		// No alignment pattern over the finders
+		if (x, y) != (6, 6) && (x, y) != (6, last) && (x, y) != (last, 6) {
+		    code.draw_square(x, y, 2, |distance| distance != 1);
+		}
+	    }
+	}
+	// Reserved for the format bits, drawn once the mask is picked
+	code.draw_format(0);
+	if version >= 7 {
+	    let bits = version_bits(version);
+	    for index in 0..18 {
+		let dark = bits >> index & 1 == 1;
+		let (a, b) = (size - 11 + index % 3, index / 3);
+		code.set_function(a, b, dark);
+		code.set_function(b, a, dark);
+	    }
+	}
+	code
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+	self.modules[y * self.size + x] = dark;
+	self.function[y * self.size + x] = true;
+    }
+
+    /// Rings around `x`, `y` out to `radius`, dark by their distance.
+    fn draw_square(&mut self, x: usize, y: usize, radius: isize, dark: impl Fn(isize) -> bool) {
+	for dy in -radius..=radius {
+	    for dx in -radius..=radius {
+		let (mx, my) = (x as isize + dx, y as isize + dy);
+		if (0..self.size as isize).contains(&mx) && (0..self.size as isize).contains(&my) {
+		    self.set_function(mx as usize, my as usize, dark(dx.abs().max(dy.abs())));
+		}
+	    }
+	}
+    }
+
+    /// Both copies of the format bits for `mask`, and the module always dark.
+    fn draw_format(&mut self, mask: usize) {
+	let bits = format_bits(mask);
+	let bit = |index: usize| bits >> index & 1 == 1;
+	let size = self.size;
+	for index in 0..6 {
+	    self.set_function(8, index, bit(index));
+	}
+	self.set_function(8, 7, bit(6));
+	self.set_function(8, 8, bit(7));
+	self.set_function(7, 8, bit(8));
+	for index in 9..15 {
+	    self.set_function(14 - index, 8, bit(index));
+	}
+	for index in 0..8 {
+	    self.set_function(size - 1 - index, 8, bit(index));
+	}
+	for index in 8..15 {
+	    self.set_function(8, size - 15 + index, bit(index));
+	}
+	self.set_function(8, size - 8, true);
+    }
+
+    /// Fill the data modules with `codewords`, in the zigzag up and down
+    /// column pairs from the right.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+	let mut bits = codewords.iter().flat_map(|&byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1));
+	let size = self.size;
+	let mut right = size - 1;
+	loop {
+	    // Pairs skip the vertical timing pattern
+	    if right == 6 {
+		right = 5;
+	    }
+	    for vertical in 0..size {
+		let y = if (right + 1) & 2 == 0 { size - 1 - vertical } else { vertical };
+		for x in [right, right - 1] {
+		    if !self.function[y * size + x] {
+			self.modules[y * size + x] = bits.next().unwrap_or(false);
+		    }
+		}
+	    }
+	    if right < 2 {
+		break;
+	    }
+	    right -= 2;
+	}
+    }
+
+    /// Flip the data modules `mask` picks.
+    fn apply_mask(&mut self, mask: usize) {
+	for y in 0..self.size {
+	    for x in 0..self.size {
+		let flip = match mask {
+		    0 => (x + y) % 2 == 0,
+		    1 => y % 2 == 0,
+		    2 => x % 3 == 0,
+		    3 => (x + y) % 3 == 0,
+		    4 => (x / 3 + y / 2) % 2 == 0,
+		    5 => x * y % 2 + x * y % 3 == 0,
+		    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+		    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+		};
+		let index = y * self.size + x;
+		self.modules[index] ^= flip && !self.function[index];
+	    }
+	}
+    }
+
+    /// How hard the code is to read by the standard's score: long runs,
+    /// blocks of a color, finder lookalikes and an uneven balance.
+    fn penalty(&self) -> usize {
+	let size = self.size;
+	let module = |x: usize, y: usize| self.modules[y * size + x];
+	let mut penalty = 0;
+	for line in (0..size).flat_map(|i| [(0..size).map(|j| module(j, i)).collect::<Vec<_>>(), (0..size).map(|j| module(i, j)).collect()]) {
+	    for run in line.chunk_by(|a, b| a == b).filter(|run| run.len() >= 5) {
+		penalty += run.len() - 2;
+	    }
+	    // Past the edges is the light quiet zone
+	    let padded: Vec<bool> = [false; 4].into_iter().chain(line).chain([false; 4]).collect();
+	    for window in padded.windows(FINDER_LIKE.len()) {
+		if window == FINDER_LIKE || window.iter().eq(FINDER_LIKE.iter().rev()) {
+		    penalty += 40;
+		}
+	    }
+	}
+	for y in 0..size - 1 {
+	    for x in 0..size - 1 {
+		let color = module(x, y);
+		if module(x + 1, y) == color && module(x, y + 1) == color && module(x + 1, y + 1) == color {
+		    penalty += 3;
+		}
+	    }
+	}
+	// 10 for each 5% away from half dark
+	let (dark, total) = (self.modules.iter().filter(|dark| **dark).count(), size * size);
+	penalty + (dark * 20).abs_diff(total * 10).div_ceil(total).saturating_sub(1) * 10
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_correction_and_format_match_the_standard() {
+	// The standard's worked example, HELLO WORLD at version 1 medium
+	let data = [32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17];
+	assert_eq!(reed_solomon_remainder(&data, &reed_solomon_divisor(10)), [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]);
+	assert_eq!(format_bits(5), 0b100000011001110);
+	assert_eq!(version_bits(7), 0b000111110010010100);
+	assert_eq!(alignment_positions(10, 57), [6, 28, 50]);
+	assert_eq!(data_codewords(1), 16);
+	assert_eq!(data_codewords(10), 216);
+    }
+
+    #[test]
+    fn codes_grow_with_the_text() {
+	let code = encode("WIFI:S:Lobby;T:WPA;P:guest1234;;").unwrap();
+	assert_eq!(code.len(), 29);
+	assert!(code.iter().all(|row| row.len() == 29));
+	// A finder in the top left, its separator and the always dark module
+	assert_eq!(code[0][..8], [true, true, true, true, true, true, true, false]);
+	assert_eq!(code[2][..7], [true, false, true, true, true, false, true]);
+	assert!(code[29 - 8][8]);
+	assert_eq!(encode("hello").unwrap().len(), 21);
+	assert_eq!(encode(&"x".repeat(213)).unwrap().len(), 57);
+	assert!(encode(&"x".repeat(214)).is_none());
+    }
+}
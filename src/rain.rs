@@ -0,0 +1,107 @@
+//! Drops falling down the grid's columns in the `rain` pattern.
+
+use crate::config::Config;
+use crate::pattern::Pattern;
+use crate::shapes::Shape;
+use macroquad::rand::RandGenerator;
+
+/// Length of a drop's fading trail, in shape radii.
+const TRAIL: f32 = 8.0;
+/// Range of drop speeds, in shape radii per second.
+const SPEEDS: (f32, f32) = (4.0, 12.0);
+
+/// One column of shapes and the drop falling down it.
+struct Column {
+    /// Indices of the column's shapes.
+    shapes: Vec<usize>,
+    /// Height of the drop's head, in pixels.
+    head: f32,
+    /// Pixels per second.
+    speed: f32,
+    bottom: f32,
+}
+
+/// The drops, and how lit they leave each shape.
+#[derive(Default)]
+pub(crate) struct Rain {
+    columns: Vec<Column>,
+    levels: Vec<f32>,
+    top: f32,
+}
+
+impl Rain {
+    /// Move the drops on by `dt` seconds, starting new ones at the top.
+    pub(crate) fn update(&mut self, shapes: &[Shape], dt: f32, config: &Config, rng: &RandGenerator) {
+	if config.pattern != Pattern::Rain || shapes.is_empty() {
+	    *self = Rain::default();
+	    return;
+	}
+	// A new layout gets new columns
+	if self.levels.len() != shapes.len() {
+	    self.columns = columns(shapes);
+	    self.levels = vec![0.0; shapes.len()];
+	    self.top = shapes.iter().map(|shape| shape.y).fold(f32::MAX, f32::min);
+	}
+
+	let radius = shapes[0].radius;
+	let trail = TRAIL * radius;
+	for column in &mut self.columns {
+	    if column.speed == 0.0 || column.head > column.bottom + trail {
+		// Start over above the screen, some drops later than others
+		column.head = self.top - rng.gen_range(0.0, trail * 2.0);
+		column.speed = rng.gen_range(SPEEDS.0, SPEEDS.1) * radius;
+	    }
+	    column.head += column.speed * dt;
+	    for &index in &column.shapes {
+		let behind = column.head - shapes[index].y;
+		self.levels[index] = if (0.0..trail).contains(&behind) { 1.0 - behind / trail } else { 0.0 };
+	    }
+	}
+    }
+
+    /// How lit the `index`th shape is, 1 at a drop's head.
+    pub(crate) fn level(&self, index: usize) -> f32 {
+	self.levels.get(index).copied().unwrap_or(0.0)
+    }
+}
+
+/// Shapes grouped into columns by position, each sorted top to bottom.
+fn columns(shapes: &[Shape]) -> Vec<Column> {
+    let mut order: Vec<usize> = (0..shapes.len()).collect();
+    order.sort_by(|&a, &b| shapes[a].x.total_cmp(&shapes[b].x));
+    let mut columns: Vec<Column> = Vec::new();
+    for index in order {
+	match columns.last_mut() {
+	    // Shapes in a column line up within half a radius
+	    Some(column) if shapes[index].x - shapes[column.shapes[0]].x < shapes[index].radius * 0.5 => {
+		column.shapes.push(index);
+		column.bottom = column.bottom.max(shapes[index].y);
+	    }
+	    _ => columns.push(Column { shapes: vec![index], head: 0.0, speed: 0.0, bottom: shapes[index].y }),
+	}
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+
+    #[test]
+    fn drops_leave_a_trail_above_their_head() {
+	let rng = RandGenerator::new();
+	let config = Config { pattern: Pattern::Rain, ..Default::default() };
+	let shapes: Vec<Shape> =
+	    (0..20).map(|row| Shape::new(0.0, row as f32 * 10.0, 5.0, false, &Palette::random(), &rng)).collect();
+	let mut rain = Rain::default();
+	rain.update(&shapes, 0.0, &config, &rng);
+	assert_eq!(rain.columns.len(), 1);
+	rain.columns[0].head = 100.0;
+	rain.columns[0].speed = 1e-6;
+	rain.update(&shapes, 0.0, &config, &rng);
+	assert_eq!(rain.level(10), 1.0);
+	assert!(rain.level(9) > rain.level(8) && rain.level(8) > 0.0);
+	assert_eq!(rain.level(11), 0.0);
+    }
+}
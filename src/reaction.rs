@@ -0,0 +1,160 @@
+//! A Gray-Scott reaction-diffusion simulation behind the `reaction`
+//! pattern, sampled at each shape's position.
+
+use crate::config::Config;
+use crate::pattern::Pattern;
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+
+/// Named feed and kill rates, for the `reaction` config key.
+pub(crate) const PRESETS: [(&str, f32, f32); 4] =
+    [("coral", 0.0545, 0.062), ("mitosis", 0.0367, 0.0649), ("maze", 0.029, 0.057), ("waves", 0.014, 0.045)];
+
+/// Simulation steps per second of animation.
+const STEPS_PER_SECOND: f32 = 300.0;
+/// Most steps run in one frame, so slow frames don't snowball.
+const MAX_STEPS_PER_FRAME: usize = 40;
+/// Simulation cells per shape radius.
+const RESOLUTION: f32 = 2.0;
+/// Largest simulation grid side.
+const MAX_SIDE: usize = 256;
+
+/// The feed and kill rates `config` selects.
+pub(crate) fn rates(config: &Config) -> (f32, f32) {
+    PRESETS
+	.iter()
+	.find(|(name, ..)| name.eq_ignore_ascii_case(&config.reaction))
+	.map_or((config.feed, config.kill), |&(_, feed, kill)| (feed, kill))
+}
+
+/// Concentrations of the two chemicals on a grid over the shapes.
+#[derive(Default)]
+pub(crate) struct Reaction {
+    width: usize,
+    height: usize,
+    u: Vec<f32>,
+    v: Vec<f32>,
+    /// The grid cell under each shape.
+    samples: Vec<usize>,
+    since_step: f32,
+}
+
+impl Reaction {
+    /// Run the simulation for `dt` seconds.
+    pub(crate) fn update(&mut self, shapes: &[Shape], dt: f32, config: &Config, rng: &RandGenerator) {
+	if config.pattern != Pattern::Reaction || shapes.is_empty() {
+	    *self = Reaction::default();
+	    return;
+	}
+	// A new layout starts a new simulation
+	if self.samples.len() != shapes.len() {
+	    self.cover(shapes);
+	    self.seed(rng);
+	}
+
+	let (feed, kill) = rates(config);
+	self.since_step += dt * STEPS_PER_SECOND;
+	let steps = (self.since_step as usize).min(MAX_STEPS_PER_FRAME);
+	self.since_step = self.since_step.fract();
+	for _ in 0..steps {
+	    self.step(feed, kill);
+	}
+	// Died out; start again
+	if steps > 0 && self.v.iter().all(|&v| v < 0.01) {
+	    self.seed(rng);
+	}
+    }
+
+    /// How much of the second chemical is under the `index`th shape, 0..1.
+    pub(crate) fn level(&self, index: usize) -> f32 {
+	self.samples.get(index).map_or(0.0, |&cell| (self.v[cell] * 3.0).clamp(0.0, 1.0))
+    }
+
+    /// Size the grid to the bounding box of `shapes`.
+    fn cover(&mut self, shapes: &[Shape]) {
+	let (min, max) = shapes.iter().fold((Vec2::MAX, Vec2::MIN), |(min, max), shape| {
+	    let pos = Vec2::new(shape.x, shape.y);
+	    (min.min(pos), max.max(pos))
+	});
+	let cell = shapes[0].radius / RESOLUTION;
+	let cells = ((max - min) / cell).ceil() + Vec2::ONE;
+	// Coarser cells on huge screens keep the grid small
+	let cell = cell * (cells.max_element() / MAX_SIDE as f32).max(1.0);
+	self.width = (((max.x - min.x) / cell) as usize + 1).max(3);
+	self.height = (((max.y - min.y) / cell) as usize + 1).max(3);
+	self.samples = shapes
+	    .iter()
+	    .map(|shape| {
+		let x = (((shape.x - min.x) / cell) as usize).min(self.width - 1);
+		let y = (((shape.y - min.y) / cell) as usize).min(self.height - 1);
+		y * self.width + x
+	    })
+	    .collect();
+    }
+
+    /// All the first chemical, with a few patches of the second.
+    fn seed(&mut self, rng: &RandGenerator) {
+	self.u = vec![1.0; self.width * self.height];
+	self.v = vec![0.0; self.width * self.height];
+	for _ in 0..(self.width * self.height / 400).max(3) {
+	    let (x, y) = (rng.gen_range(0, self.width), rng.gen_range(0, self.height));
+	    for dy in 0..6 {
+		for dx in 0..6 {
+		    self.v[((y + dy) % self.height) * self.width + (x + dx) % self.width] = 1.0;
+		}
+	    }
+	}
+    }
+
+    /// One Euler step, with the grid wrapping around at its edges.
+    fn step(&mut self, feed: f32, kill: f32) {
+	let (width, height) = (self.width, self.height);
+	let laplacian = |field: &[f32], x: usize, y: usize| {
+	    let at = |dx: usize, dy: usize| field[(y + dy) % height * width + (x + dx) % width];
+	    let (left, right, up, down) = (width - 1, 1, height - 1, 1);
+	    0.2 * (at(left, 0) + at(right, 0) + at(0, up) + at(0, down))
+		+ 0.05 * (at(left, up) + at(right, up) + at(left, down) + at(right, down))
+		- at(0, 0)
+	};
+	let mut u = self.u.clone();
+	let mut v = self.v.clone();
+	for y in 0..height {
+	    for x in 0..width {
+		let cell = y * width + x;
+		let (cu, cv) = (self.u[cell], self.v[cell]);
+		let reaction = cu * cv * cv;
+		u[cell] = (cu + laplacian(&self.u, x, y) - reaction + feed * (1.0 - cu)).clamp(0.0, 1.0);
+		v[cell] = (cv + 0.5 * laplacian(&self.v, x, y) + reaction - (feed + kill) * cv).clamp(0.0, 1.0);
+	    }
+	}
+	self.u = u;
+	self.v = v;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coral_grows_from_its_seeds() {
+	let mut reaction = Reaction { width: 40, height: 40, ..Default::default() };
+	reaction.seed(&RandGenerator::new());
+	let seeded = reaction.v.iter().filter(|&&v| v > 0.1).count();
+	let (_, feed, kill) = PRESETS[0];
+	for _ in 0..1000 {
+	    reaction.step(feed, kill);
+	}
+	let grown = reaction.v.iter().filter(|&&v| v > 0.1).count();
+	assert!(grown > seeded * 2, "{seeded} -> {grown}");
+    }
+
+    #[test]
+    fn presets_override_the_rates() {
+	let config = Config { reaction: "Mitosis".to_string(), feed: 0.01, kill: 0.02, ..Default::default() };
+	assert_eq!(rates(&config), (0.0367, 0.0649));
+	let config = Config { reaction: "custom".to_string(), ..config };
+	assert_eq!(rates(&config), (0.01, 0.02));
+    }
+}
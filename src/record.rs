@@ -0,0 +1,140 @@
+//! Export the animation to a GIF, through ffmpeg to any video format, or
+//! as numbered PNG frames.
+
+use crate::capture::{flip_rows, save_png};
+use crate::config::Config;
+use crate::screensaver::Screensaver;
+use log::{error, info};
+use macroquad::prelude::*;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Frames per second of a recording; 25 divides GIF's 1/100s delays evenly.
+const RECORD_FPS: u32 = 25;
+
+enum Encoder {
+    Gif(gif::Encoder<File>),
+    Ffmpeg(Child),
+}
+
+impl Encoder {
+    fn create(out: &Path, width: u16, height: u16) -> io::Result<Self> {
+	let is_gif = out.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gif"));
+	if is_gif {
+	    let mut encoder = gif::Encoder::new(File::create(out)?, width, height, &[]).map_err(io::Error::other)?;
+	    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+	    return Ok(Encoder::Gif(encoder));
+	}
+	let child = Command::new("ffmpeg")
+	    .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+	    .args(["-s", &format!("{width}x{height}"), "-r", &RECORD_FPS.to_string(), "-i", "-"])
+	    .args(["-pix_fmt", "yuv420p"])
+	    .arg(out)
+	    .stdin(Stdio::piped())
+	    .spawn()?;
+	Ok(Encoder::Ffmpeg(child))
+    }
+
+    fn write_frame(&mut self, width: u16, height: u16, rgba: &mut [u8]) -> io::Result<()> {
+	match self {
+	    Encoder::Gif(encoder) => {
+		let mut frame = gif::Frame::from_rgba_speed(width, height, rgba, 10);
+		frame.delay = (100 / RECORD_FPS) as u16;
+		encoder.write_frame(&frame).map_err(io::Error::other)
+	    }
+	    Encoder::Ffmpeg(child) => child.stdin.as_mut().expect("stdin is piped").write_all(rgba),
+	}
+    }
+
+    fn finish(self) -> io::Result<()> {
+	match self {
+	    Encoder::Gif(_) => Ok(()),
+	    Encoder::Ffmpeg(mut child) => {
+		// Dropping the pipe closes it, so ffmpeg sees the end
+		child.stdin.take();
+		let status = child.wait()?;
+		if status.success() { Ok(()) } else { Err(io::Error::other(format!("ffmpeg exited with {status}"))) }
+	    }
+	}
+    }
+}
+
+/// Render `seconds` of animation offscreen at a fixed timestep and encode it
+/// to `out`: a `.gif` is written directly, anything else is piped to ffmpeg.
+pub async fn record(config: Config, seconds: f32, out: &Path) {
+    if let Err(err) = try_record(config, seconds, out).await {
+	error!("recording to {} failed: {}", out.display(), err);
+    }
+}
+
+async fn try_record(config: Config, seconds: f32, out: &Path) -> io::Result<()> {
+    let mut renderer = FrameRenderer::new(config);
+    let (width, height) = (renderer.width, renderer.height);
+    let mut encoder = Encoder::create(out, width, height)?;
+    let frames = (seconds.max(0.0) * RECORD_FPS as f32).round() as u32;
+
+    for frame in 0..frames {
+	let mut rgba = renderer.next(&format!("Recording {}/{}", frame + 1, frames)).await;
+	encoder.write_frame(width, height, &mut rgba)?;
+    }
+    encoder.finish()?;
+    info!("record: wrote {} frames to {}", frames, out.display());
+    Ok(())
+}
+
+/// Write `count` consecutive frames as `frame-0000.png` and so on into `dir`.
+pub async fn dump_frames(config: Config, count: u32, dir: &Path) {
+    let mut renderer = FrameRenderer::new(config);
+    for frame in 0..count {
+	let rgba = renderer.next(&format!("Saving frame {}/{}", frame + 1, count)).await;
+	let path = dir.join(format!("frame-{frame:04}.png"));
+	if let Err(err) = save_png(&path, renderer.width as u32, renderer.height as u32, &rgba) {
+	    error!("cannot write {}: {}", path.display(), err);
+	    return;
+	}
+    }
+    info!("record: wrote {} frames to {}", count, dir.display());
+}
+
+/// Renders the screensaver at a fixed timestep into a window-sized render
+/// target and reads each frame back.
+struct FrameRenderer {
+    screensaver: Screensaver,
+    target: RenderTarget,
+    camera: Camera2D,
+    width: u16,
+    height: u16,
+}
+
+impl FrameRenderer {
+    fn new(config: Config) -> Self {
+	let (width, height) = (screen_width() as u16, screen_height() as u16);
+	let bounds = Rect::new(0.0, 0.0, width as f32, height as f32);
+	let target = render_target(width as u32, height as u32);
+	let mut camera = Camera2D::from_display_rect(bounds);
+	camera.render_target = Some(target.clone());
+	let radius = config.radius;
+	let screensaver = Screensaver::new(config, &[bounds], radius);
+	Self { screensaver, target, camera, width, height }
+    }
+
+    /// Advance one frame and return its pixels as top-down RGBA, showing it
+    /// in the window with `status` on top.
+    async fn next(&mut self, status: &str) -> Vec<u8> {
+	set_camera(&self.camera);
+	clear_background(self.screensaver.background_color());
+	let commands = self.screensaver.step(1.0 / RECORD_FPS as f32, &[]);
+	self.screensaver.draw(&commands);
+
+	// Showing the frame also submits it before it is read back
+	set_default_camera();
+	clear_background(BLACK);
+	draw_texture(&self.target.texture, 0.0, 0.0, WHITE);
+	draw_text(status, 10.0, 24.0, 20.0, WHITE);
+	next_frame().await;
+
+	flip_rows(&self.target.texture.get_texture_data().bytes, self.width as usize * 4)
+    }
+}
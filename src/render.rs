@@ -0,0 +1,170 @@
+use crate::shapes::{DrawCommand, ShapeRegistry, Tessellation};
+use macroquad::models::{Mesh, Vertex, draw_mesh};
+use macroquad::prelude::*;
+
+// macroquad clamps a single draw call to its default geometry capacity
+pub(crate) const MAX_MESH_VERTICES: usize = 10_000;
+pub(crate) const MAX_MESH_INDICES: usize = 5_000;
+
+/// Collects tessellated shapes into meshes so a whole frame is submitted
+/// with a handful of `draw_mesh` calls instead of one call per triangle.
+pub struct MeshBatch {
+    mesh: Mesh,
+    /// Where the texture is stretched over, when there is one.
+    texture_area: Rect,
+    /// `draw_mesh` calls since `take_draw_count`.
+    draws: usize,
+}
+
+impl MeshBatch {
+    pub fn new() -> Self {
+	Self {
+	    mesh: Mesh { vertices: Vec::new(), indices: Vec::new(), texture: None },
+	    texture_area: Rect::new(0.0, 0.0, 1.0, 1.0),
+	    draws: 0,
+	}
+    }
+
+    /// Fill what is added next with `texture` stretched over `area`,
+    /// tinted by each shape's color, or with plain colors for `None`.
+    pub fn set_texture(&mut self, texture: Option<Texture2D>, area: Rect) {
+	self.flush();
+	self.mesh.texture = texture;
+	self.texture_area = area;
+    }
+
+    /// Add `tessellation` scaled by `radius`, rotated by `rotation` radians
+    /// and moved to `center`, flushing first if it would not fit in the
+    /// current mesh.
+    pub fn add_tessellation(&mut self, tessellation: &Tessellation, center: Vec2, radius: f32, rotation: f32, color: Color) {
+	let rotation = Vec2::from_angle(rotation);
+	self.add_mapped(tessellation, color, |vertex| center + rotation.rotate(vertex) * radius);
+    }
+
+    /// Add `tessellation` with each vertex moved to where `place` puts it,
+    /// flushing first if it would not fit in the current mesh.
+    pub(crate) fn add_mapped(&mut self, tessellation: &Tessellation, color: Color, place: impl Fn(Vec2) -> Vec2) {
+	let Tessellation { vertices, indices } = tessellation;
+	if self.mesh.vertices.len() + vertices.len() > MAX_MESH_VERTICES || self.mesh.indices.len() + indices.len() > MAX_MESH_INDICES {
+	    self.flush();
+	}
+
+	let base = self.mesh.vertices.len() as u16;
+	let area = self.texture_area;
+	for vertex in vertices {
+	    let point = place(*vertex);
+	    let uv = (point - area.point()) / area.size();
+	    self.mesh.vertices.push(Vertex::new(point.x, point.y, 0.0, uv.x, uv.y, color));
+	}
+	self.mesh.indices.extend(indices.iter().map(|index| base + index));
+    }
+
+    pub fn add(&mut self, registry: &ShapeRegistry, command: &DrawCommand) {
+	let center = Vec2::new(command.x, command.y);
+	self.add_tessellation(registry.tessellation(command.shape), center, command.radius, command.rotation, command.color);
+    }
+
+    /// Submit everything collected so far.
+    pub fn flush(&mut self) {
+	if !self.mesh.indices.is_empty() {
+	    draw_mesh(&self.mesh);
+	    self.draws += 1;
+	}
+	self.mesh.vertices.clear();
+	self.mesh.indices.clear();
+    }
+
+    /// How many meshes were submitted since the last call.
+    pub fn take_draw_count(&mut self) -> usize {
+	std::mem::take(&mut self.draws)
+    }
+}
+
+impl Default for MeshBatch {
+    fn default() -> Self {
+	Self::new()
+    }
+}
+
+/// Draw a frame's worth of shapes as batched meshes.
+pub fn draw_commands(registry: &ShapeRegistry, commands: &[DrawCommand]) {
+    let mut batch = MeshBatch::new();
+    for command in commands {
+	batch.add(registry, command);
+    }
+    batch.flush();
+}
+
+/// Draw `commands` in software into a `width` by `height` black image and
+/// return it as top-down RGBA, blending like the GPU path does. Needs no
+/// window or GL context, so tests can compare frames pixel by pixel.
+pub fn rasterize(registry: &ShapeRegistry, commands: &[DrawCommand], width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = vec![Vec3::ZERO; width * height];
+    let mut points = Vec::new();
+    for command in commands {
+	let Tessellation { vertices, indices } = registry.tessellation(command.shape);
+	let rotation = Vec2::from_angle(command.rotation);
+	let center = Vec2::new(command.x, command.y);
+	points.clear();
+	points.extend(vertices.iter().map(|vertex| center + rotation.rotate(*vertex) * command.radius));
+	let color = Vec3::new(command.color.r, command.color.g, command.color.b);
+	for triangle in indices.chunks_exact(3) {
+	    let [a, b, c] = [0, 1, 2].map(|i| points[triangle[i] as usize]);
+	    fill_triangle(&mut pixels, width, height, [a, b, c], color, command.color.a);
+	}
+    }
+    pixels
+	.iter()
+	.flat_map(|pixel| {
+	    let [r, g, b] = pixel.to_array().map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+	    [r, g, b, 255]
+	})
+	.collect()
+}
+
+/// Blend every pixel whose center lies inside the triangle.
+fn fill_triangle(pixels: &mut [Vec3], width: usize, height: usize, [a, b, c]: [Vec2; 3], color: Vec3, alpha: f32) {
+    let edge = |p: Vec2, q: Vec2, point: Vec2| (q - p).perp_dot(point - p);
+    let area = edge(a, b, c);
+    if area == 0.0 {
+	return;
+    }
+    let min = a.min(b).min(c).max(Vec2::ZERO);
+    let max = a.max(b).max(c).min(Vec2::new(width as f32, height as f32));
+    for y in min.y as usize..max.y.ceil() as usize {
+	for x in min.x as usize..max.x.ceil() as usize {
+	    let point = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+	    // Same sign as the winding on all three edges means inside
+	    let inside = [edge(a, b, point), edge(b, c, point), edge(c, a, point)]
+		.iter()
+		.all(|weight| weight * area.signum() >= 0.0);
+	    if inside {
+		let pixel = &mut pixels[y * width + x];
+		*pixel = pixel.lerp(color, alpha);
+	    }
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rasterize_fills_shapes_and_leaves_the_rest_black() {
+	let registry = ShapeRegistry::with_builtins();
+	let command = DrawCommand {
+	    x: 10.0,
+	    y: 10.0,
+	    radius: 8.0,
+	    rotation: 0.0,
+	    shape: registry.lookup("hexagon").unwrap(),
+	    color: Color::new(1.0, 0.0, 0.0, 0.5),
+	};
+	let pixels = rasterize(&registry, &[command], 20, 20);
+	let pixel = |x: usize, y: usize| &pixels[(y * 20 + x) * 4..][..4];
+	assert_eq!(pixel(10, 10), &[128, 0, 0, 255]);
+	assert_eq!(pixel(0, 0), &[0, 0, 0, 255]);
+	assert_eq!(pixel(19, 19), &[0, 0, 0, 255]);
+    }
+}
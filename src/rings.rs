@@ -0,0 +1,70 @@
+//! Rings spreading from each click in a window, lighting the shapes they
+//! pass over and turning their hues.
+
+use macroquad::prelude::*;
+
+/// Pixels a ring grows by each second.
+const RING_SPEED: f32 = 500.0;
+/// Seconds a ring lasts, fading all the while.
+const RING_SECONDS: f32 = 2.0;
+/// Pixels on either side of a ring that it still reaches.
+const RING_WIDTH: f32 = 60.0;
+/// Part of the color wheel the hues of shapes right under a ring turn by.
+pub(crate) const RING_HUE_SHIFT: f32 = 0.25;
+
+/// The rings spreading at the moment, as where they started and for how
+/// many seconds.
+#[derive(Default)]
+pub(crate) struct Rings {
+    rings: Vec<(Vec2, f32)>,
+}
+
+impl Rings {
+    /// Start a ring at `pos`.
+    pub(crate) fn add(&mut self, pos: Vec2) {
+	self.rings.push((pos, 0.0));
+    }
+
+    /// Spread the rings for `dt` seconds, dropping those that faded out.
+    pub(crate) fn step(&mut self, dt: f32) {
+	for (_, age) in &mut self.rings {
+	    *age += dt;
+	}
+	self.rings.retain(|(_, age)| *age < RING_SECONDS);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+	self.rings.is_empty()
+    }
+
+    /// How strongly the rings reach `pos`, 0..1: the most near a young
+    /// ring's edge.
+    pub(crate) fn strength(&self, pos: Vec2) -> f32 {
+	self.rings
+	    .iter()
+	    .map(|(center, age)| {
+		let off = (pos.distance(*center) - age * RING_SPEED).abs();
+		(1.0 - off / RING_WIDTH).max(0.0) * (1.0 - age / RING_SECONDS)
+	    })
+	    .fold(0.0, f32::max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rings_spread_and_fade() {
+	let mut rings = Rings::default();
+	rings.add(Vec2::ZERO);
+	assert_eq!(rings.strength(Vec2::ZERO), 1.0);
+	rings.step(0.5);
+	// Past the middle, reaching out to its edge
+	assert_eq!(rings.strength(Vec2::ZERO), 0.0);
+	let edge = rings.strength(Vec2::new(RING_SPEED * 0.5, 0.0));
+	assert!((edge - 0.75).abs() < 1e-5);
+	rings.step(RING_SECONDS);
+	assert!(rings.is_empty());
+    }
+}
@@ -0,0 +1,2331 @@
+use crate::attract::{Attract, CrossFade, Mode, Surprise};
+use crate::audio::AudioMeter;
+use crate::background::{BackgroundLayer, layers, load_image};
+use crate::bloom::Bloom;
+use crate::capture::save_screenshot;
+use crate::clock::{current_text, light_text, local_now};
+use crate::config::{Config, ConfigWatcher, ExitTriggers, Fade};
+use crate::control::{Command, Control, add_overrides, with_overrides};
+use crate::crash;
+use crate::debug_overlay::{DebugOverlay, FrameStats};
+use crate::flow::{Motion, drift, fall};
+use crate::gamepad::Gamepads;
+use crate::gpu::GpuGrid;
+use crate::input::{DEBUG_OVERLAY_KEY, ExitWatch, PAUSE_KEY, SCREENSHOT_KEY, STEP_KEY, exit_input, parse_key_combo};
+use crate::isometric;
+use crate::kaleidoscope::Kaleidoscope;
+use crate::font::rasterize_text;
+use crate::layout::{CellShape, Tile, create_tiling};
+use crate::live::{Live, Tuning};
+use crate::logging::FrameWatch;
+use crate::media::{Media, Track};
+use crate::marquee::light_marquee;
+use crate::passphrase::Prompt;
+use crate::palette::{ColorMode, Harmony, Palette, average_color, hsv_to_rgb, parse_hex_color, rgb_to_hsv};
+use crate::overlay::Overlay;
+use crate::pattern::{Pattern, PatternState};
+use crate::post::{PostEffect, PostPass};
+use crate::power::on_battery;
+use crate::prisms::{Prisms, RenderMode};
+use crate::qr::{self, light_code};
+use crate::render::{MeshBatch, rasterize};
+use crate::rings::{RING_HUE_SHIFT, Rings};
+use crate::shapes::{DrawCommand, Heart, Hexagon, MORPH_STEPS, Morph, Polygon, Shape, ShapeId, ShapeRegistry, mix_entries, nearest_shape};
+use crate::seasons::themed;
+use crate::share::TextureShare;
+use crate::slideshow::{Slideshow, covering};
+use crate::smoothing::Smoothing;
+use crate::sync::{Beat, SyncLink};
+use crate::temperature::current_tint;
+use crate::ticker::Ticker;
+use crate::timer::{Timer, parse_periods};
+use crate::trails::Trails;
+use crate::weather::{Conditions, Weather};
+use crate::webcam::Webcam;
+#[cfg(windows)]
+use crate::win32;
+#[cfg(target_os = "linux")]
+use crate::x11;
+use log::{debug, info, trace, warn};
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+use std::path::Path;
+
+/// Advance the simulation by `dt` and return what should be drawn this frame.
+/// Kept free of macroquad draw calls so it can be exercised without a window.
+/// `audio_bands` are audio levels from low to high frequencies, spread across
+/// each display from left to right to scale opacity and size; empty when the
+/// audio is off. `patterns` carries the state of the blink patterns between
+/// frames.
+pub(crate) fn step_frame(
+    shapes: &mut [Shape],
+    time: &mut f32,
+    patterns: &mut PatternState,
+    dt: f32,
+    config: &Config,
+    style: &ShapeStyle,
+    audio_bands: &[f32],
+) -> Vec<DrawCommand> {
+    // The flash limit holds in real time, whatever the animation speed
+    let frame_dt = dt;
+    let dt = dt * config.animation_speed();
+    *time += dt;
+    patterns.update(shapes, *time, dt, config, &style.palette, &style.rng);
+    // Every shape shares the frame's harmony, each keeping to one color of it
+    let harmony = (config.color_mode == ColorMode::Harmony).then(|| {
+	let time = if config.static_color { 0.0 } else { *time };
+	config.harmony.palette(Harmony::base_hue(style.seed, time, config.harmony_minutes * 60.0))
+    });
+    let mut commands = Vec::new();
+    for (index, shape) in shapes.iter_mut().enumerate() {
+	// Hidden shapes won't show before their next rise, so leave them be
+	if *time < shape.next_visible && shape.opacity == 0.0 && !shape.focus && !shape.lit && shape.boost == 0.0 {
+	    continue;
+	}
+	// Ants pick the colors of the cells they visit
+	shape.update(dt, &style.palette, &style.rng, config.pattern == Pattern::Ant, config);
+	let wave = config.pattern.wave(shape, index, *time, config, patterns);
+	let command = shape.draw_command(wave, frame_dt, style.shape_id, config);
+	if command.is_none() && shape.opacity == 0.0 {
+	    shape.next_visible = config.pattern.next_visible(shape, *time, config).unwrap_or(0.0);
+	}
+	if let Some(mut command) = command {
+	    if !config.reduced_motion {
+		command.rotation += *time * config.rotation_speed.to_radians() * shape.spin;
+		command.radius *= patterns.scale(index, config);
+	    }
+	    if !style.morph_steps.is_empty() && shape.own_shape.is_none() {
+		// Back and forth, each shape at its own point of the cycle
+		let cycle = (*time / config.morph_period + shape.phase_offset / (2. * PI)).fract();
+		let t = 1.0 - (cycle * 2.0 - 1.0).abs();
+		command.shape = style.morph_steps[(t * (MORPH_STEPS - 1) as f32).round() as usize];
+	    }
+	    if let Some(color) = patterns.color(index, config, &style.palette) {
+		command.color = Color { a: command.color.a, ..color };
+	    }
+	    if config.color_mode.is_gradient() {
+		// A static field keeps its hues instead of drifting
+		let drift_time = if config.static_color { 0.0 } else { *time };
+		let color = ColorMode::gradient_color(shape.gradient_pos, drift_time);
+		command.color = Color { a: command.color.a, ..color };
+	    }
+	    if let Some(harmony) = &harmony {
+		let index = (shape.phase_offset / (2. * PI) * harmony.colors().len() as f32) as usize;
+		command.color = Color { a: command.color.a, ..harmony.nth(index) };
+	    }
+	    if config.color_mode == ColorMode::SyncedCycle {
+		let drift_time = if config.static_color { 0.0 } else { *time };
+		let variation = shape.phase_offset / PI - 1.0;
+		let color = ColorMode::synced_color(variation, drift_time, config.color_cycle_period);
+		command.color = Color { a: command.color.a, ..color };
+	    }
+	    if !audio_bands.is_empty() {
+		let band = ((shape.audio_pos * audio_bands.len() as f32) as usize).min(audio_bands.len() - 1);
+		let level = audio_bands[band];
+		command.color.a *= 0.25 + 0.75 * level;
+		if !config.reduced_motion {
+		    command.radius *= 1.0 + 0.25 * level;
+		}
+	    }
+	    commands.push(command);
+	}
+    }
+    commands
+}
+
+/// The shape drawn in every cell, the colors it picks from, and the random
+/// number generator behind every random choice.
+pub(crate) struct ShapeStyle {
+    pub(crate) shape_id: ShapeId,
+    pub(crate) palette: Palette,
+    pub(crate) rng: RandGenerator,
+    /// The steps of the morph, when that is the shape drawn.
+    pub(crate) morph_steps: Vec<ShapeId>,
+    /// The shapes of a `mix:` and their weights, one picked per cell.
+    pub(crate) mix: Vec<(ShapeId, f32)>,
+    /// What `rng` was seeded with, for the choices made again each frame.
+    pub(crate) seed: u64,
+}
+
+impl ShapeStyle {
+    /// `seed` 0 seeds from the clock, so each run differs.
+    pub(crate) fn new(shape_id: ShapeId, palette: Palette, seed: u64) -> Self {
+	let seed = if seed == 0 { clock_seed() } else { seed };
+	let rng = RandGenerator::new();
+	rng.srand(seed);
+	Self { shape_id, palette, rng, morph_steps: Vec::new(), mix: Vec::new(), seed }
+    }
+
+    /// Follow the shapes to their ids after the registry `moved` them.
+    fn remap(&mut self, moved: impl Fn(ShapeId) -> ShapeId) {
+	self.shape_id = moved(self.shape_id);
+	for step in &mut self.morph_steps {
+	    *step = moved(*step);
+	}
+	for (shape, _) in &mut self.mix {
+	    *shape = moved(*shape);
+	}
+    }
+}
+
+/// A seed from the time now, in nanoseconds.
+pub(crate) fn clock_seed() -> u64 {
+    // The clock the browser build has too
+    ((miniquad::date::now() * 1e9) as u64).max(1)
+}
+
+/// The palette named by `config`, preferring its `palette_image`.
+fn load_palette(config: &Config) -> Palette {
+    let palette = match config.palette_image.as_str() {
+	"" => Palette::resolve(&config.palette),
+	path => Palette::from_image(Path::new(path)).unwrap_or_else(|err| {
+	    warn!("cannot read palette image {path}: {err}");
+	    Palette::resolve(&config.palette)
+	}),
+    };
+    palette.with_random_ranges(config.random_saturation, config.random_value)
+}
+
+/// Register the shapes that follow `config` in `registry`: the hexagon,
+/// tile and morph of its orientation and layout, the polygons its shape
+/// names, with their corners rounded, and the heart outlined for shapes of
+/// `shape_radius`.
+fn register_shapes(config: &Config, registry: &mut ShapeRegistry, shape_radius: f32) {
+    registry.register(Hexagon { orientation: config.orientation, corner_radius: config.corner_radius });
+    registry.register(Heart::for_radius(shape_radius, config.shape_quality));
+    registry.register(Tile { layout: config.layout, orientation: config.orientation, corner_radius: config.corner_radius });
+    registry.register(Morph::new(config.orientation, 0.0));
+    let mix = mix_entries(&config.shape);
+    let names = if mix.is_empty() { vec![config.shape.as_str()] } else { mix.into_iter().map(|(name, _)| name).collect() };
+    for polygon in names.into_iter().filter_map(Polygon::parse) {
+	registry.register(polygon.rounded(config.corner_radius));
+    }
+}
+
+/// The steps of the morph if `config` draws it, registered in `registry`.
+fn morph_steps(config: &Config, registry: &mut ShapeRegistry) -> Vec<ShapeId> {
+    if !config.shape.eq_ignore_ascii_case("morph") {
+	return Vec::new();
+    }
+    (0..MORPH_STEPS).map(|step| registry.register(Morph::step(config.orientation, step))).collect()
+}
+
+/// Whether `config` draws the layout's own tiles, which are turned to fit
+/// their cells; other shapes all stay upright.
+fn fills_cells(config: &Config) -> bool {
+    config.shape.eq_ignore_ascii_case("tile")
+}
+
+/// Whether `config` asks for the shader animation and draws nothing it
+/// cannot do.
+fn animates_on_gpu(config: &Config, style: &ShapeStyle) -> bool {
+    config.gpu_animation
+	&& config.pattern == Pattern::Random
+	&& config.color_mode == ColorMode::Random
+	&& !config.focus
+	&& !config.clock
+	&& config.message.is_empty()
+	&& config.timer.is_empty()
+	&& config.qr.is_empty()
+	&& config.render_mode == RenderMode::Flat
+	&& !config.camera_drift
+	&& !config.spotlight
+	&& !config.click_rings
+	&& config.motion == Motion::Grid
+	&& config.trails == 0.0
+	&& config.hue_shift == 0.0
+	&& config.color_hold_seconds == 0.0
+	&& !config.audio_reactive
+	&& (config.rotation_speed == 0.0 || config.reduced_motion)
+	&& style.morph_steps.is_empty()
+	&& config.photo_folder.is_empty()
+}
+
+/// The picture the `image` color mode paints, if it loads.
+fn mosaic_image(config: &Config) -> Option<Image> {
+    if config.color_mode != ColorMode::Image {
+	return None;
+    }
+    load_image(Path::new(&config.color_image))
+	.map_err(|err| warn!("cannot read color image {}: {err}", config.color_image))
+	.ok()
+}
+
+/// The camera the `webcam` color mode films, capturing about one pixel per
+/// shape over the bounds of `displays`.
+fn webcam(config: &Config, displays: &[Rect], shape_radius: f32) -> Option<Webcam> {
+    if config.color_mode != ColorMode::Webcam {
+	return None;
+    }
+    let bounds = union_bounds(displays);
+    let cell_radius = cell_radius(config, shape_radius);
+    let pixels = |length: f32| (length / cell_radius.max(1.0)).ceil().max(1.0) as u32;
+    Webcam::start(&config.webcam_device, pixels(bounds.w), pixels(bounds.h))
+}
+
+/// Color `shape` with the average of the `cell_radius` square it mostly
+/// covers of `image`, shown covering `area`.
+fn paint_from(shape: &mut Shape, image: &Image, area: Rect, cell_radius: f32) {
+    let area = covering(Vec2::new(image.width as f32, image.height as f32), area);
+    let scale = image.width as f32 / area.w;
+    let corner = (Vec2::new(shape.x, shape.y) - area.point() - Vec2::splat(cell_radius / 2.0)) * scale;
+    shape.color = average_color(image, Rect::new(corner.x, corner.y, cell_radius * scale, cell_radius * scale));
+    shape.next_color = shape.color;
+}
+
+/// One of the shapes of a mix, each as likely as its weight; none from
+/// an empty one.
+fn pick_weighted(mix: &[(ShapeId, f32)], rng: &RandGenerator) -> Option<ShapeId> {
+    let total: f32 = mix.iter().map(|(_, weight)| weight).sum();
+    let mut left = rng.gen_range(0.0, total);
+    let (last, _) = mix.last()?;
+    Some(mix.iter().find(|(_, weight)| {
+	left -= weight;
+	left < 0.0
+    }).map_or(*last, |(shape, _)| *shape))
+}
+
+/// The distance from a cell's center to its edge for `shape_radius` sized
+/// shapes.
+fn cell_radius(config: &Config, shape_radius: f32) -> f32 {
+    config.layout.cell_radius(shape_radius, config.gap * shape_radius / config.radius)
+}
+
+/// The rect around all of `displays`.
+fn union_bounds(displays: &[Rect]) -> Rect {
+    displays.iter().skip(1).fold(displays.first().copied().unwrap_or_default(), |bounds, display| bounds.combine_with(*display))
+}
+
+/// The font pixels of the message `config` scrolls, if it has any the font
+/// can draw.
+fn message_pixels(config: &Config) -> Option<Vec<Vec<bool>>> {
+    Some(rasterize_text(&config.message)).filter(|pixels| !pixels[0].is_empty())
+}
+
+/// The countdown `config` shows, if any.
+fn timer(config: &Config) -> Option<Timer> {
+    parse_periods(&config.timer).filter(|_| !config.timer.is_empty()).map(Timer::new)
+}
+
+/// The modules of the QR code `config` shows, if any.
+fn qr_code(config: &Config) -> Option<Vec<Vec<bool>>> {
+    Some(&config.qr).filter(|text| !text.is_empty()).and_then(|text| qr::encode(text))
+}
+
+/// The photos `config` fills the shapes with, if any load.
+fn slideshow(config: &Config) -> Option<Slideshow> {
+    if config.photo_folder.is_empty() {
+	return None;
+    }
+    Slideshow::new(Path::new(&config.photo_folder), config.photo_minutes)
+	.map_err(|err| warn!("cannot show photos from {}: {err}", config.photo_folder))
+	.ok()
+}
+
+/// One grid of `shape_radius` sized shapes per rect in `displays`. Cells
+/// with their own outlines are registered in `registry` as `cell:<index>`.
+fn layout(config: &Config, style: &mut ShapeStyle, registry: &mut ShapeRegistry, displays: &[Rect], shape_radius: f32) -> Vec<Shape> {
+    // The last layout's cells go, rather than piling up with each one
+    style.remap(registry.remove_prefixed("cell:"));
+    let cell_radius = cell_radius(config, shape_radius);
+    // Overhang by the pixel shift and camera drift so drifting never
+    // uncovers an edge
+    let shift = config.pixel_shift * shape_radius / config.radius;
+    let mosaic = mosaic_image(config);
+    let mut shapes = Vec::new();
+    for display in displays {
+	let overhang = Vec2::splat(shift) + if drifts(config) { drift_overhang(*display) } else { Vec2::ZERO };
+	let bounds = Rect::new(display.x - overhang.x, display.y - overhang.y, display.w + 2.0 * overhang.x, display.h + 2.0 * overhang.y);
+	let tiling =
+	    create_tiling(config.layout, cell_radius, bounds.w, bounds.h, config.grid_rotation, config.orientation, &style.rng);
+	let first = shapes.len();
+	for (cell, neighbors) in tiling.cells.into_iter().zip(tiling.neighbors) {
+	    let own_shape = (!cell.outline.is_empty()).then(|| {
+		let outline = cell.outline.iter().map(|point| *point / cell_radius).collect();
+		registry.register(CellShape { name: format!("cell:{}", shapes.len()), outline })
+	    });
+	    let cell_rotation = if fills_cells(config) || own_shape.is_some() { cell.rotation } else { 0.0 };
+	    let cell = cell.pos + bounds.point();
+	    let mut shape = Shape::new(cell.x, cell.y, shape_radius, config.static_color, &style.palette, &style.rng);
+	    shape.own_shape = own_shape.or_else(|| pick_weighted(&style.mix, &style.rng));
+	    if config.radius_jitter > 0.0 {
+		shape.radius *= 1.0 + style.rng.gen_range(-config.radius_jitter, config.radius_jitter);
+	    }
+	    shape.rotation = config.grid_rotation.to_radians() + cell_rotation;
+	    shape.gradient_pos = config.color_mode.gradient_pos(cell - display.point(), display.size());
+	    if config.color_mode == ColorMode::Bands {
+		let color = style.palette.band(shape.gradient_pos);
+		(shape.color, shape.next_color, shape.static_color) = (color, color, true);
+	    }
+	    shape.audio_pos = ((cell.x - display.x) / display.w.max(1.0)).clamp(0.0, 1.0);
+	    shape.neighbors = neighbors.into_iter().map(|neighbor| first + neighbor).collect();
+	    if config.random_spin && style.rng.gen_range(0, 2) == 0 {
+		shape.spin = -1.0;
+	    }
+	    if let Some(image) = &mosaic {
+		paint_from(&mut shape, image, *display, cell_radius);
+	    }
+	    // Colors come from the picture alone
+	    if mosaic.is_some() || config.color_mode == ColorMode::Webcam {
+		shape.static_color = true;
+	    }
+	    shapes.push(shape);
+	}
+	trace!("grid: {} shapes of radius {shape_radius:.1} over {}x{}", shapes.len() - first, display.w, display.h);
+    }
+    debug!("grid: {} shapes on {} displays", shapes.len(), displays.len());
+    if config.focus
+	&& let Some(display) = displays.first()
+    {
+	let focus_pos = display.point() + config.focus_pos * display.size();
+	if let Some(index) = nearest_shape(&shapes, focus_pos) {
+	    shapes[index].focus = true;
+	}
+    }
+    shapes
+}
+
+/// Whether `command` could show on one of `displays`, allowing a shape's
+/// size of margin for outlines reaching past its radius.
+fn on_screen(displays: &[Rect], command: &DrawCommand) -> bool {
+    let margin = command.radius * 2.0;
+    displays.iter().any(|display| {
+	command.x >= display.left() - margin
+	    && command.x <= display.right() + margin
+	    && command.y >= display.top() - margin
+	    && command.y <= display.bottom() + margin
+    })
+}
+
+/// Seconds the dimming takes to get all the way down to `dim_to`.
+const DIM_FADE: f32 = 600.0;
+
+/// Seconds the playing track's label takes to fade in or out.
+const TRACK_FADE: f32 = 1.5;
+
+/// Opacity of the timer's pulse at its strongest, a wash over the shapes.
+const TIMER_PULSE_OPACITY: f32 = 0.3;
+
+/// The grid offset `amplitude` pixels of pixel shift give after `seconds`,
+/// in whole pixels along a Lissajous figure taking half an hour to repeat.
+fn pixel_shift(amplitude: f32, seconds: f32) -> Vec2 {
+    let (x_period, y_period) = (300.0, 360.0);
+    let angle = |period: f32| (seconds / period).fract() * 2. * PI;
+    (Vec2::new(angle(x_period).sin(), angle(y_period).sin()) * amplitude).round()
+}
+
+/// Seconds the launch spiral takes to reach the corners.
+const SPIRAL_SECONDS: f32 = 2.5;
+/// Turns the launch spiral makes on its way out.
+const SPIRAL_TURNS: f32 = 3.0;
+/// Seconds each shape takes to fade in once the spiral reaches it.
+const SPIRAL_FADE: f32 = 0.3;
+
+/// How much the shape at `pos` on `display` has faded in `seconds` after
+/// launch, 0 until the launch spiral's arm sweeps past it and 1 soon after.
+fn spiral_reveal(pos: Vec2, display: Rect, seconds: f32) -> f32 {
+    let offset = pos - display.center();
+    let distance = offset.length() / (display.size().length() / 2.0).max(1.0) * SPIRAL_TURNS;
+    let angle = (offset.y.atan2(offset.x) / (2. * PI)).rem_euclid(1.0);
+    // The arm is `n + angle` turns out as it passes `angle` for the `n`th time
+    let reached = ((distance - angle).ceil().max(0.0) + angle) / (SPIRAL_TURNS + 1.0);
+    ((seconds - reached * SPIRAL_SECONDS) / SPIRAL_FADE).clamp(0.0, 1.0)
+}
+
+/// Widest the camera drift turns the view either way, in degrees.
+const DRIFT_DEGREES: f32 = 3.0;
+/// Furthest the camera drift zooms in or out, 0.1 being 10%.
+const DRIFT_ZOOM: f32 = 0.1;
+
+/// Whether `config` drifts the camera.
+fn drifts(config: &Config) -> bool {
+    config.camera_drift && !config.reduced_motion
+}
+
+/// The camera drift's zoom and turn in radians `seconds` in, both slow
+/// waves whose periods never line up.
+fn camera_drift(seconds: f32) -> (f32, f32) {
+    let (zoom_period, turn_period) = (420.0, 660.0);
+    let angle = |period: f32| (seconds / period).fract() * 2. * PI;
+    (1.0 + DRIFT_ZOOM * angle(zoom_period).sin(), DRIFT_DEGREES.to_radians() * angle(turn_period).sin())
+}
+
+/// How far past each edge of `display` the grid must reach to keep covering
+/// it as the camera drifts: zoomed out all the way and turned furthest.
+fn drift_overhang(display: Rect) -> Vec2 {
+    let (sin, cos) = DRIFT_DEGREES.to_radians().sin_cos();
+    let turned = Vec2::new(display.w * cos + display.h * sin, display.h * cos + display.w * sin);
+    (turned / (1.0 - DRIFT_ZOOM) - display.size()) / 2.0
+}
+
+/// A grid of twinkling shapes and the clock driving them.
+///
+/// `step` only advances the simulation, so embedders and tests can drive it
+/// without a window; `run` owns a macroquad window until dismissed.
+pub struct Screensaver {
+    config: Config,
+    registry: ShapeRegistry,
+    style: ShapeStyle,
+    shapes: Vec<Shape>,
+    displays: Vec<Rect>,
+    shape_radius: f32,
+    time: f32,
+    patterns: PatternState,
+    background: Vec<Box<dyn BackgroundLayer>>,
+    /// Seconds run, driving the pixel shift at any animation speed.
+    elapsed: f32,
+    /// The grid animated in a shader, uploaded on the first frame drawn.
+    gpu: Option<GpuGrid>,
+    /// Factor on every color on top of the dimming, such as on battery.
+    power_brightness: f32,
+    /// Kept between frames so its meshes are allocated once.
+    batch: MeshBatch,
+    /// The glow's textures, made on the first frame drawn with it.
+    bloom: Option<Bloom>,
+    /// The pass drawing `config.post_effect`, likewise.
+    post: Option<PostPass>,
+    /// The pass folding the frame for `config.kaleidoscope`, likewise.
+    kaleidoscope: Option<Kaleidoscope>,
+    /// The pass smoothing edges for `config.smooth_edges`, likewise.
+    smoothing: Option<Smoothing>,
+    /// The shapes' trails, when on.
+    trails: Option<Trails>,
+    /// The old look fading out after the attract mode moves on.
+    cross_fade: Option<CrossFade>,
+    /// Builds the shapes as prisms in the `3d` render mode.
+    prisms: Prisms,
+    /// The photos filling the shapes, when set.
+    slideshow: Option<Slideshow>,
+    /// The camera coloring the shapes in the `webcam` color mode.
+    webcam: Option<Webcam>,
+    /// The time the clock's shapes spell, empty until they are lit.
+    clock_text: String,
+    overlay: Overlay,
+    /// The temperature shown in the overlay, when known.
+    weather_readout: Option<String>,
+    /// The playing track's label, kept while it fades out.
+    track: String,
+    track_playing: bool,
+    /// How far the track's label has faded in, 0..1.
+    track_fade: f32,
+    ticker: Option<Ticker>,
+    timer: Option<Timer>,
+    /// The font pixels of `config.message`.
+    message: Option<Vec<Vec<bool>>>,
+    /// The modules of `config.qr`.
+    qr: Option<Vec<Vec<bool>>>,
+    /// Where the spotlight shines, when on.
+    spotlight: Option<Vec2>,
+    /// Rings spreading from clicks.
+    rings: Rings,
+    /// Whether the shapes were lit by either last step.
+    boosted: bool,
+}
+
+impl Screensaver {
+    /// Lay out one grid of `shape_radius` sized shapes per rect in `displays`.
+    pub fn new(config: Config, displays: &[Rect], shape_radius: f32) -> Self {
+	Self::with_registry(config, ShapeRegistry::with_builtins(), displays, shape_radius)
+    }
+
+    /// Like `new`, looking `config.shape` up in `registry` so embedders can
+    /// supply their own shapes. The `hexagon` and `tile` entries are
+    /// replaced by built-in ones matching `config.orientation` and
+    /// `config.layout`, and the `heart` by one as fine as `shape_radius`
+    /// needs.
+    pub fn with_registry(config: Config, mut registry: ShapeRegistry, displays: &[Rect], shape_radius: f32) -> Self {
+	register_shapes(&config, &mut registry, shape_radius);
+	let shape_id = registry.resolve(&config.shape);
+	let mut style = ShapeStyle::new(shape_id, load_palette(&config), config.seed);
+	style.morph_steps = morph_steps(&config, &mut registry);
+	style.mix = registry.resolve_mix(&config.shape);
+	let shapes = layout(&config, &mut style, &mut registry, displays, shape_radius);
+	let background = layers(&config, displays, &style.rng);
+	let slideshow = slideshow(&config);
+	let webcam = webcam(&config, displays, shape_radius);
+	let timer = timer(&config);
+	let message = message_pixels(&config);
+	let qr = qr_code(&config);
+	let ticker = Ticker::start(&config.ticker);
+	Self {
+	    config,
+	    registry,
+	    style,
+	    shapes,
+	    displays: displays.to_vec(),
+	    shape_radius,
+	    time: 0.0,
+	    patterns: PatternState::default(),
+	    background,
+	    elapsed: 0.0,
+	    gpu: None,
+	    power_brightness: 1.0,
+	    batch: MeshBatch::new(),
+	    bloom: None,
+	    post: None,
+	    kaleidoscope: None,
+	    smoothing: None,
+	    trails: None,
+	    cross_fade: None,
+	    prisms: Prisms::new(),
+	    slideshow,
+	    webcam,
+	    clock_text: String::new(),
+	    overlay: Overlay::new(),
+	    weather_readout: None,
+	    track: String::new(),
+	    track_playing: false,
+	    track_fade: 0.0,
+	    ticker,
+	    timer,
+	    message,
+	    qr,
+	    spotlight: None,
+	    rings: Rings::default(),
+	    boosted: false,
+	}
+    }
+
+    pub fn config(&self) -> &Config {
+	&self.config
+    }
+
+    /// Switch to `config` while running. Shape, palette and timing changes
+    /// apply to the current grid; anything affecting the layout or the
+    /// random seed lays the grid out again.
+    pub fn set_config(&mut self, config: Config) {
+	let relayout = config.grid_rotation != self.config.grid_rotation
+	    || config.focus != self.config.focus
+	    || config.focus_pos != self.config.focus_pos
+	    || config.static_color != self.config.static_color
+	    || config.color_mode != self.config.color_mode
+	    || config.color_image != self.config.color_image
+	    || config.seed != self.config.seed
+	    || config.radius != self.config.radius
+	    || config.gap != self.config.gap
+	    || config.radius_jitter != self.config.radius_jitter
+	    || config.orientation != self.config.orientation
+	    || config.layout != self.config.layout
+	    // Back into their cells
+	    || config.motion != self.config.motion
+	    || config.random_spin != self.config.random_spin
+	    || config.pixel_shift != self.config.pixel_shift
+	    || drifts(&config) != drifts(&self.config)
+	    || fills_cells(&config) != fills_cells(&self.config)
+	    // Each cell is given its shape of a mix as it is laid out
+	    || config.shape != self.config.shape && !(self.style.mix.is_empty() && mix_entries(&config.shape).is_empty())
+	    // The stripes are colored as the shapes are laid out
+	    || config.color_mode == ColorMode::Bands && (config.palette != self.config.palette || config.palette_image != self.config.palette_image);
+	if config.orientation != self.config.orientation
+	    || config.layout != self.config.layout
+	    || config.corner_radius != self.config.corner_radius
+	    || config.shape != self.config.shape
+	    || config.radius != self.config.radius
+	    || config.shape_quality != self.config.shape_quality
+	{
+	    // At the radius the grid is laid out at below
+	    register_shapes(&config, &mut self.registry, self.shape_radius * config.radius / self.config.radius);
+	}
+	if config.shape != self.config.shape {
+	    self.style.shape_id = self.registry.resolve(&config.shape);
+	    self.style.mix = self.registry.resolve_mix(&config.shape);
+	}
+	if config.shape != self.config.shape || config.orientation != self.config.orientation {
+	    self.style.morph_steps = morph_steps(&config, &mut self.registry);
+	}
+	if config.palette != self.config.palette
+	    || config.palette_image != self.config.palette_image
+	    || config.random_saturation != self.config.random_saturation
+	    || config.random_value != self.config.random_value
+	{
+	    self.style.palette = load_palette(&config);
+	}
+	if relayout {
+	    // Keeps a preview's scale relative to the configured radius
+	    self.shape_radius *= config.radius / self.config.radius;
+	    let (morph_steps, mix) = (std::mem::take(&mut self.style.morph_steps), std::mem::take(&mut self.style.mix));
+	    self.style = ShapeStyle::new(self.style.shape_id, self.style.palette.clone(), config.seed);
+	    (self.style.morph_steps, self.style.mix) = (morph_steps, mix);
+	    self.shapes = layout(&config, &mut self.style, &mut self.registry, &self.displays, self.shape_radius);
+	}
+	// Visibility was worked out for the old settings
+	for shape in &mut self.shapes {
+	    shape.next_visible = 0.0;
+	}
+	if relayout
+	    || config.stars != self.config.stars
+	    || config.star_density != self.config.star_density
+	    || config.star_speed != self.config.star_speed
+	    || config.background_gradient != self.config.background_gradient
+	    || config.background_image != self.config.background_image
+	    || config.background_fit != self.config.background_fit
+	{
+	    self.background = layers(&config, &self.displays, &self.style.rng);
+	}
+	self.gpu = None;
+	if config.post_effect != self.config.post_effect {
+	    self.post = None;
+	}
+	if config.photo_folder != self.config.photo_folder || config.photo_minutes != self.config.photo_minutes {
+	    self.slideshow = slideshow(&config);
+	}
+	if relayout || config.webcam_device != self.config.webcam_device {
+	    // Stop the old capture before opening the camera again
+	    self.webcam = None;
+	    self.webcam = webcam(&config, &self.displays, self.shape_radius);
+	}
+	if config.timer != self.config.timer {
+	    self.timer = timer(&config);
+	}
+	if config.message != self.config.message {
+	    self.message = message_pixels(&config);
+	}
+	if config.qr != self.config.qr {
+	    self.qr = qr_code(&config);
+	}
+	if config.ticker != self.config.ticker {
+	    self.ticker = Ticker::start(&config.ticker);
+	}
+	// The clock, timer or QR code lights them again on the next step
+	for shape in &mut self.shapes {
+	    shape.lit = false;
+	    shape.dark = false;
+	}
+	self.clock_text.clear();
+	self.prisms.clear();
+	self.config = config;
+    }
+
+    /// Lay the grids out again over `displays`, such as after a resize.
+    /// Shapes left at the same position keep their colors and phases.
+    pub fn set_displays(&mut self, displays: &[Rect]) {
+	let key = |shape: &Shape| (shape.x.round() as i32, shape.y.round() as i32);
+	let previous: HashMap<_, _> = self.shapes.drain(..).map(|shape| (key(&shape), shape)).collect();
+	self.displays = displays.to_vec();
+	self.shapes = layout(&self.config, &mut self.style, &mut self.registry, &self.displays, self.shape_radius);
+	self.background = layers(&self.config, &self.displays, &self.style.rng);
+	self.gpu = None;
+	self.clock_text.clear();
+	self.prisms.clear();
+	if self.webcam.is_some() {
+	    self.webcam = None;
+	    self.webcam = webcam(&self.config, &self.displays, self.shape_radius);
+	}
+	for shape in &mut self.shapes {
+	    if let Some(old) = previous.get(&key(shape)) {
+		shape.color = old.color;
+		shape.next_color = old.next_color;
+		shape.transition_progress = old.transition_progress;
+		shape.hold = old.hold;
+		shape.phase_offset = old.phase_offset;
+	    }
+	}
+    }
+
+    pub fn registry(&self) -> &ShapeRegistry {
+	&self.registry
+    }
+
+    /// Advance the animation by `dt` seconds and return what should be drawn,
+    /// pulsing with `audio_bands` (levels 0..1, low to high frequencies) when
+    /// given. Nothing is returned while the shader animates the grid.
+    pub fn step(&mut self, dt: f32, audio_bands: &[f32]) -> Vec<DrawCommand> {
+	for layer in &mut self.background {
+	    layer.step(dt * self.config.animation_speed());
+	}
+	if let Some(slideshow) = &mut self.slideshow {
+	    slideshow.step(dt);
+	}
+	let fade = if self.track_playing { dt } else { -dt } / TRACK_FADE;
+	self.track_fade = (self.track_fade + fade).clamp(0.0, 1.0);
+	if let Some(ticker) = &mut self.ticker {
+	    ticker.step(dt, self.config.ticker_speed);
+	}
+	if let Some(cross_fade) = &mut self.cross_fade {
+	    cross_fade.step(dt);
+	}
+	if self.gpu.is_some() {
+	    self.time += dt * self.config.animation_speed();
+	    self.elapsed += dt;
+	    return Vec::new();
+	}
+	if let Some(code) = &self.qr {
+	    light_code(&mut self.shapes, &self.displays, code, cell_radius(&self.config, self.shape_radius));
+	} else if let Some(timer) = &mut self.timer {
+	    timer.step(dt);
+	    // The bar shrinks toward the left of each display
+	    let remaining = timer.remaining();
+	    for shape in &mut self.shapes {
+		shape.lit = shape.audio_pos < remaining;
+	    }
+	} else if let Some(message) = &self.message {
+	    let cell_radius = cell_radius(&self.config, self.shape_radius);
+	    light_marquee(&mut self.shapes, &self.displays, message, cell_radius, self.elapsed, self.config.message_speed);
+	} else if self.config.clock {
+	    // Checked every frame, relit once a minute
+	    let text = current_text();
+	    if text != self.clock_text {
+		light_text(&mut self.shapes, &self.displays, &text, cell_radius(&self.config, self.shape_radius));
+		self.clock_text = text;
+	    }
+	}
+	if let Some(frame) = self.webcam.as_ref().and_then(Webcam::take_frame) {
+	    let cell_radius = cell_radius(&self.config, self.shape_radius);
+	    let area = union_bounds(&self.displays);
+	    for shape in &mut self.shapes {
+		paint_from(shape, &frame, area, cell_radius);
+	    }
+	}
+	self.rings.step(dt);
+	let boosting = self.spotlight.is_some() || !self.rings.is_empty();
+	// Once more after both are gone, to put the shapes out
+	if boosting || self.boosted {
+	    let radius = self.config.spotlight_radius;
+	    for shape in &mut self.shapes {
+		let pos = Vec2::new(shape.x, shape.y);
+		let closeness = self.spotlight.map_or(0.0, |spot| (1.0 - pos.distance(spot) / radius).max(0.0));
+		shape.boost = (closeness * closeness).max(self.rings.strength(pos));
+	    }
+	    self.boosted = boosting;
+	}
+	if self.config.motion != Motion::Grid && !self.config.reduced_motion {
+	    let scale = self.shape_radius / self.config.radius;
+	    let motion_dt = dt * self.config.animation_speed();
+	    if self.config.motion == Motion::Flow {
+		let (speed, field) = (self.config.flow_speed * scale, self.config.flow_scale * self.shape_radius);
+		drift(&mut self.shapes, &self.displays, self.time, motion_dt, speed, field);
+	    } else {
+		fall(&mut self.shapes, &self.displays, motion_dt, scale, &self.style.rng);
+	    }
+	}
+	let mut commands = step_frame(&mut self.shapes, &mut self.time, &mut self.patterns, dt, &self.config, &self.style, audio_bands);
+	self.elapsed += dt;
+	if !self.rings.is_empty() {
+	    for command in &mut commands {
+		let strength = self.rings.strength(Vec2::new(command.x, command.y));
+		if strength > 0.0 {
+		    let (hue, saturation, value) = rgb_to_hsv(command.color);
+		    command.color = Color { a: command.color.a, ..hsv_to_rgb(hue + strength * RING_HUE_SHIFT, saturation, value) };
+		}
+	    }
+	}
+	if self.spiraling() {
+	    commands.retain_mut(|command| {
+		let pos = Vec2::new(command.x, command.y);
+		let display = self.displays.iter().find(|display| display.contains(pos)).or(self.displays.first());
+		command.color.a *= display.map_or(1.0, |display| spiral_reveal(pos, *display, self.elapsed));
+		command.color.a > 0.0
+	    });
+	}
+	if self.slideshow.is_some() {
+	    // The photo shows in its own colors
+	    for command in &mut commands {
+		command.color = Color { a: command.color.a, ..WHITE };
+	    }
+	}
+	let brightness = self.brightness();
+	if brightness < 1.0 {
+	    for command in &mut commands {
+		command.color.r *= brightness;
+		command.color.g *= brightness;
+		command.color.b *= brightness;
+	    }
+	}
+	if self.config.pixel_shift > 0.0 {
+	    let offset = pixel_shift(self.config.pixel_shift * self.shape_radius / self.config.radius, self.elapsed);
+	    for command in &mut commands {
+		command.x += offset.x;
+		command.y += offset.y;
+	    }
+	}
+	if drifts(&self.config) {
+	    let (zoom, turn) = camera_drift(self.elapsed);
+	    let rotation = Vec2::from_angle(turn);
+	    for command in &mut commands {
+		let pos = Vec2::new(command.x, command.y);
+		let Some(display) = self.displays.iter().find(|display| display.contains(pos)).or(self.displays.first()) else {
+		    break;
+		};
+		let pos = display.center() + rotation.rotate(pos - display.center()) * zoom;
+		(command.x, command.y) = (pos.x, pos.y);
+		command.radius *= zoom;
+		command.rotation += turn;
+	    }
+	}
+	// Nothing moves the grid off screen but the pixel shift and camera
+	// drift, which the layout overhangs; this culls the overhang
+	commands.retain(|command| on_screen(&self.displays, command));
+	if self.config.temperature_shift {
+	    let tint = current_tint(&self.config);
+	    for command in &mut commands {
+		command.color.r *= tint.r;
+		command.color.g *= tint.g;
+		command.color.b *= tint.b;
+	    }
+	}
+	if self.config.hue_shift != 0.0 {
+	    let turn = self.config.hue_shift / 360.0;
+	    for command in &mut commands {
+		let (hue, saturation, value) = rgb_to_hsv(command.color);
+		command.color = Color { a: command.color.a, ..hsv_to_rgb(hue + turn, saturation, value) };
+	    }
+	}
+	commands
+    }
+
+    /// Seconds until the next shape shows while none are drawn and nothing
+    /// else moves, the time the caller can sleep instead of drawing empty
+    /// frames; `None` while frames should keep coming.
+    pub fn idle_time(&self) -> Option<f32> {
+	let speed = self.config.animation_speed();
+	if self.gpu.is_some() || self.cross_fade.as_ref().is_some_and(CrossFade::fading) || self.timer.is_some() || self.message.is_some() || self.ticker.is_some() || self.spotlight.is_some() || !self.rings.is_empty() || self.background.iter().any(|layer| layer.animated()) || speed <= 0.0 || self.shapes.iter().any(|shape| shape.opacity > 0.0 || shape.focus || shape.lit) {
+	    return None;
+	}
+	let next = self.shapes.iter().map(|shape| shape.next_visible).fold(f32::INFINITY, f32::min);
+	Some((next - self.time) / speed).filter(|idle| *idle > 0.0)
+    }
+
+    /// Factor on every color: `dim`, dimming further after
+    /// `dim_after_minutes` of running.
+    pub fn brightness(&self) -> f32 {
+	let brightness = self.config.dim * self.power_brightness;
+	if self.config.dim_after_minutes <= 0.0 {
+	    return brightness;
+	}
+	let progress = ((self.elapsed - self.config.dim_after_minutes * 60.0) / DIM_FADE).clamp(0.0, 1.0);
+	(1.0 - (1.0 - self.config.dim_to) * progress) * brightness
+    }
+
+    /// Whether the shapes are still spiraling in after launch.
+    fn spiraling(&self) -> bool {
+	self.config.spiral_start && self.elapsed < SPIRAL_SECONDS + SPIRAL_FADE
+    }
+
+    /// How many shapes the grids hold, lit or not.
+    pub fn shape_count(&self) -> usize {
+	self.shapes.len()
+    }
+
+    /// The shapes, those among `commands` and the meshes drawn since the
+    /// last call, for the debug overlay.
+    pub(crate) fn frame_stats(&mut self, commands: &[DrawCommand]) -> FrameStats {
+	FrameStats { shapes: self.shape_count(), visible: self.gpu.is_none().then_some(commands.len()), draws: self.batch.take_draw_count() }
+    }
+
+    /// Where the animation is, for other instances to keep in step with.
+    pub(crate) fn beat(&self) -> Beat {
+	Beat { time: self.time, seed: self.style.seed, palette: self.config.palette.clone() }
+    }
+
+    /// Jump the animation to `time`, as another instance has it.
+    pub(crate) fn set_time(&mut self, time: f32) {
+	self.time = time;
+	for shape in &mut self.shapes {
+	    shape.next_visible = 0.0;
+	}
+    }
+
+    /// Take the settings in `tuning` until the next `set_config`, without
+    /// laying anything out again, as live input moves them every frame.
+    pub(crate) fn tune(&mut self, tuning: Tuning) {
+	if let Some(density) = tuning.density
+	    && density != self.config.density
+	{
+	    self.config.density = density.clamp(0.0, 1.0);
+	    for shape in &mut self.shapes {
+		shape.next_visible = 0.0;
+	    }
+	}
+	if let Some(speed) = tuning.speed {
+	    self.config.speed = speed.clamp(0.0, 10.0);
+	}
+	if let Some(hue_shift) = tuning.hue_shift {
+	    self.config.hue_shift = hue_shift;
+	    if !animates_on_gpu(&self.config, &self.style) {
+		self.gpu = None;
+	    }
+	}
+    }
+
+    /// Scale every color by `brightness` on top of the dimming.
+    pub(crate) fn set_power_brightness(&mut self, brightness: f32) {
+	self.power_brightness = brightness;
+    }
+
+    /// Light up the shapes around `pos` from the next step, or none for
+    /// `None`.
+    pub(crate) fn set_spotlight(&mut self, pos: Option<Vec2>) {
+	self.spotlight = pos;
+    }
+
+    /// Spread a ring from `pos`.
+    pub(crate) fn add_ring(&mut self, pos: Vec2) {
+	self.rings.add(pos);
+    }
+
+    /// Show `readout`, such as the temperature, in the overlay.
+    pub(crate) fn set_weather_readout(&mut self, readout: Option<String>) {
+	self.weather_readout = readout;
+    }
+
+    /// Fade `label`, the track playing, in the overlay, or fade the last one
+    /// out for `None`.
+    pub(crate) fn set_track(&mut self, label: Option<String>) {
+	self.track_playing = label.is_some();
+	if let Some(label) = label {
+	    if label != self.track {
+		self.track_fade = 0.0;
+	    }
+	    self.track = label;
+	}
+    }
+
+    /// The color to clear each frame to, dimmed along with the shapes.
+    pub fn background_color(&self) -> Color {
+	let color = self.config.background_color();
+	let brightness = self.brightness();
+	Color::new(color.r * brightness, color.g * brightness, color.b * brightness, color.a)
+    }
+
+    /// Draw the background layers, then the commands returned by `step`,
+    /// or the grid in a shader when `config.gpu_animation` can do it, over
+    /// their trails, then their glow, smoothed by `config.smooth_edges` and
+    /// folded by `config.kaleidoscope`, then the overlay,
+    /// the ticker and the timer's pulse, all through `config.post_effect`.
+    pub fn draw(&mut self, commands: &[DrawCommand]) {
+	let bounds = union_bounds(&self.displays);
+	let flat = self.config.render_mode != RenderMode::Prisms;
+	if flat && self.config.post_effect != PostEffect::None && !self.post.as_ref().is_some_and(|post| post.fits(bounds)) {
+	    match PostPass::new(&self.config.post_effect, bounds) {
+		Ok(post) => self.post = Some(post),
+		Err(err) => {
+		    warn!("cannot draw the {:?} effect: {err}", self.config.post_effect);
+		    self.config.post_effect = PostEffect::None;
+		}
+	    }
+	}
+	let mut post = self.post.take().filter(|_| flat && self.config.post_effect != PostEffect::None);
+	if let Some(post) = &mut post {
+	    post.capture(bounds, self.background_color());
+	}
+	if flat && self.config.kaleidoscope > 0 && !self.kaleidoscope.as_ref().is_some_and(|kaleidoscope| kaleidoscope.fits(bounds)) {
+	    match Kaleidoscope::new(bounds) {
+		Ok(kaleidoscope) => self.kaleidoscope = Some(kaleidoscope),
+		Err(err) => {
+		    warn!("cannot draw the kaleidoscope: {err}");
+		    self.config.kaleidoscope = 0;
+		}
+	    }
+	}
+	let mut kaleidoscope = self.kaleidoscope.take().filter(|_| flat && self.config.kaleidoscope > 0);
+	if let Some(kaleidoscope) = &mut kaleidoscope {
+	    kaleidoscope.capture(bounds, self.background_color());
+	}
+	if flat && self.config.smooth_edges && !self.smoothing.as_ref().is_some_and(|smoothing| smoothing.fits(bounds)) {
+	    match Smoothing::new(bounds) {
+		Ok(smoothing) => self.smoothing = Some(smoothing),
+		Err(err) => {
+		    warn!("cannot smooth the edges: {err}");
+		    self.config.smooth_edges = false;
+		}
+	    }
+	}
+	let mut smoothing = self.smoothing.take().filter(|_| flat && self.config.smooth_edges);
+	if let Some(smoothing) = &mut smoothing {
+	    smoothing.capture(bounds, self.background_color());
+	}
+	for layer in &self.background {
+	    layer.draw(self.brightness());
+	}
+	if flat && self.config.trails > 0.0 && !self.trails.as_ref().is_some_and(|trails| trails.fits(bounds)) {
+	    match Trails::new(bounds) {
+		Ok(trails) => self.trails = Some(trails),
+		Err(err) => {
+		    warn!("cannot draw the trails: {err}");
+		    self.config.trails = 0.0;
+		}
+	    }
+	}
+	let mut trails = self.trails.take().filter(|_| flat && self.config.trails > 0.0);
+	if let Some(trails) = &mut trails {
+	    trails.capture(bounds, self.config.trails);
+	}
+	self.draw_shapes(bounds, commands);
+	if let Some(trails) = &trails {
+	    trails.finish(bounds);
+	}
+	self.trails = trails;
+	if flat && self.config.bloom {
+	    self.draw_bloom(bounds, commands);
+	}
+	if let Some(smoothing) = &smoothing {
+	    smoothing.finish(bounds);
+	}
+	self.smoothing = smoothing;
+	if let Some(kaleidoscope) = &kaleidoscope {
+	    kaleidoscope.finish(bounds, &self.displays, self.config.kaleidoscope);
+	}
+	self.kaleidoscope = kaleidoscope;
+	let mut lines = Vec::new();
+	lines.extend(self.weather_readout.as_deref().map(|readout| (readout, 1.0)));
+	let date = if self.config.date_overlay { Some(self.overlay.date().to_string()) } else { None };
+	lines.extend(date.as_deref().map(|date| (date, 1.0)));
+	if self.config.media_overlay && self.track_fade > 0.0 {
+	    lines.push((&self.track, self.track_fade));
+	}
+	if !lines.is_empty() {
+	    self.overlay.draw(&self.displays, self.config.date_corner, self.brightness(), &lines);
+	}
+	let brightness = self.brightness();
+	if let Some(ticker) = &mut self.ticker {
+	    ticker.draw(&self.displays, brightness);
+	}
+	if let Some(pulse) = self.timer.as_ref().map(Timer::pulse).filter(|pulse| *pulse > 0.0) {
+	    let color = parse_hex_color(&self.config.timer_color).unwrap_or(WHITE);
+	    let brightness = self.brightness();
+	    let color = Color::new(color.r * brightness, color.g * brightness, color.b * brightness, pulse * TIMER_PULSE_OPACITY);
+	    for display in &self.displays {
+		draw_rectangle(display.x, display.y, display.w, display.h, color);
+	    }
+	}
+	if let Some(post) = &mut post {
+	    post.finish(bounds);
+	}
+	self.post = post;
+	if let Some(cross_fade) = &self.cross_fade {
+	    cross_fade.draw(bounds);
+	}
+    }
+
+    /// Keep the frame `commands` draw to fade out from, before moving on
+    /// to a new look.
+    pub(crate) fn cross_fade_from(&mut self, commands: &[DrawCommand]) {
+	let bounds = union_bounds(&self.displays);
+	let mut cross_fade = self.cross_fade.take().filter(|cross_fade| cross_fade.fits(bounds)).unwrap_or_else(|| CrossFade::new(bounds));
+	cross_fade.capture(bounds, self.background_color());
+	self.draw(commands);
+	cross_fade.finish();
+	self.cross_fade = Some(cross_fade);
+    }
+
+    /// Add the glow of `commands` over `bounds`.
+    fn draw_bloom(&mut self, bounds: Rect, commands: &[DrawCommand]) {
+	if !self.bloom.as_ref().is_some_and(|bloom| bloom.fits(bounds)) {
+	    match Bloom::new(bounds) {
+		Ok(bloom) => self.bloom = Some(bloom),
+		Err(err) => {
+		    warn!("cannot draw the glow: {err}");
+		    self.config.bloom = false;
+		    return;
+		}
+	    }
+	}
+	let mut bloom = self.bloom.take().expect("made above");
+	bloom.capture(bounds);
+	self.draw_shapes(bounds, commands);
+	bloom.compose(bounds, self.config.bloom_radius * self.shape_radius / self.config.radius, self.config.bloom_intensity);
+	self.bloom = Some(bloom);
+    }
+
+    fn draw_shapes(&mut self, bounds: Rect, commands: &[DrawCommand]) {
+	if self.config.gpu_animation && self.gpu.is_none() && !self.spiraling() && animates_on_gpu(&self.config, &self.style) {
+	    match GpuGrid::new(&self.registry, &self.shapes, self.style.shape_id) {
+		Ok(gpu) => self.gpu = Some(gpu),
+		Err(err) => {
+		    warn!("cannot animate on the GPU: {err}");
+		    self.config.gpu_animation = false;
+		}
+	    }
+	}
+	let Some(gpu) = &self.gpu else {
+	    match self.config.render_mode {
+		RenderMode::Flat => {}
+		RenderMode::Prisms => return self.prisms.draw(&self.registry, commands, &self.displays, bounds, self.elapsed),
+		RenderMode::Isometric => return isometric::draw(&mut self.batch, &self.registry, commands, &self.displays),
+	    }
+	    let photo = self.slideshow.as_mut().and_then(|slideshow| slideshow.texture()).cloned();
+	    if let Some(photo) = &photo {
+		self.batch.set_texture(Some(photo.clone()), covering(photo.size(), bounds));
+	    }
+	    for command in commands {
+		self.batch.add(&self.registry, command);
+	    }
+	    self.batch.flush();
+	    if photo.is_some() {
+		self.batch.set_texture(None, bounds);
+	    }
+	    return;
+	};
+	let mut tint = Vec3::splat(self.brightness());
+	if self.config.temperature_shift {
+	    let color = current_tint(&self.config);
+	    tint *= Vec3::new(color.r, color.g, color.b);
+	}
+	let offset = if self.config.pixel_shift > 0.0 {
+	    pixel_shift(self.config.pixel_shift * self.shape_radius / self.config.radius, self.elapsed)
+	} else {
+	    Vec2::ZERO
+	};
+	gpu.draw(self.time, &self.config, tint, offset);
+    }
+
+    /// Advance by `dt` and render the frame in software, returning
+    /// `width` by `height` top-down RGBA pixels. Works without a window.
+    pub fn render_headless(&mut self, dt: f32, width: usize, height: usize) -> Vec<u8> {
+	let commands = self.step(dt, &[]);
+	rasterize(&self.registry, &commands, width, height)
+    }
+
+    /// Run in the current window until Escape, a click or mouse movement.
+    /// Space pauses and resumes, and `.` steps a paused animation.
+    pub async fn run(config: Config) {
+	run_screensaver(config, None, false, true).await;
+    }
+}
+
+fn screen_rect() -> Rect {
+    Rect::new(0.0, 0.0, screen_width(), screen_height())
+}
+
+/// Whether the preview host window still exists.
+fn preview_host_alive(parent: isize) -> bool {
+    #[cfg(windows)]
+    {
+	win32::is_window(parent)
+    }
+    #[cfg(target_os = "linux")]
+    {
+	x11::is_window(parent as u64)
+    }
+    #[cfg(not(any(windows, target_os = "linux")))]
+    {
+	let _ = parent;
+	false
+    }
+}
+
+/// Render a scaled-down screensaver inside the settings dialog's preview.
+pub async fn run_preview(config: Config, parent: isize) {
+    #[cfg(windows)]
+    {
+	if !win32::embed_in(parent) {
+	    return;
+	}
+	// Let the resize from reparenting land before the grid is laid out
+	next_frame().await;
+	run_screensaver(config, Some(parent), false, false).await;
+    }
+    #[cfg(not(windows))]
+    let _ = (config, parent);
+}
+
+/// Frames to wait for our window to show before giving up on xscreensaver's.
+#[cfg(target_os = "linux")]
+const XSCREENSAVER_TRIES: u32 = 120;
+
+/// The title of the window `run_xscreensaver` and `run_widget` look for,
+/// unique to this process.
+pub fn xscreensaver_title() -> String {
+    format!("Heart Screensaver {}", std::process::id())
+}
+
+/// Draw inside the X window xscreensaver provides, `window` or with `-root`
+/// the one it names or the virtual root, until it kills us.
+pub async fn run_xscreensaver(config: Config, window: Option<u64>) {
+    #[cfg(target_os = "linux")]
+    {
+	// The window is only mapped some frames after it is created
+	let mut parent = None;
+	for _ in 0..XSCREENSAVER_TRIES {
+	    next_frame().await;
+	    parent = x11::embed_in(window, &xscreensaver_title());
+	    if parent.is_some() {
+		break;
+	    }
+	}
+	let Some(parent) = parent else {
+	    warn!("xscreensaver: could not draw in the X window");
+	    return;
+	};
+	// Let the resize from reparenting land before the grid is laid out
+	next_frame().await;
+	run_screensaver(config, Some(parent as isize), false, false).await;
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (config, window);
+}
+
+/// The connected displays when `spanning` them, otherwise the window.
+fn current_displays(spanning: bool) -> Vec<Rect> {
+    #[cfg(windows)]
+    let displays = match spanning {
+	// Monitors are measured in pixels, the grids in points
+	true => {
+	    let scale = screen_dpi_scale();
+	    win32::span_monitors().into_iter().map(|rect| Rect::new(rect.x / scale, rect.y / scale, rect.w / scale, rect.h / scale)).collect()
+	}
+	false => Vec::new(),
+    };
+    #[cfg(not(windows))]
+    let displays = {
+	let _ = spanning;
+	Vec::new()
+    };
+    if displays.is_empty() { vec![screen_rect()] } else { displays }
+}
+
+/// Grow a window opened at its size in pixels to that size in points, so it
+/// is not tiny on a scaled display.
+pub async fn size_in_points() {
+    let scale = screen_dpi_scale();
+    if scale != 1.0 {
+	request_new_screen_size(screen_width() * scale, screen_height() * scale);
+	next_frame().await;
+    }
+}
+
+/// Screensaver mode: cover every connected display, each with its own grid.
+pub async fn run_fullscreen(config: Config) {
+    #[cfg(windows)]
+    next_frame().await;
+    // Spread the window now, to pick up its new size before laying out the
+    // grids
+    #[cfg(windows)]
+    {
+	current_displays(true);
+	next_frame().await;
+    }
+    run_screensaver(config, None, true, false).await;
+}
+
+/// Widget mode: decorate `widget_region` of the desktop under the other
+/// windows, the black background letting the desktop show where that can
+/// be done, ignoring input as the window never has it.
+pub async fn run_widget(config: Config) {
+    next_frame().await;
+    #[cfg(windows)]
+    let placed = win32::make_widget(config.widget_region);
+    #[cfg(target_os = "linux")]
+    let placed = x11::make_widget(&xscreensaver_title(), config.widget_region);
+    #[cfg(not(any(windows, target_os = "linux")))]
+    let placed = false;
+    if !placed {
+	warn!("widget: cannot place the window on the desktop here, running in it as it is");
+    }
+    // Pick up the new window size before laying out the grid
+    next_frame().await;
+    let config = Config {
+	kiosk: true,
+	hide_cursor: false,
+	background: "#000000".to_string(),
+	background_gradient: String::new(),
+	background_image: String::new(),
+	..config
+    };
+    run_screensaver(config, None, false, false).await;
+}
+
+/// Wallpaper mode: draw behind the desktop icons, slowed to
+/// `wallpaper_fps`, until the shell closes the window behind them.
+pub async fn run_wallpaper(config: Config) {
+    #[cfg(windows)]
+    {
+	next_frame().await;
+	if !win32::attach_to_wallpaper() {
+	    warn!("wallpaper: could not find the window behind the desktop icons");
+	    return;
+	}
+	// Let the resize from reparenting land before the grid is laid out
+	next_frame().await;
+	let max_fps = if config.max_fps > 0.0 { config.max_fps.min(config.wallpaper_fps) } else { config.wallpaper_fps };
+	// The icons take the input, and the desktop redrawn while fading
+	// would flash through
+	let config = Config { kiosk: true, hide_cursor: false, max_fps, fade_in: 0.0, ..config };
+	run_screensaver(config, None, false, false).await;
+    }
+    #[cfg(not(windows))]
+    {
+	let _ = config;
+    }
+}
+
+/// `config`, with `reduced_motion` also on when the system prefers it.
+fn honor_system_motion(config: Config) -> Config {
+    Config { reduced_motion: config.reduced_motion || system_prefers_reduced_motion(), ..config }
+}
+
+/// Whether the desktop asks applications to cut down on animation, where
+/// that can be found out.
+fn system_prefers_reduced_motion() -> bool {
+    #[cfg(windows)]
+    {
+	win32::animations_disabled()
+    }
+    #[cfg(target_os = "macos")]
+    {
+	command_output("defaults", &["read", "com.apple.universalaccess", "reduceMotion"]).is_some_and(|value| value == "1")
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+	command_output("gsettings", &["get", "org.gnome.desktop.interface", "enable-animations"]).is_some_and(|value| value == "false")
+    }
+}
+
+/// Lock the session, showing the login screen.
+fn lock_session() {
+    #[cfg(windows)]
+    win32::lock_workstation();
+    #[cfg(target_os = "macos")]
+    warn!("lock_on_exit: locking is not supported on macOS");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    if command_output("loginctl", &["lock-session"]).is_none() {
+	warn!("lock_on_exit: loginctl lock-session failed");
+    }
+}
+
+/// The trimmed standard output of running `program`, if it succeeded.
+#[cfg(not(windows))]
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Hides the mouse cursor until dropped, which also happens when
+/// unwinding from a panic.
+struct HiddenCursor;
+
+impl HiddenCursor {
+    fn new() -> Self {
+	show_mouse(false);
+	Self
+    }
+}
+
+impl Drop for HiddenCursor {
+    fn drop(&mut self) {
+	show_mouse(true);
+    }
+}
+
+/// Sleeps out the rest of each frame to hold a frame rate.
+struct FramePacer {
+    /// In seconds, from `miniquad::date::now`.
+    frame_start: f64,
+}
+
+impl FramePacer {
+    fn new() -> Self {
+	Self { frame_start: miniquad::date::now() }
+    }
+
+    /// Wait until a frame at `max_fps` has passed since the last call;
+    /// 0 doesn't wait.
+    fn wait(&mut self, max_fps: f32) {
+	if max_fps > 0.0 {
+	    let remaining = 1.0 / f64::from(max_fps) - (miniquad::date::now() - self.frame_start);
+	    if remaining > 0.0 {
+		sleep(remaining as f32);
+	    }
+	}
+	self.frame_start = miniquad::date::now();
+    }
+}
+
+/// Wait `seconds` before the next frame. A page cannot block, and the
+/// browser paces its frames anyway.
+fn sleep(seconds: f32) {
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::sleep(std::time::Duration::from_secs_f32(seconds));
+    #[cfg(target_arch = "wasm32")]
+    let _ = seconds;
+}
+
+/// Longest sleep between frames while no shape shows, so input is still
+/// noticed in time.
+const MAX_IDLE_SLEEP: f32 = 0.1;
+
+/// Frame rate of a window in the background, and while minimized the rate
+/// it checks whether it is back.
+const BACKGROUND_FPS: f32 = 1.0;
+
+/// How much of our window the user can see.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+enum WindowState {
+    Focused,
+    Background,
+    Minimized,
+}
+
+/// The state of our window, where the platform tells; otherwise focused.
+fn window_state() -> WindowState {
+    #[cfg(windows)]
+    {
+	if win32::is_minimized() {
+	    WindowState::Minimized
+	} else if win32::has_focus() {
+	    WindowState::Focused
+	} else {
+	    WindowState::Background
+	}
+    }
+    #[cfg(not(windows))]
+    WindowState::Focused
+}
+
+/// Seconds between checks whether monitors were plugged in, unplugged or
+/// changed resolution.
+#[cfg(windows)]
+const DISPLAY_CHECK_INTERVAL: f32 = 1.0;
+
+/// Seconds between checks whether the machine went on or off battery.
+const POWER_CHECK_INTERVAL: f32 = 10.0;
+
+/// Seconds between checks which `schedule` entry is in force.
+const SCHEDULE_CHECK_INTERVAL: f32 = 10.0;
+
+/// Step length of the frame advance key, in seconds.
+const FRAME_STEP: f32 = 1.0 / 60.0;
+
+/// The config's `ignore_keys`, plus the pause, step and debug overlay keys
+/// with `controls`.
+fn ignored_keys(config: &Config, controls: bool) -> HashSet<KeyCode> {
+    let mut keys = config.ignore_keys.clone();
+    if controls {
+	keys.extend([PAUSE_KEY, STEP_KEY, DEBUG_OVERLAY_KEY]);
+    }
+    keys
+}
+
+/// The window's opacity and the factor on every color `running` seconds
+/// after launch and, once dismissed, `leaving` seconds after that, with
+/// `desktop` set when the desktop can show through the window.
+fn fade(config: &Config, running: f32, leaving: Option<f32>, desktop: bool) -> (f32, f32) {
+    let fade_in = if config.fade_in > 0.0 { (running / config.fade_in).min(1.0) } else { 1.0 };
+    let fade_out = match leaving {
+	None => 1.0,
+	Some(leaving) if config.fade_out > 0.0 => (1.0 - leaving / config.fade_out).max(0.0),
+	Some(_) => 0.0,
+    };
+    let (mut opacity, mut brightness) = (1.0, 1.0);
+    if desktop {
+	opacity *= fade_in;
+    } else {
+	brightness *= fade_in;
+    }
+    if desktop && config.fade_to == Fade::Desktop {
+	opacity *= fade_out;
+    } else {
+	brightness *= fade_out;
+    }
+    (opacity, brightness)
+}
+
+/// Run the animation until dismissed and faded out, `spanning` with one grid
+/// per monitor or one for the window, and again whenever monitors come and
+/// go. With `preview_parent` set it runs embedded in that window, ignores
+/// input and stops once the host is gone. `controls` enables the pause and
+/// frame step keys and, being a normal window, slows down in the background
+/// and stops while minimized.
+async fn run_screensaver(config: Config, preview_parent: Option<isize>, spanning: bool, controls: bool) {
+    let shape_radius = match preview_parent {
+	// Scale relative to a 1080p screen so the preview looks like a thumbnail
+	Some(_) => (config.radius * screen_height() / 1080.0).max(3.0),
+	None => config.radius,
+    };
+
+    // As loaded, for the control API's overrides to go on top of
+    let mut base = config.clone();
+    let mut remote: Vec<(String, String)> = Vec::new();
+    let mut config = honor_system_motion(config);
+    // Kept for the whole run, reloads included
+    let surprise = config.surprise.then(|| Surprise::pick(&config));
+    if let Some(surprise) = &surprise {
+	let settings = surprise.settings();
+	info!("surprise: picked {}", settings.trim_end().replace('\n', ", "));
+	if let Some(path) = Config::save_beside("surprise.toml", &settings) {
+	    info!("surprise: saved to {}", path.display());
+	}
+	config = surprise.apply(config);
+    }
+    // Picked once too, so the look holds past midnight
+    let today = local_now().date();
+    if config.auto_theme {
+	config = themed(config, today);
+    }
+    let mut battery = on_battery();
+    let mut power_check = POWER_CHECK_INTERVAL;
+    let mut schedule_check = SCHEDULE_CHECK_INTERVAL;
+    let mut scheduled = config.schedule_entry(local_now().time()).map(str::to_string);
+    let mut weather = Weather::start(&config.weather_url);
+    let mut conditions: Option<Conditions> = None;
+    let wants_media = |config: &Config| config.media_overlay || config.media_tint;
+    let mut media = wants_media(&config).then(Media::start);
+    let mut track: Option<Track> = None;
+    let mut attract = (config.mode == Mode::Attract).then(|| Attract::new(&config));
+    let effective = |config: &Config, battery: bool, conditions: Option<Conditions>, track: &Option<Track>, attract: &Option<Attract>, scheduled: &Option<String>| {
+	let mut config = scheduled.as_deref().map_or_else(|| config.clone(), |name| config.scheduled(name)).for_power(battery);
+	if let Some(attract) = attract {
+	    config = attract.apply(config);
+	}
+	if let Some(conditions) = conditions {
+	    config = conditions.theme(config);
+	}
+	if config.media_tint
+	    && let Some(art) = track.as_ref().and_then(|track| track.art.as_ref())
+	{
+	    config.palette_image = art.display().to_string();
+	}
+	config
+    };
+    crash::note_config(&config);
+    let mut displays = current_displays(spanning);
+    let mut screensaver = Screensaver::new(effective(&config, battery, None, &None, &attract, &scheduled), &displays, shape_radius);
+    info!("grid: {} shapes of radius {shape_radius:.1} on {} displays", screensaver.shape_count(), displays.len());
+    let readout = |config: &Config, conditions: Option<Conditions>| conditions.filter(|_| config.weather_overlay).map(Conditions::readout);
+
+    let mut audio_meter = if config.audio_reactive { AudioMeter::start() } else { None };
+    let mut watcher = ConfigWatcher::new();
+
+    let mut window_size = (screen_width(), screen_height());
+    #[cfg(windows)]
+    let mut monitors = win32::monitor_layout();
+    #[cfg(windows)]
+    let mut display_check = DISPLAY_CHECK_INTERVAL;
+
+    let _cursor = (preview_parent.is_none() && config.hide_cursor).then(HiddenCursor::new);
+    #[cfg(windows)]
+    if preview_parent.is_none() && config.kiosk && config.kiosk_hide {
+	win32::hide_from_switcher();
+    }
+
+    let mut delay_remaining = config.start_delay;
+    let mut exit_watch = ExitWatch::new(config.exit_grace, config.exit_distance);
+    let mut gamepads = Gamepads::new();
+    let mut ignored = ignored_keys(&config, controls);
+    let mut paused = false;
+    let mut pacer = FramePacer::new();
+    // A child window cannot show the desktop through it
+    let desktop_fade = cfg!(windows) && preview_parent.is_none();
+    // Only a window has the mouse to play with; as a screensaver it exits
+    let spotlight = |config: &Config| controls && preview_parent.is_none() && config.spotlight;
+    let click_rings = |config: &Config| controls && preview_parent.is_none() && config.click_rings;
+    #[cfg(windows)]
+    let mut window_opacity = 1.0;
+    let mut running = 0.0;
+    let mut leaving: Option<f32> = None;
+    let mut prompt = Prompt::default();
+    let mut frames = FrameWatch::new();
+    let mut debug_overlay = DebugOverlay::new();
+    let mut show_debug = config.debug_overlay;
+    let mut control = preview_parent.is_none().then(|| Control::start(&config)).flatten();
+    let mut live = preview_parent.is_none().then(|| Live::start(&config)).flatten();
+    let mut sync = preview_parent.is_none().then(|| SyncLink::start(&config)).flatten();
+    let mut share = (preview_parent.is_none() && config.share_texture).then(TextureShare::start).flatten();
+
+    loop {
+	let window = if controls { window_state() } else { WindowState::Focused };
+	let max_fps = if window == WindowState::Focused { screensaver.config().max_fps } else { BACKGROUND_FPS };
+	pacer.wait(max_fps);
+	clear_background(screensaver.background_color());
+
+	let dt = get_frame_time();
+	if let Some(slow) = frames.step(dt, max_fps) {
+	    warn!("{slow}");
+	}
+
+	// A fullscreen window keeps its size when monitors change under it
+	#[cfg(windows)]
+	let monitors_changed = spanning && {
+	    display_check -= dt;
+	    display_check <= 0.0 && {
+		display_check = DISPLAY_CHECK_INTERVAL;
+		let layout = win32::monitor_layout();
+		layout != std::mem::replace(&mut monitors, layout.clone())
+	    }
+	};
+	#[cfg(not(windows))]
+	let monitors_changed = false;
+	if monitors_changed || (screen_width(), screen_height()) != window_size {
+	    if monitors_changed {
+		info!("the monitors changed, laying the grids out again");
+	    }
+	    displays = current_displays(spanning);
+	    screensaver.set_displays(&displays);
+	    window_size = (screen_width(), screen_height());
+	}
+
+	if let Some(parent) = preview_parent {
+	    if !preview_host_alive(parent) {
+		break;
+	    }
+	} else if leaving.is_none() {
+	    let mut dismissed = false;
+	    prompt.step(dt);
+	    if prompt.is_open() {
+		while let Some(key) = get_char_pressed() {
+		    prompt.type_char(key);
+		}
+		if is_key_pressed(KeyCode::Backspace) {
+		    prompt.backspace();
+		}
+		if is_key_pressed(KeyCode::Escape) {
+		    prompt.close();
+		} else if (is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter)) && prompt.submit(&config.exit_passphrase) {
+		    leaving = Some(0.0);
+		}
+		// What moved while it was open does not count once it closes
+		exit_watch.mouse_moved(Vec2::from(mouse_position()), dt);
+	    } else if config.kiosk {
+		dismissed = parse_key_combo(&config.kiosk_exit).is_some_and(|combo| combo.pressed());
+	    } else {
+		if exit_watch.mouse_moved(Vec2::from(mouse_position()), dt) && config.exit_on.mouse_move && !spotlight(&config) {
+		    dismissed = true;
+		}
+
+		// Polled even when ignored so presses don't pile up
+		let gamepad = gamepads.poll() && config.exit_on.gamepad;
+		let exit_on = if click_rings(&config) { ExitTriggers { buttons: false, ..config.exit_on } } else { config.exit_on };
+		if !exit_watch.in_grace() && (exit_input(&exit_on, &ignored) || gamepad) {
+		    dismissed = true;
+		}
+	    }
+	    if dismissed {
+		if config.exit_passphrase.is_empty() {
+		    leaving = Some(0.0);
+		} else {
+		    prompt.open();
+		}
+	    }
+	}
+
+	// Dismissed, the screen fades out before quitting
+	if let Some(leaving) = &mut leaving {
+	    if *leaving >= config.fade_out {
+		// Before the window goes, so the desktop never shows
+		if config.lock_on_exit && !controls {
+		    lock_session();
+		}
+		break;
+	    }
+	    *leaving += dt;
+	}
+	let (opacity, fade_brightness) = fade(&config, running, leaving, desktop_fade);
+	running += dt;
+	#[cfg(windows)]
+	if desktop_fade && opacity != window_opacity {
+	    win32::set_opacity(opacity);
+	    window_opacity = opacity;
+	}
+	#[cfg(not(windows))]
+	let _ = opacity;
+	screensaver.set_power_brightness(fade_brightness);
+
+	// Hold on black until the start delay has passed, still honoring exits
+	if delay_remaining > 0.0 {
+	    delay_remaining -= dt;
+	    next_frame().await;
+	    continue;
+	}
+
+	power_check -= dt;
+	if power_check <= 0.0 {
+	    power_check = POWER_CHECK_INTERVAL;
+	    if on_battery() != battery {
+		battery = !battery;
+		screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	    }
+	}
+
+	schedule_check -= dt;
+	if schedule_check <= 0.0 {
+	    schedule_check = SCHEDULE_CHECK_INTERVAL;
+	    let entry = config.schedule_entry(local_now().time()).map(str::to_string);
+	    if entry != scheduled {
+		scheduled = entry;
+		screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	    }
+	}
+
+	let mut changed = watcher.poll(dt);
+	if let Some(reloaded) = &changed {
+	    base = reloaded.clone();
+	}
+	let (mut remote_ring, mut remote_screenshot) = (None, false);
+	let mut received = control.as_ref().map(Control::poll).unwrap_or_default();
+	if let Some(live) = &mut live {
+	    received.extend(live.poll(dt, &config));
+	}
+	if let Some(sync) = &mut sync {
+	    let (commands, jump) = sync.step(dt, &screensaver.beat());
+	    received.extend(commands);
+	    if let Some(time) = jump {
+		screensaver.set_time(time);
+	    }
+	}
+	for command in received {
+	    match command {
+		Command::Set(overrides) => {
+		    add_overrides(&mut remote, overrides);
+		    changed = Some(base.clone());
+		}
+		Command::Reset => {
+		    remote.clear();
+		    changed = Some(base.clone());
+		}
+		Command::Ring(at) => remote_ring = Some(at),
+		Command::Pause(pause) => paused = pause,
+		Command::Screenshot => remote_screenshot = true,
+	    }
+	}
+
+	if let Some(reloaded) = changed {
+	    let mut reloaded = honor_system_motion(reloaded);
+	    if let Some(surprise) = surprise.as_ref().filter(|_| reloaded.surprise) {
+		reloaded = surprise.apply(reloaded);
+	    }
+	    if reloaded.auto_theme {
+		reloaded = themed(reloaded, today);
+	    }
+	    // What was asked for over the API wins over what was picked
+	    let reloaded = with_overrides(reloaded, &remote);
+	    crash::note_config(&reloaded);
+	    if reloaded.weather_url != config.weather_url {
+		weather = Weather::start(&reloaded.weather_url);
+		conditions = None;
+	    }
+	    if wants_media(&reloaded) != media.is_some() {
+		media = wants_media(&reloaded).then(Media::start);
+		track = None;
+		screensaver.set_track(None);
+	    }
+	    if reloaded.mode != config.mode {
+		attract = (reloaded.mode == Mode::Attract).then(|| Attract::new(&reloaded));
+	    }
+	    if reloaded.debug_overlay != config.debug_overlay {
+		show_debug = reloaded.debug_overlay;
+	    }
+	    if preview_parent.is_none() && control.as_ref().map_or(0, Control::port) != reloaded.control_port {
+		// The old port is let go of once its thread sees this
+		control = Control::start(&reloaded);
+	    }
+	    if let Some(control) = &control {
+		control.publish(&reloaded);
+	    }
+	    if preview_parent.is_none() && reloaded.share_texture != config.share_texture {
+		share = reloaded.share_texture.then(TextureShare::start).flatten();
+	    }
+	    if preview_parent.is_none() && !SyncLink::same_link(&config, &reloaded) {
+		drop(sync.take());
+		sync = SyncLink::start(&reloaded);
+	    }
+	    if Live::same_inputs(&config, &reloaded) {
+		if let Some(live) = &mut live {
+		    live.reconfigure(&reloaded);
+		}
+	    } else if preview_parent.is_none() {
+		// Let go of the port and device before opening them again
+		drop(live.take());
+		live = Live::start(&reloaded);
+	    }
+	    scheduled = reloaded.schedule_entry(local_now().time()).map(str::to_string);
+	    config = reloaded;
+	    screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	    screensaver.set_weather_readout(readout(&config, conditions));
+	    ignored = ignored_keys(&config, controls);
+	    if config.audio_reactive != audio_meter.is_some() {
+		audio_meter = if config.audio_reactive { AudioMeter::start() } else { None };
+	    }
+	}
+
+	if let Some(reading) = weather.as_ref().and_then(Weather::poll) {
+	    conditions = Some(reading);
+	    screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	    screensaver.set_weather_readout(readout(&config, conditions));
+	}
+	if let Some(playing) = media.as_ref().and_then(Media::poll) {
+	    track = playing;
+	    screensaver.set_track(track.as_ref().map(Track::label));
+	    if config.media_tint {
+		screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	    }
+	}
+
+	// Simulation time stands still while paused, but for single steps
+	if controls && is_key_pressed(PAUSE_KEY) {
+	    paused = !paused;
+	}
+	if controls && preview_parent.is_none() && is_key_pressed(DEBUG_OVERLAY_KEY) {
+	    show_debug = !show_debug;
+	}
+	let sim_dt = match paused {
+	    false => dt * sync.as_ref().map_or(1.0, SyncLink::rate),
+	    true if controls && is_key_pressed(STEP_KEY) => FRAME_STEP,
+	    true => 0.0,
+	};
+
+	// The animation holds still until the window is restored
+	if window == WindowState::Minimized {
+	    next_frame().await;
+	    continue;
+	}
+
+	let saving = battery && config.battery.enabled;
+	if saving && config.battery.blank {
+	    clear_background(BLACK);
+	    sleep(MAX_IDLE_SLEEP);
+	    next_frame().await;
+	    continue;
+	}
+
+	screensaver.set_power_brightness(if saving { config.battery.brightness } else { 1.0 } * fade_brightness);
+	screensaver.set_spotlight(spotlight(&config).then(|| Vec2::from(mouse_position())));
+	if click_rings(&config) && is_mouse_button_pressed(MouseButton::Left) {
+	    screensaver.add_ring(Vec2::from(mouse_position()));
+	}
+	if let Some(at) = remote_ring
+	    && let Some(display) = displays.first()
+	{
+	    screensaver.add_ring(display.point() + at * display.size());
+	}
+	if let Some(live) = &live {
+	    screensaver.tune(live.tuning());
+	}
+	let audio_bands = audio_meter.as_mut().map_or(&[][..], |meter| meter.bands(dt));
+	let commands = screensaver.step(sim_dt, audio_bands);
+	if attract.as_mut().is_some_and(|attract| attract.step(sim_dt, &config)) {
+	    screensaver.cross_fade_from(&commands);
+	    screensaver.set_config(effective(&config, battery, conditions, &track, &attract, &scheduled));
+	}
+	screensaver.draw(&commands);
+	// Before what is only for looking at on this screen
+	if let Some(share) = &mut share {
+	    share.publish();
+	}
+	let stats = screensaver.frame_stats(&commands);
+	debug_overlay.step(dt);
+	if show_debug && let Some(display) = displays.first() {
+	    debug_overlay.draw(*display, &stats);
+	}
+	prompt.draw(&displays);
+	if let Some(idle) = screensaver.idle_time() {
+	    sleep(idle.min(MAX_IDLE_SLEEP));
+	}
+
+	if preview_parent.is_none() && (is_key_pressed(SCREENSHOT_KEY) || remote_screenshot) {
+	    match save_screenshot() {
+		Ok(path) => info!("screenshot: saved {}", path.display()),
+		Err(err) => warn!("cannot save screenshot: {}", err),
+	    }
+	}
+
+	next_frame().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+
+    fn test_shape(static_color: bool) -> Shape {
+	let mut shape = Shape::new(0.0, 0.0, 40.0, static_color, &Palette::random(), &RandGenerator::new());
+	shape.phase_offset = 0.0;
+	shape
+    }
+
+    fn style() -> ShapeStyle {
+	ShapeStyle::new(ShapeRegistry::with_builtins().lookup("hexagon").unwrap(), Palette::random(), 1)
+    }
+
+    fn run_frames(config: &Config, frames: usize, dt: f32) -> Vec<Vec<DrawCommand>> {
+	let mut shapes = vec![test_shape(config.static_color)];
+	let mut time = 0.0;
+	let mut patterns = PatternState::default();
+	(0..frames).map(|_| step_frame(&mut shapes, &mut time, &mut patterns, dt, config, &style(), &[])).collect()
+    }
+
+    #[test]
+    fn shape_below_threshold_emits_nothing() {
+	// The pace threshold 0.95 had: sin(0.5 * t) stays below 0.95 until t
+	// is roughly 2.5
+	let config = Config { density: 0.05, speed: 0.05, ..Default::default() };
+	let frames = run_frames(&config, 20, 0.1);
+	assert!(frames.iter().all(|commands| commands.is_empty()));
+    }
+
+    #[test]
+    fn opacity_rises_monotonically_through_threshold() {
+	let config = Config { density: 0.05, speed: 0.05, ..Default::default() };
+	// Stop short of the sine peak at t = PI
+	let opacities: Vec<f32> = run_frames(&config, 31, 0.1)
+	    .into_iter()
+	    .flatten()
+	    .map(|command| command.color.a)
+	    .collect();
+	assert!(!opacities.is_empty());
+	assert!(opacities.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn breathing_grows_shapes_as_they_fade_in() {
+	let config = Config { density: 0.05, speed: 0.05, breathing: 0.2, ..Default::default() };
+	let commands: Vec<DrawCommand> = run_frames(&config, 31, 0.1).into_iter().flatten().collect();
+	assert!(commands.windows(2).all(|pair| pair[0].radius <= pair[1].radius));
+	for command in commands {
+	    assert!((command.radius - 40.0 * (1.0 + 0.2 * command.color.a)).abs() < 1e-4);
+	}
+    }
+
+    #[test]
+    fn flash_limit_caps_opacity_changes() {
+	let opacity_steps = |flash_limit| {
+	    let config = Config { density: 1.0, speed: 5.0, flash_limit, ..Default::default() };
+	    let opacities: Vec<f32> = run_frames(&config, 100, 0.05)
+		.into_iter()
+		.map(|commands| commands.first().map_or(0.0, |command| command.color.a))
+		.collect();
+	    opacities.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max)
+	};
+	// Six fades a second allow three full flashes
+	assert!(opacity_steps(true) <= 6.0 * 0.05 + 0.01 + 1e-4);
+	assert!(opacity_steps(false) > 0.5);
+    }
+
+    #[test]
+    fn reduced_motion_slows_down_and_stops_spinning() {
+	let config = Config { density: 1.0, rotation_speed: 360.0, breathing: 0.5, ..Default::default() };
+	let reduced = Config { reduced_motion: true, ..config.clone() };
+	let last = |config: &Config| run_frames(config, 2, 0.1).pop().unwrap().pop().unwrap();
+	let (moving, calm) = (last(&config), last(&reduced));
+	assert!(moving.rotation > 0.5 && calm.rotation == 0.0);
+	assert!(moving.radius > 40.0 && calm.radius == 40.0);
+	// sin(10 t) with a quarter of the time elapsed
+	assert!(calm.color.a < moving.color.a);
+    }
+
+    #[test]
+    fn hidden_shapes_are_skipped_until_they_rise() {
+	let config = Config { density: 0.02, seed: 3, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 400.0, 300.0)], 40.0);
+	// Every wave starting on its way down, so none rises for a while
+	for shape in &mut screensaver.shapes {
+	    shape.phase_offset = PI;
+	}
+	screensaver.step(0.1, &[]);
+	assert!(screensaver.shapes.iter().all(|shape| shape.opacity == 0.0));
+	assert!(screensaver.idle_time().is_some_and(|idle| idle > 0.0));
+	// Shapes still show up on time
+	let visible: usize = (0..200).map(|_| screensaver.step(0.1, &[]).len()).sum();
+	assert!(visible > 0);
+	assert!(screensaver.shapes.iter().all(|shape| shape.opacity > 0.0 || shape.next_visible > screensaver.time - 0.1));
+    }
+
+    #[test]
+    fn pixel_shift_drifts_within_its_amplitude() {
+	let offsets: Vec<Vec2> = (0..1800).map(|second| pixel_shift(4.0, second as f32)).collect();
+	assert_eq!(offsets[0], Vec2::ZERO);
+	assert!(offsets.iter().all(|offset| offset.x.abs() <= 4.0 && offset.y.abs() <= 4.0 && *offset == offset.round()));
+	assert!(offsets.iter().any(|offset| offset.x == 4.0) && offsets.iter().any(|offset| offset.y == -4.0));
+	assert_eq!(pixel_shift(4.0, 1800.0 + 75.0), offsets[75]);
+    }
+
+    #[test]
+    fn fades_show_the_desktop_where_they_can() {
+	let config = Config { fade_in: 2.0, fade_out: 1.0, fade_to: Fade::Desktop, ..Default::default() };
+	assert_eq!(fade(&config, 1.0, None, true), (0.5, 1.0));
+	assert_eq!(fade(&config, 1.0, None, false), (1.0, 0.5));
+	assert_eq!(fade(&config, 10.0, Some(0.25), true), (0.75, 1.0));
+	let black = Config { fade_to: Fade::Black, ..config };
+	assert_eq!(fade(&black, 10.0, Some(0.25), true), (1.0, 0.75));
+	// No fade out means gone at once
+	let instant = Config { fade_in: 0.0, fade_out: 0.0, ..Default::default() };
+	assert_eq!(fade(&instant, 0.0, None, true), (1.0, 1.0));
+	assert_eq!(fade(&instant, 0.0, Some(0.0), false), (1.0, 0.0));
+    }
+
+    #[test]
+    fn the_launch_spiral_reveals_from_the_center_out() {
+	let display = Rect::new(0.0, 0.0, 1920.0, 1080.0);
+	let reveal = |x: f32, y: f32, seconds: f32| spiral_reveal(Vec2::new(x, y), display, seconds);
+	assert_eq!(reveal(960.0, 545.0, 0.0), 0.0);
+	assert!(reveal(960.0, 545.0, 0.5) == 1.0 && reveal(1900.0, 1070.0, 0.5) == 0.0);
+	// Starting out to the right, the arm passes left of the center half a
+	// turn later, and right of it again only a turn later
+	assert!(reveal(820.0, 540.0, 0.5) > reveal(1100.0, 540.0, 0.5));
+	for (x, y) in [(0.0, 0.0), (1920.0, 0.0), (0.0, 1080.0), (1919.0, 1079.0), (960.0, 540.0)] {
+	    assert_eq!(reveal(x, y, SPIRAL_SECONDS + SPIRAL_FADE), 1.0);
+	}
+    }
+
+    #[test]
+    fn camera_drift_stays_covered_by_the_grid() {
+	let display = Rect::new(0.0, 0.0, 1920.0, 1080.0);
+	// A pixel of slack where both waves peak together
+	let overhang = drift_overhang(display) + Vec2::ONE;
+	let grid = Rect::new(-overhang.x, -overhang.y, display.w + 2.0 * overhang.x, display.h + 2.0 * overhang.y);
+	for seconds in (0..1800).map(|second| second as f32) {
+	    let (zoom, turn) = camera_drift(seconds);
+	    assert!((1.0 - DRIFT_ZOOM..=1.0 + DRIFT_ZOOM).contains(&zoom));
+	    assert!(turn.abs() <= DRIFT_DEGREES.to_radians());
+	    // Every corner of the screen shows a point of the grid
+	    let back = Vec2::from_angle(-turn);
+	    for corner in [display.point(), display.point() + display.size(), Vec2::new(display.right(), display.top()), Vec2::new(display.left(), display.bottom())] {
+		let shown = display.center() + back.rotate(corner - display.center()) / zoom;
+		assert!(grid.contains(shown), "{seconds}: {shown}");
+	    }
+	}
+    }
+
+    #[test]
+    fn dimming_fades_down_after_the_delay() {
+	let config = Config { dim_after_minutes: 1.0, dim_to: 0.2, background: "#ffffff".to_string(), ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 200.0, 200.0)], 40.0);
+	screensaver.step(59.0, &[]);
+	assert_eq!(screensaver.brightness(), 1.0);
+	screensaver.step(1.0 + DIM_FADE / 2.0, &[]);
+	assert!((screensaver.brightness() - 0.6).abs() < 1e-4);
+	assert!((screensaver.background_color().r - 0.6).abs() < 1e-4);
+	screensaver.step(DIM_FADE, &[]);
+	assert!((screensaver.brightness() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn shapes_off_every_display_are_culled() {
+	let displays = [Rect::new(0.0, 0.0, 200.0, 100.0), Rect::new(200.0, 0.0, 200.0, 100.0)];
+	let command = |x, y| DrawCommand { x, y, radius: 10.0, rotation: 0.0, shape: ShapeRegistry::with_builtins().resolve("hexagon"), color: WHITE };
+	assert!(on_screen(&displays, &command(390.0, 50.0)));
+	assert!(on_screen(&displays, &command(-15.0, 110.0)));
+	assert!(!on_screen(&displays, &command(430.0, 50.0)));
+	assert!(!on_screen(&displays, &command(100.0, -30.0)));
+    }
+
+    #[test]
+    fn only_plain_twinkles_animate_on_the_gpu() {
+	let config = Config { gpu_animation: true, ..Default::default() };
+	assert!(animates_on_gpu(&config, &style()));
+	assert!(!animates_on_gpu(&Config { gpu_animation: false, ..config.clone() }, &style()));
+	assert!(!animates_on_gpu(&Config { pattern: Pattern::Wave, ..config.clone() }, &style()));
+	assert!(!animates_on_gpu(&Config { rotation_speed: 10.0, ..config.clone() }, &style()));
+	assert!(animates_on_gpu(&Config { rotation_speed: 10.0, reduced_motion: true, ..config }, &style()));
+    }
+
+    #[test]
+    fn focus_shape_ignores_threshold() {
+	let config = Config { density: 0.01, ..Default::default() };
+	let mut shapes = vec![test_shape(false), Shape::new(100.0, 0.0, 40.0, false, &Palette::random(), &RandGenerator::new())];
+	let index = nearest_shape(&shapes, Vec2::new(90.0, 10.0)).unwrap();
+	assert_eq!(index, 1);
+	shapes[index].focus = true;
+	let mut time = 0.0;
+	let mut patterns = PatternState::default();
+	for _ in 0..50 {
+	    let commands = step_frame(&mut shapes, &mut time, &mut patterns, 0.1, &config, &style(), &[]);
+	    assert!(commands.iter().any(|command| command.x == 100.0 && command.color.a == 1.0));
+	}
+    }
+
+    #[test]
+    fn screensaver_steps_without_a_window() {
+	let config = Config { density: 0.5, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	let visible: usize = (0..20).map(|_| screensaver.step(0.1, &[]).len()).sum();
+	assert!(visible > 0);
+    }
+
+    #[test]
+    fn radius_change_lays_the_grid_out_again() {
+	let config = Config::default();
+	let mut screensaver = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	let before = screensaver.shapes.len();
+	screensaver.set_config(Config { radius: 80.0, ..config });
+	assert!(screensaver.shapes.len() < before);
+	assert!(screensaver.shapes.iter().all(|shape| shape.radius == 80.0));
+    }
+
+    #[test]
+    fn mixes_give_each_cell_one_of_their_shapes() {
+	let config = Config::default();
+	let mut screensaver = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	assert!(screensaver.shapes.iter().all(|shape| shape.own_shape.is_none()));
+	screensaver.set_config(Config { shape: "mix:heart,star=3".to_string(), ..config });
+	let (heart, star) = (screensaver.registry.lookup("heart"), screensaver.registry.lookup("star"));
+	let count = |shape| screensaver.shapes.iter().filter(|other| other.own_shape == shape).count();
+	assert_eq!(count(heart) + count(star), screensaver.shapes.len());
+	assert!(count(heart) > 0 && count(star) > count(heart));
+    }
+
+    #[test]
+    fn the_spotlight_lights_shapes_near_it() {
+	let config = Config { density: 0.0, spotlight: true, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	assert!(screensaver.step(0.1, &[]).is_empty());
+	assert!(screensaver.idle_time().is_some());
+	screensaver.set_spotlight(Some(Vec2::new(400.0, 300.0)));
+	assert_eq!(screensaver.idle_time(), None);
+	let lit = screensaver.step(0.1, &[]);
+	assert!(!lit.is_empty());
+	assert!(lit.iter().all(|command| Vec2::new(command.x, command.y).distance(Vec2::new(400.0, 300.0)) < 150.0));
+	screensaver.set_spotlight(None);
+	assert!(screensaver.step(0.1, &[]).is_empty());
+    }
+
+    #[test]
+    fn click_rings_light_and_turn_the_shapes_they_pass() {
+	let config = Config { density: 0.0, click_rings: true, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	screensaver.add_ring(Vec2::new(400.0, 300.0));
+	assert_eq!(screensaver.idle_time(), None);
+	let lit = screensaver.step(0.3, &[]);
+	assert!(!lit.is_empty());
+	assert!(lit.iter().all(|command| (Vec2::new(command.x, command.y).distance(Vec2::new(400.0, 300.0)) - 150.0).abs() < 100.0));
+	// Out once the ring has faded
+	for _ in 0..30 {
+	    screensaver.step(0.1, &[]);
+	}
+	assert!(screensaver.step(0.1, &[]).is_empty());
+    }
+
+    #[test]
+    fn flowing_shapes_leave_their_cells() {
+	let config = Config { motion: Motion::Flow, ..Default::default() };
+	let mut screensaver = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	let cells: Vec<Vec2> = screensaver.shapes.iter().map(|shape| Vec2::new(shape.x, shape.y)).collect();
+	for _ in 0..50 {
+	    screensaver.step(0.1, &[]);
+	}
+	assert!(screensaver.shapes.iter().zip(&cells).any(|(shape, cell)| Vec2::new(shape.x, shape.y).distance(*cell) > 1.0));
+	screensaver.set_config(Config { motion: Motion::Grid, ..config });
+	assert!(screensaver.shapes.iter().zip(&cells).all(|(shape, cell)| Vec2::new(shape.x, shape.y) == *cell));
+    }
+
+    #[test]
+    fn radius_jitter_varies_shapes_on_the_same_grid() {
+	let config = Config::default();
+	let mut screensaver = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	let before: Vec<Vec2> = screensaver.shapes.iter().map(|shape| Vec2::new(shape.x, shape.y)).collect();
+	screensaver.set_config(Config { radius_jitter: 0.3, ..config });
+	assert_eq!(screensaver.shapes.iter().map(|shape| Vec2::new(shape.x, shape.y)).collect::<Vec<_>>(), before);
+	assert!(screensaver.shapes.iter().all(|shape| (28.0..=52.0).contains(&shape.radius)));
+	assert!(screensaver.shapes.iter().any(|shape| shape.radius != 40.0));
+    }
+
+    #[test]
+    fn gap_spreads_the_grid_without_shrinking_shapes() {
+	let config = Config::default();
+	let mut screensaver = Screensaver::new(config.clone(), &[Rect::new(0.0, 0.0, 800.0, 600.0)], 40.0);
+	let before = screensaver.shapes.len();
+	screensaver.set_config(Config { gap: 20.0, ..config });
+	assert!(screensaver.shapes.len() < before);
+	assert!(screensaver.shapes.iter().all(|shape| shape.radius == 40.0));
+    }
+
+    #[test]
+    fn resizing_keeps_the_shapes_still_on_screen() {
+	let mut screensaver = Screensaver::new(Config::default(), &[Rect::new(0.0, 0.0, 400.0, 300.0)], 40.0);
+	let phases: Vec<(Vec2, f32)> =
+	    screensaver.shapes.iter().map(|shape| (Vec2::new(shape.x, shape.y), shape.phase_offset)).collect();
+	screensaver.set_displays(&[Rect::new(0.0, 0.0, 800.0, 600.0)]);
+	assert!(screensaver.shapes.len() > phases.len());
+	for (pos, phase) in phases {
+	    let shape = screensaver.shapes.iter().find(|shape| Vec2::new(shape.x, shape.y).distance(pos) < 0.5).unwrap();
+	    assert_eq!(shape.phase_offset, phase);
+	}
+    }
+
+    #[test]
+    fn voronoi_cells_draw_their_own_polygons() {
+	let config = Config { layout: Layout::Voronoi, density: 0.5, seed: 2, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 200.0, 150.0)], 20.0);
+	assert!(screensaver.shapes.iter().all(|shape| shape.own_shape.is_some()));
+	let commands: Vec<_> = (0..20).flat_map(|_| screensaver.step(0.1, &[])).collect();
+	let first = commands[0].shape;
+	assert!(commands.iter().any(|command| command.shape != first));
+
+	// A smaller layout drops the cells it no longer has
+	let shape = screensaver.registry.renderer(screensaver.style.shape_id).name().to_string();
+	screensaver.set_displays(&[Rect::new(0.0, 0.0, 100.0, 75.0)]);
+	let cells = screensaver.registry.iter().filter(|(_, renderer)| renderer.name().starts_with("cell:")).count();
+	assert_eq!(cells, screensaver.shapes.len());
+	assert_eq!(screensaver.registry.renderer(screensaver.style.shape_id).name(), shape);
+	for own in screensaver.shapes.iter().filter_map(|shape| shape.own_shape) {
+	    assert!(screensaver.registry.renderer(own).name().starts_with("cell:"));
+	}
+    }
+
+    #[test]
+    fn shapes_spin_with_rotation_speed() {
+	let config = Config { rotation_speed: 90.0, focus: true, ..Default::default() };
+	let mut shapes = vec![test_shape(false)];
+	shapes[0].focus = true;
+	let mut time = 0.0;
+	let mut patterns = PatternState::default();
+	let first = step_frame(&mut shapes, &mut time, &mut patterns, 1.0, &config, &style(), &[]);
+	shapes[0].spin = -1.0;
+	let second = step_frame(&mut shapes, &mut time, &mut patterns, 1.0, &config, &style(), &[]);
+	assert!((first[0].rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+	assert!((second[0].rotation + std::f32::consts::PI).abs() < 1e-5);
+    }
+
+    #[test]
+    fn morph_shapes_cycle_through_the_steps() {
+	let config = Config { shape: "morph".to_string(), morph_period: 2.0, focus: true, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 200.0, 150.0)], 20.0);
+	let shapes: std::collections::HashSet<_> = (0..20).flat_map(|_| screensaver.step(0.1, &[])).map(|command| command.shape).collect();
+	assert!(shapes.len() > MORPH_STEPS / 2);
+	assert!(shapes.iter().all(|shape| screensaver.registry().renderer(*shape).name().starts_with("morph:")));
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+	let run = |seed| {
+	    let config = Config { density: 0.5, seed, ..Default::default() };
+	    let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 400.0, 300.0)], 40.0);
+	    (0..10).flat_map(|_| screensaver.step(0.1, &[])).collect::<Vec<_>>()
+	};
+	assert_eq!(run(7), run(7));
+	assert_ne!(run(7), run(8));
+    }
+
+    #[test]
+    fn headless_frames_show_the_focus_shape() {
+	let config = Config { density: 0.01, focus: true, ..Default::default() };
+	let mut screensaver = Screensaver::new(config, &[Rect::new(0.0, 0.0, 160.0, 120.0)], 20.0);
+	let pixels = screensaver.render_headless(0.1, 160, 120);
+	assert_eq!(pixels.len(), 160 * 120 * 4);
+	assert!(pixels.chunks_exact(4).any(|pixel| pixel[..3] != [0, 0, 0]));
+    }
+
+    #[test]
+    fn static_color_keeps_a_single_color() {
+	let config = Config { density: 1.0, static_color: true, ..Default::default() };
+	let mut shape = test_shape(true);
+	let initial = shape.color;
+	let mut time = 0.0;
+	let mut patterns = PatternState::default();
+	for _ in 0..100 {
+	    for command in step_frame(std::slice::from_mut(&mut shape), &mut time, &mut patterns, 0.1, &config, &style(), &[]) {
+		assert_eq!((command.color.r, command.color.g, command.color.b), (initial.r, initial.g, initial.b));
+	    }
+	}
+    }
+
+    #[test]
+    fn gradient_mode_colors_by_position() {
+	let config = Config { color_mode: ColorMode::Gradient, focus: true, ..Default::default() };
+	let mut shapes = vec![test_shape(false), test_shape(false)];
+	shapes[0].gradient_pos = 0.0;
+	shapes[1].gradient_pos = 0.5;
+	for shape in &mut shapes {
+	    shape.focus = true;
+	}
+	let mut time = 0.0;
+	let mut patterns = PatternState::default();
+	let first = step_frame(&mut shapes, &mut time, &mut patterns, 0.1, &config, &style(), &[]);
+	assert_ne!(first[0].color, first[1].color);
+	let later = step_frame(&mut shapes, &mut time, &mut patterns, 5.0, &config, &style(), &[]);
+	assert_ne!(first[0].color, later[0].color);
+    }
+}
@@ -0,0 +1,426 @@
+//! The `script` pattern: a file of assignments, one per line, run on every
+//! shape each frame, so new patterns need no rebuild. `#` starts a comment.
+//!
+//! ```text
+//! wave = sin(time * 2 + x / 80)
+//! opacity = max(wave, 0)
+//! hue = time * 20 + index
+//! scale = 1 + 0.2 * wave
+//! ```
+//!
+//! Expressions read the shape's `x` and `y` in pixels, `radius`, random
+//! `phase`, 0..2pi, `index`, `column`, its position across its display,
+//! 0..1, and `neighbors`, along with the animation `time` and the grid's
+//! `count`; names assigned earlier read as their values. They add,
+//! subtract, multiply, divide, take remainders with `%`, powers with `^`
+//! and compare with `<` and `>` to 1 or 0, and call the functions in
+//! `FUNCTIONS`. What a script leaves in `opacity`, 0..1, lights the shape
+//! and `scale` sizes it; `hue`, in degrees, with `saturation` and `value`,
+//! or `red`, `green` and `blue`, 0..1, color it, and it keeps its own color
+//! when none is set.
+
+use crate::config::Config;
+use crate::palette::hsv_to_rgb;
+use crate::pattern::{Pattern, noise};
+use crate::shapes::Shape;
+use log::warn;
+use macroquad::prelude::*;
+use std::path::Path;
+
+/// What a script reads of each shape and the grid, in the order `run`
+/// takes them.
+const INPUTS: [&str; 9] = ["time", "count", "index", "x", "y", "radius", "phase", "column", "neighbors"];
+
+/// How deeply parentheses, calls and negations can nest in an expression.
+const MAX_DEPTH: usize = 64;
+
+/// Functions scripts can call, and how many arguments each takes.
+const FUNCTIONS: [(&str, usize); 16] = [
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("abs", 1),
+    ("sqrt", 1),
+    ("exp", 1),
+    ("floor", 1),
+    ("fract", 1),
+    ("sign", 1),
+    ("min", 2),
+    ("max", 2),
+    ("atan2", 2),
+    ("step", 2),
+    ("clamp", 3),
+    ("mix", 3),
+    ("noise", 3),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f32),
+    /// Read from the slot of an input or an earlier assignment.
+    Variable(usize),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    /// A call to the `FUNCTIONS` entry at the index.
+    Call(usize, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, slots: &[f32]) -> f32 {
+	match self {
+	    Expr::Number(value) => *value,
+	    Expr::Variable(slot) => slots[*slot],
+	    Expr::Negate(expr) => -expr.eval(slots),
+	    Expr::Binary(op, left, right) => {
+		let (a, b) = (left.eval(slots), right.eval(slots));
+		match op {
+		    '+' => a + b,
+		    '-' => a - b,
+		    '*' => a * b,
+		    '/' => a / b,
+		    '%' => a.rem_euclid(b),
+		    '^' => a.powf(b),
+		    '<' => f32::from(a < b),
+		    _ => f32::from(a > b),
+		}
+	    }
+	    Expr::Call(function, args) => {
+		let arg = |index: usize| args[index].eval(slots);
+		match FUNCTIONS[*function].0 {
+		    "sin" => arg(0).sin(),
+		    "cos" => arg(0).cos(),
+		    "tan" => arg(0).tan(),
+		    "abs" => arg(0).abs(),
+		    "sqrt" => arg(0).sqrt(),
+		    "exp" => arg(0).exp(),
+		    "floor" => arg(0).floor(),
+		    "fract" => arg(0).rem_euclid(1.0),
+		    "sign" => arg(0).signum(),
+		    "min" => arg(0).min(arg(1)),
+		    "max" => arg(0).max(arg(1)),
+		    "atan2" => arg(0).atan2(arg(1)),
+		    "step" => f32::from(arg(1) >= arg(0)),
+		    "clamp" => arg(0).max(arg(1)).min(arg(2)),
+		    "mix" => arg(0) + (arg(1) - arg(0)) * arg(2),
+		    _ => noise(Vec3::new(arg(0), arg(1), arg(2))),
+		}
+	    }
+	}
+    }
+}
+
+/// A parsed script.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Script {
+    /// The slot each statement assigns and what it is set to, in order.
+    statements: Vec<(usize, Expr)>,
+    /// Names of the slots, the inputs first.
+    names: Vec<String>,
+}
+
+/// What a script made of one shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Output {
+    pub(crate) opacity: f32,
+    pub(crate) scale: f32,
+    pub(crate) color: Option<Color>,
+}
+
+impl Script {
+    /// The script in `text`, or what is wrong with it and on which line.
+    pub(crate) fn parse(text: &str) -> Result<Script, String> {
+	let mut script = Script { statements: Vec::new(), names: INPUTS.iter().map(|name| name.to_string()).collect() };
+	for (number, line) in text.lines().enumerate() {
+	    let line = line.split('#').next().unwrap_or_default().trim();
+	    if line.is_empty() {
+		continue;
+	    }
+	    script.statement(line).map_err(|err| format!("line {}: {err}", number + 1))?;
+	}
+	Ok(script)
+    }
+
+    fn statement(&mut self, line: &str) -> Result<(), String> {
+	let (name, expr) = line.split_once('=').ok_or("expected `name = expression`")?;
+	let name = name.trim();
+	if !is_name(name) {
+	    return Err(format!("`{name}` is not a name"));
+	}
+	if INPUTS.contains(&name) {
+	    return Err(format!("`{name}` cannot be assigned"));
+	}
+	let mut parser = Parser { tokens: tokenize(expr)?, next: 0, names: &self.names, depth: 0 };
+	let expr = parser.expression()?;
+	if let Some(token) = parser.tokens.get(parser.next) {
+	    return Err(format!("unexpected `{token}`"));
+	}
+	let slot = match self.slot(name) {
+	    Some(slot) => slot,
+	    None => {
+		self.names.push(name.to_string());
+		self.names.len() - 1
+	    }
+	};
+	self.statements.push((slot, expr));
+	Ok(())
+    }
+
+    fn slot(&self, name: &str) -> Option<usize> {
+	self.names.iter().position(|other| other == name)
+    }
+
+    /// Run over `inputs`, the values of `INPUTS`, in `slots` kept between
+    /// calls to save allocating.
+    pub(crate) fn run(&self, inputs: [f32; INPUTS.len()], slots: &mut Vec<f32>) -> Output {
+	slots.clear();
+	slots.extend(inputs);
+	slots.resize(self.names.len(), f32::NAN);
+	for (slot, expr) in &self.statements {
+	    slots[*slot] = expr.eval(slots);
+	}
+	// Unset or broken values fall back to the defaults
+	let read = |name: &str| self.slot(name).map(|slot| slots[slot]).filter(|value| value.is_finite());
+	let color = if let Some(hue) = read("hue") {
+	    let unit = |name| read(name).unwrap_or(1.0).clamp(0.0, 1.0);
+	    Some(hsv_to_rgb(hue / 360.0, unit("saturation"), unit("value")))
+	} else if ["red", "green", "blue"].iter().any(|name| read(name).is_some()) {
+	    let unit = |name| read(name).unwrap_or(0.0).clamp(0.0, 1.0);
+	    Some(Color::new(unit("red"), unit("green"), unit("blue"), 1.0))
+	} else {
+	    None
+	};
+	Output {
+	    opacity: read("opacity").unwrap_or(0.0).clamp(0.0, 1.0),
+	    scale: read("scale").unwrap_or(1.0).clamp(0.0, 4.0),
+	    color,
+	}
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+	if c.is_whitespace() {
+	    continue;
+	}
+	if "+-*/%^<>(),".contains(c) {
+	    tokens.push(c.to_string());
+	    continue;
+	}
+	if !c.is_ascii_alphanumeric() && c != '_' && c != '.' {
+	    return Err(format!("unexpected `{c}`"));
+	}
+	let mut end = start + c.len_utf8();
+	while let Some(&(index, next)) = chars.peek() {
+	    if !next.is_ascii_alphanumeric() && next != '_' && next != '.' {
+		break;
+	    }
+	    end = index + next.len_utf8();
+	    chars.next();
+	}
+	tokens.push(text[start..end].to_string());
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens of one expression, loosest binding
+/// first.
+struct Parser<'a> {
+    tokens: Vec<String>,
+    next: usize,
+    names: &'a [String],
+    /// How many operands are being parsed inside one another.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+	self.tokens.get(self.next).map(String::as_str)
+    }
+
+    fn take(&mut self) -> Result<String, String> {
+	let token = self.tokens.get(self.next).cloned().ok_or("unexpected end of line")?;
+	self.next += 1;
+	Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+	match self.take()? {
+	    token if token == expected => Ok(()),
+	    token => Err(format!("expected `{expected}`, found `{token}`")),
+	}
+    }
+
+    /// Operands joined by any of `ops`, left to right.
+    fn binary(&mut self, ops: &str, operand: fn(&mut Self) -> Result<Expr, String>) -> Result<Expr, String> {
+	let mut expr = operand(self)?;
+	while let Some(op) = self.peek().filter(|token| token.len() == 1 && ops.contains(*token)).and_then(|token| token.chars().next()) {
+	    self.next += 1;
+	    expr = Expr::Binary(op, Box::new(expr), Box::new(operand(self)?));
+	}
+	Ok(expr)
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+	self.binary("<>", |parser| parser.binary("+-", |parser| parser.binary("*/%", Self::unary)))
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+	if self.depth == MAX_DEPTH {
+	    return Err("nested too deeply".to_string());
+	}
+	self.depth += 1;
+	let expr = if self.peek() == Some("-") {
+	    self.next += 1;
+	    self.unary().map(|expr| Expr::Negate(Box::new(expr)))
+	} else {
+	    self.power()
+	};
+	self.depth -= 1;
+	expr
+    }
+
+    /// Powers bind tighter than negation and group to the right.
+    fn power(&mut self) -> Result<Expr, String> {
+	let base = self.atom()?;
+	if self.peek() == Some("^") {
+	    self.next += 1;
+	    return Ok(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+	}
+	Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+	let token = self.take()?;
+	if token == "(" {
+	    let expr = self.expression()?;
+	    self.expect(")")?;
+	    return Ok(expr);
+	}
+	if token.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+	    return token.parse().map(Expr::Number).map_err(|_| format!("`{token}` is not a number"));
+	}
+	if !is_name(&token) {
+	    return Err(format!("unexpected `{token}`"));
+	}
+	if self.peek() == Some("(") {
+	    let function = FUNCTIONS.iter().position(|(name, _)| *name == token).ok_or(format!("unknown function `{token}`"))?;
+	    self.next += 1;
+	    let mut args = Vec::new();
+	    if self.peek() != Some(")") {
+		args.push(self.expression()?);
+		while self.peek() == Some(",") {
+		    self.next += 1;
+		    args.push(self.expression()?);
+		}
+	    }
+	    self.expect(")")?;
+	    let arity = FUNCTIONS[function].1;
+	    if args.len() != arity {
+		return Err(format!("`{token}` takes {arity} argument{}", if arity == 1 { "" } else { "s" }));
+	    }
+	    return Ok(Expr::Call(function, args));
+	}
+	match token.as_str() {
+	    "pi" => Ok(Expr::Number(std::f32::consts::PI)),
+	    _ => self.names.iter().position(|name| *name == token).map(Expr::Variable).ok_or(format!("`{token}` is not set")),
+	}
+    }
+}
+
+/// The `script` pattern's loaded script and what it made of each shape
+/// this frame.
+#[derive(Default)]
+pub(crate) struct Scripted {
+    /// The `script` setting last loaded, and its script unless that failed.
+    loaded: Option<(String, Option<Script>)>,
+    outputs: Vec<Output>,
+    slots: Vec<f32>,
+}
+
+impl Scripted {
+    /// Run the script on every shape at animation `time`, loading it first
+    /// when `config` names a different one.
+    pub(crate) fn update(&mut self, shapes: &[Shape], time: f32, config: &Config) {
+	if config.pattern != Pattern::Script || shapes.is_empty() {
+	    // Loaded again when picked again, so edits show
+	    *self = Scripted::default();
+	    return;
+	}
+	if self.loaded.as_ref().is_none_or(|(name, _)| *name != config.script) {
+	    self.loaded = Some((config.script.clone(), load(&config.script)));
+	}
+	let Some((_, Some(script))) = &self.loaded else {
+	    self.outputs.clear();
+	    return;
+	};
+	let count = shapes.len() as f32;
+	self.outputs = shapes
+	    .iter()
+	    .enumerate()
+	    .map(|(index, shape)| {
+		let inputs = [time, count, index as f32, shape.x, shape.y, shape.radius, shape.phase_offset, shape.audio_pos, shape.neighbors.len() as f32];
+		script.run(inputs, &mut self.slots)
+	    })
+	    .collect();
+    }
+
+    /// The script's output for the `index`th shape, if it ran.
+    pub(crate) fn output(&self, index: usize) -> Option<&Output> {
+	self.outputs.get(index)
+    }
+}
+
+/// The script in the file `name`, looked up in the config directory.
+fn load(name: &str) -> Option<Script> {
+    let path = Config::find_file(Path::new(name));
+    let text = std::fs::read_to_string(&path)
+	.map_err(|err| warn!("cannot read script {}: {err}", path.display()))
+	.ok()?;
+    Script::parse(&text).map_err(|err| warn!("script {}: {err}", path.display())).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, inputs: [f32; INPUTS.len()]) -> Output {
+	Script::parse(text).unwrap().run(inputs, &mut Vec::new())
+    }
+
+    #[test]
+    fn scripts_compute_each_shape() {
+	let script = "# in a band across the screen\nband = abs(x - 100) < radius\nopacity = band * (0.5 + time / 4)\nscale = 2 ^ -1\n";
+	let inputs = |x| [1.0, 10.0, 0.0, x, 0.0, 20.0, 0.0, 0.0, 6.0];
+	assert_eq!(run(script, inputs(110.0)), Output { opacity: 0.75, scale: 0.5, color: None });
+	assert_eq!(run(script, inputs(130.0)).opacity, 0.0);
+
+	assert_eq!(run("hue = 360\nvalue = 0.5", inputs(0.0)).color, Some(Color::new(0.5, 0.0, 0.0, 1.0)));
+	assert_eq!(run("green = clamp(neighbors - 5, 0, 1)", inputs(0.0)).color, Some(Color::new(0.0, 1.0, 0.0, 1.0)));
+	// Precedence, and broken values falling back
+	assert_eq!(run("opacity = -1 + 2 * 3 % 4 - -(0.5)", inputs(0.0)).opacity, 1.0);
+	assert_eq!(run("scale = sqrt(-1)", inputs(0.0)).scale, 1.0);
+    }
+
+    #[test]
+    fn mistakes_name_their_line() {
+	let err = |text| Script::parse(text).unwrap_err();
+	assert_eq!(err("a = 1\n\nb = wobble(a)"), "line 3: unknown function `wobble`");
+	assert_eq!(err("opacity = later"), "line 1: `later` is not set");
+	assert_eq!(err("time = 1"), "line 1: `time` cannot be assigned");
+	assert_eq!(err("opacity = max(1)"), "line 1: `max` takes 2 arguments");
+	assert_eq!(err("opacity = (1 + 2"), "line 1: unexpected end of line");
+	assert_eq!(err("opacity 1"), "line 1: expected `name = expression`");
+	assert_eq!(err("opacity = 1 2"), "line 1: unexpected `2`");
+	let nested = format!("opacity = {}1{}", "(".repeat(100_000), ")".repeat(100_000));
+	assert_eq!(err(&nested), "line 1: nested too deeply");
+	let negated = format!("opacity = {}1", "-".repeat(100_000));
+	assert_eq!(err(&negated), "line 1: nested too deeply");
+	assert!(Script::parse(&format!("opacity = {}1{}", "(".repeat(40), ")".repeat(40))).is_ok());
+    }
+}
@@ -0,0 +1,53 @@
+//! The looks `auto_theme` picks by the calendar: snowflakes in December,
+//! hearts around Valentine's Day and pumpkins before Halloween.
+
+use crate::config::Config;
+use chrono::{Datelike, NaiveDate};
+
+/// A day of the year, as (month, day).
+type MonthDay = (u32, u32);
+
+/// Each season's first and last day, then its shape and palette.
+const SEASONS: [(MonthDay, MonthDay, &str, &str); 3] = [
+    ((12, 1), (12, 31), "snowflake", "winter"),
+    ((2, 10), (2, 16), "heart", "valentine"),
+    ((10, 20), (10, 31), "pumpkin", "halloween"),
+];
+
+/// The shape and palette of the season `date` is in, if any.
+fn season(date: NaiveDate) -> Option<(&'static str, &'static str)> {
+    let day = (date.month(), date.day());
+    SEASONS.iter().find(|(first, last, ..)| (*first..=*last).contains(&day)).map(|(_, _, shape, palette)| (*shape, *palette))
+}
+
+/// `config` in the look of `date`'s season, or unchanged outside them.
+pub(crate) fn themed(mut config: Config, date: NaiveDate) -> Config {
+    if let Some((shape, palette)) = season(date) {
+	config.shape = shape.to_string();
+	config.palette = palette.to_string();
+	config.palette_image.clear();
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::palette::Palette;
+    use crate::shapes::ShapeRegistry;
+
+    #[test]
+    fn seasons_pick_their_looks_by_date() {
+	let date = |month, day| NaiveDate::from_ymd_opt(2026, month, day).unwrap();
+	assert_eq!(season(date(12, 24)), Some(("snowflake", "winter")));
+	assert_eq!(season(date(2, 14)), Some(("heart", "valentine")));
+	assert_eq!(season(date(10, 31)), Some(("pumpkin", "halloween")));
+	assert_eq!(season(date(10, 19)), None);
+	assert_eq!(themed(Config::default(), date(7, 1)).shape, Config::default().shape);
+	// Every look is built in
+	let registry = ShapeRegistry::with_builtins();
+	for (_, _, shape, palette) in SEASONS {
+	    assert!(registry.lookup(shape).is_some() && Palette::named(palette).is_some(), "{shape} {palette}");
+	}
+    }
+}
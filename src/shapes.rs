@@ -0,0 +1,971 @@
+use crate::config::Config;
+use crate::easing::Easing;
+use crate::flow::Fall;
+use crate::layout::{Orientation, Tile};
+use crate::palette::Palette;
+use crate::render::draw_commands;
+use crate::glyph::GlyphShape;
+use crate::svg::SvgShape;
+use log::warn;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+/// Triangles for a shape of radius 1 centered on the origin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tessellation {
+    pub vertices: Vec<Vec2>,
+    pub indices: Vec<u16>,
+}
+
+impl Tessellation {
+    /// Fill a closed outline as a triangle fan from the origin, which is
+    /// correct for any outline the origin can see all of.
+    pub fn fan(outline: &[Vec2]) -> Self {
+	let mut vertices = vec![Vec2::ZERO];
+	vertices.extend_from_slice(outline);
+	let points = outline.len() as u16;
+	let indices = (0..points)
+	    .flat_map(|i| [0, 1 + i, 1 + (i + 1) % points])
+	    .collect();
+	Self { vertices, indices }
+    }
+}
+
+/// A shape that can be drawn in each grid cell.
+///
+/// Shapes are tessellated once when registered; the renderer scales,
+/// rotates and moves the result for every cell.
+pub trait ShapeRenderer {
+    /// Name used to select the shape in the config, as in `shape = "heart"`.
+    fn name(&self) -> &str;
+
+    /// Label shown in the settings dialog.
+    fn label(&self) -> &str {
+	self.name()
+    }
+
+    fn tessellate(&self) -> Tessellation;
+}
+
+#[derive(Default)]
+pub struct Hexagon {
+    pub orientation: Orientation,
+    /// Radius of the rounded corners, as a share of the hexagon's.
+    pub corner_radius: f32,
+}
+
+impl ShapeRenderer for Hexagon {
+    fn name(&self) -> &str {
+	"hexagon"
+    }
+
+    fn label(&self) -> &str {
+	"Hexagons"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&round_corners(&hexagon_outline(self.orientation), self.corner_radius))
+    }
+}
+
+/// Segments of a heart's outline unless sized with `Heart::for_radius`.
+const HEART_SEGMENTS: usize = 100;
+/// Fewest and most segments `Heart::for_radius` picks.
+const HEART_SEGMENT_RANGE: (usize, usize) = (16, 400);
+
+pub struct Heart {
+    /// Points around the outline.
+    pub segments: usize,
+}
+
+impl Heart {
+    /// A heart outlined finely enough to look smooth drawn at `radius`
+    /// pixels, with `quality` times the segments that keep its edges within
+    /// about a quarter pixel of the curve.
+    pub fn for_radius(radius: f32, quality: f32) -> Self {
+	// A chord strays by r(1 - cos(pi / n)) from an arc of radius r
+	let segments = (quality * 2.0 * PI * (2.0 * radius.max(0.0)).sqrt()).ceil() as usize;
+	Self { segments: segments.clamp(HEART_SEGMENT_RANGE.0, HEART_SEGMENT_RANGE.1) }
+    }
+}
+
+impl Default for Heart {
+    fn default() -> Self {
+	Self { segments: HEART_SEGMENTS }
+    }
+}
+
+impl ShapeRenderer for Heart {
+    fn name(&self) -> &str {
+	"heart"
+    }
+
+    fn label(&self) -> &str {
+	"Hearts"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&heart_outline(self.segments))
+    }
+}
+
+pub struct Star;
+
+impl ShapeRenderer for Star {
+    fn name(&self) -> &str {
+	"star"
+    }
+
+    fn label(&self) -> &str {
+	"Stars"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&star_outline())
+    }
+}
+
+pub struct Triangle;
+
+impl ShapeRenderer for Triangle {
+    fn name(&self) -> &str {
+	"triangle"
+    }
+
+    fn label(&self) -> &str {
+	"Triangles"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&triangle_outline())
+    }
+}
+
+pub struct Circle;
+
+impl ShapeRenderer for Circle {
+    fn name(&self) -> &str {
+	"circle"
+    }
+
+    fn label(&self) -> &str {
+	"Circles"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&circle_outline())
+    }
+}
+
+pub struct Pumpkin;
+
+impl ShapeRenderer for Pumpkin {
+    fn name(&self) -> &str {
+	"pumpkin"
+    }
+
+    fn label(&self) -> &str {
+	"Pumpkins"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&pumpkin_outline())
+    }
+}
+
+pub struct Snowflake;
+
+impl ShapeRenderer for Snowflake {
+    fn name(&self) -> &str {
+	"snowflake"
+    }
+
+    fn label(&self) -> &str {
+	"Snowflakes"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&snowflake_outline())
+    }
+}
+
+/// A regular polygon, `polygon:<sides>`, or star polygon joining every
+/// `skip`th corner, `star:<sides>/<skip>`, with a corner pointing up.
+pub struct Polygon {
+    name: String,
+    sides: usize,
+    skip: usize,
+    corner_radius: f32,
+}
+
+impl Polygon {
+    /// Most sides accepted; beyond that they look like circles anyway.
+    const MAX_SIDES: usize = 100;
+
+    /// The polygon `name` asks for, if it is one.
+    pub fn parse(name: &str) -> Option<Self> {
+	let lower = name.to_ascii_lowercase();
+	let (sides, skip) = if let Some(sides) = lower.strip_prefix("polygon:") {
+	    (sides.trim().parse().ok()?, 1)
+	} else {
+	    let (sides, skip) = lower.strip_prefix("star:")?.split_once('/')?;
+	    (sides.trim().parse().ok()?, skip.trim().parse().ok()?)
+	};
+	// Skipping half the corners or more retraces a smaller star
+	let valid = (3..=Self::MAX_SIDES).contains(&sides) && skip >= 1 && skip * 2 < sides;
+	valid.then(|| Self { name: name.to_string(), sides, skip, corner_radius: 0.0 })
+    }
+
+    /// The same polygon with its corners rounded by `corner_radius`, a
+    /// share of its radius.
+    pub fn rounded(self, corner_radius: f32) -> Self {
+	Self { corner_radius, ..self }
+    }
+}
+
+impl ShapeRenderer for Polygon {
+    fn name(&self) -> &str {
+	&self.name
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&round_corners(&polygon_outline(self.sides, self.skip, 0.0), self.corner_radius))
+    }
+}
+
+/// Points around each outline the morph interpolates.
+const MORPH_POINTS: usize = 96;
+/// Steps from hexagon to heart the screensaver registers, as `morph:<step>`.
+pub(crate) const MORPH_STEPS: usize = 32;
+/// Full on and off flashes per second `flash_limit` allows a shape.
+pub(crate) const MAX_FLASH_HZ: f32 = 3.0;
+/// How much faster or slower than `color_transition_seconds` and
+/// `color_hold_seconds` a shape may be, so they drift apart.
+pub(crate) const COLOR_JITTER: f32 = 0.15;
+/// Color transitions per animation second of focus shapes.
+const FOCUS_FADE_SPEED: f32 = 0.05;
+
+/// A hexagon part way to a heart, selectable as `morph` and animated back
+/// and forth by the screensaver.
+pub struct Morph {
+    name: String,
+    outline: Vec<Vec2>,
+}
+
+impl Morph {
+    /// The shape `t` of the way, 0..1, from a hexagon facing `orientation`
+    /// to a heart.
+    pub fn new(orientation: Orientation, t: f32) -> Self {
+	Self::named("morph".to_string(), orientation, t)
+    }
+
+    /// The `step`th of the `MORPH_STEPS` steps of the morph.
+    pub(crate) fn step(orientation: Orientation, step: usize) -> Self {
+	Self::named(format!("morph:{step}"), orientation, step as f32 / (MORPH_STEPS - 1) as f32)
+    }
+
+    fn named(name: String, orientation: Orientation, t: f32) -> Self {
+	// Both outlines resampled at the same angles, so points pair up
+	let hexagon = resample_radially(&hexagon_outline(orientation), MORPH_POINTS);
+	let heart = resample_radially(&heart_outline(HEART_SEGMENTS), MORPH_POINTS);
+	let outline = hexagon.iter().zip(&heart).map(|(from, to)| from.lerp(*to, t)).collect();
+	Self { name, outline }
+    }
+}
+
+impl ShapeRenderer for Morph {
+    fn name(&self) -> &str {
+	&self.name
+    }
+
+    fn label(&self) -> &str {
+	"Morphing"
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	Tessellation::fan(&self.outline)
+    }
+}
+
+/// `count` points on a closed `outline` the origin can see all of, where
+/// rays at equal angles from the origin cross it.
+pub(crate) fn resample_radially(outline: &[Vec2], count: usize) -> Vec<Vec2> {
+    (0..count)
+	.map(|i| {
+	    let ray = Vec2::from_angle(i as f32 / count as f32 * 2.0 * PI);
+	    // The nearest crossing of the ray with any edge
+	    let distance = (0..outline.len())
+		.filter_map(|edge| {
+		    let (start, end) = (outline[edge], outline[(edge + 1) % outline.len()]);
+		    let along = end - start;
+		    let denominator = ray.perp_dot(along);
+		    if denominator.abs() < 1e-9 {
+			return None;
+		    }
+		    let distance = start.perp_dot(along) / denominator;
+		    let fraction = start.perp_dot(ray) / denominator;
+		    (distance >= 0.0 && (0.0..=1.0).contains(&fraction)).then_some(distance)
+		})
+		.fold(f32::INFINITY, f32::min);
+	    ray * if distance.is_finite() { distance } else { 0.0 }
+	})
+	.collect()
+}
+
+/// Handle to a shape in a `ShapeRegistry`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShapeId(usize);
+
+struct RegisteredShape {
+    renderer: Box<dyn ShapeRenderer>,
+    tessellation: Tessellation,
+}
+
+/// The shapes named by a `mix:<shape>[=<weight>],...` name, with their
+/// weights, 1 when left out; empty for other names.
+pub(crate) fn mix_entries(name: &str) -> Vec<(&str, f32)> {
+    let Some(list) = name.strip_prefix("mix:") else {
+	return Vec::new();
+    };
+    list.split(',')
+	.map(str::trim)
+	.filter(|entry| !entry.is_empty())
+	.map(|entry| match entry.split_once('=') {
+	    None => (entry, 1.0),
+	    Some((shape, weight)) => {
+		let weight = weight.trim().parse().ok().filter(|weight: &f32| *weight > 0.0).unwrap_or_else(|| {
+		    warn!("invalid weight in `{entry}`, using 1");
+		    1.0
+		});
+		(shape.trim(), weight)
+	    }
+	})
+	.collect()
+}
+
+/// Shapes available to the screensaver, keyed by name.
+pub struct ShapeRegistry {
+    shapes: Vec<RegisteredShape>,
+    /// Where each lowercased name is in `shapes`.
+    names: HashMap<String, usize>,
+}
+
+impl ShapeRegistry {
+    /// A registry holding every built-in shape.
+    pub fn with_builtins() -> Self {
+	let mut registry = Self { shapes: Vec::new(), names: HashMap::new() };
+	registry.register(Hexagon::default());
+	registry.register(Heart::default());
+	registry.register(Star);
+	registry.register(Triangle);
+	registry.register(Circle);
+	registry.register(Snowflake);
+	registry.register(Pumpkin);
+	registry.register(Tile::default());
+	registry.register(Morph::new(Orientation::default(), 0.0));
+	registry
+    }
+
+    /// Add `renderer`, replacing any shape registered under the same name.
+    pub fn register(&mut self, renderer: impl ShapeRenderer + 'static) -> ShapeId {
+	let shape = RegisteredShape { tessellation: renderer.tessellate(), renderer: Box::new(renderer) };
+	match self.lookup(shape.renderer.name()) {
+	    Some(id) => {
+		self.shapes[id.0] = shape;
+		id
+	    }
+	    None => {
+		self.names.insert(shape.renderer.name().to_ascii_lowercase(), self.shapes.len());
+		self.shapes.push(shape);
+		ShapeId(self.shapes.len() - 1)
+	    }
+	}
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<ShapeId> {
+	self.names.get(&name.to_ascii_lowercase()).copied().map(ShapeId)
+    }
+
+    /// Remove the shapes whose names start with `prefix`. The shapes after
+    /// them move down; the result maps each old id to its new one, and a
+    /// removed shape to the first one, like `resolve` falls back to.
+    pub(crate) fn remove_prefixed(&mut self, prefix: &str) -> impl Fn(ShapeId) -> ShapeId + use<> {
+	let mut kept = 0;
+	let moved: Vec<Option<ShapeId>> = self
+	    .shapes
+	    .iter()
+	    .map(|shape| {
+		(!shape.renderer.name().starts_with(prefix)).then(|| {
+		    kept += 1;
+		    ShapeId(kept - 1)
+		})
+	    })
+	    .collect();
+	self.shapes.retain(|shape| !shape.renderer.name().starts_with(prefix));
+	self.names = self.shapes.iter().enumerate().map(|(index, shape)| (shape.renderer.name().to_ascii_lowercase(), index)).collect();
+	move |id| moved[id.0].unwrap_or(ShapeId(0))
+    }
+
+    /// Like `lookup`, but loads `svg:<path>` and `glyph:<character>` names
+    /// on first use, and `polygon:` and `star:` names, and falls back to
+    /// the first registered shape with a warning, so a stale config name
+    /// still draws something. A `mix:` stands for its first shape.
+    pub fn resolve(&mut self, name: &str) -> ShapeId {
+	if let Some(id) = self.lookup(name) {
+	    return id;
+	}
+	if let Some(&(first, _)) = mix_entries(name).first() {
+	    return self.resolve(first);
+	}
+	if let Some(polygon) = Polygon::parse(name) {
+	    return self.register(polygon);
+	}
+	if name.starts_with("svg:") {
+	    match SvgShape::load(name) {
+		Ok(shape) => return self.register(shape),
+		Err(err) => warn!("cannot load shape `{name}`: {err}"),
+	    }
+	}
+	if name.starts_with("glyph:") {
+	    match GlyphShape::load(name) {
+		Ok(shape) => return self.register(shape),
+		Err(err) => warn!("cannot load shape `{name}`: {err}"),
+	    }
+	}
+	warn!("unknown shape `{name}`, using `{}`", self.shapes[0].renderer.name());
+	ShapeId(0)
+    }
+
+    /// The shapes of a `mix:` name and their weights, loaded like
+    /// `resolve` does; empty for other names.
+    pub(crate) fn resolve_mix(&mut self, name: &str) -> Vec<(ShapeId, f32)> {
+	mix_entries(name).into_iter().map(|(shape, weight)| (self.resolve(shape), weight)).collect()
+    }
+
+    pub fn renderer(&self, id: ShapeId) -> &dyn ShapeRenderer {
+	self.shapes[id.0].renderer.as_ref()
+    }
+
+    pub fn tessellation(&self, id: ShapeId) -> &Tessellation {
+	&self.shapes[id.0].tessellation
+    }
+
+    /// Every registered shape, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = (ShapeId, &dyn ShapeRenderer)> {
+	self.shapes
+	    .iter()
+	    .enumerate()
+	    .map(|(index, shape)| (ShapeId(index), shape.renderer.as_ref()))
+    }
+}
+
+impl Default for ShapeRegistry {
+    fn default() -> Self {
+	Self::with_builtins()
+    }
+}
+
+pub(crate) struct Shape {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) radius: f32,
+    pub(crate) color: Color,
+    pub(crate) next_color: Color,
+    pub(crate) transition_progress: f32,
+    /// Animation seconds left on the color before the next transition.
+    pub(crate) hold: f32,
+    pub(crate) phase_offset: f32,
+    pub(crate) static_color: bool,
+    /// Focus shapes skip the opacity wave and stay fully visible.
+    pub(crate) focus: bool,
+    /// Part of the clock's digits, likewise fully visible.
+    pub(crate) lit: bool,
+    /// Under a dark module of the QR code, kept hidden.
+    pub(crate) dark: bool,
+    /// Opacity added near the spotlight and under click rings, 0..1.
+    pub(crate) boost: f32,
+    /// Orientation in radians, following the grid rotation.
+    pub(crate) rotation: f32,
+    /// Position along the color field in the gradient color modes.
+    pub(crate) gradient_pos: f32,
+    /// Horizontal position on its display, 0..1, picking an audio band and
+    /// how much of the timer's bar it is.
+    pub(crate) audio_pos: f32,
+    /// Drawn instead of the style's shape, for cells with their own outline.
+    pub(crate) own_shape: Option<ShapeId>,
+    /// Indices of the bordering shapes on the same display.
+    pub(crate) neighbors: Vec<usize>,
+    /// 1 to spin clockwise with `rotation_speed`, -1 the other way.
+    pub(crate) spin: f32,
+    /// Opacity drawn last frame, which `flash_limit` fades from.
+    pub(crate) opacity: f32,
+    /// Animation time before which the shape stays hidden and is skipped.
+    pub(crate) next_visible: f32,
+    /// Pixels a second it drifts by in the `flow` motion.
+    pub(crate) velocity: Vec2,
+    /// On the way down from its cell in the `fall` motion.
+    pub(crate) fall: Option<Fall>,
+}
+
+impl Shape {
+    pub(crate) fn new(x: f32, y: f32, radius: f32, static_color: bool, palette: &Palette, rng: &RandGenerator) -> Self {
+	let color = palette.pick(rng);
+	Self {
+	    x,
+	    y,
+	    radius,
+	    color,
+	    next_color: if static_color { color } else { palette.pick(rng) },
+	    transition_progress: 0.0,
+	    hold: 0.0,
+	    phase_offset: rng.gen_range(0.0, 2. * PI),
+	    static_color,
+	    focus: false,
+	    lit: false,
+	    dark: false,
+	    boost: 0.0,
+	    rotation: 0.0,
+	    gradient_pos: 0.0,
+	    audio_pos: 0.0,
+	    own_shape: None,
+	    neighbors: Vec::new(),
+	    spin: 1.0,
+	    opacity: 0.0,
+	    next_visible: 0.0,
+	    velocity: Vec2::ZERO,
+	    fall: None,
+	}
+    }
+
+    /// This shape's share of `color_transition_seconds` and
+    /// `color_hold_seconds`, within `COLOR_JITTER` of 1.
+    pub(crate) fn color_pace(&self) -> f32 {
+	1.0 + COLOR_JITTER * (self.phase_offset * 3.0).sin()
+    }
+
+    /// Advance the color transition, as timed by `config`; `keep` keeps the
+    /// color it ends on instead of moving on to another from `palette`.
+    pub(crate) fn update(&mut self, mut dt: f32, palette: &Palette, rng: &RandGenerator, keep: bool, config: &Config) {
+	if self.static_color {
+	    return;
+	}
+	if self.hold > 0.0 {
+	    self.hold -= dt;
+	    if self.hold > 0.0 {
+		return;
+	    }
+	    // Fading for what is left of the frame
+	    dt = -self.hold;
+	    self.hold = 0.0;
+	}
+	let transition_speed = if self.focus { FOCUS_FADE_SPEED } else { 1.0 / (config.color_transition_seconds * self.color_pace()) };
+	self.transition_progress += dt * transition_speed;
+
+	if self.transition_progress >= 1.0 {
+	    self.color = self.next_color;
+	    if !keep {
+		self.next_color = palette.pick(rng);
+	    }
+	    self.transition_progress = 0.0;
+	    self.hold = config.color_hold_seconds * self.color_pace();
+	}
+    }
+
+    /// Start a transition from the current color, eased by `easing`, to
+    /// `color`.
+    pub(crate) fn fade_to(&mut self, color: Color, easing: Easing) {
+	self.color = self.current_color(easing);
+	self.next_color = color;
+	self.transition_progress = 0.0;
+    }
+
+    fn current_color(&self, easing: Easing) -> Color {
+	let progress = easing.apply(self.transition_progress);
+	Color::new(
+	    self.color.r + (self.next_color.r - self.color.r) * progress,
+	    self.color.g + (self.next_color.g - self.color.g) * progress,
+	    self.color.b + (self.next_color.b - self.color.b) * progress,
+	    1.0,
+	)
+    }
+
+    /// The command drawing this shape with its opacity `wave`, -1..1, or
+    /// none while the wave is below `config.threshold()`. Shapes grow by
+    /// `config.breathing` as they fade all the way in; with
+    /// `config.flash_limit` the opacity changes by at most one full fade
+    /// per half flash over the `dt` seconds since the last frame.
+    pub(crate) fn draw_command(&mut self, wave: f32, dt: f32, shape: ShapeId, config: &Config) -> Option<DrawCommand> {
+	let target = if let Some(fall) = &self.fall {
+	    fall.opacity(self.y)
+	} else if self.dark {
+	    0.0
+	} else if self.focus || self.lit {
+	    1.0
+	} else {
+	    Envelope::new(config).opacity(wave)
+	};
+	let target = if self.dark { target } else { (target + self.boost).min(1.0) };
+	// Focusing is a single change the viewer asked for, not a flash
+	let opacity = if config.flash_limit && !self.focus {
+	    let step = 2.0 * MAX_FLASH_HZ * dt;
+	    self.opacity + (target - self.opacity).clamp(-step, step)
+	} else {
+	    target
+	};
+	self.opacity = opacity;
+
+	if opacity <= 0.01 {
+	    return None;
+	}
+
+	let current_color = Color { a: opacity, ..self.current_color(config.color_easing) };
+
+	Some(DrawCommand {
+	    x: self.x,
+	    y: self.y,
+	    radius: if config.reduced_motion { self.radius } else { self.radius * (1.0 + config.breathing * opacity) },
+	    rotation: self.rotation,
+	    shape: self.own_shape.unwrap_or(shape),
+	    color: current_color,
+	})
+    }
+}
+
+/// How a shape's wave maps to its opacity: hidden up to `threshold`, then
+/// fading in by `easing` and at full brightness over the top `duty` of the
+/// rest.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Envelope {
+    pub(crate) threshold: f32,
+    pub(crate) duty: f32,
+    pub(crate) easing: Easing,
+}
+
+impl Envelope {
+    pub(crate) fn new(config: &Config) -> Self {
+	Self { threshold: config.threshold(), duty: config.twinkle_duty, easing: config.fade_easing }
+    }
+
+    /// Opacity, 0..1, at `wave`, -1..1.
+    pub(crate) fn opacity(self, wave: f32) -> f32 {
+	if wave <= self.threshold {
+	    return 0.0;
+	}
+	self.easing.apply((wave - self.threshold) / self.fade_width()).clamp(0.0, 1.0)
+    }
+
+    /// The steepest the opacity changes per unit of wave.
+    pub(crate) fn max_slope(self) -> f32 {
+	self.easing.max_slope() / self.fade_width().max(1e-3)
+    }
+
+    /// How much of the wave the fade takes.
+    fn fade_width(self) -> f32 {
+	(1.0 - self.threshold) * (1.0 - self.duty)
+    }
+}
+
+/// Index of the shape closest to `pos`, given in pixels.
+pub(crate) fn nearest_shape(shapes: &[Shape], pos: Vec2) -> Option<usize> {
+    shapes
+	.iter()
+	.enumerate()
+	.min_by(|(_, a), (_, b)| {
+	    let da = Vec2::new(a.x, a.y).distance_squared(pos);
+	    let db = Vec2::new(b.x, b.y).distance_squared(pos);
+	    da.total_cmp(&db)
+	})
+	.map(|(index, _)| index)
+}
+
+/// A single visible shape for the current frame, produced by `step_frame`
+/// and consumed by the render loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawCommand {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+    pub rotation: f32,
+    pub shape: ShapeId,
+    pub color: Color,
+}
+
+impl DrawCommand {
+    pub fn draw(&self, registry: &ShapeRegistry) {
+	draw_commands(registry, std::slice::from_ref(self));
+    }
+}
+
+fn heart_outline(segments: usize) -> Vec<Vec2> {
+    // Parametric heart equation, scaled so its lobes span roughly the radius
+    (0..segments)
+	.map(|i| {
+	    let t = (i as f32 / segments as f32) * 2.0 * PI;
+	    let heart_x = 16.0 * t.sin().powi(3);
+	    let heart_y = -(13.0 * t.cos() - 5.0 * (2.0 * t).cos() - 2.0 * (3.0 * t).cos() - (4.0 * t).cos());
+	    Vec2::new(heart_x, heart_y) / 20.0
+	})
+	.collect()
+}
+
+pub(crate) fn hexagon_outline(orientation: Orientation) -> Vec<Vec2> {
+    let start = match orientation {
+	Orientation::Flat => 90.0,
+	Orientation::Pointy => 120.0,
+    };
+    polygon_outline(6, 1, start)
+}
+
+/// The corners of a regular polygon with `sides` on the unit circle, the
+/// first at `start` degrees from straight up. With a `skip` above 1 it is
+/// the star joining every `skip`th corner, its outline dipping between
+/// corners to where the lines cross.
+pub(crate) fn polygon_outline(sides: usize, skip: usize, start: f32) -> Vec<Vec2> {
+    let step = 360.0 / sides as f32;
+    if skip <= 1 {
+	return polar_outline((0..sides).map(|i| (1.0, start + i as f32 * step)));
+    }
+    let inner = (PI * skip as f32 / sides as f32).cos() / (PI * (skip - 1) as f32 / sides as f32).cos();
+    polar_outline((0..sides * 2).map(|i| (if i % 2 == 0 { 1.0 } else { inner }, start + i as f32 * step / 2.0)))
+}
+
+/// Points along each rounded corner.
+const CORNER_SEGMENTS: usize = 6;
+
+/// `outline` with each corner cut round by an arc of `radius`, or the
+/// largest that fits between it and its neighbors.
+pub(crate) fn round_corners(outline: &[Vec2], radius: f32) -> Vec<Vec2> {
+    if radius <= 0.0 {
+	return outline.to_vec();
+    }
+    let count = outline.len();
+    (0..count)
+	.flat_map(|i| {
+	    let corner = outline[i];
+	    let (to_previous, to_next) = (outline[(i + count - 1) % count] - corner, outline[(i + 1) % count] - corner);
+	    let half_angle = to_previous.angle_between(to_next).abs() / 2.0;
+	    // Arcs end no further along an edge than its middle, so they
+	    // never overlap the next corner's
+	    let tangent = (radius / half_angle.tan()).min(to_previous.length() / 2.0).min(to_next.length() / 2.0);
+	    let arc_radius = tangent * half_angle.tan();
+	    let center = corner + (to_previous.normalize() + to_next.normalize()).normalize_or_zero() * arc_radius / half_angle.sin().max(f32::EPSILON);
+	    let start = corner + to_previous.normalize() * tangent - center;
+	    let sweep = start.angle_between(corner + to_next.normalize() * tangent - center);
+	    (0..=CORNER_SEGMENTS).map(move |step| center + Vec2::from_angle(sweep * step as f32 / CORNER_SEGMENTS as f32).rotate(start))
+	})
+	.collect()
+}
+
+/// Points at `(radius, degrees)` pairs, with 0 degrees pointing up.
+fn polar_outline(points: impl IntoIterator<Item = (f32, f32)>) -> Vec<Vec2> {
+    points
+	.into_iter()
+	.map(|(radius, degrees)| Vec2::from_angle((degrees - 90.0).to_radians()) * radius)
+	.collect()
+}
+
+fn star_outline() -> Vec<Vec2> {
+    polar_outline((0..10).map(|i| (if i % 2 == 0 { 1.0 } else { 0.4 }, i as f32 * 36.0)))
+}
+
+pub(crate) fn triangle_outline() -> Vec<Vec2> {
+    polygon_outline(3, 1, 0.0)
+}
+
+/// An axis aligned square with its corners on the unit circle.
+pub(crate) fn square_outline() -> Vec<Vec2> {
+    polygon_outline(4, 1, 45.0)
+}
+
+fn circle_outline() -> Vec<Vec2> {
+    polar_outline((0..48).map(|i| (1.0, i as f32 * 7.5)))
+}
+
+fn pumpkin_outline() -> Vec<Vec2> {
+    // A squat body notched between five ribs, under a stem; still one point
+    // per angle, so fan-fillable from its center
+    polar_outline((0..72).map(|i| {
+	let degrees = i as f32 * 5.0;
+	let (sin, cos) = degrees.to_radians().sin_cos();
+	let body = 0.75 / ((sin * 0.75 / 0.95).powi(2) + cos.powi(2)).sqrt();
+	let rib = 1.0 - 0.08 * (1.0 - (degrees.to_radians() * 2.5).sin().abs()).powi(4);
+	let stem = !(10.0..=350.0).contains(&degrees);
+	(if stem { 1.0 } else { body * rib }, degrees)
+    }))
+}
+
+fn snowflake_outline() -> Vec<Vec2> {
+    // Six arms with a pair of side branches each. Every point is at a larger
+    // angle than the last, so the outline is still fan-fillable from its center.
+    let arm = [(0.3, -30.0), (0.6, -14.0), (0.45, -5.0), (1.0, -2.0), (1.0, 2.0), (0.45, 5.0), (0.6, 14.0)];
+    polar_outline((0..6).flat_map(|i| arm.map(|(radius, degrees)| (radius, degrees + i as f32 * 60.0))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Square;
+
+    impl ShapeRenderer for Square {
+	fn name(&self) -> &str {
+	    "square"
+	}
+
+	fn tessellate(&self) -> Tessellation {
+	    Tessellation::fan(&[vec2(1.0, 1.0), vec2(-1.0, 1.0), vec2(-1.0, -1.0), vec2(1.0, -1.0)])
+	}
+    }
+
+    #[test]
+    fn registry_looks_up_custom_shapes_by_name() {
+	let mut registry = ShapeRegistry::with_builtins();
+	let square = registry.register(Square);
+	assert_eq!(registry.lookup("Square"), Some(square));
+	assert_eq!(registry.tessellation(square).indices.len(), 12);
+	assert_eq!(registry.resolve("teapot"), registry.lookup("hexagon").unwrap());
+
+	// Registering the same name again replaces the shape in place
+	assert_eq!(registry.register(Square), square);
+	assert_eq!(registry.iter().count(), 10);
+
+	// Shapes after removed ones move down
+	let hexagon = registry.lookup("hexagon").unwrap();
+	let moved = registry.remove_prefixed("mor");
+	assert_eq!(moved(square), ShapeId(square.0 - 1));
+	assert_eq!(moved(hexagon), hexagon);
+	assert!(registry.lookup("morph").is_none());
+	assert_eq!(registry.lookup("square"), Some(ShapeId(square.0 - 1)));
+    }
+
+    #[test]
+    fn morph_runs_from_hexagon_to_heart() {
+	let hexagon = Morph::step(Orientation::Flat, 0).outline;
+	let heart = Morph::step(Orientation::Flat, MORPH_STEPS - 1).outline;
+	assert_eq!(hexagon.len(), heart.len());
+	// Corners stay on the unit circle, edge midpoints dip to sin 60°
+	assert!((hexagon[0].length() - 1.0).abs() < 1e-4);
+	assert!((hexagon[MORPH_POINTS / 12].length() - 0.866).abs() < 1e-3);
+	// The heart's outline is a hundred points, so resampled points lie
+	// close to its edges
+	let outline = heart_outline(HEART_SEGMENTS);
+	for point in heart {
+	    let nearest = outline.iter().map(|vertex| vertex.distance(point)).fold(f32::INFINITY, f32::min);
+	    assert!(nearest < 0.05);
+	}
+    }
+
+    #[test]
+    fn polygons_and_stars_have_their_corners() {
+	let flat = hexagon_outline(Orientation::Flat);
+	assert!(flat[0].distance(vec2(1.0, 0.0)) < 1e-5);
+	assert!(hexagon_outline(Orientation::Pointy)[0].distance(Vec2::from_angle(30f32.to_radians())) < 1e-5);
+	let pentagon = polygon_outline(5, 1, 0.0);
+	assert_eq!(pentagon.len(), 5);
+	assert!(pentagon[0].distance(vec2(0.0, -1.0)) < 1e-5);
+	// The pentagram dips to 0.382 between its points
+	let pentagram = polygon_outline(5, 2, 0.0);
+	assert_eq!(pentagram.len(), 10);
+	assert!((pentagram[0].length() - 1.0).abs() < 1e-5 && (pentagram[1].length() - 0.382).abs() < 1e-3);
+	assert_eq!(Polygon::parse("Star:7/2").map(|star| (star.sides, star.skip)), Some((7, 2)));
+	assert_eq!(Polygon::parse("polygon:8").map(|polygon| (polygon.sides, polygon.skip)), Some((8, 1)));
+	for name in ["polygon:2", "star:5/3", "star:5", "polygon:x", "hexagon"] {
+	    assert!(Polygon::parse(name).is_none(), "{name}");
+	}
+	let mut registry = ShapeRegistry::with_builtins();
+	let star = registry.resolve("star:7/3");
+	assert_eq!(registry.renderer(star).name(), "star:7/3");
+    }
+
+    #[test]
+    fn rounded_corners_pull_in_from_the_unit_circle() {
+	let square = square_outline();
+	assert_eq!(round_corners(&square, 0.0), square);
+	let rounded = round_corners(&square, 0.2);
+	assert_eq!(rounded.len(), 4 * (CORNER_SEGMENTS + 1));
+	// The diagonal loses the corner's overhang past the arc
+	let farthest = rounded.iter().map(|point| point.length()).fold(0.0, f32::max);
+	assert!((farthest - (1.0 - 0.2 * (2f32.sqrt() - 1.0))).abs() < 1e-3);
+	// Edges stay put
+	let half_side = 0.5f32.sqrt();
+	assert!(rounded.iter().all(|point| point.x.abs() <= half_side + 1e-5 && point.y.abs() <= half_side + 1e-5));
+	// However large the radius, arcs end at the middle of the edges
+	let circle = round_corners(&square, 10.0);
+	assert!(circle.iter().all(|point| (point.length() - half_side).abs() < 1e-4));
+    }
+
+    #[test]
+    fn mixes_list_their_shapes_and_weights() {
+	assert_eq!(mix_entries("mix:hexagon, heart=2,star:7/2=0.5"), vec![("hexagon", 1.0), ("heart", 2.0), ("star:7/2", 0.5)]);
+	assert_eq!(mix_entries("mix:heart=-1"), vec![("heart", 1.0)]);
+	assert!(mix_entries("hexagon").is_empty());
+	let mut registry = ShapeRegistry::with_builtins();
+	assert_eq!(registry.resolve("mix:heart,star"), registry.lookup("heart").unwrap());
+    }
+
+    #[test]
+    fn builtin_tessellations_are_in_bounds() {
+	let registry = ShapeRegistry::with_builtins();
+	for (id, renderer) in registry.iter() {
+	    let Tessellation { vertices, indices } = registry.tessellation(id);
+	    assert_eq!(indices.len() % 3, 0, "{}", renderer.name());
+	    assert!(indices.iter().all(|&index| (index as usize) < vertices.len()), "{}", renderer.name());
+	    assert!(vertices.iter().all(|vertex| vertex.length() <= 1.0 + 1e-4), "{}", renderer.name());
+	}
+    }
+
+    #[test]
+    fn hearts_get_finer_as_they_grow() {
+	let segments = |radius| Heart::for_radius(radius, 1.0).segments;
+	assert!(segments(5.0) < segments(40.0) && segments(40.0) < segments(400.0));
+	assert_eq!((segments(0.0), segments(1e6)), HEART_SEGMENT_RANGE);
+	assert!(Heart::for_radius(40.0, 2.0).segments.abs_diff(2 * segments(40.0)) <= 1);
+	// Each segment stays within a quarter pixel of the curve
+	for radius in [40.0, 400.0] {
+	    let outline = heart_outline(segments(radius));
+	    let fine = heart_outline(20 * segments(radius));
+	    for (index, point) in fine.iter().enumerate() {
+		let chord = (outline[index / 20], outline[(index / 20 + 1) % outline.len()]);
+		let along = (*point - chord.0).dot(chord.1 - chord.0) / (chord.1 - chord.0).length_squared();
+		let nearest = chord.0.lerp(chord.1, along.clamp(0.0, 1.0));
+		assert!(point.distance(nearest) * radius < 0.25, "{radius}: {index}");
+	    }
+	}
+	assert_eq!(Heart::for_radius(40.0, 1.0).tessellate().vertices.len(), segments(40.0) + 1);
+    }
+
+    #[test]
+    fn envelopes_hold_full_brightness_for_their_duty() {
+	let config = Config { density: 0.5, fade_easing: Easing::Linear, ..Default::default() };
+	let plain = Envelope::new(&config);
+	assert_eq!(plain.opacity(0.4), 0.0);
+	assert!((plain.opacity(0.75) - 0.5).abs() < 1e-5);
+	let held = Envelope::new(&Config { twinkle_duty: 0.5, ..config });
+	assert!((held.opacity(0.625) - 0.5).abs() < 1e-5);
+	assert_eq!((held.opacity(0.75), held.opacity(1.0)), (1.0, 1.0));
+	assert!((held.max_slope() - 2.0 * plain.max_slope()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn colors_fade_then_hold() {
+	let rng = RandGenerator::new();
+	rng.srand(3);
+	let palette = Palette::from_colors(vec![RED, BLUE]);
+	let config = Config { color_transition_seconds: 2.0, color_hold_seconds: 4.0, ..Default::default() };
+	let mut shape = Shape::new(0.0, 0.0, 10.0, false, &palette, &rng);
+	let pace = shape.color_pace();
+	assert!((1.0 - COLOR_JITTER..=1.0 + COLOR_JITTER).contains(&pace));
+	shape.update(pace, &palette, &rng, false, &config);
+	assert!((shape.transition_progress - 0.5).abs() < 1e-4);
+	shape.update(pace, &palette, &rng, true, &config);
+	let held = shape.color;
+	assert_eq!((shape.transition_progress, shape.hold), (0.0, 4.0 * pace));
+	// Nothing moves until the hold is over
+	shape.update(3.0 * pace, &palette, &rng, true, &config);
+	assert_eq!((shape.color, shape.transition_progress), (held, 0.0));
+	shape.update(2.0 * pace, &palette, &rng, true, &config);
+	assert!((shape.transition_progress - 0.5).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,247 @@
+//! `share_texture`: each finished frame copied into a texture on the GPU
+//! and published through Spout on Windows or Syphon on macOS, for OBS,
+//! Resolume and other VJ software to take as a live source. The sharing
+//! libraries are loaded when sharing starts, so only the machines sharing
+//! need them installed; it is built with the `share_texture` feature.
+
+use log::warn;
+use macroquad::prelude::*;
+
+/// What the shared texture is called in the software picking it up.
+#[cfg(feature = "share_texture")]
+const SENDER_NAME: &str = "Hexagon Screensaver";
+
+/// The frame, copied out of the window, and where it goes.
+#[cfg_attr(not(feature = "share_texture"), allow(dead_code))]
+pub(crate) struct TextureShare {
+    frame: Option<Texture2D>,
+    #[cfg(feature = "share_texture")]
+    sender: Sender,
+}
+
+#[cfg_attr(not(feature = "share_texture"), allow(dead_code))]
+impl TextureShare {
+    /// Start publishing, or nothing where the sharing library is missing.
+    pub(crate) fn start() -> Option<Self> {
+	#[cfg(feature = "share_texture")]
+	return match Sender::open() {
+	    Ok(sender) => {
+		log::info!("share: publishing frames as \"{SENDER_NAME}\"");
+		Some(Self { frame: None, sender })
+	    }
+	    Err(err) => {
+		warn!("cannot share the texture: {err}");
+		None
+	    }
+	};
+	#[cfg(not(feature = "share_texture"))]
+	{
+	    warn!("cannot share the texture: built without the share_texture feature");
+	    None
+	}
+    }
+
+    /// Copy what has been drawn this frame and publish it.
+    pub(crate) fn publish(&mut self) {
+	let scale = screen_dpi_scale();
+	let (width, height) = ((screen_width() * scale) as u32, (screen_height() * scale) as u32);
+	if !self.frame.as_ref().is_some_and(|frame| frame.size() == vec2(width as f32, height as f32)) {
+	    self.frame = Some(render_target(width, height).texture);
+	}
+	let Some(frame) = &self.frame else {
+	    return;
+	};
+	unsafe { get_internal_gl() }.flush();
+	frame.grab_screen();
+	#[allow(unreachable_patterns)]
+	let id = match unsafe { get_internal_gl().quad_context.texture_raw_id(frame.raw_miniquad_id()) } {
+	    miniquad::RawId::OpenGl(id) => id,
+	    _ => return,
+	};
+	#[cfg(feature = "share_texture")]
+	self.sender.send(id, width, height);
+	#[cfg(not(feature = "share_texture"))]
+	let _ = id;
+    }
+}
+
+/// A Spout sender, through the SpoutLibrary DLL of the Spout SDK.
+#[cfg(all(feature = "share_texture", windows))]
+struct Sender {
+    spout: *mut *const usize,
+}
+
+/// Slots of the methods called in the `SPOUTLIBRARY` interface's table,
+/// as declared in SpoutLibrary.h of Spout 2.007.
+#[cfg(all(feature = "share_texture", windows))]
+mod spout_slots {
+    pub(super) const SET_SENDER_NAME: usize = 0;
+    pub(super) const RELEASE_SENDER: usize = 2;
+    pub(super) const SEND_TEXTURE: usize = 4;
+}
+
+#[cfg(all(feature = "share_texture", windows))]
+impl Sender {
+    fn open() -> Result<Self, String> {
+	use windows_sys::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
+
+	let library: Vec<u16> = "SpoutLibrary.dll".encode_utf16().chain([0]).collect();
+	unsafe {
+	    let module = LoadLibraryW(library.as_ptr());
+	    if module.is_null() {
+		return Err("SpoutLibrary.dll was not found beside the program or on the path".to_string());
+	    }
+	    let get_spout = GetProcAddress(module, c"GetSpout".as_ptr().cast()).ok_or("SpoutLibrary.dll has no GetSpout")?;
+	    let get_spout: extern "system" fn() -> *mut *const usize = std::mem::transmute(get_spout);
+	    let spout = get_spout();
+	    if spout.is_null() {
+		return Err("Spout could not start".to_string());
+	    }
+	    let sender = Self { spout };
+	    let set_sender_name: extern "system" fn(*mut *const usize, *const std::ffi::c_char) = std::mem::transmute(sender.method(spout_slots::SET_SENDER_NAME));
+	    let name = std::ffi::CString::new(SENDER_NAME).unwrap_or_default();
+	    set_sender_name(spout, name.as_ptr());
+	    Ok(sender)
+	}
+    }
+
+    /// The address in slot `slot` of the interface's method table.
+    unsafe fn method(&self, slot: usize) -> usize {
+	unsafe { *(*self.spout).add(slot) }
+    }
+
+    fn send(&mut self, texture: u32, width: u32, height: u32) {
+	unsafe {
+	    let send_texture: extern "system" fn(*mut *const usize, u32, u32, u32, u32, bool, u32) -> bool = std::mem::transmute(self.method(spout_slots::SEND_TEXTURE));
+	    // GL textures are upside down to everyone else
+	    send_texture(self.spout, texture, miniquad::gl::GL_TEXTURE_2D, width, height, true, 0);
+	}
+    }
+}
+
+#[cfg(all(feature = "share_texture", windows))]
+impl Drop for Sender {
+    fn drop(&mut self) {
+	unsafe {
+	    let release_sender: extern "system" fn(*mut *const usize, u32) = std::mem::transmute(self.method(spout_slots::RELEASE_SENDER));
+	    release_sender(self.spout, 0);
+	}
+    }
+}
+
+/// A Syphon OpenGL server, through the Objective-C runtime and the Syphon
+/// framework.
+#[cfg(all(feature = "share_texture", target_os = "macos"))]
+struct Sender {
+    server: *mut std::ffi::c_void,
+}
+
+#[cfg(all(feature = "share_texture", target_os = "macos"))]
+mod objc {
+    use std::ffi::{c_char, c_int, c_void};
+
+    pub(super) type Id = *mut c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub(super) struct Size {
+	pub(super) width: f64,
+	pub(super) height: f64,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub(super) struct Rect {
+	pub(super) origin: Size,
+	pub(super) size: Size,
+    }
+
+    #[link(name = "objc")]
+    unsafe extern "C" {
+	pub(super) fn objc_getClass(name: *const c_char) -> Id;
+	pub(super) fn sel_registerName(name: *const c_char) -> Id;
+	pub(super) fn objc_msgSend();
+    }
+
+    #[link(name = "OpenGL", kind = "framework")]
+    unsafe extern "C" {
+	pub(super) fn CGLGetCurrentContext() -> Id;
+    }
+
+    unsafe extern "C" {
+	pub(super) fn dlopen(path: *const c_char, mode: c_int) -> *mut c_void;
+    }
+
+    pub(super) const RTLD_NOW: c_int = 2;
+}
+
+#[cfg(all(feature = "share_texture", target_os = "macos"))]
+impl Sender {
+    /// Where the Syphon framework is looked for: bundled with the program,
+    /// then installed for everyone.
+    const FRAMEWORKS: [&'static std::ffi::CStr; 2] =
+	[c"@executable_path/../Frameworks/Syphon.framework/Syphon", c"/Library/Frameworks/Syphon.framework/Syphon"];
+
+    fn open() -> Result<Self, String> {
+	use objc::*;
+
+	unsafe {
+	    if !Self::FRAMEWORKS.iter().any(|path| !dlopen(path.as_ptr(), RTLD_NOW).is_null()) {
+		return Err("the Syphon framework is not installed".to_string());
+	    }
+	    let server_class = objc_getClass(c"SyphonOpenGLServer".as_ptr());
+	    if server_class.is_null() {
+		return Err("the Syphon framework has no SyphonOpenGLServer".to_string());
+	    }
+	    let send: extern "C" fn(Id, Id) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+	    let send_str: extern "C" fn(Id, Id, *const std::ffi::c_char) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+	    let init: extern "C" fn(Id, Id, Id, Id, Id) -> Id = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+	    let name = std::ffi::CString::new(SENDER_NAME).unwrap_or_default();
+	    let name = send_str(objc_getClass(c"NSString".as_ptr()), sel_registerName(c"stringWithUTF8String:".as_ptr()), name.as_ptr());
+	    let server = send(server_class, sel_registerName(c"alloc".as_ptr()));
+	    let server = init(server, sel_registerName(c"initWithName:context:options:".as_ptr()), name, CGLGetCurrentContext(), std::ptr::null_mut());
+	    if server.is_null() {
+		return Err("Syphon could not start a server".to_string());
+	    }
+	    Ok(Self { server })
+	}
+    }
+
+    fn send(&mut self, texture: u32, width: u32, height: u32) {
+	use objc::*;
+
+	let size = Size { width: width as f64, height: height as f64 };
+	let region = Rect { origin: Size { width: 0.0, height: 0.0 }, size };
+	unsafe {
+	    let publish: extern "C" fn(Id, Id, u32, u32, Rect, Size, bool) = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+	    let selector = sel_registerName(c"publishFrameTexture:textureTarget:imageRegion:textureDimensions:flipped:".as_ptr());
+	    publish(self.server, selector, texture, miniquad::gl::GL_TEXTURE_2D, region, size, true);
+	}
+    }
+}
+
+#[cfg(all(feature = "share_texture", target_os = "macos"))]
+impl Drop for Sender {
+    fn drop(&mut self) {
+	use objc::*;
+
+	unsafe {
+	    let send: extern "C" fn(Id, Id) = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+	    send(self.server, sel_registerName(c"stop".as_ptr()));
+	    send(self.server, sel_registerName(c"release".as_ptr()));
+	}
+    }
+}
+
+/// Nowhere to publish to on other systems.
+#[cfg(all(feature = "share_texture", not(any(windows, target_os = "macos"))))]
+struct Sender;
+
+#[cfg(all(feature = "share_texture", not(any(windows, target_os = "macos"))))]
+impl Sender {
+    fn open() -> Result<Self, String> {
+	Err("sharing needs Spout on Windows or Syphon on macOS".to_string())
+    }
+
+    fn send(&mut self, _: u32, _: u32, _: u32) {}
+}
@@ -0,0 +1,109 @@
+//! Photos from a folder, taking turns as the fill of the lit shapes.
+
+use crate::background::load_image;
+use log::warn;
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The photos of a folder and which one shows.
+pub(crate) struct Slideshow {
+    paths: Vec<PathBuf>,
+    index: usize,
+    /// Seconds each photo shows.
+    interval: f32,
+    shown: f32,
+    /// The current photo, uploaded when first drawn.
+    texture: Option<Texture2D>,
+}
+
+impl Slideshow {
+    /// The PNG and TGA files in `folder` in name order, each showing for
+    /// `minutes`.
+    pub(crate) fn new(folder: &Path, minutes: f32) -> io::Result<Self> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(folder)?
+	    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+	    .filter(|path| is_photo(path))
+	    .collect();
+	if paths.is_empty() {
+	    return Err(io::Error::new(io::ErrorKind::NotFound, "no PNG or TGA images"));
+	}
+	paths.sort();
+	Ok(Self { paths, index: 0, interval: minutes * 60.0, shown: 0.0, texture: None })
+    }
+
+    /// Advance by `dt` seconds, moving on to the next photo once the
+    /// current one has had its time.
+    pub(crate) fn step(&mut self, dt: f32) {
+	self.shown += dt;
+	if self.shown >= self.interval {
+	    self.shown = 0.0;
+	    self.index = (self.index + 1) % self.paths.len();
+	    self.texture = None;
+	}
+    }
+
+    /// The current photo, loading it if needed. Photos that fail to load
+    /// are skipped; `None` once none of them do.
+    pub(crate) fn texture(&mut self) -> Option<&Texture2D> {
+	while self.texture.is_none() && !self.paths.is_empty() {
+	    let path = &self.paths[self.index];
+	    match load_image(path) {
+		Ok(image) => self.texture = Some(Texture2D::from_image(&image)),
+		Err(err) => {
+		    warn!("cannot read photo {}: {err}", path.display());
+		    self.paths.remove(self.index);
+		    self.index = if self.paths.is_empty() { 0 } else { self.index % self.paths.len() };
+		}
+	    }
+	}
+	self.texture.as_ref()
+    }
+}
+
+/// Where a photo `size` pixels big goes to cover all of `bounds`, cropping
+/// what sticks out evenly at both sides.
+pub(crate) fn covering(size: Vec2, bounds: Rect) -> Rect {
+    let size = size * (bounds.size() / size.max(Vec2::ONE)).max_element();
+    let corner = bounds.center() - size / 2.0;
+    Rect::new(corner.x, corner.y, size.x, size.y)
+}
+
+fn is_photo(path: &Path) -> bool {
+    path.extension()
+	.and_then(|extension| extension.to_str())
+	.is_some_and(|extension| extension.eq_ignore_ascii_case("png") || extension.eq_ignore_ascii_case("tga"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn photos_cover_the_bounds() {
+	let bounds = Rect::new(100.0, 0.0, 200.0, 100.0);
+	assert_eq!(covering(Vec2::new(50.0, 50.0), bounds), Rect::new(100.0, -50.0, 200.0, 200.0));
+	assert_eq!(covering(Vec2::new(800.0, 200.0), bounds), Rect::new(0.0, 0.0, 400.0, 100.0));
+    }
+
+    #[test]
+    fn photos_take_turns_in_name_order() {
+	let folder = std::env::temp_dir().join(format!("hexagon_screensaver_slides_{}", std::process::id()));
+	fs::create_dir_all(&folder).unwrap();
+	for name in ["b.png", "a.TGA", "notes.txt"] {
+	    fs::write(folder.join(name), b"").unwrap();
+	}
+	let mut slideshow = Slideshow::new(&folder, 1.0).unwrap();
+	let names = |slideshow: &Slideshow| slideshow.paths[slideshow.index].file_name().unwrap().to_owned();
+	assert_eq!(slideshow.paths.len(), 2);
+	assert_eq!(names(&slideshow), "a.TGA");
+	slideshow.step(59.0);
+	assert_eq!(names(&slideshow), "a.TGA");
+	slideshow.step(1.0);
+	assert_eq!(names(&slideshow), "b.png");
+	slideshow.step(60.0);
+	assert_eq!(names(&slideshow), "a.TGA");
+	fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! Edge smoothing in a shader, for when multisampling is not to be had:
+//! the frame is drawn into a texture, then blurred along the edges found
+//! in it and nowhere else.
+
+use crate::offscreen::Offscreen;
+use macroquad::miniquad::{PipelineParams, UniformType};
+use macroquad::prelude::*;
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+
+varying lowp vec2 uv;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    uv = texcoord;
+}
+"#;
+
+// FXAA as first published: the lightness of the four diagonal neighbors
+// gives the direction along the edge, which is sampled a few pixels either
+// way, unless that strays outside what the neighbors span
+const FRAGMENT_SHADER: &str = r#"#version 100
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+uniform vec2 resolution;
+
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main() {
+    vec2 texel = 1.0 / resolution;
+    vec4 middle = texture2D(Texture, uv);
+    float lumaNW = dot(texture2D(Texture, uv + vec2(-1.0, -1.0) * texel).rgb, LUMA);
+    float lumaNE = dot(texture2D(Texture, uv + vec2(1.0, -1.0) * texel).rgb, LUMA);
+    float lumaSW = dot(texture2D(Texture, uv + vec2(-1.0, 1.0) * texel).rgb, LUMA);
+    float lumaSE = dot(texture2D(Texture, uv + vec2(1.0, 1.0) * texel).rgb, LUMA);
+    float lumaM = dot(middle.rgb, LUMA);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float reduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * 0.25 / 8.0, 1.0 / 128.0);
+    float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * scale, -8.0, 8.0) * texel;
+
+    vec3 near = 0.5 * (texture2D(Texture, uv - dir / 6.0).rgb + texture2D(Texture, uv + dir / 6.0).rgb);
+    vec3 far = near * 0.5 + 0.25 * (texture2D(Texture, uv - dir * 0.5).rgb + texture2D(Texture, uv + dir * 0.5).rgb);
+    float lumaFar = dot(far, LUMA);
+    vec3 color = lumaFar < lumaMin || lumaFar > lumaMax ? near : far;
+    gl_FragColor = vec4(color, middle.a);
+}
+"#;
+
+/// The texture the frame is drawn into and the shader smoothing it out.
+pub(crate) struct Smoothing {
+    offscreen: Offscreen,
+    material: Material,
+}
+
+impl Smoothing {
+    /// Smoothing over `bounds`, or the reason its shader could not be
+    /// built.
+    pub(crate) fn new(bounds: Rect) -> Result<Self, String> {
+	let material = load_material(
+	    ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+	    MaterialParams {
+		uniforms: vec![UniformDesc::new("resolution", UniformType::Float2)],
+		pipeline_params: PipelineParams::default(),
+		..Default::default()
+	    },
+	)
+	.map_err(|err| err.to_string())?;
+	Ok(Self { offscreen: Offscreen::new(bounds, 1.0, FilterMode::Linear), material })
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.offscreen.fits(bounds)
+    }
+
+    /// Capture what is drawn next over `bounds`, starting from
+    /// `background`; `finish` smooths it.
+    pub(crate) fn capture(&mut self, bounds: Rect, background: Color) {
+	self.offscreen.begin(bounds);
+	clear_background(background);
+    }
+
+    /// Draw the captured frame over `bounds` with its edges smoothed.
+    pub(crate) fn finish(&self, bounds: Rect) {
+	self.offscreen.end();
+	gl_use_material(&self.material);
+	self.material.set_uniform("resolution", [self.offscreen.size.0 as f32, self.offscreen.size.1 as f32]);
+	let params = DrawTextureParams { dest_size: Some(bounds.size()), flip_y: true, ..Default::default() };
+	draw_texture_ex(&self.offscreen.target.texture, bounds.x, bounds.y, WHITE, params);
+	gl_use_default_material();
+    }
+}
@@ -0,0 +1,295 @@
+//! Custom shapes loaded from the `d` attributes of SVG `<path>` elements.
+
+use crate::config::Config;
+use crate::render::MAX_MESH_INDICES;
+use crate::shapes::{ShapeRenderer, Tessellation};
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Line segments used to flatten each bezier curve.
+const CURVE_SEGMENTS: usize = 12;
+
+/// A shape read from an SVG file, selected with `shape = "svg:<path>"`.
+///
+/// Each subpath is filled as a triangle fan from its centroid, and the whole
+/// drawing is scaled to fit the unit circle. The tessellation is built once
+/// on load and cached by the `ShapeRegistry` like any other shape.
+pub struct SvgShape {
+    name: String,
+    tessellation: Tessellation,
+}
+
+impl SvgShape {
+    /// Load the file named by `name`, which is `svg:` followed by a path.
+    /// Relative paths are looked up in the config directories first.
+    pub fn load(name: &str) -> io::Result<Self> {
+	let path = name.strip_prefix("svg:").unwrap_or(name);
+	let source = fs::read_to_string(Config::find_file(Path::new(path)))?;
+	let tessellation = tessellate(&source).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+	Ok(Self { name: name.to_string(), tessellation })
+    }
+}
+
+impl ShapeRenderer for SvgShape {
+    fn name(&self) -> &str {
+	&self.name
+    }
+
+    fn tessellate(&self) -> Tessellation {
+	self.tessellation.clone()
+    }
+}
+
+/// Tessellate every path in an SVG document into one unit-sized shape.
+fn tessellate(source: &str) -> Result<Tessellation, String> {
+    let mut subpaths = Vec::new();
+    for data in path_data(source) {
+	subpaths.extend(parse_path(data)?);
+    }
+    subpaths.retain(|subpath| subpath.len() >= 3);
+    if subpaths.is_empty() {
+	return Err("no fillable <path> elements".to_string());
+    }
+
+    // Center the bounding box on the origin and fit it in the unit circle
+    let points = subpaths.iter().flatten();
+    let min = points.clone().fold(Vec2::splat(f32::MAX), |min, point| min.min(*point));
+    let max = points.fold(Vec2::splat(f32::MIN), |max, point| max.max(*point));
+    let center = (min + max) / 2.0;
+    let extent = subpaths.iter().flatten().map(|point| point.distance(center)).fold(0.0, f32::max);
+    let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+
+    let mut tessellation = Tessellation::default();
+    for subpath in &subpaths {
+	let outline: Vec<Vec2> = subpath.iter().map(|point| (*point - center) * scale).collect();
+	let centroid = outline.iter().sum::<Vec2>() / outline.len() as f32;
+	let relative: Vec<Vec2> = outline.iter().map(|point| *point - centroid).collect();
+	let fan = Tessellation::fan(&relative);
+	let base = tessellation.vertices.len() as u16;
+	tessellation.vertices.extend(fan.vertices.iter().map(|vertex| *vertex + centroid));
+	tessellation.indices.extend(fan.indices.iter().map(|index| base + index));
+    }
+    if tessellation.indices.len() > MAX_MESH_INDICES {
+	return Err(format!("too detailed: {} triangles, at most {} supported", tessellation.indices.len() / 3, MAX_MESH_INDICES / 3));
+    }
+    Ok(tessellation)
+}
+
+/// The `d` attribute of each `<path>` element in `source`.
+fn path_data(source: &str) -> Vec<&str> {
+    let mut data = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("<path") {
+	rest = &rest[start + 5..];
+	let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+	if let Some(value) = attribute(tag, "d") {
+	    data.push(value);
+	}
+    }
+    data
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(found) = rest.find(name) {
+	let preceded_by_space = rest[..found].ends_with(char::is_whitespace);
+	rest = &rest[found + name.len()..];
+	let after = rest.trim_start();
+	if preceded_by_space && let Some(value) = after.strip_prefix('=') {
+	    let value = value.trim_start();
+	    let quote = value.chars().next().filter(|quote| matches!(quote, '"' | '\''))?;
+	    let value = &value[quote.len_utf8()..];
+	    return Some(&value[..value.find(quote)?]);
+	}
+    }
+    None
+}
+
+/// Parse SVG path data into closed polylines, flattening curves. Arcs are
+/// approximated by a straight line to their end point.
+fn parse_path(data: &str) -> Result<Vec<Vec<Vec2>>, String> {
+    let mut tokens = Tokens { rest: data };
+    let mut subpaths = Vec::new();
+    let mut current: Vec<Vec2> = Vec::new();
+    let mut pos = Vec2::ZERO;
+    let mut start = Vec2::ZERO;
+    // Reflected control point for the smooth curve commands
+    let mut last_control: Option<Vec2> = None;
+    let mut command = None;
+
+    loop {
+	let next = match tokens.command() {
+	    Some(letter) => letter,
+	    None if tokens.at_end() => break,
+	    // Repeated coordinates reuse the previous command, with a moveto
+	    // continuing as lineto
+	    None => match command {
+		Some('M') => 'L',
+		Some('m') => 'l',
+		Some(letter) => letter,
+		None => return Err("path data must start with a command".to_string()),
+	    },
+	};
+	command = Some(next);
+	let relative = next.is_ascii_lowercase();
+	let offset = if relative { pos } else { Vec2::ZERO };
+
+	let mut control = None;
+	match next.to_ascii_uppercase() {
+	    'M' => {
+		if !current.is_empty() {
+		    subpaths.push(std::mem::take(&mut current));
+		}
+		pos = offset + tokens.point()?;
+		start = pos;
+		current.push(pos);
+	    }
+	    'L' => {
+		pos = offset + tokens.point()?;
+		current.push(pos);
+	    }
+	    'H' => {
+		pos.x = if relative { pos.x } else { 0.0 } + tokens.number()?;
+		current.push(pos);
+	    }
+	    'V' => {
+		pos.y = if relative { pos.y } else { 0.0 } + tokens.number()?;
+		current.push(pos);
+	    }
+	    'C' | 'S' => {
+		let c1 = if next.eq_ignore_ascii_case(&'C') {
+		    offset + tokens.point()?
+		} else {
+		    last_control.map_or(pos, |last| 2.0 * pos - last)
+		};
+		let c2 = offset + tokens.point()?;
+		let end = offset + tokens.point()?;
+		current.extend((1..=CURVE_SEGMENTS).map(|i| cubic(pos, c1, c2, end, i as f32 / CURVE_SEGMENTS as f32)));
+		control = Some(c2);
+		pos = end;
+	    }
+	    'Q' | 'T' => {
+		let c = if next.eq_ignore_ascii_case(&'Q') {
+		    offset + tokens.point()?
+		} else {
+		    last_control.map_or(pos, |last| 2.0 * pos - last)
+		};
+		let end = offset + tokens.point()?;
+		current.extend((1..=CURVE_SEGMENTS).map(|i| quadratic(pos, c, end, i as f32 / CURVE_SEGMENTS as f32)));
+		control = Some(c);
+		pos = end;
+	    }
+	    'A' => {
+		for _ in 0..5 {
+		    tokens.number()?;
+		}
+		pos = offset + tokens.point()?;
+		current.push(pos);
+	    }
+	    'Z' => {
+		if !current.is_empty() {
+		    subpaths.push(std::mem::take(&mut current));
+		}
+		pos = start;
+	    }
+	    other => return Err(format!("unsupported path command `{other}`")),
+	}
+	last_control = control;
+    }
+    if !current.is_empty() {
+	subpaths.push(current);
+    }
+    Ok(subpaths)
+}
+
+pub(crate) fn cubic(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let u = 1.0 - t;
+    p0 * u * u * u + p1 * 3.0 * u * u * t + p2 * 3.0 * u * t * t + p3 * t * t * t
+}
+
+pub(crate) fn quadratic(p0: Vec2, p1: Vec2, p2: Vec2, t: f32) -> Vec2 {
+    let u = 1.0 - t;
+    p0 * u * u + p1 * 2.0 * u * t + p2 * t * t
+}
+
+struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl Tokens<'_> {
+    fn skip_separators(&mut self) {
+	self.rest = self.rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    fn at_end(&mut self) -> bool {
+	self.skip_separators();
+	self.rest.is_empty()
+    }
+
+    fn command(&mut self) -> Option<char> {
+	self.skip_separators();
+	let letter = self.rest.chars().next().filter(|c| c.is_ascii_alphabetic() && *c != 'e' && *c != 'E')?;
+	self.rest = &self.rest[1..];
+	Some(letter)
+    }
+
+    fn number(&mut self) -> Result<f32, String> {
+	self.skip_separators();
+	let bytes = self.rest.as_bytes();
+	let mut end = 0;
+	let mut seen_dot = false;
+	let mut seen_exponent = false;
+	while end < bytes.len() {
+	    match bytes[end] {
+		b'+' | b'-' if end == 0 || matches!(bytes[end - 1], b'e' | b'E') => {}
+		b'0'..=b'9' => {}
+		b'.' if !seen_dot && !seen_exponent => seen_dot = true,
+		b'e' | b'E' if !seen_exponent && end > 0 => seen_exponent = true,
+		_ => break,
+	    }
+	    end += 1;
+	}
+	let (number, rest) = self.rest.split_at(end);
+	self.rest = rest;
+	number.parse().map_err(|_| format!("expected a number at `{}`", truncate(number, rest)))
+    }
+
+    fn point(&mut self) -> Result<Vec2, String> {
+	Ok(Vec2::new(self.number()?, self.number()?))
+    }
+}
+
+/// What to quote of a bad number: itself, or the first few characters after.
+fn truncate<'a>(number: &'a str, rest: &'a str) -> &'a str {
+    if !number.is_empty() {
+	return number;
+    }
+    let end = rest.char_indices().nth(10).map_or(rest.len(), |(at, _)| at);
+    &rest[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_relative_and_implicit_commands() {
+	let subpaths = parse_path("M10,10 l10-0 0 10h-10z M0 0 L1 1e1").unwrap();
+	assert_eq!(subpaths.len(), 2);
+	assert_eq!(subpaths[0], vec![vec2(10.0, 10.0), vec2(20.0, 10.0), vec2(20.0, 20.0), vec2(10.0, 20.0)]);
+	assert_eq!(subpaths[1], vec![vec2(0.0, 0.0), vec2(1.0, 10.0)]);
+    }
+
+    #[test]
+    fn curves_are_flattened_and_fit_the_unit_circle() {
+	let svg = r#"<svg><path fill="red" d="M0 0 C 0 -50 100 -50 100 0 Q 50 100 0 0 Z"/></svg>"#;
+	let tessellation = tessellate(svg).unwrap();
+	assert_eq!(tessellation.vertices.len(), 2 + 2 * CURVE_SEGMENTS);
+	assert!(tessellation.vertices.iter().all(|vertex| vertex.length() <= 1.0 + 1e-4));
+	assert!(tessellate("<svg></svg>").is_err());
+	assert_eq!(parse_path("M ääääääääääää"), Err("expected a number at `ääääääääää`".to_string()));
+	assert_eq!(path_data("<path d='M0 0'/><path d=“M0 0”/>"), ["M0 0"]);
+    }
+}
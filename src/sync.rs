@@ -0,0 +1,218 @@
+//! Several machines in step, such as for a wall of monitors driven by
+//! separate PCs: the `master` multicasts its animation time, seed and
+//! palette a few times a second and each `follower` takes the seed and
+//! palette and runs its clock a little faster or slower to lock on. A
+//! follower only takes beats sent for its own group, and from one master
+//! until that one goes quiet.
+
+use crate::config::{Config, SyncRole};
+use crate::control::Command;
+use log::{info, warn};
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+
+/// `sync_group` when the config has none: an address for use within an
+/// organisation, and one of the unassigned ports.
+pub(crate) const DEFAULT_GROUP: &str = "239.255.72.42:47474";
+/// Seconds between the master's beats.
+const BEAT_INTERVAL: f32 = 0.25;
+/// Seconds without a beat before a follower runs on its own clock.
+const MASTER_TIMEOUT: f32 = 3.0;
+/// Animation seconds off the master beyond which a follower jumps rather
+/// than gliding there.
+const SNAP_ERROR: f32 = 1.0;
+/// How much of the error a follower makes up each second, and the most its
+/// clock runs fast or slow.
+const LOCK_GAIN: f32 = 0.5;
+const MAX_RATE_CHANGE: f32 = 0.2;
+/// What every beat starts with, so stray packets on the port are ignored.
+const BEAT_HEADER: &str = "hexagon-sync 2";
+const MAX_PACKET: usize = 2048;
+
+/// `sync_group` as an address to send to and join, when it is a multicast
+/// one.
+pub(crate) fn parse_group(group: &str) -> Option<SocketAddrV4> {
+    group.trim().parse::<SocketAddrV4>().ok().filter(|address| address.ip().is_multicast())
+}
+
+/// What the master sends: where its animation is and what it draws.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Beat {
+    pub(crate) time: f32,
+    pub(crate) seed: u64,
+    pub(crate) palette: String,
+}
+
+impl Beat {
+    /// The packet sending this beat to `group`.
+    fn encode(&self, group: SocketAddrV4) -> String {
+	format!("{BEAT_HEADER}\t{group}\t{}\t{}\t{}", self.time, self.seed, self.palette)
+    }
+
+    /// The beat `packet` sends to `group`, if it is one for that group.
+    fn decode(packet: &str, group: SocketAddrV4) -> Option<Self> {
+	let mut fields = packet.strip_prefix(BEAT_HEADER)?.strip_prefix('\t')?.splitn(4, '\t');
+	if fields.next()?.parse::<SocketAddrV4>().ok()? != group {
+	    return None;
+	}
+	Some(Self { time: fields.next()?.parse().ok()?, seed: fields.next()?.parse().ok()?, palette: fields.next()?.trim_end().to_string() })
+    }
+}
+
+/// A follower's lock on the master's clock.
+#[derive(Default)]
+struct Lock {
+    /// How much faster than real time the clock runs to catch up.
+    rate_change: f32,
+    since_beat: f32,
+    /// Where the beats locked on to come from.
+    master: Option<SocketAddr>,
+    /// The seed and palette last switched to.
+    taken: Option<(u64, String)>,
+}
+
+impl Lock {
+    /// Take `beat` with the animation at `own`, returning what to switch to
+    /// and where to jump.
+    fn take(&mut self, beat: Beat, own: &Beat) -> (Vec<Command>, Option<f32>) {
+	self.since_beat = 0.0;
+	let mut commands = Vec::new();
+	let settings = (beat.seed, beat.palette.clone());
+	if (own.seed, &own.palette) != (beat.seed, &beat.palette) && self.taken.as_ref() != Some(&settings) {
+	    commands.push(Command::Set(vec![("seed".to_string(), beat.seed.to_string()), ("palette".to_string(), beat.palette.clone())]));
+	    self.taken = Some(settings);
+	}
+	let error = beat.time - own.time;
+	if error.abs() > SNAP_ERROR {
+	    self.rate_change = 0.0;
+	    return (commands, Some(beat.time));
+	}
+	self.rate_change = (error * LOCK_GAIN).clamp(-MAX_RATE_CHANGE, MAX_RATE_CHANGE);
+	(commands, None)
+    }
+
+    fn step(&mut self, dt: f32) {
+	self.since_beat += dt;
+	if self.since_beat > MASTER_TIMEOUT {
+	    self.rate_change = 0.0;
+	    self.master = None;
+	}
+    }
+
+    /// Whether to take beats from `source`: the master locked on to, or
+    /// any while there is none.
+    fn hears(&mut self, source: SocketAddr) -> bool {
+	*self.master.get_or_insert(source) == source
+    }
+}
+
+/// The open socket, either sending beats or taking them.
+pub(crate) struct SyncLink {
+    socket: UdpSocket,
+    group: SocketAddrV4,
+    role: SyncRole,
+    until_beat: f32,
+    lock: Lock,
+}
+
+impl SyncLink {
+    /// Join `sync_group` as `config.sync` has it, or nothing when off or
+    /// the network will not have it.
+    pub(crate) fn start(config: &Config) -> Option<Self> {
+	let group = parse_group(&config.sync_group)?;
+	let socket = match config.sync {
+	    SyncRole::Off => return None,
+	    SyncRole::Master => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)),
+	    SyncRole::Follower => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, group.port())).and_then(|socket| socket.join_multicast_v4(group.ip(), &Ipv4Addr::UNSPECIFIED).map(|()| socket)),
+	};
+	let socket = socket
+	    .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+	    .map_err(|err| warn!("cannot join sync group {group}: {err}"))
+	    .ok()?;
+	info!("sync: {:?} on {group}", config.sync);
+	Some(Self { socket, group, role: config.sync, until_beat: 0.0, lock: Lock::default() })
+    }
+
+    /// Whether `start` would join the same way for `config`.
+    pub(crate) fn same_link(a: &Config, b: &Config) -> bool {
+	a.sync == b.sync && a.sync_group == b.sync_group
+    }
+
+    /// As the master, send `own` when a beat is due; as a follower, take
+    /// the master's latest beat, returning the settings to switch to and
+    /// the time to jump to.
+    pub(crate) fn step(&mut self, dt: f32, own: &Beat) -> (Vec<Command>, Option<f32>) {
+	if self.role == SyncRole::Master {
+	    self.until_beat -= dt;
+	    if self.until_beat <= 0.0 {
+		self.until_beat = BEAT_INTERVAL;
+		if let Err(err) = self.socket.send_to(own.encode(self.group).as_bytes(), self.group) {
+		    warn!("sync: {err}");
+		}
+	    }
+	    return (Vec::new(), None);
+	}
+	self.lock.step(dt);
+	let mut packet = [0; MAX_PACKET];
+	let mut latest = None;
+	while let Ok((length, source)) = self.socket.recv_from(&mut packet) {
+	    if let Some(beat) = std::str::from_utf8(&packet[..length]).ok().and_then(|packet| Beat::decode(packet, self.group))
+		&& self.lock.hears(source)
+	    {
+		latest = Some(beat);
+	    }
+	}
+	latest.map_or_else(|| (Vec::new(), None), |beat| self.lock.take(beat, own))
+    }
+
+    /// How much faster than real time to run the animation, locking on.
+    pub(crate) fn rate(&self) -> f32 {
+	1.0 + self.lock.rate_change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beat(time: f32, palette: &str) -> Beat {
+	Beat { time, seed: 7, palette: palette.to_string() }
+    }
+
+    #[test]
+    fn beats_survive_the_network() {
+	let group = parse_group(DEFAULT_GROUP).unwrap();
+	let sent = beat(12.5, "#ff0000,#00ff00");
+	assert_eq!(Beat::decode(&sent.encode(group), group), Some(sent.clone()));
+	assert_eq!(Beat::decode("hello\t1\t2\tocean", group), None);
+	assert_eq!(Beat::decode(&sent.encode(parse_group("239.1.2.3:47474").unwrap()), group), None);
+	assert!(parse_group("192.168.1.2:47474").is_none());
+    }
+
+    #[test]
+    fn followers_lock_on_to_the_master() {
+	let mut lock = Lock::default();
+	let (commands, jump) = lock.take(beat(100.0, "ocean"), &Beat { seed: 3, ..beat(10.0, "random") });
+	assert_eq!(jump, Some(100.0));
+	assert_eq!(commands, [Command::Set(vec![("seed".to_string(), "7".to_string()), ("palette".to_string(), "ocean".to_string())])]);
+	// The switch is asked for once, not again while it is taking effect
+	assert_eq!(lock.take(beat(100.25, "ocean"), &Beat { seed: 3, ..beat(100.0, "random") }), (Vec::new(), None));
+	assert!(lock.rate_change > 0.0);
+	lock.take(beat(100.0, "ocean"), &beat(100.4, "ocean"));
+	assert!(lock.rate_change < 0.0);
+	lock.step(MASTER_TIMEOUT + 1.0);
+	assert_eq!(lock.rate_change, 0.0);
+    }
+
+    #[test]
+    fn followers_keep_to_one_master() {
+	let (first, second) = ("10.0.0.1:5000".parse().unwrap(), "10.0.0.2:5000".parse().unwrap());
+	let mut lock = Lock::default();
+	assert!(lock.hears(first));
+	assert!(!lock.hears(second));
+	assert!(lock.hears(first));
+	// Another takes over once the first goes quiet
+	lock.step(MASTER_TIMEOUT + 1.0);
+	assert!(lock.hears(second));
+	assert!(!lock.hears(first));
+    }
+}
@@ -0,0 +1,72 @@
+//! Time-of-day color temperature, warming colors at night like f.lux.
+
+use crate::clock::local_now;
+use crate::config::Config;
+use chrono::Timelike;
+use macroquad::prelude::*;
+
+/// Hours taken to shift between the day and night settings.
+const TRANSITION_HOURS: f32 = 1.0;
+
+/// Multiplier for final shape colors at the current local time.
+pub(crate) fn current_tint(config: &Config) -> Color {
+    let now = local_now();
+    let hour = now.hour() as f32 + now.minute() as f32 / 60.0;
+    tint_at(config, hour)
+}
+
+/// Multiplier for final shape colors at `hour`, 0..24 in local time.
+pub(crate) fn tint_at(config: &Config, hour: f32) -> Color {
+    let night = night_amount(hour, config.sunrise_hour, config.sunset_hour);
+    let kelvin = config.day_temperature + (config.night_temperature - config.day_temperature) * night;
+    let brightness = 1.0 + (config.night_brightness - 1.0) * night;
+    let rgb = kelvin_to_rgb(kelvin) * brightness;
+    Color::new(rgb.x, rgb.y, rgb.z, 1.0)
+}
+
+/// 0 during the day and 1 at night, ramping linearly after sunset and
+/// before sunrise.
+fn night_amount(hour: f32, sunrise: f32, sunset: f32) -> f32 {
+    let since_sunset = (hour - sunset).rem_euclid(24.0);
+    let until_sunrise = (sunrise - hour).rem_euclid(24.0);
+    let night_length = (sunrise - sunset).rem_euclid(24.0);
+    if since_sunset > night_length {
+	return 0.0;
+    }
+    (since_sunset.min(until_sunrise) / TRANSITION_HOURS).min(1.0)
+}
+
+/// Approximate RGB of a black body at `kelvin`, normalized so 6500K is white.
+fn kelvin_to_rgb(kelvin: f32) -> Vec3 {
+    fn raw(kelvin: f32) -> Vec3 {
+	// Tanner Helland's fit of the CIE black body locus
+	let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+	let r = if t <= 66.0 { 255.0 } else { 329.699 * (t - 60.0).powf(-0.133_205) };
+	let g = if t <= 66.0 { 99.470_8 * t.ln() - 161.119_57 } else { 288.122 * (t - 60.0).powf(-0.075_514_85) };
+	let b = if t >= 66.0 {
+	    255.0
+	} else if t <= 19.0 {
+	    0.0
+	} else {
+	    138.517_73 * (t - 10.0).ln() - 305.044_8
+	};
+	(Vec3::new(r, g, b) / 255.0).clamp(Vec3::ZERO, Vec3::ONE)
+    }
+    (raw(kelvin) / raw(6500.0)).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nights_are_warmer_and_dimmer() {
+	let config = Config { temperature_shift: true, ..Default::default() };
+	let noon = tint_at(&config, 12.0);
+	let midnight = tint_at(&config, 0.0);
+	let dusk = tint_at(&config, config.sunset_hour + TRANSITION_HOURS / 2.0);
+	assert!((noon.r - 1.0).abs() < 0.01 && (noon.b - 1.0).abs() < 0.01);
+	assert!(midnight.b < dusk.b && dusk.b < noon.b);
+	assert!(midnight.r <= config.night_brightness + 0.01);
+    }
+}
@@ -0,0 +1,162 @@
+//! Headlines scrolling along the bottom of each display over a translucent
+//! band, from RSS or Atom feeds or the lines of text files, refreshed on a
+//! background thread.
+
+use crate::net;
+use log::warn;
+use macroquad::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time between refreshes of the sources.
+const REFRESH: Duration = Duration::from_secs(15 * 60);
+/// Between headlines.
+const SEPARATOR: &str = "   +++   ";
+/// Opacity of the band behind the text.
+const BAND_OPACITY: f32 = 0.45;
+
+/// The headlines and how far they have scrolled.
+pub(crate) struct Ticker {
+    /// The latest headlines, picked up at the start of each pass.
+    latest: Arc<Mutex<Vec<String>>>,
+    text: String,
+    /// Display heights scrolled this pass.
+    scrolled: f32,
+}
+
+impl Ticker {
+    /// Read `sources`, URLs of feeds or paths of text files, now and every
+    /// `REFRESH` after; nothing without sources.
+    pub(crate) fn start(sources: &[String]) -> Option<Self> {
+	if sources.is_empty() {
+	    return None;
+	}
+	let latest = Arc::new(Mutex::new(Vec::new()));
+	let weak = Arc::downgrade(&latest);
+	let sources = sources.to_vec();
+	std::thread::spawn(move || {
+	    loop {
+		let headlines: Vec<String> = sources.iter().flat_map(|source| headlines(source)).collect();
+		// Stop once the screensaver no longer shows the ticker
+		let Some(latest) = weak.upgrade() else {
+		    break;
+		};
+		if !headlines.is_empty() {
+		    *latest.lock().unwrap() = headlines;
+		}
+		drop(latest);
+		std::thread::sleep(REFRESH);
+	    }
+	});
+	Some(Self { latest, text: String::new(), scrolled: 0.0 })
+    }
+
+    /// Scroll on by `dt` seconds at `speed` display heights a second.
+    pub(crate) fn step(&mut self, dt: f32, speed: f32) {
+	self.scrolled += dt * speed;
+    }
+
+    /// Draw the band and text along the bottom of each of `displays`,
+    /// dimmed by `brightness`.
+    pub(crate) fn draw(&mut self, displays: &[Rect], brightness: f32) {
+	if displays.is_empty() {
+	    return;
+	}
+	// A pass ends once the text has left every display, each scrolling it
+	// at its own size
+	let passed = displays.iter().all(|display| self.scrolled * display.h > display.w + measure_text(&self.text, None, font_size(display), 1.0).width);
+	if self.text.is_empty() || passed {
+	    self.text = self.latest.lock().unwrap().join(SEPARATOR);
+	    self.scrolled = 0.0;
+	}
+	if self.text.is_empty() {
+	    return;
+	}
+	for display in displays {
+	    let font_size = font_size(display);
+	    let dimensions = measure_text(&self.text, None, font_size, 1.0);
+	    let band = font_size as f32 * 1.8;
+	    let top = display.bottom() - band;
+	    draw_rectangle(display.x, top, display.w, band, Color::new(0.0, 0.0, 0.0, BAND_OPACITY));
+	    let x = display.right() - self.scrolled * display.h;
+	    let y = top + (band - dimensions.height) / 2.0 + dimensions.offset_y;
+	    draw_text(&self.text, x, y, font_size as f32, Color::new(brightness, brightness, brightness, 0.85));
+	}
+    }
+}
+
+/// Size of the text along the bottom of `display`.
+fn font_size(display: &Rect) -> u16 {
+    (display.h / 30.0).max(8.0).round() as u16
+}
+
+/// The headlines of `source`: the item titles of a feed at a URL, or the
+/// non-empty lines of a file.
+fn headlines(source: &str) -> Vec<String> {
+    let read = if source.starts_with("http://") || source.starts_with("https://") {
+	net::fetch(source).map(|feed| feed_titles(&feed))
+    } else {
+	std::fs::read_to_string(source)
+	    .map(|text| text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect())
+	    .map_err(|err| err.to_string())
+    };
+    read.unwrap_or_else(|err| {
+	warn!("cannot read ticker source {source}: {err}");
+	Vec::new()
+    })
+}
+
+/// The titles of the `<item>`s of an RSS feed or `<entry>`s of an Atom
+/// one, leaving out the feed's own title.
+fn feed_titles(feed: &str) -> Vec<String> {
+    let mut titles = Vec::new();
+    for tag in ["<item", "<entry"] {
+	for item in feed.split(tag).skip(1) {
+	    let Some(start) = item.find("<title") else {
+		continue;
+	    };
+	    let title = &item[start..];
+	    let Some(title) = title.find('>').map(|end| &title[end + 1..]) else {
+		continue;
+	    };
+	    let Some(end) = title.find("</title>") else {
+		continue;
+	    };
+	    let title = decode_text(&title[..end]);
+	    if !title.is_empty() {
+		titles.push(title);
+	    }
+	}
+    }
+    titles
+}
+
+/// XML character data as plain text: CDATA unwrapped, entities decoded and
+/// whitespace collapsed.
+fn decode_text(text: &str) -> String {
+    let text = text.trim();
+    let text = text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")).unwrap_or(text);
+    let text = text
+	.replace("&lt;", "<")
+	.replace("&gt;", ">")
+	.replace("&quot;", "\"")
+	.replace("&apos;", "'")
+	.replace("&#39;", "'")
+	.replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feeds_give_their_item_titles() {
+	let rss = "<rss><channel><title>News</title>\
+		   <item><title>Rates &amp; markets</title></item>\
+		   <item><title><![CDATA[Storm <b>warning</b>]]></title></item></channel></rss>";
+	assert_eq!(feed_titles(rss), ["Rates & markets", "Storm <b>warning</b>"]);
+	let atom = "<feed><title>Blog</title><entry><title type=\"text\">New\n   release</title></entry></feed>";
+	assert_eq!(feed_titles(atom), ["New release"]);
+    }
+}
@@ -0,0 +1,117 @@
+//! A countdown shown as a bar of lit shapes shrinking toward the left of
+//! each display, such as for Pomodoro work and break periods.
+
+use std::f32::consts::PI;
+
+/// Seconds the screen pulses for once a period runs out.
+const PULSE_SECONDS: f32 = 3.0;
+/// Pulses in that time.
+const PULSES: f32 = 2.0;
+
+/// Seconds in `text`, a number with units such as `25m`, `90s` or `1h30m`.
+/// A bare number counts minutes.
+pub(crate) fn parse_duration(text: &str) -> Option<f32> {
+    let text = text.trim();
+    if let Ok(minutes) = text.parse::<f32>() {
+	return (minutes > 0.0).then_some(minutes * 60.0);
+    }
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for character in text.chars() {
+	let unit = match character {
+	    'h' => 3600.0,
+	    'm' => 60.0,
+	    's' => 1.0,
+	    digit if digit.is_ascii_digit() || digit == '.' => {
+		number.push(digit);
+		continue;
+	    }
+	    _ => return None,
+	};
+	seconds += number.parse::<f32>().ok()? * unit;
+	number.clear();
+    }
+    (number.is_empty() && seconds > 0.0).then_some(seconds)
+}
+
+/// The periods of `text`, durations separated by commas, which run in
+/// turn and then start over.
+pub(crate) fn parse_periods(text: &str) -> Option<Vec<f32>> {
+    text.split(',').map(parse_duration).collect()
+}
+
+/// Where the countdown is.
+pub(crate) struct Timer {
+    periods: Vec<f32>,
+    index: usize,
+    elapsed: f32,
+    /// Seconds since the last period ran out.
+    since_done: f32,
+}
+
+impl Timer {
+    /// A timer at the start of the first of `periods`, in seconds.
+    pub(crate) fn new(periods: Vec<f32>) -> Self {
+	Self { periods, index: 0, elapsed: 0.0, since_done: PULSE_SECONDS }
+    }
+
+    /// Count down `dt` seconds, moving on to the next period when one runs
+    /// out.
+    pub(crate) fn step(&mut self, dt: f32) {
+	self.elapsed += dt;
+	self.since_done += dt;
+	if self.elapsed >= self.periods[self.index] {
+	    self.elapsed = 0.0;
+	    self.since_done = 0.0;
+	    self.index = (self.index + 1) % self.periods.len();
+	}
+    }
+
+    /// The share of the current period left, from 1 down to 0.
+    pub(crate) fn remaining(&self) -> f32 {
+	1.0 - self.elapsed / self.periods[self.index]
+    }
+
+    /// How strongly the screen pulses, 0..1, rising and falling softly just
+    /// after a period ran out.
+    pub(crate) fn pulse(&self) -> f32 {
+	if self.since_done >= PULSE_SECONDS {
+	    return 0.0;
+	}
+	0.5 - 0.5 * (self.since_done / PULSE_SECONDS * PULSES * 2.0 * PI).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_take_units_or_minutes() {
+	assert_eq!(parse_duration("25m"), Some(1500.0));
+	assert_eq!(parse_duration("1h30m"), Some(5400.0));
+	assert_eq!(parse_duration(" 90s "), Some(90.0));
+	assert_eq!(parse_duration("5"), Some(300.0));
+	for invalid in ["", "0", "10x", "m", "5m3"] {
+	    assert_eq!(parse_duration(invalid), None, "{invalid}");
+	}
+	assert_eq!(parse_periods("25m, 5m"), Some(vec![1500.0, 300.0]));
+	assert_eq!(parse_periods("25m,"), None);
+    }
+
+    #[test]
+    fn periods_count_down_in_turn_and_pulse_when_done() {
+	let mut timer = Timer::new(vec![10.0, 4.0]);
+	assert_eq!((timer.remaining(), timer.pulse()), (1.0, 0.0));
+	timer.step(7.5);
+	assert_eq!(timer.remaining(), 0.25);
+	timer.step(2.5);
+	assert_eq!((timer.index, timer.remaining()), (1, 1.0));
+	timer.step(PULSE_SECONDS / PULSES / 2.0);
+	assert!((timer.pulse() - 1.0).abs() < 1e-5);
+	timer.step(PULSE_SECONDS);
+	assert_eq!(timer.pulse(), 0.0);
+	timer.step(4.0);
+	assert_eq!(timer.index, 0);
+    }
+}
@@ -0,0 +1,110 @@
+//! Trails behind the shapes: they are drawn into a texture kept from frame
+//! to frame, faded a little each time, and laid over the background, so
+//! bright shapes leave soft streaks whatever is behind them.
+
+use crate::offscreen::Offscreen;
+use macroquad::miniquad::{BlendFactor, BlendState, BlendValue, Equation, PipelineParams};
+use macroquad::prelude::*;
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+"#;
+
+/// The texture the shapes build up in and the blends drawing into and out
+/// of it, which keep its colors premultiplied by their opacity.
+pub(crate) struct Trails {
+    offscreen: Offscreen,
+    /// Scales what is there by one minus the opacity drawn.
+    fade: Material,
+    /// Takes off what is drawn, so the faintest trails end.
+    subtract: Material,
+    /// Draws the shapes over what is there, opacity included.
+    paint: Material,
+    /// Lays the premultiplied texture over the screen.
+    lay: Material,
+    /// Whether the texture has been cleared yet.
+    cleared: bool,
+}
+
+impl Trails {
+    /// Trails over `bounds`, or the reason their shaders could not be built.
+    pub(crate) fn new(bounds: Rect) -> Result<Self, String> {
+	let material = |color_blend: BlendState, alpha_blend: BlendState| {
+	    load_material(
+		ShaderSource::Glsl { vertex: VERTEX_SHADER, fragment: FRAGMENT_SHADER },
+		MaterialParams {
+		    pipeline_params: PipelineParams { color_blend: Some(color_blend), alpha_blend: Some(alpha_blend), ..Default::default() },
+		    ..Default::default()
+		},
+	    )
+	    .map_err(|err| err.to_string())
+	};
+	let over = BlendState::new(Equation::Add, BlendFactor::One, BlendFactor::OneMinusValue(BlendValue::SourceAlpha));
+	let fade = BlendState::new(Equation::Add, BlendFactor::Zero, BlendFactor::OneMinusValue(BlendValue::SourceAlpha));
+	let subtract = BlendState::new(Equation::ReverseSubtract, BlendFactor::One, BlendFactor::One);
+	let paint = BlendState::new(Equation::Add, BlendFactor::Value(BlendValue::SourceAlpha), BlendFactor::OneMinusValue(BlendValue::SourceAlpha));
+	Ok(Self {
+	    offscreen: Offscreen::new(bounds, 1.0, FilterMode::Linear),
+	    fade: material(fade, fade)?,
+	    subtract: material(subtract, subtract)?,
+	    paint: material(paint, over)?,
+	    lay: material(over, over)?,
+	    cleared: false,
+	})
+    }
+
+    /// Whether the texture fits `bounds`.
+    pub(crate) fn fits(&self, bounds: Rect) -> bool {
+	self.offscreen.fits(bounds)
+    }
+
+    /// Fade the trails by `fade`, 0..1, and send what is drawn next, in the
+    /// coordinates of `bounds`, onto them; `finish` lays them on the screen.
+    pub(crate) fn capture(&mut self, bounds: Rect, fade: f32) {
+	self.offscreen.begin(bounds);
+	if !self.cleared {
+	    clear_background(Color::new(0.0, 0.0, 0.0, 0.0));
+	    self.cleared = true;
+	}
+	gl_use_material(&self.fade);
+	draw_rectangle(bounds.x, bounds.y, bounds.w, bounds.h, Color::new(0.0, 0.0, 0.0, fade));
+	let step = 1.0 / 255.0;
+	gl_use_material(&self.subtract);
+	draw_rectangle(bounds.x, bounds.y, bounds.w, bounds.h, Color::new(step, step, step, step));
+	gl_use_material(&self.paint);
+    }
+
+    /// Stop drawing onto the trails and lay them over `bounds`.
+    pub(crate) fn finish(&self, bounds: Rect) {
+	self.offscreen.end();
+	gl_use_material(&self.lay);
+	let params = DrawTextureParams { dest_size: Some(bounds.size()), flip_y: true, ..Default::default() };
+	draw_texture_ex(&self.offscreen.target.texture, bounds.x, bounds.y, WHITE, params);
+	gl_use_default_material();
+    }
+}
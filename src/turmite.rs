@@ -0,0 +1,155 @@
+//! Turmites, Langton's ant generalized, walking the shapes' neighbor graph
+//! in the `ant` pattern.
+
+use crate::config::Config;
+use crate::palette::Palette;
+use crate::pattern::Pattern;
+use crate::shapes::Shape;
+use macroquad::prelude::*;
+use macroquad::rand::RandGenerator;
+
+/// Steps each ant takes per second.
+const STEPS_PER_SECOND: f32 = 20.0;
+
+/// Turns an ant makes on a cell in each state, in neighbors clockwise;
+/// negative ones turn counterclockwise.
+///
+/// Written one letter per state: `N` goes straight on, `U` turns around,
+/// and `L` or `R` turn by one neighbor, or by as many as a following digit
+/// says. Langton's ant is `RL`.
+pub(crate) fn parse_rule(text: &str) -> Option<Vec<i32>> {
+    let mut turns = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    while let Some(letter) = chars.next() {
+	let count = chars.next_if(char::is_ascii_digit).map_or(1, |digit| digit as i32 - '0' as i32);
+	turns.push(match letter.to_ascii_uppercase() {
+	    'N' => 0,
+	    // Half of any neighborhood; resolved against the cell's neighbors
+	    'U' => i32::MAX,
+	    'L' => -count,
+	    'R' => count,
+	    _ => return None,
+	});
+    }
+    (2..=16).contains(&turns.len()).then_some(turns)
+}
+
+struct Ant {
+    cell: usize,
+    /// Direction of the last step, in radians.
+    heading: f32,
+}
+
+/// The ants and the cell states they flip, one state per shape.
+#[derive(Default)]
+pub(crate) struct Turmites {
+    ants: Vec<Ant>,
+    states: Vec<usize>,
+    /// Opacity of each cell, fading in once an ant has visited it.
+    levels: Vec<f32>,
+    since_step: f32,
+}
+
+impl Turmites {
+    /// Walk the ants for `dt` seconds, fading each cell they flip into the
+    /// `palette` color of its new state.
+    pub(crate) fn update(&mut self, shapes: &mut [Shape], dt: f32, config: &Config, palette: &Palette, rng: &RandGenerator) {
+	if config.pattern != Pattern::Ant || shapes.is_empty() {
+	    self.ants.clear();
+	    self.states.clear();
+	    self.levels.clear();
+	    return;
+	}
+	// The config is sanitized, so the rule parses
+	let rule = parse_rule(&config.ant_rule).unwrap_or_else(|| vec![1, -1]);
+	// A new layout or ant count starts over
+	if self.states.len() != shapes.len() || self.ants.len() != config.ants as usize {
+	    self.states = vec![0; shapes.len()];
+	    self.levels = vec![0.0; shapes.len()];
+	    self.ants = (0..config.ants)
+		.map(|_| Ant { cell: rng.gen_range(0, shapes.len()), heading: rng.gen_range(0.0, std::f32::consts::TAU) })
+		.collect();
+	}
+
+	self.since_step += dt * STEPS_PER_SECOND;
+	while self.since_step >= 1.0 {
+	    self.since_step -= 1.0;
+	    for ant in &mut self.ants {
+		let state = self.states[ant.cell] % rule.len();
+		let next_state = (state + 1) % rule.len();
+		self.states[ant.cell] = next_state;
+		shapes[ant.cell].fade_to(palette.nth(next_state), config.color_easing);
+		step(ant, shapes, rule[state]);
+	    }
+	}
+
+	let fade = (dt * 2.0).min(1.0);
+	for (level, &state) in self.levels.iter_mut().zip(&self.states) {
+	    *level += (if state == 0 { 0.0 } else { 1.0 } - *level) * fade;
+	}
+    }
+
+    /// How lit the `index`th cell is, 0..1.
+    pub(crate) fn level(&self, index: usize) -> f32 {
+	self.levels.get(index).copied().unwrap_or(0.0)
+    }
+}
+
+/// Move `ant` to the neighbor `turn` steps clockwise from the one straight
+/// ahead.
+fn step(ant: &mut Ant, shapes: &[Shape], turn: i32) {
+    let here = Vec2::new(shapes[ant.cell].x, shapes[ant.cell].y);
+    let mut neighbors: Vec<(usize, f32)> = shapes[ant.cell]
+	.neighbors
+	.iter()
+	.map(|&neighbor| (neighbor, (Vec2::new(shapes[neighbor].x, shapes[neighbor].y) - here).to_angle()))
+	.collect();
+    if neighbors.is_empty() {
+	return;
+    }
+    // Screen y points down, so increasing angles are clockwise
+    neighbors.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let count = neighbors.len() as i32;
+    let heading = Vec2::from_angle(ant.heading);
+    let ahead = (0..neighbors.len())
+	.max_by(|&a, &b| {
+	    let along = |index: usize| Vec2::from_angle(neighbors[index].1).dot(heading);
+	    along(a).total_cmp(&along(b))
+	})
+	.unwrap_or(0) as i32;
+    let turn = if turn == i32::MAX { count / 2 } else { turn };
+    let (cell, heading) = neighbors[(ahead + turn).rem_euclid(count) as usize];
+    *ant = Ant { cell, heading };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_parse_turns_per_state() {
+	assert_eq!(parse_rule("RL"), Some(vec![1, -1]));
+	assert_eq!(parse_rule("L2NNL1L2L1"), Some(vec![-2, 0, 0, -1, -2, -1]));
+	assert_eq!(parse_rule("nu"), Some(vec![0, i32::MAX]));
+	assert_eq!(parse_rule("R"), None);
+	assert_eq!(parse_rule("RX"), None);
+    }
+
+    #[test]
+    fn langtons_ant_turns_right_on_fresh_cells() {
+	// A plus of five cells, the ant heading right from the center
+	let rng = RandGenerator::new();
+	let mut shapes: Vec<Shape> = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (-10.0, 0.0), (0.0, -10.0)]
+	    .into_iter()
+	    .map(|(x, y)| Shape::new(x, y, 5.0, false, &Palette::random(), &rng))
+	    .collect();
+	shapes[0].neighbors = vec![1, 2, 3, 4];
+	let mut ant = Ant { cell: 0, heading: 0.0 };
+	step(&mut ant, &shapes, 1);
+	// Right of heading along +x is +y, down the screen
+	assert_eq!(ant.cell, 2);
+	let mut ant = Ant { cell: 0, heading: 0.0 };
+	step(&mut ant, &shapes, i32::MAX);
+	assert_eq!(ant.cell, 3);
+    }
+}
@@ -0,0 +1,153 @@
+//! Current conditions from a weather service, theming the animation: a
+//! slow blue for rain, warm colors in sunshine and drifting white cells in
+//! snow.
+//!
+//! Fetched from an endpoint answering like Open-Meteo's
+//! `current=temperature_2m,weather_code`.
+
+use crate::config::Config;
+use crate::net;
+use crate::pattern::Pattern;
+use log::warn;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Time between fetches.
+const REFRESH: Duration = Duration::from_secs(30 * 60);
+
+const SUN_PALETTE: &str = "#ffb347,#ffcc33,#ff8c42,#ffd274,#f9a03f";
+const SNOW_PALETTE: &str = "#ffffff,#eef4ff,#d8e6f5,#f6f9ff";
+
+/// What the sky is doing, as far as the theme cares.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Sky {
+    Clear,
+    Cloudy,
+    Rain,
+    Snow,
+}
+
+impl Sky {
+    /// The sky of a WMO weather interpretation code.
+    fn from_code(code: u32) -> Self {
+	match code {
+	    0 | 1 => Sky::Clear,
+	    51..=67 | 80..=82 | 95..=99 => Sky::Rain,
+	    71..=77 | 85 | 86 => Sky::Snow,
+	    _ => Sky::Cloudy,
+	}
+    }
+}
+
+/// The latest reading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Conditions {
+    /// Degrees Celsius.
+    pub(crate) temperature: f32,
+    pub(crate) sky: Sky,
+}
+
+impl Conditions {
+    /// The temperature as the overlay shows it.
+    pub(crate) fn readout(self) -> String {
+	format!("{:.0}°C", self.temperature)
+    }
+
+    /// `config` themed for these conditions; cloudy skies leave it be.
+    pub(crate) fn theme(self, config: Config) -> Config {
+	let palette = |palette: &str| (palette.to_string(), String::new());
+	match self.sky {
+	    Sky::Clear => {
+		let (palette, palette_image) = palette(SUN_PALETTE);
+		Config { palette, palette_image, ..config }
+	    }
+	    Sky::Rain => {
+		let (palette, palette_image) = palette("ocean");
+		Config { palette, palette_image, speed: config.speed * 0.5, ..config }
+	    }
+	    Sky::Snow => {
+		let (palette, palette_image) = palette(SNOW_PALETTE);
+		Config { palette, palette_image, pattern: Pattern::Noise, ..config }
+	    }
+	    Sky::Cloudy => config,
+	}
+    }
+}
+
+/// Fetching in the background and the reading not taken yet.
+pub(crate) struct Weather {
+    latest: Arc<Mutex<Option<Conditions>>>,
+}
+
+impl Weather {
+    /// Fetch from `url` now and every `REFRESH` after, or nothing for an
+    /// empty one.
+    pub(crate) fn start(url: &str) -> Option<Self> {
+	if url.is_empty() {
+	    return None;
+	}
+	let latest = Arc::new(Mutex::new(None));
+	let weak = Arc::downgrade(&latest);
+	let url = url.to_string();
+	std::thread::spawn(move || {
+	    loop {
+		let reading = fetch(&url);
+		// Stop once the screensaver no longer wants readings
+		let Some(latest) = weak.upgrade() else {
+		    break;
+		};
+		match reading {
+		    Ok(conditions) => *latest.lock().unwrap() = Some(conditions),
+		    Err(err) => warn!("cannot fetch the weather: {err}"),
+		}
+		drop(latest);
+		std::thread::sleep(REFRESH);
+	    }
+	});
+	Some(Self { latest })
+    }
+
+    /// The reading fetched since the last call, if any.
+    pub(crate) fn poll(&self) -> Option<Conditions> {
+	self.latest.lock().unwrap().take()
+    }
+}
+
+fn fetch(url: &str) -> Result<Conditions, String> {
+    parse_conditions(&net::fetch(url)?).ok_or_else(|| "no current temperature and weather code in the answer".to_string())
+}
+
+/// The conditions in the `current` object of an Open-Meteo style answer.
+fn parse_conditions(json: &str) -> Option<Conditions> {
+    let current = &json[json.find("\"current\"")?..];
+    let current = &current[..current.find('}')?];
+    let number = |key: &str| {
+	let value = current[current.find(&format!("\"{key}\""))? + key.len() + 2..].trim_start().strip_prefix(':')?.trim_start();
+	let end = value.find(|character: char| !(character.is_ascii_digit() || "+-.eE".contains(character))).unwrap_or(value.len());
+	value[..end].parse::<f32>().ok()
+    };
+    Some(Conditions { temperature: number("temperature_2m")?, sky: Sky::from_code(number("weather_code")? as u32) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_meteo_answers_parse() {
+	let json = r#"{"latitude":52.52,"current_units":{"temperature_2m":"°C"},"current":{"time":"2026-10-14T09:00","temperature_2m": -3.4,"weather_code":73}}"#;
+	assert_eq!(parse_conditions(json), Some(Conditions { temperature: -3.4, sky: Sky::Snow }));
+	assert_eq!(parse_conditions(r#"{"current":{"weather_code":0}}"#), None);
+	assert_eq!(Conditions { temperature: -3.4, sky: Sky::Snow }.readout(), "-3°C");
+    }
+
+    #[test]
+    fn rain_slows_down_and_clouds_change_nothing() {
+	let config = Config { palette_image: "photo.png".to_string(), ..Config::default() };
+	let rainy = Conditions { temperature: 9.0, sky: Sky::Rain }.theme(config.clone());
+	assert_eq!((rainy.palette.as_str(), rainy.palette_image.as_str()), ("ocean", ""));
+	assert_eq!(rainy.speed, config.speed * 0.5);
+	let cloudy = Conditions { temperature: 9.0, sky: Sky::Cloudy }.theme(config.clone());
+	assert_eq!((cloudy.palette, cloudy.speed), (config.palette, config.speed));
+    }
+}
@@ -0,0 +1,100 @@
+//! The browser build, behind the `web` feature: settings come from the page
+//! URL's query string, such as `?shape=heart&palette=ocean&threshold=0.8`,
+//! as a page has no config file. Built with
+//! `cargo build --release --target wasm32-unknown-unknown --features web
+//! --bin hexagon_screensaver_web` and served from `web/` with miniquad's
+//! `mq_js_bundle.js`.
+
+use crate::config::Config;
+use crate::net::form_pairs;
+use log::warn;
+
+/// Keys needing files, threads or devices a page does not offer.
+const UNSUPPORTED: [&str; 19] = [
+    "palette_image",
+    "color_image",
+    "background_image",
+    "photo_folder",
+    "webcam_device",
+    "weather_url",
+    "ticker",
+    "media_overlay",
+    "media_tint",
+    "audio_reactive",
+    "lock_on_exit",
+    "config_backend",
+    "profile",
+    "control_port",
+    "osc_port",
+    "midi_device",
+    "sync",
+    "share_texture",
+    "script",
+];
+
+/// The settings in `query`, `key=value` pairs joined by `&` with or without
+/// the leading `?`, applied over the defaults as `--KEY VALUE` overrides
+/// are. A bare key is set to true. Input is ignored but for `kiosk_exit`,
+/// as nothing takes over once the animation stops; `kiosk=false` turns
+/// that off.
+pub fn config_from_query(query: &str) -> Config {
+    let mut config = Config { kiosk: true, hide_cursor: false, ..Default::default() };
+    for (key, value) in form_pairs(query) {
+	if UNSUPPORTED.contains(&key.as_str()) {
+	    warn!("`{key}` is not available in a browser, ignoring it");
+	    continue;
+	}
+	match config.clone().with_override(&key, &value) {
+	    Ok(overridden) => config = overridden,
+	    Err(err) => warn!("{err}"),
+	}
+    }
+    config
+}
+
+#[cfg(target_arch = "wasm32")]
+unsafe extern "C" {
+    // From web/hexagon.js
+    fn hexagon_query_length() -> u32;
+    fn hexagon_query(buffer: *mut u8, length: u32);
+    fn hexagon_utc_offset() -> i32;
+}
+
+/// The page's query string, without the `?`.
+#[cfg(target_arch = "wasm32")]
+fn page_query() -> String {
+    unsafe {
+	let length = hexagon_query_length();
+	let mut query = vec![0; length as usize];
+	hexagon_query(query.as_mut_ptr(), length);
+	String::from_utf8_lossy(&query).into_owned()
+    }
+}
+
+/// Seconds local time is ahead of UTC, from the browser.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn utc_offset() -> i32 {
+    unsafe { hexagon_utc_offset() * 60 }
+}
+
+/// Run in the page's canvas with the settings from its URL.
+#[cfg(target_arch = "wasm32")]
+pub async fn run() {
+    crate::logging::init(false);
+    crate::Screensaver::run(config_from_query(&page_query()).effective()).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parameters_override_the_defaults() {
+	let config = config_from_query("?shape=heart&threshold=0.75&palette=ocean&message=Hello+w%C3%B6rld&bad=1&focus");
+	assert_eq!((config.shape.as_str(), config.palette.as_str(), config.density), ("heart", "ocean", 0.25));
+	assert_eq!(config.message, "Hello wörld");
+	assert!(config.focus && config.kiosk);
+	assert!(!config_from_query("kiosk=false").kiosk);
+	assert_eq!(config_from_query("weather_url=http%3A%2F%2Fexample.com").weather_url, "");
+    }
+}
@@ -0,0 +1,133 @@
+//! Frames from the default webcam, for the `webcam` color mode.
+//!
+//! Captured by an `ffmpeg` child process, which already knows every
+//! platform's camera API, scaled down to about one pixel per shape and
+//! read on a thread as raw RGB.
+
+use log::warn;
+use macroquad::prelude::*;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Frames per second asked of the camera.
+const CAPTURE_FPS: u32 = 5;
+
+/// A running capture and the newest frame not taken yet.
+pub(crate) struct Webcam {
+    frame: Arc<Mutex<Option<Image>>>,
+    child: Child,
+}
+
+impl Webcam {
+    /// Capture `width` x `height` frames, mirrored and cropped to fill
+    /// them, from `device` or the platform's default camera when empty.
+    pub(crate) fn start(device: &str, width: u32, height: u32) -> Option<Self> {
+	let Some(input) = input_args(device) else {
+	    warn!("no webcam found, webcam colors disabled");
+	    return None;
+	};
+	let filter = format!("fps={CAPTURE_FPS},scale={width}:{height}:force_original_aspect_ratio=increase,crop={width}:{height},hflip");
+	let mut command = Command::new("ffmpeg");
+	command
+	    .args(["-loglevel", "error"])
+	    .args(&input)
+	    .args(["-vf", &filter, "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+	    .stdin(Stdio::null())
+	    .stdout(Stdio::piped())
+	    .stderr(Stdio::null());
+	hide_console(&mut command);
+	let child = command.spawn();
+	let mut child = match child {
+	    Ok(child) => child,
+	    Err(err) => {
+		warn!("cannot run ffmpeg for the webcam, webcam colors disabled: {err}");
+		return None;
+	    }
+	};
+
+	let frame = Arc::new(Mutex::new(None));
+	let mut stdout = child.stdout.take()?;
+	let latest = frame.clone();
+	std::thread::spawn(move || {
+	    let mut rgb = vec![0; width as usize * height as usize * 3];
+	    while stdout.read_exact(&mut rgb).is_ok() {
+		let rgba = rgb.chunks_exact(3).flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255]).collect();
+		let image = Image { bytes: rgba, width: width as u16, height: height as u16 };
+		*latest.lock().unwrap() = Some(image);
+	    }
+	    warn!("the webcam stopped sending frames");
+	});
+	Some(Self { frame, child })
+    }
+
+    /// The newest frame since the last call, if any.
+    pub(crate) fn take_frame(&self) -> Option<Image> {
+	self.frame.lock().unwrap().take()
+    }
+}
+
+impl Drop for Webcam {
+    fn drop(&mut self) {
+	let _ = self.child.kill();
+	let _ = self.child.wait();
+    }
+}
+
+/// The ffmpeg input arguments opening `device`, or the default camera.
+fn input_args(device: &str) -> Option<Vec<String>> {
+    let args = |format: &str, input: String| Some(vec!["-f".to_string(), format.to_string(), "-i".to_string(), input]);
+    if cfg!(target_os = "macos") {
+	args("avfoundation", if device.is_empty() { "0".to_string() } else { device.to_string() })
+    } else if cfg!(windows) {
+	let device = if device.is_empty() { first_dshow_camera()? } else { device.to_string() };
+	args("dshow", format!("video={device}"))
+    } else {
+	args("v4l2", if device.is_empty() { "/dev/video0".to_string() } else { device.to_string() })
+    }
+}
+
+/// Keep `command` from opening a console window over the screensaver.
+pub(crate) fn hide_console(command: &mut Command) {
+    #[cfg(windows)]
+    {
+	use std::os::windows::process::CommandExt;
+	const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+	command.creation_flags(CREATE_NO_WINDOW);
+    }
+    #[cfg(not(windows))]
+    let _ = command;
+}
+
+/// The first camera DirectShow lists; ffmpeg has no default for it.
+fn first_dshow_camera() -> Option<String> {
+    let mut command = Command::new("ffmpeg");
+    command.args(["-hide_banner", "-list_devices", "true", "-f", "dshow", "-i", "dummy"]);
+    hide_console(&mut command);
+    let output = command.output().ok()?;
+    dshow_camera(&String::from_utf8_lossy(&output.stderr))
+}
+
+/// The first video device in ffmpeg's DirectShow device `listing`.
+fn dshow_camera(listing: &str) -> Option<String> {
+    listing
+	.lines()
+	.filter(|line| line.ends_with("(video)"))
+	.find_map(|line| line.split('"').nth(1))
+	.map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dshow_listing_names_the_first_camera() {
+	let listing = "[dshow @ 0x1] \"Microphone (USB)\" (audio)\n\
+		       [dshow @ 0x1] \"Integrated Camera\" (video)\n\
+		       [dshow @ 0x1]   Alternative name \"@device_pnp_...\"\n\
+		       [dshow @ 0x1] \"OBS Virtual Camera\" (video)\n";
+	assert_eq!(dshow_camera(listing).as_deref(), Some("Integrated Camera"));
+	assert_eq!(dshow_camera("no devices"), None);
+    }
+}
@@ -0,0 +1,598 @@
+//! Win32 glue for running inside windows provided by the screensaver host.
+
+use macroquad::math::Rect;
+use crate::launcher::TrayCommand;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::sync::mpsc::Sender;
+use windows_sys::Win32::Foundation::{BOOL, ERROR_NO_MORE_ITEMS, ERROR_SUCCESS, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM};
+use windows_sys::Win32::Globalization::GetUserDefaultLocaleName;
+use windows_sys::Win32::Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR};
+use windows_sys::Win32::Media::Audio::{
+    CALLBACK_FUNCTION, HMIDIIN, MIDIINCAPSW, midiInClose, midiInGetDevCapsW, midiInGetNumDevs, midiInOpen, midiInReset, midiInStart, midiInStop,
+};
+use windows_sys::Win32::Media::{MM_MIM_DATA, MMSYSERR_NOERROR};
+use windows_sys::Win32::System::LibraryLoader::GetModuleHandleW;
+use windows_sys::Win32::System::Registry::{
+    HKEY, HKEY_CURRENT_USER, KEY_READ, REG_SZ, RRF_RT_REG_SZ, RegCloseKey, RegDeleteKeyValueW, RegEnumValueW, RegGetValueW, RegOpenKeyExW,
+    RegSetKeyValueW,
+};
+use windows_sys::Win32::System::Shutdown::LockWorkStation;
+use windows_sys::Win32::System::SystemInformation::GetTickCount;
+use windows_sys::Win32::System::Threading::GetCurrentThreadId;
+use windows_sys::Win32::UI::Input::KeyboardAndMouse::{EnableWindow, GetLastInputInfo, LASTINPUTINFO};
+use windows_sys::Win32::UI::Shell::{NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW, Shell_NotifyIconW};
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    EnumThreadWindows, EnumWindows, FindWindowExW, FindWindowW, GW_HWNDPREV, GWL_EXSTYLE, GWL_STYLE, GWLP_HWNDPARENT, GetClientRect, GetSystemMetrics,
+    GetForegroundWindow, GetLayeredWindowAttributes, GetWindow, GetWindowLongPtrW, GetWindowRect, HWND_BOTTOM, HWND_TOP, HWND_TOPMOST, IsIconic, IsWindow,
+    IsWindowVisible, LWA_ALPHA, LWA_COLORKEY, MoveWindow,
+    SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SMTO_NORMAL, SendMessageTimeoutW,
+    SPI_GETCLIENTAREAANIMATION, SPI_SETSCREENSAVEACTIVE, SPIF_SENDCHANGE, SW_HIDE, SW_SHOW, SWP_ASYNCWINDOWPOS, SWP_FRAMECHANGED, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, SWP_SHOWWINDOW, SetForegroundWindow, SetParent,
+    SetLayeredWindowAttributes, SetWindowLongPtrW, SetWindowPos, ShowCursor, ShowWindow, SystemParametersInfoW, WS_CHILD,
+    WS_EX_APPWINDOW, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT, WS_POPUP, WS_VISIBLE,
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DispatchMessageW, GetCursorPos, GetMessageW,
+    HWND_MESSAGE, IDI_APPLICATION, LoadIconW, MF_STRING, MSG, RegisterClassW, TPM_RIGHTBUTTON, TrackPopupMenu,
+    TranslateMessage, WM_APP, WM_COMMAND, WM_LBUTTONDBLCLK, WM_RBUTTONUP, WNDCLASSW,
+};
+
+unsafe extern "system" fn find_visible(hwnd: HWND, found: LPARAM) -> BOOL {
+    unsafe {
+	if IsWindowVisible(hwnd) != 0 {
+	    *(found as *mut HWND) = hwnd;
+	    return 0;
+	}
+    }
+    1
+}
+
+/// The macroquad window, which is created on the calling thread.
+fn own_window() -> Option<HWND> {
+    let mut found: HWND = std::ptr::null_mut();
+    unsafe {
+	EnumThreadWindows(GetCurrentThreadId(), Some(find_visible), &mut found as *mut HWND as LPARAM);
+    }
+    (!found.is_null()).then_some(found)
+}
+
+/// Turn our window into a child filling the client area of `parent`.
+pub fn embed_in(parent: isize) -> bool {
+    let parent = parent as HWND;
+    let Some(hwnd) = own_window() else {
+	return false;
+    };
+    unsafe {
+	if IsWindow(parent) == 0 {
+	    return false;
+	}
+	let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+	GetClientRect(parent, &mut rect);
+	SetWindowLongPtrW(hwnd, GWL_STYLE, (WS_CHILD | WS_VISIBLE) as isize);
+	SetParent(hwnd, parent);
+	MoveWindow(hwnd, 0, 0, rect.right - rect.left, rect.bottom - rect.top, 1);
+    }
+    true
+}
+
+/// Keep our window out of Alt-Tab and the taskbar, as a tool window.
+pub fn hide_from_switcher() {
+    let Some(hwnd) = own_window() else {
+	return;
+    };
+    unsafe {
+	// The change only shows once the window is shown again
+	ShowWindow(hwnd, SW_HIDE);
+	let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+	SetWindowLongPtrW(hwnd, GWL_EXSTYLE, (style | WS_EX_TOOLWINDOW as isize) & !(WS_EX_APPWINDOW as isize));
+	ShowWindow(hwnd, SW_SHOW);
+    }
+}
+
+/// Let the desktop show through our window, `opacity` 0 showing only the
+/// desktop.
+pub fn set_opacity(opacity: f32) {
+    let Some(hwnd) = own_window() else {
+	return;
+    };
+    unsafe {
+	let style = GetWindowLongPtrW(hwnd, GWL_EXSTYLE);
+	if style & WS_EX_LAYERED as isize == 0 {
+	    SetWindowLongPtrW(hwnd, GWL_EXSTYLE, style | WS_EX_LAYERED as isize);
+	}
+	// Keep the widget's colour key through fades
+	let (mut key, mut flags) = (0, 0);
+	GetLayeredWindowAttributes(hwnd, &mut key, std::ptr::null_mut(), &mut flags);
+	SetLayeredWindowAttributes(hwnd, key, (opacity.clamp(0.0, 1.0) * 255.0).round() as u8, LWA_ALPHA | (flags & LWA_COLORKEY));
+    }
+}
+
+/// Make `owner` the owner of our window, centered over it and disabled
+/// while we are open, like a native modal dialog.
+pub fn set_owner(owner: isize) {
+    let owner = owner as HWND;
+    let Some(hwnd) = own_window() else {
+	return;
+    };
+    unsafe {
+	if IsWindow(owner) == 0 {
+	    return;
+	}
+	SetWindowLongPtrW(hwnd, GWLP_HWNDPARENT, owner as isize);
+	let mut owner_rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+	let mut rect = RECT { left: 0, top: 0, right: 0, bottom: 0 };
+	GetWindowRect(owner, &mut owner_rect);
+	GetWindowRect(hwnd, &mut rect);
+	let x = (owner_rect.left + owner_rect.right - (rect.right - rect.left)) / 2;
+	let y = (owner_rect.top + owner_rect.bottom - (rect.bottom - rect.top)) / 2;
+	SetWindowPos(hwnd, HWND_TOP, x, y, 0, 0, SWP_NOSIZE);
+	EnableWindow(owner, 0);
+	SetForegroundWindow(hwnd);
+    }
+}
+
+/// Hand focus back to `owner` before our window goes away.
+pub fn release_owner(owner: isize) {
+    let owner = owner as HWND;
+    unsafe {
+	if IsWindow(owner) != 0 {
+	    EnableWindow(owner, 1);
+	    SetForegroundWindow(owner);
+	}
+    }
+}
+
+unsafe extern "system" fn collect_monitor(_: HMONITOR, _: HDC, rect: *mut RECT, monitors: LPARAM) -> BOOL {
+    unsafe {
+	(*(monitors as *mut Vec<RECT>)).push(*rect);
+    }
+    1
+}
+
+fn monitors() -> Vec<RECT> {
+    let mut monitors: Vec<RECT> = Vec::new();
+    unsafe {
+	EnumDisplayMonitors(std::ptr::null_mut(), std::ptr::null(), Some(collect_monitor), &mut monitors as *mut Vec<RECT> as LPARAM);
+    }
+    monitors
+}
+
+/// Where each connected monitor is on the virtual desktop, as `[left, top,
+/// right, bottom]`, to tell when one is plugged in, unplugged or changes
+/// resolution.
+pub fn monitor_layout() -> Vec<[i32; 4]> {
+    monitors().iter().map(|rect| [rect.left, rect.top, rect.right, rect.bottom]).collect()
+}
+
+/// Stretch our fullscreen window over the whole virtual desktop when more
+/// than one monitor is connected, or fit it to the only one. Returns each
+/// monitor's area relative to the window, or nothing if there is only a
+/// single display.
+pub fn span_monitors() -> Vec<Rect> {
+    let monitors = monitors();
+    let Some(hwnd) = own_window() else {
+	return Vec::new();
+    };
+    if let [only] = monitors[..] {
+	// Back onto it when the others are unplugged
+	unsafe { SetWindowPos(hwnd, HWND_TOPMOST, only.left, only.top, only.right - only.left, only.bottom - only.top, 0) };
+	return Vec::new();
+    }
+
+    let (x, y, width, height) = unsafe {
+	(
+	    GetSystemMetrics(SM_XVIRTUALSCREEN),
+	    GetSystemMetrics(SM_YVIRTUALSCREEN),
+	    GetSystemMetrics(SM_CXVIRTUALSCREEN),
+	    GetSystemMetrics(SM_CYVIRTUALSCREEN),
+	)
+    };
+    unsafe {
+	SetWindowPos(hwnd, HWND_TOPMOST, x, y, width, height, 0);
+    }
+    monitors
+	.iter()
+	.map(|rect| Rect::new(
+	    (rect.left - x) as f32,
+	    (rect.top - y) as f32,
+	    (rect.right - rect.left) as f32,
+	    (rect.bottom - rect.top) as f32,
+	))
+	.collect()
+}
+
+/// Seconds between putting the widget back above the desktop, which rises
+/// over it when clicked.
+const WIDGET_RESTACK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The window for ours to go under to sit just above the desktop: the one
+/// above the shell's desktop window, or the bottom without a shell.
+fn above_desktop() -> HWND {
+    let progman = wide("Progman");
+    unsafe {
+	let desktop = FindWindowW(progman.as_ptr(), std::ptr::null());
+	if desktop.is_null() {
+	    return HWND_BOTTOM;
+	}
+	GetWindow(desktop, GW_HWNDPREV)
+    }
+}
+
+/// Turn our window into decoration over `region` of the virtual desktop,
+/// fractions of it `[x, y, width, height]`: borderless, passing clicks
+/// through, black letting the desktop show, and kept just above the
+/// desktop under every other window.
+pub fn make_widget(region: [f32; 4]) -> bool {
+    let Some(hwnd) = own_window() else {
+	return false;
+    };
+    let (x, y, width, height) = unsafe {
+	(
+	    GetSystemMetrics(SM_XVIRTUALSCREEN),
+	    GetSystemMetrics(SM_YVIRTUALSCREEN),
+	    GetSystemMetrics(SM_CXVIRTUALSCREEN),
+	    GetSystemMetrics(SM_CYVIRTUALSCREEN),
+	)
+    };
+    let [left, top, across, down] = region;
+    unsafe {
+	SetWindowLongPtrW(hwnd, GWL_STYLE, (WS_POPUP | WS_VISIBLE) as isize);
+	SetWindowLongPtrW(hwnd, GWL_EXSTYLE, (WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE) as isize);
+	SetLayeredWindowAttributes(hwnd, 0, 255, LWA_COLORKEY);
+	SetWindowPos(
+	    hwnd,
+	    above_desktop(),
+	    x + (left * width as f32) as i32,
+	    y + (top * height as f32) as i32,
+	    (across * width as f32) as i32,
+	    (down * height as f32) as i32,
+	    SWP_NOACTIVATE | SWP_FRAMECHANGED | SWP_SHOWWINDOW,
+	);
+    }
+    // Window handles are not Send, though any thread can use one
+    let widget = hwnd as isize;
+    std::thread::spawn(move || {
+	loop {
+	    std::thread::sleep(WIDGET_RESTACK_INTERVAL);
+	    let hwnd = widget as HWND;
+	    if unsafe { IsWindow(hwnd) } == 0 {
+		break;
+	    }
+	    let above = above_desktop();
+	    if above != hwnd {
+		unsafe { SetWindowPos(hwnd, above, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE | SWP_ASYNCWINDOWPOS) };
+	    }
+	}
+    });
+    true
+}
+
+/// The undocumented message asking the shell's desktop window to put a
+/// `WorkerW` window between the wallpaper and the icons.
+const SPAWN_WORKERW: u32 = 0x052C;
+
+unsafe extern "system" fn find_workerw(hwnd: HWND, found: LPARAM) -> BOOL {
+    let (view, workerw) = (wide("SHELLDLL_DefView"), wide("WorkerW"));
+    unsafe {
+	if FindWindowExW(hwnd, std::ptr::null_mut(), view.as_ptr(), std::ptr::null()).is_null() {
+	    return 1;
+	}
+	// The one drawn behind the icons follows the one holding them
+	*(found as *mut HWND) = FindWindowExW(std::ptr::null_mut(), hwnd, workerw.as_ptr(), std::ptr::null());
+    }
+    0
+}
+
+/// Put our window between the wallpaper and the desktop icons, filling
+/// the desktop, as live wallpaper programs do. Ours closes along with
+/// that window when the shell restarts.
+pub fn attach_to_wallpaper() -> bool {
+    let (progman, workerw) = (wide("Progman"), wide("WorkerW"));
+    let mut host: HWND = std::ptr::null_mut();
+    unsafe {
+	let desktop = FindWindowW(progman.as_ptr(), std::ptr::null());
+	if desktop.is_null() {
+	    return false;
+	}
+	let mut result = 0;
+	SendMessageTimeoutW(desktop, SPAWN_WORKERW, 0, 0, SMTO_NORMAL, 1000, &mut result);
+	EnumWindows(Some(find_workerw), &mut host as *mut HWND as LPARAM);
+	// Newer shells keep it inside the desktop window instead
+	if host.is_null() {
+	    host = FindWindowExW(desktop, std::ptr::null_mut(), workerw.as_ptr(), std::ptr::null());
+	}
+    }
+    !host.is_null() && embed_in(host as isize)
+}
+
+/// The user's locale name, such as `de-DE`.
+pub fn user_locale() -> Option<String> {
+    // LOCALE_NAME_MAX_LENGTH
+    let mut name = [0u16; 85];
+    let length = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    // The length counts the terminating nul
+    (length > 1).then(|| String::from_utf16_lossy(&name[..length as usize - 1]))
+}
+
+/// Whether "Show animations in Windows" is turned off.
+pub fn animations_disabled() -> bool {
+    let mut enabled: BOOL = 1;
+    let ok = unsafe { SystemParametersInfoW(SPI_GETCLIENTAREAANIMATION, 0, &mut enabled as *mut BOOL as *mut _, 0) };
+    ok != 0 && enabled == 0
+}
+
+/// Whether our window is minimized.
+pub fn is_minimized() -> bool {
+    own_window().is_some_and(|hwnd| unsafe { IsIconic(hwnd) != 0 })
+}
+
+/// Whether our window is the one the user is working in.
+pub fn has_focus() -> bool {
+    own_window().is_some_and(|hwnd| unsafe { GetForegroundWindow() == hwnd })
+}
+
+pub fn is_window(hwnd: isize) -> bool {
+    unsafe { IsWindow(hwnd as HWND) != 0 }
+}
+
+/// `text` as a null-terminated UTF-16 string.
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain([0]).collect()
+}
+
+/// Set the string value `name` of `key` under the current user, creating
+/// the key when missing.
+fn set_registry_value(key: &str, name: &str, data: &str) -> Result<(), String> {
+    let (key, name, data) = (wide(key), wide(name), wide(data));
+    let status = unsafe { RegSetKeyValueW(HKEY_CURRENT_USER, key.as_ptr(), name.as_ptr(), REG_SZ, data.as_ptr().cast(), (data.len() * 2) as u32) };
+    if status == ERROR_SUCCESS { Ok(()) } else { Err(format!("could not write the registry (error {status})")) }
+}
+
+/// The string values of `key` under the current user, or None when the key
+/// does not exist.
+pub fn registry_values(key: &str) -> Option<Vec<(String, String)>> {
+    let key = wide(key);
+    let mut hkey: HKEY = std::ptr::null_mut();
+    if unsafe { RegOpenKeyExW(HKEY_CURRENT_USER, key.as_ptr(), 0, KEY_READ, &mut hkey) } != ERROR_SUCCESS {
+	return None;
+    }
+    let mut values = Vec::new();
+    let (mut name, mut data) = (vec![0u16; 256], vec![0u16; 16384]);
+    for index in 0.. {
+	let (mut name_length, mut data_size, mut kind) = (name.len() as u32, (data.len() * 2) as u32, 0);
+	let status = unsafe {
+	    RegEnumValueW(hkey, index, name.as_mut_ptr(), &mut name_length, std::ptr::null(), &mut kind, data.as_mut_ptr().cast(), &mut data_size)
+	};
+	if status == ERROR_NO_MORE_ITEMS {
+	    break;
+	}
+	if status != ERROR_SUCCESS || kind != REG_SZ {
+	    continue;
+	}
+	// The size counts the terminating null, when there is one
+	let text = &data[..data_size as usize / 2];
+	let text = text.strip_suffix(&[0]).unwrap_or(text);
+	values.push((String::from_utf16_lossy(&name[..name_length as usize]), String::from_utf16_lossy(text)));
+    }
+    unsafe { RegCloseKey(hkey) };
+    Some(values)
+}
+
+/// Write `values` as strings under `key`, for the current user.
+pub fn set_registry_values(key: &str, values: &[(String, String)]) -> Result<(), String> {
+    values.iter().try_for_each(|(name, data)| set_registry_value(key, name, data))
+}
+
+/// Where Windows keeps the screensaver settings, under the current user.
+const DESKTOP_KEY: &str = "Control Panel\\Desktop";
+
+/// The screensaver Windows runs, if one is selected.
+pub fn screensaver() -> Option<PathBuf> {
+    let (key, value) = (wide(DESKTOP_KEY), wide("SCRNSAVE.EXE"));
+    let mut buffer = [0u16; 1024];
+    let mut size = (buffer.len() * 2) as u32;
+    let status = unsafe {
+	RegGetValueW(HKEY_CURRENT_USER, key.as_ptr(), value.as_ptr(), RRF_RT_REG_SZ, std::ptr::null_mut(), buffer.as_mut_ptr().cast(), &mut size)
+    };
+    if status != ERROR_SUCCESS {
+	return None;
+    }
+    let length = buffer.iter().position(|unit| *unit == 0).unwrap_or(buffer.len());
+    Some(PathBuf::from(String::from_utf16_lossy(&buffer[..length])))
+}
+
+/// Select `path` as the screensaver and turn it on, or with None turn the
+/// screensaver off and forget it.
+pub fn set_screensaver(path: Option<&Path>) -> Result<(), String> {
+    let set = |name: &str, data: &str| set_registry_value(DESKTOP_KEY, name, data);
+    match path {
+	Some(path) => {
+	    set("SCRNSAVE.EXE", &path.display().to_string())?;
+	    set("ScreenSaveActive", "1")?;
+	}
+	None => {
+	    let (key, name) = (wide(DESKTOP_KEY), wide("SCRNSAVE.EXE"));
+	    unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, key.as_ptr(), name.as_ptr()) };
+	    set("ScreenSaveActive", "0")?;
+	}
+    }
+    // Let Windows pick the change up without signing out
+    unsafe { SystemParametersInfoW(SPI_SETSCREENSAVEACTIVE, path.is_some() as u32, std::ptr::null_mut(), SPIF_SENDCHANGE) };
+    Ok(())
+}
+
+/// Seconds since the last keyboard or mouse input.
+pub fn idle_seconds() -> f64 {
+    let mut info = LASTINPUTINFO { cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32, dwTime: 0 };
+    unsafe {
+	if GetLastInputInfo(&mut info) == 0 {
+	    return 0.0;
+	}
+	f64::from(GetTickCount().wrapping_sub(info.dwTime)) / 1000.0
+    }
+}
+
+/// The message the tray icon sends its window.
+const TRAY_MESSAGE: u32 = WM_APP + 1;
+const MENU_START: usize = 1;
+const MENU_QUIT: usize = 2;
+
+/// Where the tray window passes what was picked on.
+static TRAY_COMMANDS: OnceLock<Sender<TrayCommand>> = OnceLock::new();
+
+fn send_tray(command: TrayCommand) {
+    if let Some(commands) = TRAY_COMMANDS.get() {
+	let _ = commands.send(command);
+    }
+}
+
+/// The tray icon, `hwnd` receiving its messages.
+fn tray_icon(hwnd: HWND, tooltip: &str) -> NOTIFYICONDATAW {
+    let mut icon: NOTIFYICONDATAW = unsafe { std::mem::zeroed() };
+    icon.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+    icon.hWnd = hwnd;
+    icon.uID = 1;
+    icon.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+    icon.uCallbackMessage = TRAY_MESSAGE;
+    icon.hIcon = unsafe { LoadIconW(std::ptr::null_mut(), IDI_APPLICATION) };
+    for (slot, unit) in icon.szTip.iter_mut().zip(tooltip.encode_utf16().take(127)) {
+	*slot = unit;
+    }
+    icon
+}
+
+unsafe extern "system" fn tray_proc(hwnd: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    unsafe {
+	match message {
+	    TRAY_MESSAGE => match lparam as u32 {
+		WM_LBUTTONDBLCLK => send_tray(TrayCommand::Start),
+		WM_RBUTTONUP => {
+		    let menu = CreatePopupMenu();
+		    let (start, quit) = (wide("Start now"), wide("Quit"));
+		    AppendMenuW(menu, MF_STRING, MENU_START, start.as_ptr());
+		    AppendMenuW(menu, MF_STRING, MENU_QUIT, quit.as_ptr());
+		    let mut cursor = POINT { x: 0, y: 0 };
+		    GetCursorPos(&mut cursor);
+		    // Without it the menu stays open when clicking elsewhere
+		    SetForegroundWindow(hwnd);
+		    TrackPopupMenu(menu, TPM_RIGHTBUTTON, cursor.x, cursor.y, 0, hwnd, std::ptr::null());
+		    DestroyMenu(menu);
+		}
+		_ => {}
+	    },
+	    WM_COMMAND => match wparam & 0xffff {
+		MENU_START => send_tray(TrayCommand::Start),
+		MENU_QUIT => {
+		    let icon = tray_icon(hwnd, "");
+		    Shell_NotifyIconW(NIM_DELETE, &icon);
+		    send_tray(TrayCommand::Quit);
+		}
+		_ => {}
+	    },
+	    _ => return DefWindowProcW(hwnd, message, wparam, lparam),
+	}
+    }
+    0
+}
+
+/// Show a tray icon titled `tooltip` on a thread of its own, its menu
+/// sending `commands`; false when it could not be added.
+pub fn spawn_tray(tooltip: &str, commands: Sender<TrayCommand>) -> bool {
+    if TRAY_COMMANDS.set(commands).is_err() {
+	return false;
+    }
+    let tooltip = tooltip.to_string();
+    let (added, shown) = std::sync::mpsc::channel();
+    std::thread::spawn(move || unsafe {
+	let class = wide("HexagonScreensaverTray");
+	let instance = GetModuleHandleW(std::ptr::null());
+	let window_class = WNDCLASSW { lpfnWndProc: Some(tray_proc), hInstance: instance, lpszClassName: class.as_ptr(), ..std::mem::zeroed() };
+	RegisterClassW(&window_class);
+	let hwnd = CreateWindowExW(0, class.as_ptr(), class.as_ptr(), 0, 0, 0, 0, 0, HWND_MESSAGE, std::ptr::null_mut(), instance, std::ptr::null());
+	let icon = tray_icon(hwnd, &tooltip);
+	let ok = !hwnd.is_null() && Shell_NotifyIconW(NIM_ADD, &icon) != 0;
+	let _ = added.send(ok);
+	if !ok {
+	    return;
+	}
+	let mut message: MSG = std::mem::zeroed();
+	while GetMessageW(&mut message, std::ptr::null_mut(), 0, 0) > 0 {
+	    TranslateMessage(&message);
+	    DispatchMessageW(&message);
+	}
+    });
+    shown.recv().unwrap_or(false)
+}
+
+/// Hide our window and show the cursor again, for exiting mid-frame.
+pub fn restore_desktop() {
+    unsafe {
+	if let Some(hwnd) = own_window() {
+	    ShowWindow(hwnd, SW_HIDE);
+	}
+	// Hiding it counts down a per-thread counter that shows it from 0 up
+	while ShowCursor(1) < 0 {}
+    }
+}
+
+/// Lock the workstation, showing the logon screen.
+pub fn lock_workstation() {
+    unsafe { LockWorkStation() };
+}
+
+/// Where the MIDI input passes the messages arriving.
+static MIDI_MESSAGES: Mutex<Option<Sender<[u8; 3]>>> = Mutex::new(None);
+
+/// An open MIDI input, closed when dropped.
+pub struct MidiInput(HMIDIIN);
+
+impl Drop for MidiInput {
+    fn drop(&mut self) {
+	unsafe {
+	    midiInStop(self.0);
+	    midiInReset(self.0);
+	    midiInClose(self.0);
+	}
+	if let Ok(mut messages) = MIDI_MESSAGES.lock() {
+	    *messages = None;
+	}
+    }
+}
+
+unsafe extern "system" fn midi_proc(_: HMIDIIN, message: u32, _: usize, data: usize, _: usize) {
+    if message == MM_MIM_DATA
+	&& let Ok(messages) = MIDI_MESSAGES.lock()
+	&& let Some(messages) = messages.as_ref()
+    {
+	let _ = messages.send([data as u8, (data >> 8) as u8, (data >> 16) as u8]);
+    }
+}
+
+/// Open the first MIDI input with `name` in its name, or the first of all
+/// for `first`, sending its messages as status and data bytes.
+pub fn open_midi_input(name: &str, messages: Sender<[u8; 3]>) -> Result<MidiInput, String> {
+    let name = name.to_lowercase();
+    let device = (0..unsafe { midiInGetNumDevs() }).find(|&device| {
+	let mut caps: MIDIINCAPSW = unsafe { std::mem::zeroed() };
+	if unsafe { midiInGetDevCapsW(device as usize, &mut caps, std::mem::size_of::<MIDIINCAPSW>() as u32) } != MMSYSERR_NOERROR {
+	    return false;
+	}
+	let units = caps.szPname;
+	let length = units.iter().position(|unit| *unit == 0).unwrap_or(units.len());
+	name == "first" || String::from_utf16_lossy(&units[..length]).to_lowercase().contains(&name)
+    });
+    let Some(device) = device else {
+	return Err(format!("no MIDI input named `{name}`"));
+    };
+    *MIDI_MESSAGES.lock().map_err(|err| err.to_string())? = Some(messages);
+    let mut input: HMIDIIN = std::ptr::null_mut();
+    unsafe {
+	let callback = midi_proc as unsafe extern "system" fn(HMIDIIN, u32, usize, usize, usize) as usize;
+	if midiInOpen(&mut input, device, callback, 0, CALLBACK_FUNCTION) != MMSYSERR_NOERROR {
+	    return Err(format!("cannot open MIDI input {device}"));
+	}
+	let input = MidiInput(input);
+	if midiInStart(input.0) != MMSYSERR_NOERROR {
+	    return Err(format!("cannot start MIDI input {device}"));
+	}
+	Ok(input)
+    }
+}
@@ -0,0 +1,315 @@
+//! X11 glue for running inside the window xscreensaver hands over, on the
+//! desktop as a widget and reading the idle time, with Xlib loaded at
+//! runtime like miniquad does.
+
+use crate::cli::parse_window_id;
+use std::ffi::{CStr, c_char, c_int, c_long, c_uchar, c_uint, c_ulong, c_void};
+use std::ptr::{null, null_mut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+type Display = c_void;
+type Window = c_ulong;
+type Atom = c_ulong;
+type ErrorHandler = unsafe extern "C" fn(*mut Display, *mut c_void) -> c_int;
+
+/// The `ATOM` and `WINDOW` property types.
+const XA_ATOM: Atom = 4;
+const XA_WINDOW: Atom = 33;
+/// `PropModeReplace`, and the shape extension's input region and `ShapeSet`.
+const PROP_MODE_REPLACE: c_int = 0;
+const SHAPE_INPUT: c_int = 2;
+const SHAPE_SET: c_int = 0;
+const RTLD_NOW: c_int = 2;
+
+unsafe extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+/// The Xlib calls used, and our own connection to the server.
+struct Xlib {
+    display: *mut Display,
+    default_root_window: unsafe extern "C" fn(*mut Display) -> Window,
+    query_tree: unsafe extern "C" fn(*mut Display, Window, *mut Window, *mut Window, *mut *mut Window, *mut c_uint) -> c_int,
+    fetch_name: unsafe extern "C" fn(*mut Display, Window, *mut *mut c_char) -> c_int,
+    free: unsafe extern "C" fn(*mut c_void) -> c_int,
+    intern_atom: unsafe extern "C" fn(*mut Display, *const c_char, c_int) -> Atom,
+    #[allow(clippy::type_complexity)]
+    get_window_property: unsafe extern "C" fn(
+	*mut Display,
+	Window,
+	Atom,
+	c_long,
+	c_long,
+	c_int,
+	Atom,
+	*mut Atom,
+	*mut c_int,
+	*mut c_ulong,
+	*mut c_ulong,
+	*mut *mut c_uchar,
+    ) -> c_int,
+    #[allow(clippy::type_complexity)]
+    get_geometry: unsafe extern "C" fn(*mut Display, Window, *mut Window, *mut c_int, *mut c_int, *mut c_uint, *mut c_uint, *mut c_uint, *mut c_uint) -> c_int,
+    change_property: unsafe extern "C" fn(*mut Display, Window, Atom, Atom, c_int, c_int, *const c_uchar, c_int) -> c_int,
+    unmap_window: unsafe extern "C" fn(*mut Display, Window) -> c_int,
+    reparent_window: unsafe extern "C" fn(*mut Display, Window, Window, c_int, c_int) -> c_int,
+    move_resize_window: unsafe extern "C" fn(*mut Display, Window, c_int, c_int, c_uint, c_uint) -> c_int,
+    map_raised: unsafe extern "C" fn(*mut Display, Window) -> c_int,
+    map_window: unsafe extern "C" fn(*mut Display, Window) -> c_int,
+    sync: unsafe extern "C" fn(*mut Display, c_int) -> c_int,
+    set_error_handler: unsafe extern "C" fn(Option<ErrorHandler>) -> Option<ErrorHandler>,
+}
+
+/// What `XScreenSaverQueryInfo` fills in, `idle` in milliseconds.
+#[repr(C)]
+#[derive(Default)]
+struct ScreenSaverInfo {
+    window: Window,
+    state: c_int,
+    kind: c_int,
+    til_or_since: c_ulong,
+    idle: c_ulong,
+    event_mask: c_ulong,
+}
+
+type QueryInfo = unsafe extern "C" fn(*mut Display, Window, *mut ScreenSaverInfo) -> c_int;
+type ShapeRectangles = unsafe extern "C" fn(*mut Display, Window, c_int, c_int, c_int, *const c_void, c_int, c_int, c_int);
+
+thread_local! {
+    static XLIB: Option<Xlib> = Xlib::open();
+    /// From the screensaver extension's library, which is not always there.
+    static QUERY_INFO: Option<QueryInfo> = unsafe {
+	let lib = dlopen(c"libXss.so.1".as_ptr(), RTLD_NOW);
+	if lib.is_null() { None } else { symbol(lib, c"XScreenSaverQueryInfo") }
+    };
+    /// From the X extension library, for windows clicks pass through.
+    static SHAPE_RECTANGLES: Option<ShapeRectangles> = unsafe {
+	let lib = dlopen(c"libXext.so.6".as_ptr(), RTLD_NOW);
+	if lib.is_null() { None } else { symbol(lib, c"XShapeCombineRectangles") }
+    };
+}
+
+/// Set when a request failed while `Xlib::checked` was watching.
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" fn note_error(_display: *mut Display, _event: *mut c_void) -> c_int {
+    FAILED.store(true, Ordering::Relaxed);
+    0
+}
+
+/// The function `name` in `lib`, as the pointer type `T`.
+unsafe fn symbol<T>(lib: *mut c_void, name: &CStr) -> Option<T> {
+    let found = unsafe { dlsym(lib, name.as_ptr()) };
+    (!found.is_null()).then(|| unsafe { std::mem::transmute_copy(&found) })
+}
+
+impl Xlib {
+    fn open() -> Option<Self> {
+	unsafe {
+	    let lib = dlopen(c"libX11.so.6".as_ptr(), RTLD_NOW);
+	    if lib.is_null() {
+		return None;
+	    }
+	    let open_display: unsafe extern "C" fn(*const c_char) -> *mut Display = symbol(lib, c"XOpenDisplay")?;
+	    let display = open_display(null());
+	    if display.is_null() {
+		return None;
+	    }
+	    Some(Self {
+		display,
+		default_root_window: symbol(lib, c"XDefaultRootWindow")?,
+		query_tree: symbol(lib, c"XQueryTree")?,
+		fetch_name: symbol(lib, c"XFetchName")?,
+		free: symbol(lib, c"XFree")?,
+		intern_atom: symbol(lib, c"XInternAtom")?,
+		get_window_property: symbol(lib, c"XGetWindowProperty")?,
+		get_geometry: symbol(lib, c"XGetGeometry")?,
+		change_property: symbol(lib, c"XChangeProperty")?,
+		unmap_window: symbol(lib, c"XUnmapWindow")?,
+		reparent_window: symbol(lib, c"XReparentWindow")?,
+		move_resize_window: symbol(lib, c"XMoveResizeWindow")?,
+		map_raised: symbol(lib, c"XMapRaised")?,
+		map_window: symbol(lib, c"XMapWindow")?,
+		sync: symbol(lib, c"XSync")?,
+		set_error_handler: symbol(lib, c"XSetErrorHandler")?,
+	    })
+	}
+    }
+
+    /// Run `requests`, true when the server took them all. A bad window
+    /// would otherwise have Xlib end the process.
+    fn checked(&self, requests: impl FnOnce()) -> bool {
+	unsafe {
+	    FAILED.store(false, Ordering::Relaxed);
+	    let previous = (self.set_error_handler)(Some(note_error));
+	    requests();
+	    (self.sync)(self.display, 0);
+	    (self.set_error_handler)(previous);
+	}
+	!FAILED.load(Ordering::Relaxed)
+    }
+
+    fn root(&self) -> Window {
+	unsafe { (self.default_root_window)(self.display) }
+    }
+
+    fn children(&self, window: Window) -> Vec<Window> {
+	let (mut root, mut parent, mut list, mut count) = (0, 0, null_mut(), 0);
+	unsafe {
+	    if (self.query_tree)(self.display, window, &mut root, &mut parent, &mut list, &mut count) == 0 || list.is_null() {
+		return Vec::new();
+	    }
+	    let children = std::slice::from_raw_parts(list, count as usize).to_vec();
+	    (self.free)(list.cast());
+	    children
+	}
+    }
+
+    fn name(&self, window: Window) -> Option<String> {
+	let mut name = null_mut();
+	unsafe {
+	    if (self.fetch_name)(self.display, window, &mut name) == 0 || name.is_null() {
+		return None;
+	    }
+	    let found = CStr::from_ptr(name).to_string_lossy().into_owned();
+	    (self.free)(name.cast());
+	    Some(found)
+	}
+    }
+
+    /// The window `window` names in its `__SWM_VROOT` property, which
+    /// window managers with a virtual root set on it.
+    fn swm_vroot(&self, window: Window) -> Option<Window> {
+	let (mut kind, mut format, mut items, mut left, mut data) = (0, 0, 0, 0, null_mut());
+	unsafe {
+	    let atom = (self.intern_atom)(self.display, c"__SWM_VROOT".as_ptr(), 1);
+	    if atom == 0 {
+		return None;
+	    }
+	    let got = (self.get_window_property)(self.display, window, atom, 0, 1, 0, XA_WINDOW, &mut kind, &mut format, &mut items, &mut left, &mut data);
+	    if data.is_null() {
+		return None;
+	    }
+	    let vroot = (got == 0 && kind == XA_WINDOW && items == 1).then(|| *data.cast::<Window>());
+	    (self.free)(data.cast());
+	    vroot
+	}
+    }
+
+    /// The window titled `title`, which may sit inside a window manager's
+    /// frame.
+    fn find(&self, title: &str) -> Option<Window> {
+	let mut pending = vec![self.root()];
+	while let Some(window) = pending.pop() {
+	    if self.name(window).as_deref() == Some(title) {
+		return Some(window);
+	    }
+	    pending.extend(self.children(window));
+	}
+	None
+    }
+
+    fn atom(&self, name: &CStr) -> Atom {
+	unsafe { (self.intern_atom)(self.display, name.as_ptr(), 0) }
+    }
+
+    /// Set `window`'s property `name` to the 32-bit items `values`.
+    fn set_property(&self, window: Window, name: &CStr, kind: Atom, values: &[c_ulong]) {
+	unsafe {
+	    (self.change_property)(self.display, window, self.atom(name), kind, 32, PROP_MODE_REPLACE, values.as_ptr().cast(), values.len() as c_int);
+	}
+    }
+
+    fn size(&self, window: Window) -> Option<(c_uint, c_uint)> {
+	let (mut root, mut x, mut y, mut width, mut height, mut border, mut depth) = (0, 0, 0, 0, 0, 0, 0);
+	let mut found = false;
+	let ok = self.checked(|| unsafe {
+	    found = (self.get_geometry)(self.display, window, &mut root, &mut x, &mut y, &mut width, &mut height, &mut border, &mut depth) != 0;
+	});
+	(ok && found).then_some((width, height))
+    }
+}
+
+/// The window to draw in for `-root`: the one xscreensaver names in
+/// `XSCREENSAVER_WINDOW`, else the virtual root, else the root itself.
+fn root_window(xlib: &Xlib) -> Window {
+    if let Some(window) = std::env::var("XSCREENSAVER_WINDOW").ok().and_then(|id| parse_window_id(&id)) {
+	return window as Window;
+    }
+    let root = xlib.root();
+    xlib.children(root).into_iter().find_map(|child| xlib.swm_vroot(child)).unwrap_or(root)
+}
+
+/// Move the window titled `title` into `parent`, or the `-root` window
+/// when none is given, filling it. Returns the window drawn in.
+// Window ids are narrower than u64 on 32-bit targets
+#[allow(clippy::unnecessary_cast)]
+pub fn embed_in(parent: Option<u64>, title: &str) -> Option<u64> {
+    XLIB.with(|xlib| {
+	let xlib = xlib.as_ref()?;
+	let parent = parent.map_or_else(|| root_window(xlib), |id| id as Window);
+	let (width, height) = xlib.size(parent)?;
+	let own = xlib.find(title)?;
+	let moved = xlib.checked(|| unsafe {
+	    (xlib.unmap_window)(xlib.display, own);
+	    (xlib.reparent_window)(xlib.display, own, parent, 0, 0);
+	    (xlib.move_resize_window)(xlib.display, own, 0, 0, width, height);
+	    (xlib.map_raised)(xlib.display, own);
+	});
+	moved.then_some(parent as u64)
+    })
+}
+
+/// Make the window titled `title` decoration over `region` of the root
+/// window, fractions of it `[x, y, width, height]`: undecorated, under
+/// the other windows, on every workspace and passing clicks through. X
+/// has no colour key, so the background is drawn rather than the desktop
+/// showing through it.
+pub fn make_widget(title: &str, region: [f32; 4]) -> bool {
+    XLIB.with(|xlib| {
+	let Some(xlib) = xlib.as_ref() else {
+	    return false;
+	};
+	let (Some(own), Some((width, height))) = (xlib.find(title), xlib.size(xlib.root())) else {
+	    return false;
+	};
+	let [left, top, across, down] = region;
+	let state = [c"_NET_WM_STATE_BELOW", c"_NET_WM_STATE_SKIP_TASKBAR", c"_NET_WM_STATE_SKIP_PAGER", c"_NET_WM_STATE_STICKY"].map(|name| xlib.atom(name));
+	xlib.checked(|| unsafe {
+	    // Window managers read these when the window is mapped
+	    (xlib.unmap_window)(xlib.display, own);
+	    // Motif hints with decorations flagged and none of them
+	    xlib.set_property(own, c"_MOTIF_WM_HINTS", xlib.atom(c"_MOTIF_WM_HINTS"), &[2, 0, 0, 0, 0]);
+	    xlib.set_property(own, c"_NET_WM_STATE", XA_ATOM, &state);
+	    (xlib.move_resize_window)(
+		xlib.display,
+		own,
+		(left * width as f32) as c_int,
+		(top * height as f32) as c_int,
+		((across * width as f32) as c_uint).max(1),
+		((down * height as f32) as c_uint).max(1),
+	    );
+	    // An empty input region lets every click through to the desktop
+	    if let Some(shape_rectangles) = SHAPE_RECTANGLES.with(|shape| *shape) {
+		shape_rectangles(xlib.display, own, SHAPE_INPUT, 0, 0, null(), 0, SHAPE_SET, 0);
+	    }
+	    (xlib.map_window)(xlib.display, own);
+	})
+    })
+}
+
+/// Whether `window` still exists.
+pub fn is_window(window: u64) -> bool {
+    XLIB.with(|xlib| xlib.as_ref().is_some_and(|xlib| xlib.size(window as Window).is_some()))
+}
+
+/// Seconds since the last input anywhere on the display.
+pub fn idle_seconds() -> Option<f64> {
+    let query = QUERY_INFO.with(|query| *query)?;
+    XLIB.with(|xlib| {
+	let xlib = xlib.as_ref()?;
+	let mut info = ScreenSaverInfo::default();
+	(unsafe { query(xlib.display, xlib.root(), &mut info) } != 0).then(|| info.idle as f64 / 1000.0)
+    })
+}
@@ -0,0 +1,22 @@
+// What the web build asks the page for, registered with miniquad's loader
+// before `load` is called.
+function hexagon_query_bytes() {
+    return new TextEncoder().encode(window.location.search.replace(/^\?/, ""));
+}
+
+miniquad_add_plugin({
+    name: "hexagon_screensaver",
+    version: 1,
+    register_plugin: function (importObject) {
+        importObject.env.hexagon_query_length = function () {
+            return hexagon_query_bytes().length;
+        };
+        importObject.env.hexagon_query = function (buffer, length) {
+            new Uint8Array(wasm_memory.buffer, buffer, length).set(hexagon_query_bytes().subarray(0, length));
+        };
+        // Minutes local time is ahead of UTC
+        importObject.env.hexagon_utc_offset = function () {
+            return -new Date().getTimezoneOffset();
+        };
+    },
+});